@@ -0,0 +1,121 @@
+//! Adaptive format selection benchmarks
+//!
+//! Prints which [`auto`] candidate wins per kind and the auto size against
+//! the best single format, then times auto decoding against decoding that
+//! format.
+//!
+//! [`auto`]: binostr::auto
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+
+mod common;
+
+use binostr::auto::{self, AutoOptions, AutoStats, Policy};
+use binostr::stats::{self, Format};
+use binostr::NostrEvent;
+
+fn print_report(events: &[NostrEvent], options: &AutoOptions) -> Format {
+    let stats = AutoStats::from_events(events, options);
+    let (best, best_bytes) = stats.best_single().expect("at least one candidate");
+
+    println!("\n{}", "=".repeat(72));
+    println!("ADAPTIVE FORMAT SELECTION (wins per kind)");
+    println!("{}", "=".repeat(72));
+    print!("{:<8} {:>7}", "Kind", "events");
+    for format in &options.candidates {
+        print!(" {:>14}", format.name());
+    }
+    println!();
+    println!("{}", "-".repeat(72));
+    for (kind, count) in stats.top_kinds(15) {
+        print!("{:<8} {:>7}", kind, count);
+        for format in &options.candidates {
+            print!(
+                " {:>14}",
+                stats.wins[&kind].get(format).copied().unwrap_or(0)
+            );
+        }
+        println!();
+    }
+    print!("{:<8} {:>7}", "all", stats.events);
+    for &format in &options.candidates {
+        print!(" {:>14}", stats.total_wins(format));
+    }
+    println!("\n");
+
+    println!(
+        "Auto (smallest):      {:>10} bytes ({:.1}% of {})",
+        stats.auto_bytes,
+        100.0 * stats.auto_bytes as f64 / best_bytes as f64,
+        best.name()
+    );
+    let fastest =
+        AutoStats::from_events(events, &options.clone().with_policy(Policy::FastestDecode));
+    println!(
+        "Auto (fastest decode): {:>9} bytes ({:.1}% of {})",
+        fastest.auto_bytes,
+        100.0 * fastest.auto_bytes as f64 / best_bytes as f64,
+        best.name()
+    );
+    println!(
+        "Best single format:   {:>10} bytes ({})",
+        best_bytes,
+        best.name()
+    );
+    println!();
+
+    best
+}
+
+fn bench_auto(c: &mut Criterion) {
+    let events = common::load_sample(10_000);
+    let options = AutoOptions::new();
+    let best = print_report(&events, &options);
+
+    let auto_encoded: Vec<Vec<u8>> = events.iter().map(|e| auto::encode(e, &options)).collect();
+    let best_encoded: Vec<Vec<u8>> = events.iter().map(|e| stats::serialize(e, best)).collect();
+
+    let mut group = c.benchmark_group("auto");
+    group.throughput(Throughput::Elements(events.len() as u64));
+
+    group.bench_function("serialize/auto_smallest", |b| {
+        b.iter(|| {
+            for event in &events {
+                black_box(auto::encode(event, &options));
+            }
+        })
+    });
+
+    group.bench_function(format!("serialize/{}", best.short_name()), |b| {
+        b.iter(|| {
+            for event in &events {
+                black_box(stats::serialize(event, best));
+            }
+        })
+    });
+
+    group.bench_function("deserialize/auto", |b| {
+        b.iter(|| {
+            for bytes in &auto_encoded {
+                black_box(auto::decode(bytes).unwrap());
+            }
+        })
+    });
+
+    group.bench_function(format!("deserialize/{}", best.short_name()), |b| {
+        b.iter(|| {
+            for bytes in &best_encoded {
+                black_box(stats::deserialize(bytes, best).unwrap());
+            }
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group! {
+    name = benches;
+    config = common::auto_criterion();
+    targets = bench_auto
+}
+criterion_main!(benches);
@@ -8,47 +8,56 @@ use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughpu
 mod common;
 
 use binostr::event::{SizeCategory, TagCategory};
-use binostr::{capnp, cbor, dannypack, json, notepack, proto, EventSampler, NostrEvent};
+use binostr::{capnp, cbor, dannypack, json, notepack, proto, NostrEvent};
 
-const DATA_DIR: &str = "data";
 const SAMPLE_SIZE: usize = 100;
 
 /// Load events by size category
 fn load_by_size(category: SizeCategory, count: usize) -> Vec<NostrEvent> {
-    let mut sampler = match EventSampler::from_directory(DATA_DIR, count * 20) {
-        Ok(s) => s,
+    let label = format!("size-{:?}-{}", category, count).to_lowercase();
+    let events = match common::load_cached(&label, count * 20, |sampler| {
+        sampler
+            .sample_size(category, count)
+            .into_iter()
+            .cloned()
+            .collect()
+    }) {
+        Ok(events) => events,
         Err(e) => {
             eprintln!("Warning: Could not load events: {}", e);
             return common::generate_synthetic_events(count);
         }
     };
 
-    let events = sampler.sample_size(category, count);
     if events.is_empty() {
         eprintln!("Warning: No events found for category {:?}", category);
-        return Vec::new();
     }
 
-    events.into_iter().cloned().collect()
+    events
 }
 
 /// Load events by tag category
 fn load_by_tags(category: TagCategory, count: usize) -> Vec<NostrEvent> {
-    let mut sampler = match EventSampler::from_directory(DATA_DIR, count * 20) {
-        Ok(s) => s,
+    let label = format!("tags-{:?}-{}", category, count).to_lowercase();
+    let events = match common::load_cached(&label, count * 20, |sampler| {
+        sampler
+            .sample_tags(category, count)
+            .into_iter()
+            .cloned()
+            .collect()
+    }) {
+        Ok(events) => events,
         Err(e) => {
             eprintln!("Warning: Could not load events: {}", e);
             return common::generate_synthetic_events(count);
         }
     };
 
-    let events = sampler.sample_tags(category, count);
     if events.is_empty() {
         eprintln!("Warning: No events found for category {:?}", category);
-        return Vec::new();
     }
 
-    events.into_iter().cloned().collect()
+    events
 }
 
 /// Benchmark a specific size category
@@ -1,5 +1,7 @@
 //! Common utilities for benchmarks
 
+use binostr::fixture::{FixtureCache, FixtureError};
+use binostr::sampler::BenchmarkSets;
 use binostr::{EventSampler, NostrEvent};
 use criterion::Criterion;
 use std::time::Duration;
@@ -67,11 +69,31 @@ pub fn auto_criterion() -> Criterion {
 /// Default data directory
 pub const DATA_DIR: &str = "data";
 
+/// Sampling seed shared by every bench, so all runs see identical events
+pub const BENCH_SEED: u64 = 42;
+
+/// Load events through the fixture cache.
+///
+/// The first run loads `limit` events from `DATA_DIR`, lets `select` pick from
+/// a sampler seeded with `BENCH_SEED`, and writes the result to an indexed
+/// fixture file. Later runs memory-map that file instead of inflating the
+/// dataset again. The cache is invalidated when the data files change.
+#[allow(dead_code)]
+pub fn load_cached<F>(label: &str, limit: usize, select: F) -> Result<Vec<NostrEvent>, FixtureError>
+where
+    F: FnOnce(&mut EventSampler) -> Vec<NostrEvent>,
+{
+    FixtureCache::from_env().events(DATA_DIR, label, BENCH_SEED, limit, select)
+}
+
 /// Load a sample of events for benchmarking
 #[allow(dead_code)]
 pub fn load_sample(size: usize) -> Vec<NostrEvent> {
-    let mut sampler = match EventSampler::from_directory(DATA_DIR, size * 2) {
-        Ok(s) => s,
+    let label = format!("sample-{}", size);
+    let events = match load_cached(&label, size * 2, |sampler| {
+        sampler.random_sample(size).into_iter().cloned().collect()
+    }) {
+        Ok(events) => events,
         Err(e) => {
             eprintln!("Warning: Could not load events from {}: {}", DATA_DIR, e);
             eprintln!("Using synthetic test events instead.");
@@ -79,35 +101,57 @@ pub fn load_sample(size: usize) -> Vec<NostrEvent> {
         }
     };
 
-    if sampler.len() < size {
+    if events.len() < size {
         eprintln!(
             "Warning: Only loaded {} events, requested {}",
-            sampler.len(),
+            events.len(),
             size
         );
     }
 
-    sampler.random_sample(size).into_iter().cloned().collect()
+    events
+}
+
+/// Load the cached [`BenchmarkSets`], or `None` without data
+#[allow(dead_code)]
+pub fn load_benchmark_sets(limit: usize) -> Option<BenchmarkSets> {
+    match FixtureCache::from_env().benchmark_sets(DATA_DIR, BENCH_SEED, limit) {
+        Ok(sets) if sets.is_valid() => Some(sets),
+        Ok(_) => {
+            eprintln!("Warning: Benchmark sets are empty");
+            None
+        }
+        Err(e) => {
+            eprintln!("Warning: Could not load benchmark sets: {}", e);
+            None
+        }
+    }
 }
 
 /// Load events filtered by kind
 #[allow(dead_code)]
 pub fn load_by_kind(kind: u16, size: usize) -> Vec<NostrEvent> {
-    let mut sampler = match EventSampler::from_directory(DATA_DIR, size * 10) {
-        Ok(s) => s,
+    let label = format!("kind-{}-{}", kind, size);
+    let events = match load_cached(&label, size * 10, |sampler| {
+        sampler
+            .sample_kind(kind, size)
+            .into_iter()
+            .cloned()
+            .collect()
+    }) {
+        Ok(events) => events,
         Err(e) => {
             eprintln!("Warning: Could not load events: {}", e);
             return generate_synthetic_events_kind(kind, size);
         }
     };
 
-    let events = sampler.sample_kind(kind, size);
     if events.is_empty() {
         eprintln!("Warning: No events found for kind {}", kind);
         return generate_synthetic_events_kind(kind, size);
     }
 
-    events.into_iter().cloned().collect()
+    events
 }
 
 /// Generate synthetic events for testing when data files aren't available
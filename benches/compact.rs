@@ -0,0 +1,162 @@
+//! Value compaction benchmarks
+//!
+//! Compares DannyPack and CBOR Packed with and without [`compact`] on the
+//! benchmark sets each compaction targets, prints the size difference, then
+//! times serialization and deserialization.
+//!
+//! [`compact`]: binostr::compact
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+
+mod common;
+
+use binostr::compact::CompactOptions;
+use binostr::sampler::BenchmarkSets;
+use binostr::{cbor, dannypack, NostrEvent};
+
+/// Benchmark sets paired with the compaction each one exercises
+fn targets(sets: &BenchmarkSets) -> Vec<(&'static str, &[NostrEvent], CompactOptions)> {
+    vec![
+        (
+            "kind_0_profile",
+            sets.kind_0_profile.as_slice(),
+            CompactOptions::new().with_profile(true),
+        ),
+        (
+            "kind_1_notes",
+            sets.kind_1_notes.as_slice(),
+            CompactOptions::new().with_bech32(true),
+        ),
+        (
+            "kind_3_follows",
+            sets.kind_3_follows.as_slice(),
+            CompactOptions::new().with_follows(true),
+        ),
+        (
+            "kind_4_dms",
+            sets.kind_4_dms.as_slice(),
+            CompactOptions::new().with_base64(true),
+        ),
+        (
+            "random_1000",
+            sets.random_1000.as_slice(),
+            CompactOptions::all(),
+        ),
+    ]
+}
+
+fn print_report(sets: &BenchmarkSets) {
+    println!("\n{}", "=".repeat(72));
+    println!("VALUE COMPACTION");
+    println!("{}", "=".repeat(72));
+    println!(
+        "{:<16} {:>7} {:<12} {:>12} {:>12} {:>8}",
+        "Set", "events", "Format", "plain", "compacted", "saved"
+    );
+    println!("{}", "-".repeat(72));
+
+    for (name, events, options) in targets(sets) {
+        if events.is_empty() {
+            continue;
+        }
+        let rows = [
+            (
+                "DannyPack",
+                events
+                    .iter()
+                    .map(|e| dannypack::serialize(e).len())
+                    .sum::<usize>(),
+                events
+                    .iter()
+                    .map(|e| dannypack::serialize_with(e, &options).len())
+                    .sum::<usize>(),
+            ),
+            (
+                "CBOR Packed",
+                events
+                    .iter()
+                    .map(|e| cbor::packed::serialize(e).len())
+                    .sum(),
+                events
+                    .iter()
+                    .map(|e| cbor::packed::serialize_with(e, &options).len())
+                    .sum(),
+            ),
+        ];
+        for (format, plain, compacted) in rows {
+            println!(
+                "{:<16} {:>7} {:<12} {:>12} {:>12} {:>7.1}%",
+                name,
+                events.len(),
+                format,
+                plain,
+                compacted,
+                100.0 * (1.0 - compacted as f64 / plain as f64)
+            );
+        }
+    }
+    println!();
+}
+
+fn bench_compact(c: &mut Criterion) {
+    let Some(sets) = common::load_benchmark_sets(100_000) else {
+        eprintln!("No benchmark sets loaded, skipping benchmarks");
+        return;
+    };
+
+    print_report(&sets);
+
+    for (name, events, options) in targets(&sets) {
+        if events.is_empty() {
+            continue;
+        }
+        let compacted: Vec<Vec<u8>> = events
+            .iter()
+            .map(|e| dannypack::serialize_with(e, &options))
+            .collect();
+
+        let mut group = c.benchmark_group(format!("compact_{}", name));
+        group.throughput(Throughput::Elements(events.len() as u64));
+
+        group.bench_function("serialize/dannypack", |b| {
+            b.iter(|| {
+                for event in events {
+                    black_box(dannypack::serialize(event));
+                }
+            })
+        });
+
+        group.bench_function("serialize/dannypack_compact", |b| {
+            b.iter(|| {
+                for event in events {
+                    black_box(dannypack::serialize_with(event, &options));
+                }
+            })
+        });
+
+        group.bench_function("serialize/cbor_packed_compact", |b| {
+            b.iter(|| {
+                for event in events {
+                    black_box(cbor::packed::serialize_with(event, &options));
+                }
+            })
+        });
+
+        group.bench_function("deserialize/dannypack_compact", |b| {
+            b.iter(|| {
+                for bytes in &compacted {
+                    black_box(dannypack::deserialize(bytes).unwrap());
+                }
+            })
+        });
+
+        group.finish();
+    }
+}
+
+criterion_group! {
+    name = benches;
+    config = common::auto_criterion();
+    targets = bench_compact
+}
+criterion_main!(benches);
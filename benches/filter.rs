@@ -0,0 +1,127 @@
+//! NIP-01 filter throughput benchmarks
+//!
+//! Measures how fast each format can answer "does this stored event match
+//! this subscription filter?". Cap'n Proto and DannyPack are matched lazily
+//! on the encoded bytes; the other formats must be decoded first. The
+//! `decoded` baseline matches events that are already in memory.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+
+mod common;
+
+use binostr::filter::Filter;
+use binostr::stats::{self, Format};
+use binostr::NostrEvent;
+
+const FORMATS: &[Format] = &[
+    Format::Json,
+    Format::CborPacked,
+    Format::ProtoBinary,
+    Format::CapnProto,
+    Format::DannyPack,
+    Format::Notepack,
+];
+
+/// Build representative relay filters from the sample itself, so that each
+/// one matches a realistic fraction of events
+fn build_filters(events: &[NostrEvent]) -> Vec<(&'static str, Filter)> {
+    let authors = events.iter().step_by(50).map(|e| e.pubkey);
+
+    let tag_values = |letter: &str| -> Vec<String> {
+        events
+            .iter()
+            .flat_map(|e| &e.tags)
+            .filter(|t| t.len() >= 2 && t[0] == letter)
+            .step_by(10)
+            .take(20)
+            .map(|t| t[1].clone())
+            .collect()
+    };
+
+    let mut times: Vec<i64> = events.iter().map(|e| e.created_at).collect();
+    times.sort_unstable();
+    let since = times[times.len() * 45 / 100];
+    let until = times[times.len() * 55 / 100];
+
+    vec![
+        ("kind", Filter::new().kinds([1])),
+        ("authors", Filter::new().authors(authors)),
+        ("tag_p", Filter::new().tag('p', tag_values("p"))),
+        ("time_window", Filter::new().since(since).until(until)),
+        (
+            "combined",
+            Filter::new()
+                .kinds([1, 7])
+                .tag('e', tag_values("e"))
+                .since(times[times.len() / 4]),
+        ),
+    ]
+}
+
+fn bench_filters(c: &mut Criterion) {
+    let events = common::load_sample(1000);
+    if events.is_empty() {
+        eprintln!("No events loaded, skipping benchmarks");
+        return;
+    }
+
+    let encoded: Vec<(Format, Vec<Vec<u8>>)> = FORMATS
+        .iter()
+        .map(|&format| {
+            let data = events.iter().map(|e| stats::serialize(e, format)).collect();
+            (format, data)
+        })
+        .collect();
+
+    for (name, filter) in build_filters(&events) {
+        let expected = events.iter().filter(|e| filter.matches(e)).count();
+        println!(
+            "filter_{}: {} of {} events match",
+            name,
+            expected,
+            events.len()
+        );
+
+        let mut group = c.benchmark_group(format!("filter_{}", name));
+        group.throughput(Throughput::Elements(events.len() as u64));
+
+        group.bench_function("decoded", |b| {
+            b.iter(|| {
+                let matched = events.iter().filter(|e| filter.matches(e)).count();
+                black_box(matched)
+            })
+        });
+
+        for (format, data) in &encoded {
+            // Sanity check: lazy matching must agree with decode-then-match
+            for d in data {
+                let decoded = stats::deserialize(d, *format).unwrap();
+                assert_eq!(
+                    filter.matches_encoded(d, *format).unwrap(),
+                    filter.matches(&decoded),
+                    "{} lazy match disagrees",
+                    format.name()
+                );
+            }
+
+            group.bench_function(format.short_name(), |b| {
+                b.iter(|| {
+                    let matched = data
+                        .iter()
+                        .filter(|d| filter.matches_encoded(d, *format).unwrap())
+                        .count();
+                    black_box(matched)
+                })
+            });
+        }
+
+        group.finish();
+    }
+}
+
+criterion_group! {
+    name = benches;
+    config = common::auto_criterion();
+    targets = bench_filters
+}
+criterion_main!(benches);
@@ -0,0 +1,89 @@
+//! Dataset loading benchmarks
+//!
+//! Compares the sequential directory loaders against the parallel ones.
+//! Other benches pay this cost only when their fixture cache is cold.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+mod common;
+
+use binostr::loader::{
+    default_load_threads, list_data_files, load_from_directory, load_from_directory_parallel,
+    load_limited_from_directory, load_limited_from_directory_parallel,
+};
+
+fn bench_load_limited(c: &mut Criterion) {
+    if list_data_files(common::DATA_DIR).map_or(true, |f| f.is_empty()) {
+        eprintln!(
+            "No data files in {}, skipping loading benchmarks",
+            common::DATA_DIR
+        );
+        return;
+    }
+
+    let mut group = c.benchmark_group("load_limited");
+    group.sample_size(10);
+
+    let mut thread_counts = vec![2, default_load_threads()];
+    thread_counts.sort_unstable();
+    thread_counts.dedup();
+
+    for limit in [10_000, 50_000] {
+        group.throughput(Throughput::Elements(limit as u64));
+
+        group.bench_with_input(
+            BenchmarkId::new("sequential", limit),
+            &limit,
+            |b, &limit| {
+                b.iter(|| black_box(load_limited_from_directory(common::DATA_DIR, limit).unwrap()))
+            },
+        );
+
+        for &threads in &thread_counts {
+            group.bench_with_input(
+                BenchmarkId::new(format!("parallel_{}t", threads), limit),
+                &limit,
+                |b, &limit| {
+                    b.iter(|| {
+                        black_box(
+                            load_limited_from_directory_parallel(common::DATA_DIR, limit, threads)
+                                .unwrap(),
+                        )
+                    })
+                },
+            );
+        }
+    }
+
+    group.finish();
+}
+
+fn bench_load_all(c: &mut Criterion) {
+    if list_data_files(common::DATA_DIR).map_or(true, |f| f.is_empty()) {
+        return;
+    }
+
+    let mut group = c.benchmark_group("load_all");
+    group.sample_size(10);
+
+    group.bench_function("sequential", |b| {
+        b.iter(|| black_box(load_from_directory(common::DATA_DIR).unwrap()))
+    });
+
+    group.bench_function("parallel", |b| {
+        b.iter(|| {
+            black_box(
+                load_from_directory_parallel(common::DATA_DIR, default_load_threads()).unwrap(),
+            )
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group! {
+    name = benches;
+    config = common::auto_criterion();
+    targets = bench_load_limited, bench_load_all
+}
+criterion_main!(benches);
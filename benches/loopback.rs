@@ -0,0 +1,97 @@
+//! End-to-end loopback relay throughput
+//!
+//! Streams the sample from a relay stand-in on 127.0.0.1 to a client that
+//! decodes every event, per format and wire compression mode. Prints events/sec,
+//! bytes on the wire and client CPU time per event, then lets Criterion time
+//! the uncompressed and streaming-zstd runs.
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+
+mod common;
+
+use binostr::envelope::Compression;
+use binostr::loopback::{self, WireCompression};
+use binostr::stats::Format;
+
+const FORMATS: &[Format] = &[
+    Format::Json,
+    Format::CborPacked,
+    Format::ProtoBinary,
+    Format::CapnProto,
+    Format::DannyPack,
+    Format::Notepack,
+];
+
+const MODES: &[WireCompression] = &[
+    WireCompression::None,
+    WireCompression::PerMessage(Compression::Gzip),
+    WireCompression::PerMessage(Compression::Zstd),
+    WireCompression::Streaming(Compression::Gzip),
+    WireCompression::Streaming(Compression::Zstd),
+];
+
+fn print_report(events: &[binostr::NostrEvent]) {
+    println!("\n{}", "=".repeat(80));
+    println!("LOOPBACK RELAY THROUGHPUT ({} events)", events.len());
+    println!("{}", "=".repeat(80));
+    println!(
+        "{:<14} {:<12} {:>12} {:>12} {:>14} {:>12}",
+        "Format", "Compression", "events/s", "wire B/ev", "client CPU µs", "wire MB"
+    );
+    println!("{}", "-".repeat(80));
+
+    for &format in FORMATS {
+        for &mode in MODES {
+            match loopback::run(events, format, mode) {
+                Ok(report) => println!(
+                    "{:<14} {:<12} {:>12.0} {:>12.1} {:>14} {:>12.2}",
+                    format.name(),
+                    mode.name(),
+                    report.events_per_sec(),
+                    report.wire_bytes_per_event(),
+                    report
+                        .client_cpu_per_event_us()
+                        .map(|us| format!("{:.2}", us))
+                        .unwrap_or_else(|| "n/a".to_string()),
+                    report.wire_bytes as f64 / (1024.0 * 1024.0),
+                ),
+                Err(e) => println!("{:<14} {:<12} error: {}", format.name(), mode.name(), e),
+            }
+        }
+    }
+    println!();
+}
+
+fn bench_loopback(c: &mut Criterion) {
+    let events = common::load_sample(10_000);
+    if events.is_empty() {
+        eprintln!("No events loaded, skipping benchmarks");
+        return;
+    }
+
+    print_report(&events);
+
+    for mode in [
+        WireCompression::None,
+        WireCompression::Streaming(Compression::Zstd),
+    ] {
+        let mut group = c.benchmark_group(format!("loopback_{}", mode.name()));
+        group.throughput(Throughput::Elements(events.len() as u64));
+        group.sample_size(10);
+
+        for &format in FORMATS {
+            group.bench_function(format.short_name(), |b| {
+                b.iter(|| loopback::run(&events, format, mode).unwrap())
+            });
+        }
+
+        group.finish();
+    }
+}
+
+criterion_group! {
+    name = benches;
+    config = common::auto_criterion();
+    targets = bench_loopback
+}
+criterion_main!(benches);
@@ -0,0 +1,244 @@
+//! Relay protocol message benchmarks
+//!
+//! Prints the size of each NIP-01 message in every encoding, including the
+//! framing overhead an EVENT message adds on top of the bare event, then
+//! measures encode/decode speed of a relay's EVENT stream and of REQs.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+
+mod common;
+
+use binostr::filter::Filter;
+use binostr::message::{self, ClientMessage, MessageError, RelayMessage};
+use binostr::{cbor, dannypack, json, proto, NostrEvent};
+
+type Encode<M> = fn(&M) -> Vec<u8>;
+type Decode<M> = fn(&[u8]) -> Result<M, MessageError>;
+
+struct Encoding {
+    name: &'static str,
+    event: fn(&NostrEvent) -> Vec<u8>,
+    relay: (Encode<RelayMessage>, Decode<RelayMessage>),
+    client: (Encode<ClientMessage>, Decode<ClientMessage>),
+}
+
+const ENCODINGS: &[Encoding] = &[
+    Encoding {
+        name: "json",
+        event: json::serialize,
+        relay: (
+            message::json::serialize_relay,
+            message::json::deserialize_relay,
+        ),
+        client: (
+            message::json::serialize_client,
+            message::json::deserialize_client,
+        ),
+    },
+    Encoding {
+        name: "cbor",
+        event: cbor::packed::serialize,
+        relay: (
+            message::cbor::serialize_relay,
+            message::cbor::deserialize_relay,
+        ),
+        client: (
+            message::cbor::serialize_client,
+            message::cbor::deserialize_client,
+        ),
+    },
+    Encoding {
+        name: "proto",
+        event: proto::binary::serialize,
+        relay: (
+            message::proto::serialize_relay,
+            message::proto::deserialize_relay,
+        ),
+        client: (
+            message::proto::serialize_client,
+            message::proto::deserialize_client,
+        ),
+    },
+    Encoding {
+        name: "dannypack",
+        event: dannypack::serialize,
+        relay: (
+            message::dannypack::serialize_relay,
+            message::dannypack::deserialize_relay,
+        ),
+        client: (
+            message::dannypack::serialize_client,
+            message::dannypack::deserialize_client,
+        ),
+    },
+];
+
+/// Typical subscription id: 16 random hex chars, as many clients generate
+const SUBSCRIPTION_ID: &str = "3f9a0c51d27be846";
+
+fn relay_events(events: &[NostrEvent]) -> Vec<RelayMessage> {
+    events
+        .iter()
+        .map(|event| RelayMessage::Event {
+            subscription_id: SUBSCRIPTION_ID.to_string(),
+            event: event.clone(),
+        })
+        .collect()
+}
+
+/// A home-feed REQ: follows' notes and reposts, plus replies to the user
+fn feed_req(events: &[NostrEvent]) -> ClientMessage {
+    let authors: Vec<_> = events.iter().take(100).map(|e| e.pubkey).collect();
+    ClientMessage::Req {
+        subscription_id: SUBSCRIPTION_ID.to_string(),
+        filters: vec![
+            Filter::new()
+                .authors(authors)
+                .kinds([1, 6])
+                .since(1_700_000_000)
+                .limit(500),
+            Filter::new()
+                .kinds([1, 7])
+                .tag('p', [hex::encode(events[0].pubkey)])
+                .limit(100),
+        ],
+    }
+}
+
+fn control_messages() -> Vec<(&'static str, RelayMessage)> {
+    vec![
+        (
+            "OK",
+            RelayMessage::Ok {
+                event_id: [0xab; 32],
+                accepted: true,
+                message: String::new(),
+            },
+        ),
+        ("EOSE", RelayMessage::Eose(SUBSCRIPTION_ID.to_string())),
+        (
+            "CLOSED",
+            RelayMessage::Closed {
+                subscription_id: SUBSCRIPTION_ID.to_string(),
+                message: "error: too many subscriptions".to_string(),
+            },
+        ),
+        (
+            "COUNT",
+            RelayMessage::Count {
+                subscription_id: SUBSCRIPTION_ID.to_string(),
+                count: 1234,
+            },
+        ),
+    ]
+}
+
+fn print_size_report(events: &[NostrEvent]) {
+    let messages = relay_events(events);
+    let req = feed_req(events);
+    let reactions: Vec<_> = messages
+        .iter()
+        .filter(|m| matches!(m, RelayMessage::Event { event, .. } if event.kind == 7))
+        .collect();
+
+    println!("\n{}", "=".repeat(78));
+    println!("RELAY PROTOCOL MESSAGE SIZES ({} events)", events.len());
+    println!("{}", "=".repeat(78));
+    println!(
+        "{:<10} {:>12} {:>12} {:>14} {:>12} {:>10}",
+        "Encoding", "EVENT avg", "framing", "kind 7 EVENT", "feed REQ", "OK/EOSE"
+    );
+    println!("{}", "-".repeat(78));
+
+    for encoding in ENCODINGS {
+        let (serialize, _) = encoding.relay;
+        let total: usize = messages.iter().map(|m| serialize(m).len()).sum();
+        let bare: usize = events.iter().map(|e| (encoding.event)(e).len()).sum();
+        let reaction_avg = if reactions.is_empty() {
+            0.0
+        } else {
+            reactions.iter().map(|m| serialize(m).len()).sum::<usize>() as f64
+                / reactions.len() as f64
+        };
+        let control = control_messages();
+
+        println!(
+            "{:<10} {:>12.1} {:>12.1} {:>14.1} {:>12} {:>5}/{:<4}",
+            encoding.name,
+            total as f64 / messages.len() as f64,
+            (total - bare) as f64 / messages.len() as f64,
+            reaction_avg,
+            (encoding.client.0)(&req).len(),
+            serialize(&control[0].1).len(),
+            serialize(&control[1].1).len(),
+        );
+    }
+
+    println!("\nControl messages (bytes):");
+    for (name, message) in control_messages() {
+        let sizes: Vec<String> = ENCODINGS
+            .iter()
+            .map(|e| format!("{}={}", e.name, (e.relay.0)(&message).len()))
+            .collect();
+        println!("  {:<8} {}", name, sizes.join("  "));
+    }
+    println!();
+}
+
+fn bench_messages(c: &mut Criterion) {
+    let events = common::load_sample(1000);
+    if events.is_empty() {
+        eprintln!("No events loaded, skipping benchmarks");
+        return;
+    }
+
+    print_size_report(&events);
+
+    let messages = relay_events(&events);
+
+    let mut group = c.benchmark_group("message_event_serialize");
+    group.throughput(Throughput::Elements(messages.len() as u64));
+    for encoding in ENCODINGS {
+        let (serialize, _) = encoding.relay;
+        group.bench_function(encoding.name, |b| {
+            b.iter(|| {
+                for message in &messages {
+                    black_box(serialize(black_box(message)));
+                }
+            })
+        });
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("message_event_deserialize");
+    group.throughput(Throughput::Elements(messages.len() as u64));
+    for encoding in ENCODINGS {
+        let (serialize, deserialize) = encoding.relay;
+        let data: Vec<Vec<u8>> = messages.iter().map(serialize).collect();
+        group.bench_function(encoding.name, |b| {
+            b.iter(|| {
+                for d in &data {
+                    black_box(deserialize(black_box(d)).unwrap());
+                }
+            })
+        });
+    }
+    group.finish();
+
+    let req = feed_req(&events);
+    let mut group = c.benchmark_group("message_req_roundtrip");
+    for encoding in ENCODINGS {
+        let (serialize, deserialize) = encoding.client;
+        group.bench_function(encoding.name, |b| {
+            b.iter(|| black_box(deserialize(&serialize(black_box(&req))).unwrap()))
+        });
+    }
+    group.finish();
+}
+
+criterion_group! {
+    name = benches;
+    config = common::auto_criterion();
+    targets = bench_messages
+}
+criterion_main!(benches);
@@ -0,0 +1,116 @@
+//! In-memory event store benchmarks
+//!
+//! Loads the sample into an [`EventStore`] per format, prints stored size and
+//! how many events survive the NIP-01 replacement rules, then times inserts
+//! and a set of indexed queries.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+
+mod common;
+
+use binostr::filter::Filter;
+use binostr::stats::Format;
+use binostr::store::EventStore;
+use binostr::NostrEvent;
+
+const FORMATS: &[Format] = &[
+    Format::Json,
+    Format::CborPacked,
+    Format::ProtoBinary,
+    Format::CapnProto,
+    Format::DannyPack,
+    Format::Notepack,
+];
+
+fn build_queries(events: &[NostrEvent]) -> Vec<(&'static str, Filter)> {
+    let authors: Vec<[u8; 32]> = events.iter().step_by(50).map(|e| e.pubkey).collect();
+    let p_tags: Vec<String> = events
+        .iter()
+        .flat_map(|e| &e.tags)
+        .filter(|t| t.len() >= 2 && t[0] == "p")
+        .step_by(10)
+        .take(20)
+        .map(|t| t[1].clone())
+        .collect();
+
+    vec![
+        ("recent", Filter::new().limit(100)),
+        ("kind_1", Filter::new().kinds([1]).limit(100)),
+        ("authors", Filter::new().authors(authors.clone())),
+        ("profiles", Filter::new().kinds([0]).authors(authors)),
+        ("mentions", Filter::new().tag('p', p_tags).limit(50)),
+    ]
+}
+
+fn print_report(events: &[NostrEvent]) {
+    println!("\n{}", "=".repeat(60));
+    println!("EVENT STORE ({} events)", events.len());
+    println!("{}", "=".repeat(60));
+    println!(
+        "{:<14} {:>10} {:>14} {:>12}",
+        "Format", "stored", "encoded bytes", "B/event"
+    );
+    println!("{}", "-".repeat(60));
+
+    for &format in FORMATS {
+        let mut store = EventStore::new(format);
+        store.extend(events);
+        println!(
+            "{:<14} {:>10} {:>14} {:>12.1}",
+            format.name(),
+            store.len(),
+            store.encoded_bytes(),
+            store.encoded_bytes() as f64 / store.len().max(1) as f64
+        );
+    }
+    println!();
+}
+
+fn bench_store(c: &mut Criterion) {
+    let events = common::load_sample(5000);
+    if events.is_empty() {
+        eprintln!("No events loaded, skipping benchmarks");
+        return;
+    }
+
+    print_report(&events);
+
+    let mut group = c.benchmark_group("store_insert");
+    group.throughput(Throughput::Elements(events.len() as u64));
+    group.sample_size(10);
+    for &format in FORMATS {
+        group.bench_function(format.short_name(), |b| {
+            b.iter(|| {
+                let mut store = EventStore::new(format);
+                black_box(store.extend(&events))
+            })
+        });
+    }
+    group.finish();
+
+    let stores: Vec<EventStore> = FORMATS
+        .iter()
+        .map(|&format| {
+            let mut store = EventStore::new(format);
+            store.extend(&events);
+            store
+        })
+        .collect();
+
+    for (name, filter) in build_queries(&events) {
+        let mut group = c.benchmark_group(format!("store_query_{}", name));
+        for store in &stores {
+            group.bench_function(store.format().short_name(), |b| {
+                b.iter(|| black_box(store.query(&filter).unwrap()))
+            });
+        }
+        group.finish();
+    }
+}
+
+criterion_group! {
+    name = benches;
+    config = common::auto_criterion();
+    targets = bench_store
+}
+criterion_main!(benches);
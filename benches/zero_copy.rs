@@ -3,6 +3,8 @@
 //! Compares the ability of different formats to read specific fields
 //! without fully deserializing the entire event. This is Cap'n Proto's
 //! main strength and is important for relay filtering use cases.
+//!
+//! For end-to-end NIP-01 filter throughput per format, see `filter.rs`.
 
 use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
 
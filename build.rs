@@ -2,7 +2,14 @@ use std::io::Result;
 
 fn main() -> Result<()> {
     // Compile Protocol Buffers schemas
-    prost_build::compile_protos(&["docs/nostr.proto", "docs/nostr_binary.proto"], &["docs/"])?;
+    prost_build::compile_protos(
+        &[
+            "docs/nostr.proto",
+            "docs/nostr_binary.proto",
+            "docs/nostr_message.proto",
+        ],
+        &["docs/"],
+    )?;
 
     // Compile Cap'n Proto schema
     capnpc::CompilerCommand::new()
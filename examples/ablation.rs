@@ -0,0 +1,147 @@
+//! Ablation study of the encoding optimizations
+//!
+//! Runs every [`pipeline`] optimization alone and left out of the full set
+//! on each base format, and reports the bytes (raw and zstd) and time each
+//! one contributes. The sample is encoded as one batch, newest first like a
+//! relay response, so pubkey interning and created_at deltas gain with
+//! sample size.
+//!
+//! Usage: cargo run --release --example ablation
+//!
+//! Optional arguments:
+//!   --sample-size 2000   events in the batch
+//!   --format dannypack   one base format (short name) instead of all
+//!
+//! [`pipeline`]: binostr::pipeline
+
+use std::env;
+
+use binostr::pipeline::{Ablation, Optimization};
+use binostr::sampler::EventSampler;
+use binostr::stats::Format;
+use binostr::NostrEvent;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = env::args().collect();
+
+    let sample_size = parse_arg(&args, "--sample-size").unwrap_or(2_000);
+    let formats: Vec<Format> =
+        match parse_arg::<String>(&args, "--format") {
+            Some(name) => vec![Format::from_short_name(&name)
+                .ok_or_else(|| format!("unknown format: {}", name))?],
+            None => Format::all().to_vec(),
+        };
+
+    eprintln!("Loading {} events from data directory...", sample_size);
+    let mut sampler = EventSampler::from_directory("data", sample_size * 2)?;
+    let mut events: Vec<NostrEvent> = sampler
+        .random_sample(sample_size)
+        .into_iter()
+        .cloned()
+        .collect();
+    events.sort_by_key(|event| std::cmp::Reverse(event.created_at));
+
+    let mut ablations = Vec::new();
+    for &format in &formats {
+        eprintln!("Measuring {}...", format.name());
+        let ablation = Ablation::run(format, &events)?;
+        print_ablation(&ablation, events.len());
+        ablations.push(ablation);
+    }
+
+    if ablations.len() > 1 {
+        print_summary(&ablations);
+    }
+    Ok(())
+}
+
+fn print_ablation(ablation: &Ablation, events: usize) {
+    let baseline = &ablation.baseline;
+    let full = &ablation.full;
+
+    println!("\n{}", "=".repeat(86));
+    println!("{} ({} events)", ablation.base.name(), events);
+    println!("{}", "=".repeat(86));
+    println!(
+        "No optimizations:  {:>10} bytes  {:>10} zstd  encode {:>8.2} ms  decode {:>8.2} ms",
+        baseline.bytes,
+        baseline.zstd_bytes,
+        ms(baseline.encode.as_secs_f64()),
+        ms(baseline.decode.as_secs_f64())
+    );
+    println!(
+        "All optimizations: {:>10} bytes  {:>10} zstd  encode {:>8.2} ms  decode {:>8.2} ms",
+        full.bytes,
+        full.zstd_bytes,
+        ms(full.encode.as_secs_f64()),
+        ms(full.decode.as_secs_f64())
+    );
+    println!();
+    println!(
+        "{:<18} {:>11} {:>11} {:>11} {:>11} {:>10} {:>10}",
+        "Optimization", "alone", "alone zstd", "marginal", "marg. zstd", "+enc ms", "+dec ms"
+    );
+    println!("{}", "-".repeat(86));
+    for opt in Optimization::ALL {
+        let (alone, alone_zstd) = ablation.alone_savings(opt);
+        let (marginal, marginal_zstd) = ablation.marginal_savings(opt);
+        let (encode, decode) = ablation.marginal_time(opt);
+        println!(
+            "{:<18} {:>10.1}% {:>10.1}% {:>10.1}% {:>10.1}% {:>10.2} {:>10.2}",
+            opt.name(),
+            percent(alone, baseline.bytes),
+            percent(alone_zstd, baseline.zstd_bytes),
+            percent(marginal, baseline.bytes),
+            percent(marginal_zstd, baseline.zstd_bytes),
+            ms(encode),
+            ms(decode)
+        );
+    }
+}
+
+/// Marginal raw savings of each optimization across base formats
+fn print_summary(ablations: &[Ablation]) {
+    println!("\n{}", "=".repeat(86));
+    println!("MARGINAL RAW SAVINGS (% of unoptimized size, on top of all others)");
+    println!("{}", "=".repeat(86));
+    print!("{:<18}", "Format");
+    for opt in Optimization::ALL {
+        print!(" {:>13}", short(opt));
+    }
+    println!();
+    println!("{}", "-".repeat(86));
+    for ablation in ablations {
+        print!("{:<18}", ablation.base.name());
+        for opt in Optimization::ALL {
+            let (marginal, _) = ablation.marginal_savings(opt);
+            print!(" {:>12.1}%", percent(marginal, ablation.baseline.bytes));
+        }
+        println!();
+    }
+    println!();
+}
+
+fn short(opt: Optimization) -> &'static str {
+    match opt {
+        Optimization::PubkeyInterning => "pubkeys",
+        Optimization::TagDictionary => "dictionary",
+        Optimization::HexCompaction => "hex",
+        Optimization::Base64Compaction => "base64",
+        Optimization::CreatedAtDelta => "created_at",
+    }
+}
+
+fn percent(saved: i64, of: usize) -> f64 {
+    100.0 * saved as f64 / of as f64
+}
+
+fn ms(seconds: f64) -> f64 {
+    seconds * 1000.0
+}
+
+fn parse_arg<T: std::str::FromStr>(args: &[String], name: &str) -> Option<T> {
+    args.iter()
+        .position(|a| a == name)
+        .and_then(|i| args.get(i + 1))
+        .and_then(|s| s.parse().ok())
+}
@@ -2,6 +2,7 @@
 //!
 //! Run with: cargo run --example analyze_data
 
+use binostr::event::KindClass;
 use binostr::sampler::EventSampler;
 use binostr::stats::{generate_size_report, DistributionAnalysis};
 
@@ -58,6 +59,20 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     }
     println!();
 
+    println!("=== Kind Class Distribution ===");
+    for class in KindClass::ALL {
+        let count = dist.by_class.get(&class).copied().unwrap_or(0);
+        let pct = 100.0 * count as f64 / dist.total_events as f64;
+        println!(
+            "  {:>20}: {:>6} events ({:>5.1}%), avg {:.0} bytes JSON",
+            format!("{}", class),
+            count,
+            pct,
+            dist.avg_class_json_size(class)
+        );
+    }
+    println!();
+
     // Generate size report for a smaller sample
     let sample: Vec<_> = sampler.random_sample(10_000).into_iter().cloned().collect();
     println!("=== Size Comparison Report (10000 random events) ===");
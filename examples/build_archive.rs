@@ -0,0 +1,113 @@
+//! Convert .pb.gz day files into indexed archives
+//!
+//! Run with: cargo run --release --example build_archive
+//!
+//! Optional arguments:
+//!   cargo run --release --example build_archive -- --format dannypack
+//!   cargo run --release --example build_archive -- --data-dir data --out-dir data/archive
+//!   cargo run --release --example build_archive -- --block-size 131072
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::time::Instant;
+
+use binostr::archive::{ArchiveReader, ArchiveWriter, DEFAULT_BLOCK_SIZE};
+use binostr::loader::{list_data_files, EventLoader};
+use binostr::stats::Format;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = env::args().collect();
+
+    let data_dir = parse_arg::<String>(&args, "--data-dir").unwrap_or_else(|| "data".into());
+    let out_dir =
+        parse_arg::<String>(&args, "--out-dir").unwrap_or_else(|| format!("{}/archive", data_dir));
+    let block_size = parse_arg(&args, "--block-size").unwrap_or(DEFAULT_BLOCK_SIZE);
+    let format = match parse_arg::<String>(&args, "--format") {
+        Some(name) => Format::from_short_name(&name).ok_or_else(|| {
+            let names: Vec<_> = Format::all().iter().map(|f| f.short_name()).collect();
+            format!(
+                "Unknown format '{}', expected one of: {}",
+                name,
+                names.join(", ")
+            )
+        })?,
+        None => Format::ProtoBinary,
+    };
+
+    fs::create_dir_all(&out_dir)?;
+    println!(
+        "Converting {} -> {} ({}, {} byte blocks)",
+        data_dir,
+        out_dir,
+        format.name(),
+        block_size
+    );
+    println!();
+    println!(
+        "{:<24} {:>9} {:>12} {:>12} {:>7} {:>8}",
+        "File", "Events", ".pb.gz", "Archive", "Ratio", "Blocks"
+    );
+    println!("{}", "-".repeat(78));
+
+    for path in list_data_files(&data_dir)? {
+        let events = EventLoader::open(&path)?.load_all()?;
+        let stem = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .map(|n| n.trim_end_matches(".gz").trim_end_matches(".pb"))
+            .unwrap_or("events");
+        let out_path = Path::new(&out_dir).join(format!("{}.bnar", stem));
+
+        let mut writer = ArchiveWriter::create(&out_path, format)?.with_block_size(block_size);
+        writer.extend(&events)?;
+        writer.finish()?;
+
+        let source_size = fs::metadata(&path)?.len();
+        let archive_size = fs::metadata(&out_path)?.len();
+        let reader = ArchiveReader::open(&out_path)?;
+
+        println!(
+            "{:<24} {:>9} {:>12} {:>12} {:>6.1}% {:>8}",
+            stem,
+            events.len(),
+            source_size,
+            archive_size,
+            100.0 * archive_size as f64 / source_size as f64,
+            reader.blocks().len()
+        );
+
+        if let (Some(first), Some(last)) = (events.first(), events.last()) {
+            let start = Instant::now();
+            let found = reader.get(&last.id)?;
+            let get_time = start.elapsed();
+            assert_eq!(found.as_ref(), Some(last));
+
+            let start = Instant::now();
+            let in_range = reader.range(first.created_at, first.created_at + 3600)?;
+            let range_time = start.elapsed();
+
+            let start = Instant::now();
+            let reactions = reader.by_kind(7)?;
+            let kind_time = start.elapsed();
+
+            println!(
+                "    get(id) {:?} | range(1h) {} events in {:?} | by_kind(7) {} events in {:?}",
+                get_time,
+                in_range.len(),
+                range_time,
+                reactions.len(),
+                kind_time
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn parse_arg<T: std::str::FromStr>(args: &[String], name: &str) -> Option<T> {
+    args.iter()
+        .position(|a| a == name)
+        .and_then(|i| args.get(i + 1))
+        .and_then(|s| s.parse().ok())
+}
@@ -3,15 +3,11 @@
 //! Usage: cargo run --release --example create_sample -- [num_events]
 //! Default: 50000 events
 
-use binostr::proto_gen::nostr::ProtoEvent;
-use binostr::{EventLoader, NostrEvent, EXCLUDED_KINDS};
-use flate2::write::GzEncoder;
-use flate2::Compression;
-use prost::Message;
+use binostr::loader::list_data_files;
+use binostr::writer::{dedup_by_id, write_events};
+use binostr::{EventLoader, EXCLUDED_KINDS};
 use rand::seq::SliceRandom;
 use rand::SeedableRng;
-use std::fs::{self, File};
-use std::io::Write;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args: Vec<String> = std::env::args().collect();
@@ -22,43 +18,36 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Load ALL events from all files
     let mut all_events = Vec::new();
 
-    for entry in fs::read_dir("data")? {
-        let entry = entry?;
-        let path = entry.path();
+    for path in list_data_files("data")? {
         let name = path.file_name().unwrap_or_default().to_string_lossy();
 
-        if !path.is_file() || !name.ends_with(".pb.gz") || name.starts_with("sample") {
+        if !name.ends_with(".pb.gz") || name.starts_with("sample") {
             continue;
         }
 
         println!("Loading from {}...", path.display());
 
-        let mut loader = EventLoader::open(&path)?;
-        let mut count = 0;
-        let mut errors = 0;
-
-        loop {
-            match loader.next_event() {
-                Ok(Some(event)) => {
-                    all_events.push(event);
-                    count += 1;
-                }
-                Ok(None) => break, // EOF
-                Err(_) => {
-                    errors += 1;
-                    if errors > 1000 {
-                        println!("  Too many errors, moving to next file");
-                        break;
-                    }
-                }
-            }
+        let loader = EventLoader::open_lenient(&path)?;
+        let (events, report) = loader.load_all_with_report()?;
+
+        println!(
+            "  Loaded {} events (skipped {} corrupt records)",
+            report.loaded,
+            report.skipped_count()
+        );
+        for skipped in report.skipped.iter().take(5) {
+            println!("    offset {}: {}", skipped.offset, skipped.reason);
         }
 
-        println!("  Loaded {} events (skipped {} errors)", count, errors);
+        all_events.extend(events);
     }
 
     println!("\nTotal events loaded: {}", all_events.len());
 
+    // The same event can appear in several day files
+    let duplicates = dedup_by_id(&mut all_events);
+    println!("Removed {} duplicate events", duplicates);
+
     // Filter out excluded/unknown kinds
     let before_filter = all_events.len();
     all_events.retain(|e| !EXCLUDED_KINDS.contains(&e.kind));
@@ -82,33 +71,9 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     println!("Selected {} random events", sample.len());
 
-    // Write to new file using varint length prefix (same format as input)
+    // Write to new file (same format as input)
     let output_path = "data/sample.pb.gz";
-    let file = File::create(output_path)?;
-    let mut encoder = GzEncoder::new(file, Compression::default());
-
-    for event in &sample {
-        let proto_event = ProtoEvent {
-            id: hex::encode(event.id),
-            pubkey: hex::encode(event.pubkey),
-            created_at: event.created_at,
-            kind: event.kind as i32,
-            tags: event
-                .tags
-                .iter()
-                .map(|t| binostr::proto_gen::nostr::Tag { values: t.clone() })
-                .collect(),
-            content: event.content.clone(),
-            sig: hex::encode(event.sig),
-        };
-
-        let buf = proto_event.encode_to_vec();
-        // Write varint length prefix
-        write_varint(&mut encoder, buf.len() as u64)?;
-        encoder.write_all(&buf)?;
-    }
-
-    encoder.finish()?;
+    write_events(output_path, &sample)?;
 
     // Print file size
     let metadata = std::fs::metadata(output_path)?;
@@ -126,7 +91,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         *kinds.entry(event.kind).or_insert(0) += 1;
     }
     let mut kinds: Vec<_> = kinds.into_iter().collect();
-    kinds.sort_by(|a, b| b.1.cmp(&a.1));
+    kinds.sort_by_key(|k| std::cmp::Reverse(k.1));
 
     println!("\nKind distribution:");
     for (kind, count) in kinds.iter().take(15) {
@@ -143,19 +108,3 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
-
-/// Write a varint to a writer
-fn write_varint<W: Write>(writer: &mut W, mut value: u64) -> std::io::Result<()> {
-    loop {
-        let mut byte = (value & 0x7F) as u8;
-        value >>= 7;
-        if value != 0 {
-            byte |= 0x80;
-        }
-        writer.write_all(&[byte])?;
-        if value == 0 {
-            break;
-        }
-    }
-    Ok(())
-}
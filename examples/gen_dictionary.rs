@@ -0,0 +1,192 @@
+//! Generate a static tag dictionary version from the dataset
+//!
+//! Counts tag names and relay URLs across the data directory and prints
+//! the most frequent as sorted Rust tables, ready to append to
+//! `src/dictionary.rs` as a new version. The header records the arguments
+//! used, and the sample is seeded, so a version can be regenerated from the
+//! same data. Published versions are never regenerated in place; `--check`
+//! regenerates one and lists the entries that differ from it.
+//!
+//! Usage: cargo run --release --example gen_dictionary -- --version 2
+//!        cargo run --release --example gen_dictionary -- --check 1
+//!
+//! Optional arguments:
+//!   --sample-size 200000   events to sample
+//!   --seed 1               sampler seed
+//!   --tag-names 64         tag name table size (at most 255; with --check,
+//!                          the published table's size)
+//!   --relays 128           relay table size (likewise)
+//!   --min-count 100        drop entries seen fewer times
+
+use std::collections::HashMap;
+use std::env;
+
+use binostr::dictionary::{self, Dictionary};
+use binostr::loader;
+use binostr::sampler::EventSampler;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = env::args().collect();
+
+    let published = match parse_arg::<u8>(&args, "--check") {
+        Some(version) => Some(
+            dictionary::get(version)
+                .ok_or_else(|| format!("dictionary version {} is not published", version))?,
+        ),
+        None => None,
+    };
+    let version = match published {
+        Some(dictionary) => dictionary.version,
+        None => parse_arg(&args, "--version").ok_or("--version or --check is required")?,
+    };
+    if published.is_none() && dictionary::get(version).is_some() {
+        return Err(format!(
+            "dictionary version {} is already published; use --check {} to verify it",
+            version, version
+        )
+        .into());
+    }
+    let sample_size = parse_arg(&args, "--sample-size").unwrap_or(200_000);
+    let seed = parse_arg(&args, "--seed").unwrap_or(1);
+    let tag_name_limit = parse_arg::<usize>(&args, "--tag-names")
+        .or(published.map(|d| d.tag_names.len()))
+        .unwrap_or(64)
+        .min(255);
+    let relay_limit = parse_arg::<usize>(&args, "--relays")
+        .or(published.map(|d| d.relays.len()))
+        .unwrap_or(128)
+        .min(255);
+    let min_count = parse_arg(&args, "--min-count").unwrap_or(100);
+
+    eprintln!("Loading {} events from data directory...", sample_size);
+    let events = loader::load_limited_from_directory_parallel(
+        "data",
+        sample_size,
+        loader::default_load_threads(),
+    )?;
+    let mut sampler = EventSampler::with_seed(events, seed);
+    sampler.filter_excluded_kinds();
+    let events = sampler.random_sample(sample_size);
+    eprintln!("Counting tags in {} events...", events.len());
+
+    let mut tag_names: HashMap<&str, usize> = HashMap::new();
+    let mut relays: HashMap<&str, usize> = HashMap::new();
+    for event in &events {
+        for tag in &event.tags {
+            let Some((name, values)) = tag.split_first() else {
+                continue;
+            };
+            *tag_names.entry(name).or_default() += 1;
+            for value in values {
+                if value.starts_with("wss://") || value.starts_with("ws://") {
+                    *relays.entry(value).or_default() += 1;
+                }
+            }
+        }
+    }
+
+    let tag_names = top(&tag_names, tag_name_limit, min_count);
+    let relays = top(&relays, relay_limit, min_count);
+
+    if let Some(dictionary) = published {
+        return check(dictionary, &tag_names, &relays);
+    }
+
+    println!(
+        "// Generated by examples/gen_dictionary.rs --version {} --sample-size {} --seed {} \
+         --tag-names {} --relays {} --min-count {}",
+        version, sample_size, seed, tag_name_limit, relay_limit, min_count
+    );
+    print_table(&format!("TAG_NAMES_V{}", version), &tag_names);
+    println!();
+    print_table(&format!("RELAYS_V{}", version), &relays);
+
+    eprintln!(
+        "{} tag names and {} relays; register V{} in dictionary::get",
+        tag_names.len(),
+        relays.len(),
+        version
+    );
+    Ok(())
+}
+
+/// The `limit` most frequent entries seen at least `min_count` times, with
+/// their counts, sorted by text so the tables can be binary searched
+fn top<'a>(
+    counts: &HashMap<&'a str, usize>,
+    limit: usize,
+    min_count: usize,
+) -> Vec<(&'a str, usize)> {
+    let mut entries: Vec<_> = counts
+        .iter()
+        .map(|(&entry, &count)| (entry, count))
+        .filter(|&(_, count)| count >= min_count)
+        .collect();
+    entries.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    entries.truncate(limit);
+    entries.sort_by(|a, b| a.0.cmp(b.0));
+    entries
+}
+
+fn print_table(name: &str, entries: &[(&str, usize)]) {
+    println!("const {}: [&str; {}] = [", name, entries.len());
+    for (entry, count) in entries {
+        println!("    {:?}, // {}", entry, count);
+    }
+    println!("];");
+}
+
+/// Compare a published version with the regenerated tables
+fn check(
+    dictionary: &Dictionary,
+    tag_names: &[(&str, usize)],
+    relays: &[(&str, usize)],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let differences = check_table("tag names", dictionary.tag_names, tag_names)
+        + check_table("relays", dictionary.relays, relays);
+    if differences > 0 {
+        return Err(format!(
+            "V{} differs from the regenerated tables in {} entries",
+            dictionary.version, differences
+        )
+        .into());
+    }
+    println!("V{} matches the regenerated tables", dictionary.version);
+    Ok(())
+}
+
+/// Print entries only in the published or only in the regenerated table;
+/// returns how many there are
+fn check_table(name: &str, published: &[&str], regenerated: &[(&str, usize)]) -> usize {
+    let missing: Vec<&str> = published
+        .iter()
+        .copied()
+        .filter(|entry| !regenerated.iter().any(|(e, _)| e == entry))
+        .collect();
+    let added: Vec<(&str, usize)> = regenerated
+        .iter()
+        .copied()
+        .filter(|(entry, _)| !published.contains(entry))
+        .collect();
+
+    println!(
+        "{}: {} published, {} regenerated",
+        name,
+        published.len(),
+        regenerated.len()
+    );
+    for entry in &missing {
+        println!("  - {:?}", entry);
+    }
+    for (entry, count) in &added {
+        println!("  + {:?} // {}", entry, count);
+    }
+    missing.len() + added.len()
+}
+
+fn parse_arg<T: std::str::FromStr>(args: &[String], name: &str) -> Option<T> {
+    args.iter()
+        .position(|a| a == name)
+        .and_then(|i| args.get(i + 1))
+        .and_then(|s| s.parse().ok())
+}
@@ -8,9 +8,10 @@
 
 use std::env;
 
+use binostr::compact::CompactOptions;
 use binostr::sampler::EventSampler;
 use binostr::stats::{compute_size_stats, Format};
-use binostr::NostrEvent;
+use binostr::{cbor, dannypack, NostrEvent};
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args: Vec<String> = env::args().collect();
@@ -144,10 +145,66 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             zstd_ratio
         );
     }
+    println!();
+
+    // Encrypted DMs: base64 ciphertext stored as bytes
+    let dms: Vec<NostrEvent> = sampler
+        .sample_kind(4, sample_size)
+        .into_iter()
+        .cloned()
+        .collect();
+    if !dms.is_empty() {
+        report_base64_compaction(&dms);
+    }
 
     Ok(())
 }
 
+fn report_base64_compaction(dms: &[NostrEvent]) {
+    let options = CompactOptions::new().with_base64(true);
+    let content_bytes: usize = dms.iter().map(|e| e.content.len()).sum();
+
+    let sizes = [
+        (
+            "DannyPack",
+            dms.iter()
+                .map(|e| dannypack::serialize(e).len())
+                .sum::<usize>(),
+            dms.iter()
+                .map(|e| dannypack::serialize_with(e, &options).len())
+                .sum::<usize>(),
+        ),
+        (
+            "CBOR Packed",
+            dms.iter().map(|e| cbor::packed::serialize(e).len()).sum(),
+            dms.iter()
+                .map(|e| cbor::packed::serialize_with(e, &options).len())
+                .sum(),
+        ),
+    ];
+
+    println!("🔐 Kind 4 DMs: base64 compaction ({} events):", dms.len());
+    println!(
+        "   Content: {} ({} B avg)",
+        format_bytes(content_bytes),
+        content_bytes / dms.len()
+    );
+    println!("┌──────────────────┬────────────┬────────────┬─────────┐");
+    println!("│ Format           │ Plain      │ Compacted  │ Saved   │");
+    println!("├──────────────────┼────────────┼────────────┼─────────┤");
+    for (name, plain, compacted) in sizes {
+        let saved = 100.0 * (1.0 - compacted as f64 / plain as f64);
+        println!(
+            "│ {:16} │ {:>10} │ {:>10} │ {:>6.1}% │",
+            name,
+            format_bytes(plain),
+            format_bytes(compacted),
+            saved
+        );
+    }
+    println!("└──────────────────┴────────────┴────────────┴─────────┘");
+}
+
 fn parse_arg<T: std::str::FromStr>(args: &[String], name: &str) -> Option<T> {
     args.iter()
         .position(|a| a == name)
@@ -33,7 +33,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 dp_fail += 1;
                 if dp_fail <= 3 {
                     println!("❌ DannyPack mismatch at event {}", i);
-                    println!("   ID: {}", hex::encode(&event.id));
+                    println!("   ID: {}", hex::encode(event.id));
                     println!("   Content len: {} vs {}", event.content.len(), back.content.len());
                 }
             }
@@ -0,0 +1,642 @@
+//! Indexed random-access event archive
+//!
+//! An archive stores events in any [`Format`], grouped into zstd-compressed
+//! blocks, followed by a footer index. The footer lets a reader find an event
+//! by id, or skip blocks by time range or kind, without decompressing the
+//! whole file.
+//!
+//! File layout (all integers little-endian):
+//! ```text
+//! [magic: "BNAR"][version: u8][format id: u8][reserved: 2 bytes]
+//! [block 0][block 1]...            zstd frames of [varint len][record]...
+//! [footer]
+//!   [block_count: u32]
+//!     per block: [offset: u64][compressed_len: u32][raw_len: u32][count: u32]
+//!                [min_created_at: i64][max_created_at: i64][kind bitmap: 32 bytes]
+//!   [index_count: u32]
+//!     per event, sorted by id: [id: 32 bytes][block: u32][offset in block: u32]
+//! [footer_offset: u64][magic: "BNAR"]
+//! ```
+//!
+//! The kind bitmap has 256 bits; kind `k` sets bit `k % 256`. A clear bit
+//! proves a block has no event of that kind, a set bit only that it may.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use memmap2::Mmap;
+
+use crate::event::NostrEvent;
+use crate::stats::{self, Format, FormatError};
+
+const MAGIC: &[u8; 4] = b"BNAR";
+const VERSION: u8 = 1;
+const HEADER_LEN: usize = 8;
+const TRAILER_LEN: usize = 12;
+const BLOCK_ENTRY_LEN: usize = 8 + 4 + 4 + 4 + 8 + 8 + 32;
+const INDEX_ENTRY_LEN: usize = 32 + 4 + 4;
+
+/// Default uncompressed size at which a block is flushed
+pub const DEFAULT_BLOCK_SIZE: usize = 64 * 1024;
+
+/// Largest block size a writer accepts
+pub const MAX_BLOCK_SIZE: usize = 64 * DEFAULT_BLOCK_SIZE;
+
+/// Largest uncompressed block a reader accepts. A block is flushed once it
+/// reaches the block size, so it holds at most one record past it.
+pub const MAX_BLOCK_RAW_LEN: usize = 2 * MAX_BLOCK_SIZE;
+
+/// Default zstd compression level
+pub const DEFAULT_ZSTD_LEVEL: i32 = 3;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ArchiveError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Decode error: {0}")]
+    Format(#[from] FormatError),
+
+    #[error("Unknown format id: {0}")]
+    UnknownFormat(u8),
+
+    #[error("Invalid archive: {0}")]
+    Invalid(&'static str),
+}
+
+/// Per-block metadata stored in the footer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockInfo {
+    /// Byte offset of the compressed block in the file
+    pub offset: u64,
+    pub compressed_len: u32,
+    pub raw_len: u32,
+    /// Number of events in the block
+    pub count: u32,
+    pub min_created_at: i64,
+    pub max_created_at: i64,
+    pub kinds: KindBitmap,
+}
+
+impl BlockInfo {
+    /// Check if the block may hold events with `since <= created_at <= until`
+    pub fn overlaps(&self, since: i64, until: i64) -> bool {
+        self.count > 0 && self.min_created_at <= until && self.max_created_at >= since
+    }
+}
+
+/// 256-bit set of kinds folded modulo 256
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct KindBitmap([u8; 32]);
+
+impl KindBitmap {
+    pub fn insert(&mut self, kind: u16) {
+        let bit = (kind % 256) as usize;
+        self.0[bit / 8] |= 1 << (bit % 8);
+    }
+
+    /// Check if `kind` may be present (false positives for kinds 256 apart)
+    pub fn may_contain(&self, kind: u16) -> bool {
+        let bit = (kind % 256) as usize;
+        self.0[bit / 8] & (1 << (bit % 8)) != 0
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+/// Streams events into an archive
+pub struct ArchiveWriter<W: Write> {
+    writer: W,
+    format: Format,
+    block_size: usize,
+    zstd_level: i32,
+    position: u64,
+    block: Vec<u8>,
+    block_info: BlockInfo,
+    blocks: Vec<BlockInfo>,
+    index: Vec<([u8; 32], u32, u32)>,
+}
+
+impl ArchiveWriter<BufWriter<File>> {
+    /// Create an archive file at `path`
+    pub fn create<P: AsRef<Path>>(path: P, format: Format) -> Result<Self, ArchiveError> {
+        Self::new(BufWriter::new(File::create(path)?), format)
+    }
+}
+
+impl<W: Write> ArchiveWriter<W> {
+    /// Start an archive on `writer`, encoding records in `format`
+    pub fn new(mut writer: W, format: Format) -> Result<Self, ArchiveError> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[VERSION, format.id(), 0, 0])?;
+
+        Ok(Self {
+            writer,
+            format,
+            block_size: DEFAULT_BLOCK_SIZE,
+            zstd_level: DEFAULT_ZSTD_LEVEL,
+            position: HEADER_LEN as u64,
+            block: Vec::with_capacity(DEFAULT_BLOCK_SIZE),
+            block_info: empty_block_info(),
+            blocks: Vec::new(),
+            index: Vec::new(),
+        })
+    }
+
+    /// Set the uncompressed size at which blocks are flushed, at most
+    /// [`MAX_BLOCK_SIZE`]
+    pub fn with_block_size(mut self, block_size: usize) -> Self {
+        self.block_size = block_size.clamp(1, MAX_BLOCK_SIZE);
+        self
+    }
+
+    /// Set the zstd compression level
+    pub fn with_zstd_level(mut self, level: i32) -> Self {
+        self.zstd_level = level;
+        self
+    }
+
+    /// Record format of this archive
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    /// Append an event
+    pub fn push(&mut self, event: &NostrEvent) -> Result<(), ArchiveError> {
+        let record = stats::serialize(event, self.format);
+        if self.block.len() + record.len() + 10 > MAX_BLOCK_RAW_LEN {
+            return Err(ArchiveError::Invalid("record too large for block"));
+        }
+
+        self.index
+            .push((event.id, self.blocks.len() as u32, self.block.len() as u32));
+        prost::encoding::encode_varint(record.len() as u64, &mut self.block);
+        self.block.extend_from_slice(&record);
+
+        let info = &mut self.block_info;
+        if info.count == 0 {
+            info.min_created_at = event.created_at;
+            info.max_created_at = event.created_at;
+        } else {
+            info.min_created_at = info.min_created_at.min(event.created_at);
+            info.max_created_at = info.max_created_at.max(event.created_at);
+        }
+        info.count += 1;
+        info.kinds.insert(event.kind);
+
+        if self.block.len() >= self.block_size {
+            self.flush_block()?;
+        }
+        Ok(())
+    }
+
+    /// Append every event in `events`
+    pub fn extend<'a, I>(&mut self, events: I) -> Result<(), ArchiveError>
+    where
+        I: IntoIterator<Item = &'a NostrEvent>,
+    {
+        for event in events {
+            self.push(event)?;
+        }
+        Ok(())
+    }
+
+    /// Flush the last block, write the footer and return the inner writer
+    pub fn finish(mut self) -> Result<W, ArchiveError> {
+        self.flush_block()?;
+
+        self.index.sort_unstable_by_key(|entry| entry.0);
+
+        let mut footer = Vec::with_capacity(
+            8 + self.blocks.len() * BLOCK_ENTRY_LEN + self.index.len() * INDEX_ENTRY_LEN,
+        );
+        footer.extend_from_slice(&(self.blocks.len() as u32).to_le_bytes());
+        for block in &self.blocks {
+            footer.extend_from_slice(&block.offset.to_le_bytes());
+            footer.extend_from_slice(&block.compressed_len.to_le_bytes());
+            footer.extend_from_slice(&block.raw_len.to_le_bytes());
+            footer.extend_from_slice(&block.count.to_le_bytes());
+            footer.extend_from_slice(&block.min_created_at.to_le_bytes());
+            footer.extend_from_slice(&block.max_created_at.to_le_bytes());
+            footer.extend_from_slice(block.kinds.as_bytes());
+        }
+        footer.extend_from_slice(&(self.index.len() as u32).to_le_bytes());
+        for (id, block, offset) in &self.index {
+            footer.extend_from_slice(id);
+            footer.extend_from_slice(&block.to_le_bytes());
+            footer.extend_from_slice(&offset.to_le_bytes());
+        }
+        footer.extend_from_slice(&self.position.to_le_bytes());
+        footer.extend_from_slice(MAGIC);
+
+        self.writer.write_all(&footer)?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    fn flush_block(&mut self) -> Result<(), ArchiveError> {
+        if self.block_info.count == 0 {
+            return Ok(());
+        }
+
+        let compressed = zstd::encode_all(self.block.as_slice(), self.zstd_level)?;
+        self.writer.write_all(&compressed)?;
+
+        let mut info = std::mem::replace(&mut self.block_info, empty_block_info());
+        info.offset = self.position;
+        info.compressed_len = compressed.len() as u32;
+        info.raw_len = self.block.len() as u32;
+        self.blocks.push(info);
+
+        self.position += compressed.len() as u64;
+        self.block.clear();
+        Ok(())
+    }
+}
+
+fn empty_block_info() -> BlockInfo {
+    BlockInfo {
+        offset: 0,
+        compressed_len: 0,
+        raw_len: 0,
+        count: 0,
+        min_created_at: 0,
+        max_created_at: 0,
+        kinds: KindBitmap::default(),
+    }
+}
+
+/// Write `events` to an archive file in one go
+pub fn write_archive<P: AsRef<Path>>(
+    path: P,
+    format: Format,
+    events: &[NostrEvent],
+) -> Result<(), ArchiveError> {
+    let mut writer = ArchiveWriter::create(path, format)?;
+    writer.extend(events)?;
+    writer.finish()?;
+    Ok(())
+}
+
+/// Memory-mapped archive reader
+pub struct ArchiveReader {
+    mmap: Mmap,
+    format: Format,
+    blocks: Vec<BlockInfo>,
+    index_start: usize,
+    index_count: usize,
+}
+
+impl ArchiveReader {
+    /// Open an archive file and parse its footer
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, ArchiveError> {
+        let file = File::open(path)?;
+        // SAFETY: archives are written once and not modified while being read.
+        let mmap = unsafe { Mmap::map(&file)? };
+        let data: &[u8] = &mmap;
+
+        if data.len() < HEADER_LEN + TRAILER_LEN || &data[..4] != MAGIC {
+            return Err(ArchiveError::Invalid("bad magic"));
+        }
+        if data[4] != VERSION {
+            return Err(ArchiveError::Invalid("unsupported version"));
+        }
+        let format = Format::from_id(data[5]).ok_or(ArchiveError::UnknownFormat(data[5]))?;
+        if &data[data.len() - 4..] != MAGIC {
+            return Err(ArchiveError::Invalid("missing footer"));
+        }
+
+        let footer_end = data.len() - TRAILER_LEN;
+        let footer_offset = usize::try_from(read_u64(data, footer_end))
+            .ok()
+            .filter(|&offset| offset >= HEADER_LEN)
+            .filter(|&offset| offset.checked_add(4).is_some_and(|end| end <= footer_end))
+            .ok_or(ArchiveError::Invalid("bad footer offset"))?;
+
+        let mut pos = footer_offset;
+        let block_count = read_u32(data, pos) as usize;
+        pos += 4;
+        let blocks_len = block_count
+            .checked_mul(BLOCK_ENTRY_LEN)
+            .and_then(|len| len.checked_add(4));
+        if blocks_len.is_none_or(|len| len > footer_end - pos) {
+            return Err(ArchiveError::Invalid("footer truncated"));
+        }
+
+        let mut blocks = Vec::with_capacity(block_count);
+        for _ in 0..block_count {
+            let mut kinds = [0u8; 32];
+            kinds.copy_from_slice(&data[pos + 36..pos + 68]);
+            let block = BlockInfo {
+                offset: read_u64(data, pos),
+                compressed_len: read_u32(data, pos + 8),
+                raw_len: read_u32(data, pos + 12),
+                count: read_u32(data, pos + 16),
+                min_created_at: read_u64(data, pos + 20) as i64,
+                max_created_at: read_u64(data, pos + 28) as i64,
+                kinds: KindBitmap(kinds),
+            };
+            let block_end = usize::try_from(block.offset)
+                .ok()
+                .filter(|&offset| offset >= HEADER_LEN)
+                .and_then(|offset| offset.checked_add(block.compressed_len as usize));
+            if block_end.is_none_or(|end| end > footer_offset) {
+                return Err(ArchiveError::Invalid("block out of range"));
+            }
+            if block.raw_len as usize > MAX_BLOCK_RAW_LEN {
+                return Err(ArchiveError::Invalid("block too large"));
+            }
+            blocks.push(block);
+            pos += BLOCK_ENTRY_LEN;
+        }
+
+        let index_count = read_u32(data, pos) as usize;
+        pos += 4;
+        if index_count.checked_mul(INDEX_ENTRY_LEN) != Some(footer_end - pos) {
+            return Err(ArchiveError::Invalid("index size mismatch"));
+        }
+
+        Ok(Self {
+            mmap,
+            format,
+            blocks,
+            index_start: pos,
+            index_count,
+        })
+    }
+
+    /// Record format of this archive
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    /// Number of events in the archive
+    pub fn len(&self) -> usize {
+        self.index_count
+    }
+
+    /// Check if the archive holds no events
+    pub fn is_empty(&self) -> bool {
+        self.index_count == 0
+    }
+
+    /// Per-block metadata
+    pub fn blocks(&self) -> &[BlockInfo] {
+        &self.blocks
+    }
+
+    /// Look up an event by id, decompressing only its block
+    pub fn get(&self, id: &[u8; 32]) -> Result<Option<NostrEvent>, ArchiveError> {
+        let Some(entry) = self.find(id) else {
+            return Ok(None);
+        };
+        let (block, offset) = self.index_location(entry);
+        let raw = self.decompress_block(block)?;
+        let (record, _) = read_record(&raw, offset)?;
+        Ok(Some(stats::deserialize(record, self.format)?))
+    }
+
+    /// Check if an event id is in the archive, without decompressing anything
+    pub fn contains(&self, id: &[u8; 32]) -> bool {
+        self.find(id).is_some()
+    }
+
+    /// All events with `since <= created_at <= until`, in archive order
+    pub fn range(&self, since: i64, until: i64) -> Result<Vec<NostrEvent>, ArchiveError> {
+        self.scan(
+            |block| block.overlaps(since, until),
+            |event| event.created_at >= since && event.created_at <= until,
+        )
+    }
+
+    /// All events of `kind`, in archive order
+    pub fn by_kind(&self, kind: u16) -> Result<Vec<NostrEvent>, ArchiveError> {
+        self.scan(
+            |block| block.kinds.may_contain(kind),
+            |event| event.kind == kind,
+        )
+    }
+
+    /// Decode every event, in archive order
+    pub fn read_all(&self) -> Result<Vec<NostrEvent>, ArchiveError> {
+        self.scan(|_| true, |_| true)
+    }
+
+    fn scan<B, E>(
+        &self,
+        mut block_filter: B,
+        mut event_filter: E,
+    ) -> Result<Vec<NostrEvent>, ArchiveError>
+    where
+        B: FnMut(&BlockInfo) -> bool,
+        E: FnMut(&NostrEvent) -> bool,
+    {
+        let mut events = Vec::new();
+        for (i, block) in self.blocks.iter().enumerate() {
+            if !block_filter(block) {
+                continue;
+            }
+            let raw = self.decompress_block(i)?;
+            let mut pos = 0;
+            while pos < raw.len() {
+                let (record, next) = read_record(&raw, pos)?;
+                let event = stats::deserialize(record, self.format)?;
+                if event_filter(&event) {
+                    events.push(event);
+                }
+                pos = next;
+            }
+        }
+        Ok(events)
+    }
+
+    fn decompress_block(&self, block: usize) -> Result<Vec<u8>, ArchiveError> {
+        let info = self
+            .blocks
+            .get(block)
+            .ok_or(ArchiveError::Invalid("block index out of range"))?;
+        let start = info.offset as usize;
+        let compressed = self
+            .mmap
+            .get(start..start + info.compressed_len as usize)
+            .ok_or(ArchiveError::Invalid("block out of range"))?;
+        let raw = zstd::bulk::decompress(compressed, info.raw_len as usize)?;
+        if raw.len() != info.raw_len as usize {
+            return Err(ArchiveError::Invalid("block length mismatch"));
+        }
+        Ok(raw)
+    }
+
+    fn find(&self, id: &[u8; 32]) -> Option<usize> {
+        let (mut lo, mut hi) = (0, self.index_count);
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            let start = self.index_start + mid * INDEX_ENTRY_LEN;
+            match self.mmap[start..start + 32].cmp(id) {
+                std::cmp::Ordering::Less => lo = mid + 1,
+                std::cmp::Ordering::Greater => hi = mid,
+                std::cmp::Ordering::Equal => return Some(mid),
+            }
+        }
+        None
+    }
+
+    fn index_location(&self, entry: usize) -> (usize, usize) {
+        let start = self.index_start + entry * INDEX_ENTRY_LEN + 32;
+        (
+            read_u32(&self.mmap, start) as usize,
+            read_u32(&self.mmap, start + 4) as usize,
+        )
+    }
+}
+
+/// Read one length-prefixed record at `pos`; returns it and the next position
+fn read_record(raw: &[u8], pos: usize) -> Result<(&[u8], usize), ArchiveError> {
+    let mut rest = raw
+        .get(pos..)
+        .ok_or(ArchiveError::Invalid("record offset out of range"))?;
+    let before = rest.len();
+    let len = prost::encoding::decode_varint(&mut rest)
+        .map_err(|_| ArchiveError::Invalid("bad record length"))? as usize;
+    let start = pos + (before - rest.len());
+    let end = start
+        .checked_add(len)
+        .filter(|&end| end <= raw.len())
+        .ok_or(ArchiveError::Invalid("record truncated"))?;
+    Ok((&raw[start..end], end))
+}
+
+fn read_u32(data: &[u8], pos: usize) -> u32 {
+    u32::from_le_bytes(data[pos..pos + 4].try_into().unwrap())
+}
+
+fn read_u64(data: &[u8], pos: usize) -> u64 {
+    u64::from_le_bytes(data[pos..pos + 8].try_into().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn sample_events(n: usize) -> Vec<NostrEvent> {
+        (0..n)
+            .map(|i| {
+                let mut id = [0u8; 32];
+                id[..8].copy_from_slice(&(i as u64 * 7919).to_be_bytes());
+                NostrEvent {
+                    id,
+                    pubkey: [0xcd; 32],
+                    created_at: 1700000000 + i as i64 * 10,
+                    kind: [1, 7, 30023, 1][i % 4],
+                    tags: vec![vec!["e".to_string(), hex::encode([i as u8; 32])]],
+                    content: format!("event {}", i),
+                    sig: [0xef; 64],
+                }
+            })
+            .collect()
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "binostr-archive-{}-{}.bnar",
+            name,
+            std::process::id()
+        ))
+    }
+
+    #[test]
+    fn test_archive_queries() {
+        let events = sample_events(500);
+
+        for format in [Format::ProtoBinary, Format::DannyPack, Format::CborPacked] {
+            let path = temp_path(format.short_name());
+            let mut writer = ArchiveWriter::create(&path, format)
+                .unwrap()
+                .with_block_size(2048);
+            writer.extend(&events).unwrap();
+            writer.finish().unwrap();
+
+            let reader = ArchiveReader::open(&path).unwrap();
+            assert_eq!(reader.format(), format);
+            assert_eq!(reader.len(), events.len());
+            assert!(reader.blocks().len() > 1);
+            assert_eq!(reader.read_all().unwrap(), events);
+
+            for event in events.iter().step_by(37) {
+                assert_eq!(reader.get(&event.id).unwrap().as_ref(), Some(event));
+            }
+            assert_eq!(reader.get(&[0xff; 32]).unwrap(), None);
+
+            let since = events[100].created_at;
+            let until = events[149].created_at;
+            assert_eq!(reader.range(since, until).unwrap(), events[100..150]);
+
+            let articles: Vec<_> = events.iter().filter(|e| e.kind == 30023).cloned().collect();
+            assert_eq!(reader.by_kind(30023).unwrap(), articles);
+            assert!(reader.by_kind(3).unwrap().is_empty());
+
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_empty_archive() {
+        let path = temp_path("empty");
+        write_archive(&path, Format::Json, &[]).unwrap();
+
+        let reader = ArchiveReader::open(&path).unwrap();
+        assert!(reader.is_empty());
+        assert!(reader.read_all().unwrap().is_empty());
+        assert_eq!(reader.get(&[0; 32]).unwrap(), None);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_rejects_corrupted_footer() {
+        let path = temp_path("corrupted");
+        write_archive(&path, Format::DannyPack, &sample_events(10)).unwrap();
+        let data = std::fs::read(&path).unwrap();
+        let footer_end = data.len() - TRAILER_LEN;
+        let footer_offset = read_u64(&data, footer_end) as usize;
+        let block = footer_offset + 4;
+
+        let corruptions: [(usize, &[u8]); 5] = [
+            (footer_end, &u64::MAX.to_le_bytes()),
+            (footer_end, &(u64::MAX - 2).to_le_bytes()),
+            (block, &u64::MAX.to_le_bytes()),
+            (block + 8, &u32::MAX.to_le_bytes()),
+            (block + 12, &u32::MAX.to_le_bytes()),
+        ];
+        for (pos, bytes) in corruptions {
+            let mut corrupted = data.clone();
+            corrupted[pos..pos + bytes.len()].copy_from_slice(bytes);
+            std::fs::write(&path, &corrupted).unwrap();
+            assert!(matches!(
+                ArchiveReader::open(&path),
+                Err(ArchiveError::Invalid(_))
+            ));
+        }
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_rejects_truncated_archive() {
+        let path = temp_path("truncated");
+        write_archive(&path, Format::DannyPack, &sample_events(10)).unwrap();
+
+        let data = std::fs::read(&path).unwrap();
+        std::fs::write(&path, &data[..data.len() - 5]).unwrap();
+        assert!(matches!(
+            ArchiveReader::open(&path),
+            Err(ArchiveError::Invalid(_))
+        ));
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,289 @@
+//! Adaptive per-event format selection
+//!
+//! No single format is smallest for every kind: Proto Binary wins on kind 0
+//! profiles, CBOR Packed on kind 7 reactions. The auto codec encodes each
+//! event with a set of candidate formats, keeps one according to a
+//! [`Policy`], and prefixes its [`Format::id`]:
+//!
+//! ```text
+//! [format id: u8] [payload]
+//! ```
+//!
+//! Decoding needs no options, since the tag names the format.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use thiserror::Error;
+
+use crate::event::NostrEvent;
+use crate::stats::{self, Format, FormatError};
+
+/// Candidates used by [`AutoOptions::new`]: the formats that win at least
+/// one kind in the per-kind size table
+pub const DEFAULT_CANDIDATES: [Format; 4] = [
+    Format::ProtoBinary,
+    Format::CborPacked,
+    Format::DannyPack,
+    Format::Notepack,
+];
+
+/// Decodes timed per candidate under [`Policy::FastestDecode`]
+const DECODE_RUNS: usize = 5;
+
+#[derive(Debug, Error)]
+pub enum AutoError {
+    #[error("Data is empty")]
+    Empty,
+
+    #[error("Unknown format id: {0}")]
+    UnknownFormat(u8),
+
+    #[error(transparent)]
+    Format(#[from] FormatError),
+}
+
+/// How to pick among the candidate encodings
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Policy {
+    /// Fewest bytes; ties go to the earlier candidate
+    #[default]
+    Smallest,
+    /// Least decode time, measured on this machine when encoding (the best
+    /// of several runs per candidate), so choices can differ between runs;
+    /// ties go to the smaller encoding
+    FastestDecode,
+}
+
+/// Candidate formats and selection policy
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AutoOptions {
+    pub candidates: Vec<Format>,
+    pub policy: Policy,
+}
+
+impl Default for AutoOptions {
+    fn default() -> Self {
+        Self {
+            candidates: DEFAULT_CANDIDATES.to_vec(),
+            policy: Policy::Smallest,
+        }
+    }
+}
+
+impl AutoOptions {
+    /// [`DEFAULT_CANDIDATES`], smallest encoding wins
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_candidates(mut self, candidates: &[Format]) -> Self {
+        self.candidates = candidates.to_vec();
+        self
+    }
+
+    pub fn with_policy(mut self, policy: Policy) -> Self {
+        self.policy = policy;
+        self
+    }
+}
+
+/// Pick a candidate for `event`, returning it with its payload
+///
+/// # Panics
+///
+/// If `options` has no candidates.
+pub fn choose(event: &NostrEvent, options: &AutoOptions) -> (Format, Vec<u8>) {
+    let encoded = options
+        .candidates
+        .iter()
+        .map(|&format| (format, stats::serialize(event, format)));
+
+    match options.policy {
+        Policy::Smallest => encoded.min_by_key(|(_, payload)| payload.len()),
+        Policy::FastestDecode => encoded
+            .map(|(format, payload)| (decode_time(&payload, format), format, payload))
+            .min_by_key(|(time, _, payload)| (*time, payload.len()))
+            .map(|(_, format, payload)| (format, payload)),
+    }
+    .expect("auto encoding needs at least one candidate format")
+}
+
+/// Encode with the candidate chosen by `options`, tagged with its format
+pub fn encode(event: &NostrEvent, options: &AutoOptions) -> Vec<u8> {
+    let (format, payload) = choose(event, options);
+    let mut buf = Vec::with_capacity(1 + payload.len());
+    buf.push(format.id());
+    buf.extend_from_slice(&payload);
+    buf
+}
+
+/// The format an auto-encoded event was written in
+pub fn format_of(data: &[u8]) -> Result<Format, AutoError> {
+    let &id = data.first().ok_or(AutoError::Empty)?;
+    Format::from_id(id).ok_or(AutoError::UnknownFormat(id))
+}
+
+/// Decode an auto-encoded event
+pub fn decode(data: &[u8]) -> Result<NostrEvent, AutoError> {
+    let format = format_of(data)?;
+    Ok(stats::deserialize(&data[1..], format)?)
+}
+
+fn decode_time(payload: &[u8], format: Format) -> Duration {
+    (0..DECODE_RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            let decoded = stats::deserialize(payload, format);
+            let elapsed = start.elapsed();
+            std::hint::black_box(decoded).expect("candidate payload should decode");
+            elapsed
+        })
+        .min()
+        .unwrap_or_default()
+}
+
+/// Which candidate wins per kind, and auto sizes against single formats
+#[derive(Debug, Clone, Default)]
+pub struct AutoStats {
+    pub events: usize,
+    /// Total auto-encoded bytes, format tags included
+    pub auto_bytes: usize,
+    /// Total bytes with every event in one candidate format
+    pub single_bytes: HashMap<Format, usize>,
+    /// Events won by each candidate, per kind
+    pub wins: HashMap<u16, HashMap<Format, usize>>,
+}
+
+impl AutoStats {
+    pub fn from_events(events: &[NostrEvent], options: &AutoOptions) -> Self {
+        let mut stats = Self::default();
+        for event in events {
+            let (format, payload) = choose(event, options);
+            stats.events += 1;
+            stats.auto_bytes += 1 + payload.len();
+            *stats
+                .wins
+                .entry(event.kind)
+                .or_default()
+                .entry(format)
+                .or_default() += 1;
+
+            for &candidate in &options.candidates {
+                let len = if candidate == format {
+                    payload.len()
+                } else {
+                    stats::encoded_len(event, candidate)
+                };
+                *stats.single_bytes.entry(candidate).or_default() += len;
+            }
+        }
+        stats
+    }
+
+    /// The candidate with the fewest total bytes, and that total
+    pub fn best_single(&self) -> Option<(Format, usize)> {
+        self.single_bytes
+            .iter()
+            .map(|(&format, &bytes)| (format, bytes))
+            .min_by_key(|&(format, bytes)| (bytes, format.id()))
+    }
+
+    /// Events won by `format` across all kinds
+    pub fn total_wins(&self, format: Format) -> usize {
+        self.wins
+            .values()
+            .filter_map(|by_format| by_format.get(&format))
+            .sum()
+    }
+
+    /// The `n` kinds with the most events, with their event counts
+    pub fn top_kinds(&self, n: usize) -> Vec<(u16, usize)> {
+        let mut kinds: Vec<_> = self
+            .wins
+            .iter()
+            .map(|(&kind, by_format)| (kind, by_format.values().sum::<usize>()))
+            .collect();
+        kinds.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        kinds.truncate(n);
+        kinds
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_event() -> NostrEvent {
+        NostrEvent {
+            id: [0xab; 32],
+            pubkey: [0xcd; 32],
+            created_at: 1234567890,
+            kind: 1,
+            tags: vec![
+                vec!["p".to_string(), "cd".repeat(32)],
+                vec!["t".to_string(), "nostr".to_string()],
+            ],
+            content: "Hello, Nostr!".to_string(),
+            sig: [0xef; 64],
+        }
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let event = sample_event();
+        for policy in [Policy::Smallest, Policy::FastestDecode] {
+            let options = AutoOptions::new().with_policy(policy);
+            let data = encode(&event, &options);
+            assert!(DEFAULT_CANDIDATES.contains(&format_of(&data).unwrap()));
+            assert_eq!(decode(&data).unwrap(), event);
+        }
+    }
+
+    #[test]
+    fn test_smallest_wins() {
+        let event = sample_event();
+        let data = encode(&event, &AutoOptions::new());
+        let smallest = DEFAULT_CANDIDATES
+            .iter()
+            .map(|&f| stats::serialize(&event, f).len())
+            .min()
+            .unwrap();
+        assert_eq!(data.len(), 1 + smallest);
+
+        // A single candidate is always chosen
+        let options = AutoOptions::new().with_candidates(&[Format::Json]);
+        assert_eq!(format_of(&encode(&event, &options)).unwrap(), Format::Json);
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        assert!(matches!(decode(&[]), Err(AutoError::Empty)));
+        assert!(matches!(decode(&[200]), Err(AutoError::UnknownFormat(200))));
+        assert!(matches!(
+            decode(&[Format::DannyPack.id(), 1, 2]),
+            Err(AutoError::Format(_))
+        ));
+    }
+
+    #[test]
+    fn test_auto_stats() {
+        let mut reaction = sample_event();
+        reaction.kind = 7;
+        reaction.content = "+".to_string();
+        let events = vec![sample_event(), reaction.clone(), reaction];
+
+        let stats = AutoStats::from_events(&events, &AutoOptions::new());
+        assert_eq!(stats.events, 3);
+        assert_eq!(stats.top_kinds(5), vec![(7, 2), (1, 1)]);
+        let wins: usize = DEFAULT_CANDIDATES
+            .iter()
+            .map(|&f| stats.total_wins(f))
+            .sum();
+        assert_eq!(wins, 3);
+
+        // Never worse than the best single format, tags aside
+        let (_, best) = stats.best_single().unwrap();
+        assert!(stats.auto_bytes <= best + stats.events);
+    }
+}
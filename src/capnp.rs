@@ -10,11 +10,11 @@
 //! Tags packed into single blob with length-prefixed values.
 //! Only 3 Cap'n Proto pointers: fixedData, tagData, content.
 
-use capnp::message::{Builder, ReaderOptions};
+use capnp::message::{Builder, HeapAllocator, ReaderOptions, SUGGESTED_FIRST_SEGMENT_WORDS};
 use capnp::serialize;
 use capnp::serialize_packed;
 
-use crate::event::NostrEvent;
+use crate::event::{NostrEvent, RawTagValue};
 
 // Include the generated Cap'n Proto code
 pub mod nostr_capnp {
@@ -60,10 +60,10 @@ fn is_hex_string(s: &str) -> bool {
     s.chars().all(|c| c.is_ascii_hexdigit())
 }
 
-/// Encode a tag value optimally: if it's hex, decode to bytes (50% size reduction),
-/// otherwise store as UTF-8 bytes
-fn encode_tag_value(value: &str) -> (bool, Vec<u8>) {
-    if is_hex_string(value) && value.len().is_multiple_of(2) {
+/// Encode a tag value optimally: if it's hex (and `hex` is set), decode to bytes
+/// (50% size reduction), otherwise store as UTF-8 bytes
+fn encode_tag_value(value: &str, hex: bool) -> (bool, Vec<u8>) {
+    if hex && is_hex_string(value) && value.len().is_multiple_of(2) {
         // Try to decode as hex - if successful, store as hex bytes
         if let Ok(bytes) = hex::decode(value) {
             return (true, bytes);
@@ -87,8 +87,8 @@ fn decode_tag_value(is_hex: bool, bytes: &[u8]) -> Result<String, CapnpError> {
 /// Pack all tags into a single compact blob
 /// Format: [tag_count:u16] then for each tag: [value_count:u8] then for each value:
 ///         [flags_and_len:u16 where bit15=is_hex, bits0-14=length][data]
-fn pack_tags(tags: &[Vec<String>]) -> Vec<u8> {
-    let mut buf = Vec::with_capacity(256);
+fn pack_tags(tags: &[Vec<String>], hex: bool) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(tag_data_len(tags, hex));
 
     // Tag count (u16)
     buf.extend_from_slice(&(tags.len() as u16).to_le_bytes());
@@ -98,7 +98,7 @@ fn pack_tags(tags: &[Vec<String>]) -> Vec<u8> {
         buf.push(tag.len() as u8);
 
         for value in tag {
-            let (is_hex, data) = encode_tag_value(value);
+            let (is_hex, data) = encode_tag_value(value, hex);
             // flags_and_len: bit15 = is_hex, bits0-14 = length
             let flags_and_len = if is_hex {
                 0x8000 | (data.len() as u16)
@@ -113,6 +113,23 @@ fn pack_tags(tags: &[Vec<String>]) -> Vec<u8> {
     buf
 }
 
+/// Length of the blob [`pack_tags`] writes
+fn tag_data_len(tags: &[Vec<String>], hex: bool) -> usize {
+    let values: usize = tags
+        .iter()
+        .flatten()
+        .map(|value| {
+            let data_len = if hex && is_hex_string(value) && value.len().is_multiple_of(2) {
+                value.len() / 2
+            } else {
+                value.len()
+            };
+            2 + data_len
+        })
+        .sum();
+    2 + tags.len() + values
+}
+
 /// Unpack tags from a compact blob
 fn unpack_tags(data: &[u8]) -> Result<Vec<Vec<String>>, CapnpError> {
     if data.len() < 2 {
@@ -166,8 +183,8 @@ fn unpack_tags(data: &[u8]) -> Result<Vec<Vec<String>>, CapnpError> {
     Ok(tags)
 }
 
-/// Serialize a NostrEvent to Cap'n Proto format
-pub fn serialize_event(event: &NostrEvent) -> Vec<u8> {
+/// Build a single-event message, hex-compressing tag values if `hex` is set
+fn build_event(event: &NostrEvent, hex: bool) -> Builder<HeapAllocator> {
     let mut message = Builder::new_default();
 
     {
@@ -178,17 +195,125 @@ pub fn serialize_event(event: &NostrEvent) -> Vec<u8> {
         builder.set_fixed_data(&fixed_data);
 
         // Pack all tags into single blob
-        let tag_data = pack_tags(&event.tags);
+        let tag_data = pack_tags(&event.tags, hex);
         builder.set_tag_data(&tag_data);
 
         builder.set_content(&event.content);
     }
 
-    let mut buf = Vec::new();
-    serialize::write_message(&mut buf, &message).expect("Cap'n Proto serialization failed");
+    message
+}
+
+/// Serialize a NostrEvent to Cap'n Proto format
+pub fn serialize_event(event: &NostrEvent) -> Vec<u8> {
+    write_event(event, true)
+}
+
+/// Serialize to Cap'n Proto format with every tag value stored as text
+pub fn serialize_event_plain(event: &NostrEvent) -> Vec<u8> {
+    write_event(event, false)
+}
+
+fn write_event(event: &NostrEvent, hex: bool) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(message_len(event, hex));
+    serialize::write_message(&mut buf, &build_event(event, hex))
+        .expect("Cap'n Proto serialization failed");
     buf
 }
 
+/// Exact length of [`serialize_event`] output, computed without building
+/// the message
+pub fn encoded_len(event: &NostrEvent) -> usize {
+    message_len(event, true)
+}
+
+/// Replay the allocations [`build_event`] makes on a default
+/// [`HeapAllocator`] and return the serialized size
+fn message_len(event: &NostrEvent, hex: bool) -> usize {
+    let mut arena = SegmentSizes::default();
+    arena.allocate(1); // root pointer
+    arena.allocate(3); // NostrEvent struct: three pointers, no data
+    arena.allocate(words(FIXED_DATA_SIZE));
+    arena.allocate(words(tag_data_len(&event.tags, hex)));
+    arena.allocate(words(event.content.len() + 1)); // Text is NUL-terminated
+    arena.serialized_len()
+}
+
+fn words(bytes: usize) -> u32 {
+    bytes.div_ceil(8) as u32
+}
+
+/// Most segments [`message_len`] can need: the first, then one each for
+/// tag data and content
+const MAX_SEGMENTS: usize = 3;
+
+/// Segment capacities and usage of a [`HeapAllocator`] arena, in words
+struct SegmentSizes {
+    segments: [(u32, u32); MAX_SEGMENTS],
+    count: usize,
+    next_size: u32,
+}
+
+impl Default for SegmentSizes {
+    fn default() -> Self {
+        Self {
+            segments: [(0, 0); MAX_SEGMENTS],
+            count: 0,
+            next_size: SUGGESTED_FIRST_SEGMENT_WORDS,
+        }
+    }
+}
+
+impl SegmentSizes {
+    /// Allocate an object pointed to from the first segment
+    fn allocate(&mut self, amount: u32) {
+        if self.count == 0 {
+            self.new_segment(amount);
+        }
+        if self.try_allocate(0, amount) {
+            return;
+        }
+        // Elsewhere, behind a one-word landing pad for the far pointer
+        let amount = amount + 1;
+        if (0..self.count).any(|i| self.try_allocate(i, amount)) {
+            return;
+        }
+        self.new_segment(amount);
+        self.try_allocate(self.count - 1, amount);
+    }
+
+    fn try_allocate(&mut self, segment: usize, amount: u32) -> bool {
+        let (capacity, used) = &mut self.segments[segment];
+        let fits = amount <= *capacity - *used;
+        if fits {
+            *used += amount;
+        }
+        fits
+    }
+
+    /// Mirrors `HeapAllocator::allocate_segment` with its defaults
+    fn new_segment(&mut self, minimum: u32) {
+        const MAX_SEGMENT_WORDS: u32 = 1 << 29;
+        let size = minimum.max(self.next_size);
+        self.next_size = if size < MAX_SEGMENT_WORDS - self.next_size {
+            self.next_size + size
+        } else {
+            MAX_SEGMENT_WORDS
+        };
+        self.segments[self.count] = (size, 0);
+        self.count += 1;
+    }
+
+    /// Segment table (count and sizes, padded to a word) plus segments
+    fn serialized_len(&self) -> usize {
+        let used: usize = self.segments[..self.count]
+            .iter()
+            .map(|&(_, used)| used as usize)
+            .sum();
+        8 * (self.count / 2 + 1 + used)
+    }
+}
+
 /// Deserialize a NostrEvent from Cap'n Proto format
 pub fn deserialize_event(data: &[u8]) -> Result<NostrEvent, CapnpError> {
     let reader = serialize::read_message(data, ReaderOptions::new())?;
@@ -217,24 +342,19 @@ pub fn deserialize_event(data: &[u8]) -> Result<NostrEvent, CapnpError> {
 
 /// Serialize a NostrEvent to Cap'n Proto packed format (compressed)
 pub fn serialize_event_packed(event: &NostrEvent) -> Vec<u8> {
-    let mut message = Builder::new_default();
-
-    {
-        let mut builder = message.init_root::<nostr_event::Builder>();
-
-        // Pack all fixed fields into single 138-byte blob
-        let fixed_data = pack_fixed_data(event);
-        builder.set_fixed_data(&fixed_data);
-
-        // Pack all tags into single blob
-        let tag_data = pack_tags(&event.tags);
-        builder.set_tag_data(&tag_data);
+    write_event_packed(event, true)
+}
 
-        builder.set_content(&event.content);
-    }
+/// Serialize to Cap'n Proto packed format with tag values stored as text
+pub fn serialize_event_packed_plain(event: &NostrEvent) -> Vec<u8> {
+    write_event_packed(event, false)
+}
 
-    let mut buf = Vec::new();
-    serialize_packed::write_message(&mut buf, &message)
+fn write_event_packed(event: &NostrEvent, hex: bool) -> Vec<u8> {
+    // Packing depends on every word's zero bytes, so only the unpacked size
+    // is known up front; it bounds the packed size closely enough
+    let mut buf = Vec::with_capacity(message_len(event, hex));
+    serialize_packed::write_message(&mut buf, &build_event(event, hex))
         .expect("Cap'n Proto packed serialization failed");
     buf
 }
@@ -283,7 +403,7 @@ pub fn serialize_batch(events: &[NostrEvent]) -> Vec<u8> {
             event_builder.set_fixed_data(&fixed_data);
 
             // Pack all tags into single blob
-            let tag_data = pack_tags(&event.tags);
+            let tag_data = pack_tags(&event.tags, true);
             event_builder.set_tag_data(&tag_data);
 
             event_builder.set_content(&event.content);
@@ -348,7 +468,7 @@ pub fn serialize_batch_packed(events: &[NostrEvent]) -> Vec<u8> {
             event_builder.set_fixed_data(&fixed_data);
 
             // Pack all tags into single blob
-            let tag_data = pack_tags(&event.tags);
+            let tag_data = pack_tags(&event.tags, true);
             event_builder.set_tag_data(&tag_data);
 
             event_builder.set_content(&event.content);
@@ -470,6 +590,165 @@ pub fn read_kind_and_pubkey(data: &[u8]) -> Result<(u16, [u8; 32]), CapnpError>
     Ok((kind, pubkey))
 }
 
+/// Parse the message once and hand the fixed-field blob and raw tag blob to `f`.
+/// Use this to read several fields, or to walk tags, without building a NostrEvent.
+pub fn with_raw_event<T>(data: &[u8], f: impl FnOnce(RawEvent<'_>) -> T) -> Result<T, CapnpError> {
+    let reader = serialize::read_message(data, ReaderOptions::new())?;
+    let event_reader = reader.get_root::<nostr_event::Reader>()?;
+
+    let fixed_data = event_reader.get_fixed_data()?;
+    if fixed_data.len() < FIXED_DATA_SIZE {
+        return Err(CapnpError::InvalidLength("fixed data too short"));
+    }
+    let tag_data = event_reader.get_tag_data()?;
+
+    Ok(f(RawEvent {
+        fixed_data,
+        tag_data,
+    }))
+}
+
+/// Borrowed view of an encoded event's fixed fields and tag blob
+#[derive(Debug, Clone, Copy)]
+pub struct RawEvent<'a> {
+    fixed_data: &'a [u8],
+    tag_data: &'a [u8],
+}
+
+impl<'a> RawEvent<'a> {
+    pub fn id(&self) -> [u8; 32] {
+        self.fixed_data[0..32].try_into().unwrap()
+    }
+
+    pub fn pubkey(&self) -> [u8; 32] {
+        self.fixed_data[32..64].try_into().unwrap()
+    }
+
+    pub fn created_at(&self) -> i64 {
+        i64::from_le_bytes(self.fixed_data[128..136].try_into().unwrap())
+    }
+
+    pub fn kind(&self) -> u16 {
+        u16::from_le_bytes([self.fixed_data[136], self.fixed_data[137]])
+    }
+
+    /// Iterate over the tags without decoding them into strings
+    pub fn tags(&self) -> RawTags<'a> {
+        let remaining = if self.tag_data.len() < 2 {
+            0
+        } else {
+            u16::from_le_bytes([self.tag_data[0], self.tag_data[1]]) as usize
+        };
+        RawTags {
+            data: self.tag_data,
+            pos: 2,
+            remaining,
+        }
+    }
+}
+
+/// Iterator over the tags in a packed tag blob
+pub struct RawTags<'a> {
+    data: &'a [u8],
+    pos: usize,
+    remaining: usize,
+}
+
+impl<'a> Iterator for RawTags<'a> {
+    type Item = Result<RawTag<'a>, CapnpError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+
+        let Some(&value_count) = self.data.get(self.pos) else {
+            self.remaining = 0;
+            return Some(Err(CapnpError::InvalidTagData("truncated tag data")));
+        };
+        let start = self.pos + 1;
+        let mut pos = start;
+        for _ in 0..value_count {
+            if pos + 2 > self.data.len() {
+                self.remaining = 0;
+                return Some(Err(CapnpError::InvalidTagData("truncated value header")));
+            }
+            let len = (u16::from_le_bytes([self.data[pos], self.data[pos + 1]]) & 0x7FFF) as usize;
+            pos += 2 + len;
+            if pos > self.data.len() {
+                self.remaining = 0;
+                return Some(Err(CapnpError::InvalidTagData("truncated value data")));
+            }
+        }
+        self.pos = pos;
+
+        Some(Ok(RawTag {
+            data: &self.data[start..pos],
+            count: value_count as usize,
+        }))
+    }
+}
+
+/// A single tag borrowed from a packed tag blob
+#[derive(Debug, Clone, Copy)]
+pub struct RawTag<'a> {
+    data: &'a [u8],
+    count: usize,
+}
+
+impl<'a> RawTag<'a> {
+    /// Number of values in the tag, including its name
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    /// Check if the tag has no values
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// The `index`-th value (0 is the tag name)
+    pub fn get(&self, index: usize) -> Option<RawTagValue<'a>> {
+        self.values().nth(index)
+    }
+
+    /// Iterate over the values
+    pub fn values(&self) -> RawTagValues<'a> {
+        RawTagValues {
+            data: self.data,
+            remaining: self.count,
+        }
+    }
+}
+
+/// Iterator over the values of a [`RawTag`]
+pub struct RawTagValues<'a> {
+    data: &'a [u8],
+    remaining: usize,
+}
+
+impl<'a> Iterator for RawTagValues<'a> {
+    type Item = RawTagValue<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 || self.data.len() < 2 {
+            return None;
+        }
+        self.remaining -= 1;
+        // Bounds were validated when the tag was read
+        let flags_and_len = u16::from_le_bytes([self.data[0], self.data[1]]);
+        let len = (flags_and_len & 0x7FFF) as usize;
+        let bytes = &self.data[2..2 + len];
+        self.data = &self.data[2 + len..];
+        Some(if flags_and_len & 0x8000 != 0 {
+            RawTagValue::Hex(bytes)
+        } else {
+            RawTagValue::Text(bytes)
+        })
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum CapnpError {
     #[error("Cap'n Proto error: {0}")]
@@ -542,6 +821,26 @@ mod tests {
         assert_eq!(events, back);
     }
 
+    #[test]
+    fn test_raw_event_access() {
+        let event = sample_event();
+        let bytes = serialize_event(&event);
+
+        let (id, kind, created_at, tags) = with_raw_event(&bytes, |raw| {
+            let tags: Vec<Vec<String>> = raw
+                .tags()
+                .map(|tag| tag.unwrap().values().map(|v| v.to_string_lossy()).collect())
+                .collect();
+            (raw.id(), raw.kind(), raw.created_at(), tags)
+        })
+        .unwrap();
+
+        assert_eq!(id, event.id);
+        assert_eq!(kind, event.kind);
+        assert_eq!(created_at, event.created_at);
+        assert_eq!(tags, event.tags);
+    }
+
     #[test]
     fn test_size_comparison() {
         let event = sample_event();
@@ -8,7 +8,18 @@
 use ciborium::value::Value;
 use serde::{Deserialize, Serialize};
 
+use crate::compact::{self, CompactError, CompactOptions};
 use crate::event::NostrEvent;
+use crate::follows::{self, FollowsError};
+use crate::stats::Format;
+
+/// CBOR tag wrapping a [compacted](crate::compact) value body (unassigned
+/// in the IANA registry; only [`packed::serialize_with`] emits it)
+pub const COMPACT_TAG: u64 = 0x4e43;
+
+/// CBOR tag wrapping a tag list with a [follow block](crate::follows):
+/// `[tags before, block bytes, tags after]`
+pub const FOLLOWS_TAG: u64 = 0x4e46;
 
 // ============================================
 // Variant 1: Schemaless (JSON-like)
@@ -74,11 +85,28 @@ pub mod schemaless {
 
     pub fn serialize(event: &NostrEvent) -> Vec<u8> {
         let cbor = CborSchemaless::from(event);
-        let mut buf = Vec::new();
+        let mut buf = Vec::with_capacity(encoded_len(event));
         ciborium::into_writer(&cbor, &mut buf).expect("CBOR serialization should not fail");
         buf
     }
 
+    /// Exact length of [`serialize`] output, computed without serializing
+    pub fn encoded_len(event: &NostrEvent) -> usize {
+        let keys: usize = [
+            "id",
+            "pubkey",
+            "created_at",
+            "kind",
+            "tags",
+            "content",
+            "sig",
+        ]
+        .iter()
+        .map(|key| text_len(key.len()))
+        .sum();
+        header_len(7) + keys + fields_len(event, |value| text_len(value.len()))
+    }
+
     pub fn deserialize(data: &[u8]) -> Result<NostrEvent, CborError> {
         let cbor: CborSchemaless = ciborium::from_reader(data)?;
         NostrEvent::try_from(cbor)
@@ -103,72 +131,30 @@ pub mod schemaless {
 
 // Packed format: [id, pubkey, created_at, kind, tags, content, sig]
 // No field names = smallest size
+// With compaction, content and tag values may be COMPACT_TAG(bytes)
 
 pub mod packed {
     use super::*;
 
     pub fn serialize(event: &NostrEvent) -> Vec<u8> {
-        let value = Value::Array(vec![
-            Value::Bytes(event.id.to_vec()),
-            Value::Bytes(event.pubkey.to_vec()),
-            Value::Integer(event.created_at.into()),
-            Value::Integer(event.kind.into()),
-            tags_to_value(&event.tags),
-            Value::Text(event.content.clone()),
-            Value::Bytes(event.sig.to_vec()),
-        ]);
-
-        let mut buf = Vec::new();
-        ciborium::into_writer(&value, &mut buf).expect("CBOR serialization should not fail");
+        let mut buf = Vec::with_capacity(encoded_len(event));
+        ciborium::into_writer(&to_value(event), &mut buf)
+            .expect("CBOR serialization should not fail");
         buf
     }
 
+    /// Exact length of [`serialize`] output, computed without serializing
+    pub fn encoded_len(event: &NostrEvent) -> usize {
+        header_len(7) + fields_len(event, tag_value_len)
+    }
+
     pub fn deserialize(data: &[u8]) -> Result<NostrEvent, CborError> {
         let value: Value = ciborium::from_reader(data)?;
-
-        let arr = value.as_array().ok_or(CborError::ExpectedArray)?;
-        if arr.len() != 7 {
-            return Err(CborError::InvalidLength("event array"));
-        }
-
-        let id = extract_bytes(&arr[0], "id")?;
-        let pubkey = extract_bytes(&arr[1], "pubkey")?;
-        let created_at = extract_i64(&arr[2], "created_at")?;
-        let kind = extract_u16(&arr[3], "kind")?;
-        let tags = extract_tags(&arr[4])?;
-        let content = extract_string(&arr[5], "content")?;
-        let sig = extract_bytes(&arr[6], "sig")?;
-
-        Ok(NostrEvent {
-            id: id.try_into().map_err(|_| CborError::InvalidLength("id"))?,
-            pubkey: pubkey
-                .try_into()
-                .map_err(|_| CborError::InvalidLength("pubkey"))?,
-            created_at,
-            kind,
-            tags,
-            content,
-            sig: sig
-                .try_into()
-                .map_err(|_| CborError::InvalidLength("sig"))?,
-        })
+        from_value(&value)
     }
 
     pub fn serialize_batch(events: &[NostrEvent]) -> Vec<u8> {
-        let values: Vec<Value> = events
-            .iter()
-            .map(|e| {
-                Value::Array(vec![
-                    Value::Bytes(e.id.to_vec()),
-                    Value::Bytes(e.pubkey.to_vec()),
-                    Value::Integer(e.created_at.into()),
-                    Value::Integer(e.kind.into()),
-                    tags_to_value(&e.tags),
-                    Value::Text(e.content.clone()),
-                    Value::Bytes(e.sig.to_vec()),
-                ])
-            })
-            .collect();
+        let values: Vec<Value> = events.iter().map(to_value).collect();
 
         let mut buf = Vec::new();
         ciborium::into_writer(&Value::Array(values), &mut buf)
@@ -180,30 +166,117 @@ pub mod packed {
         let value: Value = ciborium::from_reader(data)?;
         let arr = value.as_array().ok_or(CborError::ExpectedArray)?;
 
-        arr.iter()
-            .map(|v| {
-                let arr = v.as_array().ok_or(CborError::ExpectedArray)?;
-                if arr.len() != 7 {
-                    return Err(CborError::InvalidLength("event array"));
-                }
+        arr.iter().map(from_value).collect()
+    }
 
-                Ok(NostrEvent {
-                    id: extract_bytes(&arr[0], "id")?
-                        .try_into()
-                        .map_err(|_| CborError::InvalidLength("id"))?,
-                    pubkey: extract_bytes(&arr[1], "pubkey")?
-                        .try_into()
-                        .map_err(|_| CborError::InvalidLength("pubkey"))?,
-                    created_at: extract_i64(&arr[2], "created_at")?,
-                    kind: extract_u16(&arr[3], "kind")?,
-                    tags: extract_tags(&arr[4])?,
-                    content: extract_string(&arr[5], "content")?,
-                    sig: extract_bytes(&arr[6], "sig")?
-                        .try_into()
-                        .map_err(|_| CborError::InvalidLength("sig"))?,
+    /// Serialize with [`compact`](crate::compact) value compaction
+    ///
+    /// The output is read by [`deserialize`]; with no compaction enabled it
+    /// is identical to [`serialize`].
+    pub fn serialize_with(event: &NostrEvent, options: &CompactOptions) -> Vec<u8> {
+        let mut buf = Vec::new();
+        ciborium::into_writer(&to_value_with(event, options), &mut buf)
+            .expect("CBOR serialization should not fail");
+        buf
+    }
+
+    /// Build the packed array for an event, compacting values
+    pub fn to_value_with(event: &NostrEvent, options: &CompactOptions) -> Value {
+        if options.is_empty() {
+            return to_value(event);
+        }
+
+        let tags = match options
+            .follows
+            .then(|| follows::encode(&event.tags))
+            .flatten()
+        {
+            Some((run, block)) => Value::Tag(
+                FOLLOWS_TAG,
+                Box::new(Value::Array(vec![
+                    compact_tags(&event.tags[..run.start], options),
+                    Value::Bytes(block),
+                    compact_tags(&event.tags[run.end..], options),
+                ])),
+            ),
+            None => compact_tags(&event.tags, options),
+        };
+
+        Value::Array(vec![
+            Value::Bytes(event.id.to_vec()),
+            Value::Bytes(event.pubkey.to_vec()),
+            Value::Integer(event.created_at.into()),
+            Value::Integer(event.kind.into()),
+            tags,
+            compact::compact_content(event.kind, &event.content, options, Format::CborPacked)
+                .map(compacted)
+                .unwrap_or_else(|| Value::Text(event.content.clone())),
+            Value::Bytes(event.sig.to_vec()),
+        ])
+    }
+
+    fn compact_tags(tags: &[Vec<String>], options: &CompactOptions) -> Value {
+        Value::Array(
+            tags.iter()
+                .map(|tag| {
+                    Value::Array(
+                        tag.iter()
+                            .map(|v| match encode_tag_value_cbor(v) {
+                                Value::Text(text) => {
+                                    compact_value(v, options).unwrap_or(Value::Text(text))
+                                }
+                                hex => hex,
+                            })
+                            .collect(),
+                    )
                 })
-            })
-            .collect()
+                .collect(),
+        )
+    }
+
+    fn compact_value(value: &str, options: &CompactOptions) -> Option<Value> {
+        compact::compact_value(value, options, Format::CborPacked).map(compacted)
+    }
+
+    fn compacted(body: Vec<u8>) -> Value {
+        Value::Tag(COMPACT_TAG, Box::new(Value::Bytes(body)))
+    }
+
+    /// Build the packed array for an event, for embedding in larger CBOR values
+    pub fn to_value(event: &NostrEvent) -> Value {
+        Value::Array(vec![
+            Value::Bytes(event.id.to_vec()),
+            Value::Bytes(event.pubkey.to_vec()),
+            Value::Integer(event.created_at.into()),
+            Value::Integer(event.kind.into()),
+            tags_to_value(&event.tags),
+            Value::Text(event.content.clone()),
+            Value::Bytes(event.sig.to_vec()),
+        ])
+    }
+
+    /// Read an event from its packed array
+    pub fn from_value(value: &Value) -> Result<NostrEvent, CborError> {
+        let arr = value.as_array().ok_or(CborError::ExpectedArray)?;
+        if arr.len() != 7 {
+            return Err(CborError::InvalidLength("event array"));
+        }
+
+        Ok(NostrEvent {
+            id: extract_bytes(&arr[0], "id")?
+                .try_into()
+                .map_err(|_| CborError::InvalidLength("id"))?,
+            pubkey: extract_bytes(&arr[1], "pubkey")?
+                .try_into()
+                .map_err(|_| CborError::InvalidLength("pubkey"))?,
+            created_at: extract_i64(&arr[2], "created_at")?,
+            kind: extract_u16(&arr[3], "kind")?,
+            tags: extract_tags_or_follows(&arr[4])?,
+            content: extract_text_or_compact(&arr[5], "content")?,
+            sig: extract_bytes(&arr[6], "sig")?
+                .try_into()
+                .map_err(|_| CborError::InvalidLength("sig"))?,
+        })
     }
 }
 
@@ -217,6 +290,22 @@ pub mod intkey {
     use super::*;
 
     pub fn serialize(event: &NostrEvent) -> Vec<u8> {
+        write(event, tags_to_value(&event.tags), encoded_len(event))
+    }
+
+    /// Serialize with every tag value stored as text
+    pub fn serialize_plain(event: &NostrEvent) -> Vec<u8> {
+        let len = header_len(7) + 7 + fields_len(event, |value| text_len(value.len()));
+        write(event, tags_to_text_value(&event.tags), len)
+    }
+
+    /// Exact length of [`serialize`] output, computed without serializing
+    pub fn encoded_len(event: &NostrEvent) -> usize {
+        // Keys 0-6 are one byte each
+        header_len(7) + 7 + fields_len(event, tag_value_len)
+    }
+
+    fn write(event: &NostrEvent, tags: Value, len: usize) -> Vec<u8> {
         let value = Value::Map(vec![
             (Value::Integer(0.into()), Value::Bytes(event.id.to_vec())),
             (
@@ -228,12 +317,12 @@ pub mod intkey {
                 Value::Integer(event.created_at.into()),
             ),
             (Value::Integer(3.into()), Value::Integer(event.kind.into())),
-            (Value::Integer(4.into()), tags_to_value(&event.tags)),
+            (Value::Integer(4.into()), tags),
             (Value::Integer(5.into()), Value::Text(event.content.clone())),
             (Value::Integer(6.into()), Value::Bytes(event.sig.to_vec())),
         ]);
 
-        let mut buf = Vec::new();
+        let mut buf = Vec::with_capacity(len);
         ciborium::into_writer(&value, &mut buf).expect("CBOR serialization should not fail");
         buf
     }
@@ -382,7 +471,7 @@ pub mod packed_no_hex_opt {
             Value::Bytes(event.pubkey.to_vec()),
             Value::Integer(event.created_at.into()),
             Value::Integer(event.kind.into()),
-            tags_to_value_no_opt(&event.tags),
+            tags_to_text_value(&event.tags),
             Value::Text(event.content.clone()),
             Value::Bytes(event.sig.to_vec()),
         ]);
@@ -423,16 +512,6 @@ pub mod packed_no_hex_opt {
         })
     }
 
-    fn tags_to_value_no_opt(tags: &[Vec<String>]) -> Value {
-        Value::Array(
-            tags.iter()
-                .map(|tag| {
-                    Value::Array(tag.iter().map(|v| Value::Text(v.clone())).collect())
-                })
-                .collect(),
-        )
-    }
-
     fn extract_tags_no_opt(value: &Value) -> Result<Vec<Vec<String>>, CborError> {
         let arr = value.as_array().ok_or(CborError::ExpectedArray)?;
 
@@ -468,7 +547,7 @@ fn is_hex_string(s: &str) -> bool {
 /// - Event IDs in `e` tags are 64-char hex → 32 bytes (50% savings)
 /// - Public keys in `p` tags are 64-char hex → 32 bytes (50% savings)
 /// - Many relay URLs and other values are NOT hex and stored as-is
-fn encode_tag_value_cbor(value: &str) -> Value {
+pub(crate) fn encode_tag_value_cbor(value: &str) -> Value {
     if is_hex_string(value) && value.len().is_multiple_of(2) {
         // Try to decode as hex - if successful, store as bytes
         if let Ok(bytes) = hex::decode(value) {
@@ -480,17 +559,84 @@ fn encode_tag_value_cbor(value: &str) -> Value {
 }
 
 /// Decode a tag value from CBOR Value back to string
-fn decode_tag_value_cbor(value: &Value) -> Result<String, CborError> {
+pub(crate) fn decode_tag_value_cbor(value: &Value) -> Result<String, CborError> {
     match value {
         Value::Bytes(bytes) => {
             // Decode hex bytes back to hex string
             Ok(hex::encode(bytes))
         }
         Value::Text(text) => Ok(text.clone()),
+        Value::Tag(COMPACT_TAG, _) => extract_text_or_compact(value, "tag value"),
         _ => Err(CborError::ExpectedString("tag value")),
     }
 }
 
+/// Read a text value, or a compacted one written by [`packed::serialize_with`]
+fn extract_text_or_compact(value: &Value, field: &'static str) -> Result<String, CborError> {
+    match value {
+        Value::Tag(COMPACT_TAG, inner) => {
+            let body = inner.as_bytes().ok_or(CborError::ExpectedBytes(field))?;
+            Ok(compact::expand(body)?)
+        }
+        _ => extract_string(value, field),
+    }
+}
+
+/// Length of an item header whose argument is `n` (CBOR uses the shortest)
+fn header_len(n: u64) -> usize {
+    match n {
+        0..=23 => 1,
+        24..=0xFF => 2,
+        0x100..=0xFFFF => 3,
+        0x1_0000..=0xFFFF_FFFF => 5,
+        _ => 9,
+    }
+}
+
+fn integer_len(value: i64) -> usize {
+    // Negative integers store -1 - value
+    header_len(if value < 0 {
+        !value as u64
+    } else {
+        value as u64
+    })
+}
+
+/// Length of a text or byte string of `len` bytes
+fn text_len(len: usize) -> usize {
+    header_len(len as u64) + len
+}
+
+/// Length of a tag value as written by [`encode_tag_value_cbor`]
+fn tag_value_len(value: &str) -> usize {
+    if is_hex_string(value) && value.len().is_multiple_of(2) {
+        text_len(value.len() / 2)
+    } else {
+        text_len(value.len())
+    }
+}
+
+/// Length of the seven field values, with tag values measured by
+/// `tag_value_len`
+fn fields_len(event: &NostrEvent, tag_value_len: impl Fn(&str) -> usize) -> usize {
+    let tags: usize = event
+        .tags
+        .iter()
+        .map(|tag| {
+            header_len(tag.len() as u64) + tag.iter().map(|v| tag_value_len(v)).sum::<usize>()
+        })
+        .sum();
+
+    text_len(event.id.len())
+        + text_len(event.pubkey.len())
+        + integer_len(event.created_at)
+        + integer_len(event.kind.into())
+        + header_len(event.tags.len() as u64)
+        + tags
+        + text_len(event.content.len())
+        + text_len(event.sig.len())
+}
+
 fn tags_to_value(tags: &[Vec<String>]) -> Value {
     Value::Array(
         tags.iter()
@@ -499,14 +645,22 @@ fn tags_to_value(tags: &[Vec<String>]) -> Value {
     )
 }
 
-fn extract_bytes(value: &Value, field: &'static str) -> Result<Vec<u8>, CborError> {
+fn tags_to_text_value(tags: &[Vec<String>]) -> Value {
+    Value::Array(
+        tags.iter()
+            .map(|tag| Value::Array(tag.iter().map(|v| Value::Text(v.clone())).collect()))
+            .collect(),
+    )
+}
+
+pub(crate) fn extract_bytes(value: &Value, field: &'static str) -> Result<Vec<u8>, CborError> {
     value
         .as_bytes()
         .map(|b| b.to_vec())
         .ok_or(CborError::ExpectedBytes(field))
 }
 
-fn extract_i64(value: &Value, field: &'static str) -> Result<i64, CborError> {
+pub(crate) fn extract_i64(value: &Value, field: &'static str) -> Result<i64, CborError> {
     value
         .as_integer()
         .and_then(|i| {
@@ -526,7 +680,7 @@ fn extract_u16(value: &Value, field: &'static str) -> Result<u16, CborError> {
         .ok_or(CborError::ExpectedInteger(field))
 }
 
-fn extract_string(value: &Value, field: &'static str) -> Result<String, CborError> {
+pub(crate) fn extract_string(value: &Value, field: &'static str) -> Result<String, CborError> {
     value
         .as_text()
         .map(|s| s.to_string())
@@ -544,6 +698,26 @@ fn extract_tags(value: &Value) -> Result<Vec<Vec<String>>, CborError> {
         .collect()
 }
 
+/// Read a tag list, or one with a follow block written by
+/// [`packed::serialize_with`]
+fn extract_tags_or_follows(value: &Value) -> Result<Vec<Vec<String>>, CborError> {
+    let Value::Tag(FOLLOWS_TAG, inner) = value else {
+        return extract_tags(value);
+    };
+    let parts = inner.as_array().ok_or(CborError::ExpectedArray)?;
+    let [before, block, after] = parts.as_slice() else {
+        return Err(CborError::InvalidLength("follow tags"));
+    };
+    let block = block
+        .as_bytes()
+        .ok_or(CborError::ExpectedBytes("follow block"))?;
+
+    let mut tags = extract_tags(before)?;
+    tags.extend(follows::decode(block)?);
+    tags.extend(extract_tags(after)?);
+    Ok(tags)
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum CborError {
     #[error("CBOR error: {0}")]
@@ -569,6 +743,12 @@ pub enum CborError {
 
     #[error("Missing field: {0}")]
     MissingField(&'static str),
+
+    #[error("Compacted value error: {0}")]
+    Compact(#[from] CompactError),
+
+    #[error("Follow block error: {0}")]
+    Follows(#[from] FollowsError),
 }
 
 #[cfg(test)]
@@ -606,6 +786,43 @@ mod tests {
         assert_eq!(event, back);
     }
 
+    #[test]
+    fn test_packed_compact_roundtrip() {
+        use crate::compact::CompactOptions;
+        use crate::nip19::Nip19;
+
+        let npub = Nip19::Pubkey([0x11; 32]).to_string();
+        let mut event = sample_event();
+        event.content = format!("gm nostr:{}", npub);
+        event.tags.push(vec!["q".to_string(), npub]);
+
+        let plain = packed::serialize(&event);
+        let bytes = packed::serialize_with(&event, &CompactOptions::all());
+        assert!(bytes.len() < plain.len());
+        assert_eq!(packed::deserialize(&bytes).unwrap(), event);
+    }
+
+    #[test]
+    fn test_packed_follow_block_roundtrip() {
+        use crate::compact::CompactOptions;
+
+        let mut event = sample_event();
+        event.kind = 3;
+        event.tags.extend((0..20u8).map(|i| {
+            vec![
+                "p".to_string(),
+                hex::encode([i; 32]),
+                "wss://relay.example.com".to_string(),
+                format!("friend{}", i),
+            ]
+        }));
+
+        let options = CompactOptions::new().with_follows(true);
+        let bytes = packed::serialize_with(&event, &options);
+        assert!(bytes.len() < packed::serialize(&event).len());
+        assert_eq!(packed::deserialize(&bytes).unwrap(), event);
+    }
+
     #[test]
     fn test_intkey_roundtrip() {
         let event = sample_event();
@@ -0,0 +1,306 @@
+//! `tokio_util::codec` framing for every format
+//!
+//! Enabled with the `codec` cargo feature. [`EventCodec`] wraps a format
+//! with a 4-byte big-endian length prefix (the same framing as tokio-util's
+//! `LengthDelimitedCodec` defaults), enforces a maximum frame size and
+//! reports the format's own error type:
+//!
+//! ```ignore
+//! use binostr::codec::DannyPackCodec;
+//! use tokio_util::codec::Framed;
+//!
+//! let mut framed = Framed::new(socket, DannyPackCodec::new());
+//! framed.send(&event).await?;
+//! let event = framed.next().await.transpose()?;
+//! ```
+//!
+//! A `FrameTooLarge` or decode error leaves the stream mid-frame, so the
+//! connection should be dropped.
+
+use std::io;
+use std::marker::PhantomData;
+
+use bytes::{Buf, BufMut, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+use crate::event::NostrEvent;
+use crate::stats::{self, FormatError};
+use crate::{capnp, cbor, dannypack, json, notepack, proto};
+
+/// Length prefix size
+const HEADER_LEN: usize = 4;
+
+/// Default maximum frame payload (1 MiB)
+pub const DEFAULT_MAX_FRAME_SIZE: usize = 1024 * 1024;
+
+#[derive(Debug, thiserror::Error)]
+pub enum CodecError<E: std::error::Error + 'static> {
+    #[error("IO error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("Frame of {len} bytes exceeds maximum of {max}")]
+    FrameTooLarge { len: usize, max: usize },
+
+    #[error(transparent)]
+    Format(E),
+}
+
+/// An event encoding usable with [`EventCodec`]
+pub trait EventFormat {
+    type Error: std::error::Error + 'static;
+
+    fn serialize(event: &NostrEvent) -> Vec<u8>;
+
+    fn deserialize(data: &[u8]) -> Result<NostrEvent, Self::Error>;
+}
+
+macro_rules! event_format {
+    ($(#[$doc:meta])* $name:ident, $error:ty, $serialize:path, $deserialize:path) => {
+        $(#[$doc])*
+        #[derive(Debug, Clone, Copy, Default)]
+        pub struct $name;
+
+        impl EventFormat for $name {
+            type Error = $error;
+
+            fn serialize(event: &NostrEvent) -> Vec<u8> {
+                $serialize(event)
+            }
+
+            fn deserialize(data: &[u8]) -> Result<NostrEvent, Self::Error> {
+                $deserialize(data)
+            }
+        }
+    };
+}
+
+event_format!(
+    /// NIP-01 JSON
+    Json, json::JsonError, json::serialize, json::deserialize
+);
+event_format!(
+    CborSchemaless,
+    cbor::CborError,
+    cbor::schemaless::serialize,
+    cbor::schemaless::deserialize
+);
+event_format!(
+    CborPacked,
+    cbor::CborError,
+    cbor::packed::serialize,
+    cbor::packed::deserialize
+);
+event_format!(
+    CborIntKey,
+    cbor::CborError,
+    cbor::intkey::serialize,
+    cbor::intkey::deserialize
+);
+event_format!(
+    ProtoString,
+    proto::ProtoError,
+    proto::string::serialize,
+    proto::string::deserialize
+);
+event_format!(
+    ProtoBinary,
+    proto::ProtoError,
+    proto::binary::serialize,
+    proto::binary::deserialize
+);
+event_format!(
+    CapnProto,
+    capnp::CapnpError,
+    capnp::serialize_event,
+    capnp::deserialize_event
+);
+event_format!(
+    CapnProtoPacked,
+    capnp::CapnpError,
+    capnp::serialize_event_packed,
+    capnp::deserialize_event_packed
+);
+event_format!(
+    DannyPack,
+    dannypack::DannyPackError,
+    dannypack::serialize,
+    dannypack::deserialize
+);
+event_format!(
+    Notepack,
+    notepack::NotepackError,
+    notepack::serialize,
+    notepack::deserialize
+);
+
+/// Length-delimited codec for events in format `F`
+#[derive(Debug, Clone)]
+pub struct EventCodec<F> {
+    max_frame_size: usize,
+    _format: PhantomData<F>,
+}
+
+pub type JsonCodec = EventCodec<Json>;
+pub type CborSchemalessCodec = EventCodec<CborSchemaless>;
+pub type CborPackedCodec = EventCodec<CborPacked>;
+pub type CborIntKeyCodec = EventCodec<CborIntKey>;
+pub type ProtoStringCodec = EventCodec<ProtoString>;
+pub type ProtoBinaryCodec = EventCodec<ProtoBinary>;
+pub type CapnProtoCodec = EventCodec<CapnProto>;
+pub type CapnProtoPackedCodec = EventCodec<CapnProtoPacked>;
+pub type DannyPackCodec = EventCodec<DannyPack>;
+pub type NotepackCodec = EventCodec<Notepack>;
+
+impl<F> EventCodec<F> {
+    pub fn new() -> Self {
+        Self {
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+            _format: PhantomData,
+        }
+    }
+
+    /// Set the largest accepted frame payload, in bytes
+    pub fn with_max_frame_size(mut self, max_frame_size: usize) -> Self {
+        self.max_frame_size = max_frame_size;
+        self
+    }
+
+    pub fn max_frame_size(&self) -> usize {
+        self.max_frame_size
+    }
+}
+
+impl<F> Default for EventCodec<F> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<F: EventFormat> Decoder for EventCodec<F> {
+    type Item = NostrEvent;
+    type Error = CodecError<F::Error>;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<NostrEvent>, Self::Error> {
+        let Some(len) = frame_len(src, self.max_frame_size)? else {
+            return Ok(None);
+        };
+        src.advance(HEADER_LEN);
+        let frame = src.split_to(len);
+        F::deserialize(&frame).map(Some).map_err(CodecError::Format)
+    }
+}
+
+impl<F: EventFormat> Encoder<&NostrEvent> for EventCodec<F> {
+    type Error = CodecError<F::Error>;
+
+    fn encode(&mut self, event: &NostrEvent, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        put_frame(&F::serialize(event), self.max_frame_size, dst)
+    }
+}
+
+impl<F: EventFormat> Encoder<NostrEvent> for EventCodec<F> {
+    type Error = CodecError<F::Error>;
+
+    fn encode(&mut self, event: NostrEvent, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.encode(&event, dst)
+    }
+}
+
+/// Codec for a format chosen at runtime, e.g. after
+/// [`negotiate`](crate::negotiate)
+#[derive(Debug, Clone)]
+pub struct FormatCodec {
+    format: stats::Format,
+    max_frame_size: usize,
+}
+
+impl FormatCodec {
+    pub fn new(format: stats::Format) -> Self {
+        Self {
+            format,
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+        }
+    }
+
+    /// Set the largest accepted frame payload, in bytes
+    pub fn with_max_frame_size(mut self, max_frame_size: usize) -> Self {
+        self.max_frame_size = max_frame_size;
+        self
+    }
+
+    pub fn format(&self) -> stats::Format {
+        self.format
+    }
+}
+
+impl Decoder for FormatCodec {
+    type Item = NostrEvent;
+    type Error = CodecError<FormatError>;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<NostrEvent>, Self::Error> {
+        let Some(len) = frame_len(src, self.max_frame_size)? else {
+            return Ok(None);
+        };
+        src.advance(HEADER_LEN);
+        let frame = src.split_to(len);
+        stats::deserialize(&frame, self.format)
+            .map(Some)
+            .map_err(CodecError::Format)
+    }
+}
+
+impl Encoder<&NostrEvent> for FormatCodec {
+    type Error = CodecError<FormatError>;
+
+    fn encode(&mut self, event: &NostrEvent, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        put_frame(
+            &stats::serialize(event, self.format),
+            self.max_frame_size,
+            dst,
+        )
+    }
+}
+
+impl Encoder<NostrEvent> for FormatCodec {
+    type Error = CodecError<FormatError>;
+
+    fn encode(&mut self, event: NostrEvent, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.encode(&event, dst)
+    }
+}
+
+/// Payload length of the next complete frame, reserving space for a partial one
+fn frame_len<E: std::error::Error>(
+    src: &mut BytesMut,
+    max: usize,
+) -> Result<Option<usize>, CodecError<E>> {
+    if src.len() < HEADER_LEN {
+        return Ok(None);
+    }
+    let len = u32::from_be_bytes(src[..HEADER_LEN].try_into().unwrap()) as usize;
+    if len > max {
+        return Err(CodecError::FrameTooLarge { len, max });
+    }
+    if src.len() < HEADER_LEN + len {
+        src.reserve(HEADER_LEN + len - src.len());
+        return Ok(None);
+    }
+    Ok(Some(len))
+}
+
+fn put_frame<E: std::error::Error>(
+    payload: &[u8],
+    max: usize,
+    dst: &mut BytesMut,
+) -> Result<(), CodecError<E>> {
+    if payload.len() > max {
+        return Err(CodecError::FrameTooLarge {
+            len: payload.len(),
+            max,
+        });
+    }
+    dst.reserve(HEADER_LEN + payload.len());
+    dst.put_u32(payload.len() as u32);
+    dst.extend_from_slice(payload);
+    Ok(())
+}
@@ -0,0 +1,520 @@
+//! Reversible compaction of string values
+//!
+//! Hex compaction (DannyPack, CBOR, Cap'n Proto) only catches values that
+//! are entirely lowercase hex. This module recognizes structured substrings
+//! that are really binary data and stores them as bytes, restoring the exact
+//! original string on decode so event ids still verify.
+//!
+//! A compacted value is a sequence of segments:
+//!
+//! ```text
+//! 0x00 text:   [len: varint] [UTF-8 bytes]
+//! 0x01 bech32: [prefix: u8, index into nip19::PREFIXES] [len: varint] [payload]
+//! 0x02 base64: [variant: u8, bit 0 URL-safe, bit 1 padded] [len: varint] [bytes]
+//! 0x03 profile: [len: varint] [crate::profile body]
+//! 0x04 event:   [len: varint] [crate::embed body]
+//! ```
+//!
+//! Base64 runs cover NIP-04 (`ct?iv=iv`, two runs around a text `?iv=`) and
+//! NIP-44 v2 payloads. Values that are entirely lowercase hex are left to
+//! the base format's hex compaction, which stores them in half the space.
+//! A profile segment replaces the whole content of a kind 0 event; see
+//! [`compact_content`]. An event segment replaces a whole value holding
+//! an embedded JSON event (reposts, zap requests), encoded in the outer
+//! format; see [`compact_value`].
+//!
+//! Segments are only emitted when re-encoding reproduces the original text
+//! byte for byte; anything else stays text. The body carries no length of
+//! its own, so formats frame it: DannyPack behind its escape header, CBOR
+//! Packed as a tagged byte string.
+
+use std::ops::Range;
+
+use base64::engine::general_purpose::{STANDARD, STANDARD_NO_PAD, URL_SAFE, URL_SAFE_NO_PAD};
+use base64::engine::GeneralPurpose;
+use base64::Engine;
+use thiserror::Error;
+
+use crate::dannypack::{write_chunk, Reader, Truncated};
+use crate::embed::{self, EmbedError};
+use crate::nip19::{self, Nip19Error};
+use crate::profile::{self, ProfileError};
+use crate::stats::Format;
+use crate::tags::is_compressible_hex;
+
+const SEGMENT_TEXT: u8 = 0x00;
+const SEGMENT_BECH32: u8 = 0x01;
+const SEGMENT_BASE64: u8 = 0x02;
+const SEGMENT_PROFILE: u8 = 0x03;
+const SEGMENT_EVENT: u8 = 0x04;
+
+const BASE64_URL_SAFE: u8 = 0x01;
+const BASE64_PADDED: u8 = 0x02;
+
+/// Shortest base64 run worth a segment (a NIP-04 iv is 24 characters)
+const MIN_BASE64_LEN: usize = 16;
+
+#[derive(Debug, Error)]
+pub enum CompactError {
+    #[error("Compacted value is truncated")]
+    Truncated,
+
+    #[error("Unknown segment type: {0:#04x}")]
+    UnknownSegment(u8),
+
+    #[error("Unknown bech32 prefix code: {0}")]
+    UnknownPrefix(u8),
+
+    #[error("Unknown base64 variant: {0:#04x}")]
+    UnknownVariant(u8),
+
+    #[error("UTF-8 error: {0}")]
+    Utf8(#[from] std::str::Utf8Error),
+
+    #[error("NIP-19 error: {0}")]
+    Nip19(#[from] Nip19Error),
+
+    #[error("Profile error: {0}")]
+    Profile(#[from] ProfileError),
+
+    #[error("Embedded event error: {0}")]
+    Embed(#[from] EmbedError),
+}
+
+impl From<Truncated> for CompactError {
+    fn from(_: Truncated) -> Self {
+        CompactError::Truncated
+    }
+}
+
+/// Which compactions to try when encoding
+///
+/// Decoding always understands every segment type.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CompactOptions {
+    /// NIP-19 entities (`npub1…`, `note1…`, `nevent1…`, `naddr1…`, …)
+    pub bech32: bool,
+    /// Base64 runs (NIP-04 and NIP-44 ciphertexts), standard or URL-safe,
+    /// padded or not
+    pub base64: bool,
+    /// Kind 0 profile JSON as structured binary (content only)
+    pub profile: bool,
+    /// Runs of follow-list `p` tags as a [`follows`](crate::follows) block
+    /// (tags only; applied by the formats, not by [`compact`])
+    pub follows: bool,
+    /// Embedded JSON events as [`embed`](crate::embed) bodies in the outer
+    /// format (whole values only; see [`compact_value`])
+    pub events: bool,
+}
+
+impl CompactOptions {
+    /// Nothing enabled: values are stored as the base format stores them
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Every compaction enabled
+    pub fn all() -> Self {
+        Self {
+            bech32: true,
+            base64: true,
+            profile: true,
+            follows: true,
+            events: true,
+        }
+    }
+
+    pub fn with_bech32(mut self, enabled: bool) -> Self {
+        self.bech32 = enabled;
+        self
+    }
+
+    pub fn with_base64(mut self, enabled: bool) -> Self {
+        self.base64 = enabled;
+        self
+    }
+
+    pub fn with_profile(mut self, enabled: bool) -> Self {
+        self.profile = enabled;
+        self
+    }
+
+    pub fn with_follows(mut self, enabled: bool) -> Self {
+        self.follows = enabled;
+        self
+    }
+
+    pub fn with_events(mut self, enabled: bool) -> Self {
+        self.events = enabled;
+        self
+    }
+
+    /// Whether no compaction is enabled
+    pub fn is_empty(&self) -> bool {
+        *self == Self::new()
+    }
+}
+
+/// Compact an event's content, if that makes it smaller
+///
+/// Like [`compact_value`], but kind 0 content is first tried as a profile
+/// segment when [`CompactOptions::profile`] is enabled.
+pub fn compact_content(
+    kind: u16,
+    content: &str,
+    options: &CompactOptions,
+    format: Format,
+) -> Option<Vec<u8>> {
+    if options.profile && kind == 0 {
+        if let Some(body) =
+            profile::encode(content).and_then(|e| whole(SEGMENT_PROFILE, e, content))
+        {
+            return Some(body);
+        }
+    }
+    compact_value(content, options, format)
+}
+
+/// Compact a content or tag value written in `format`, if that makes it
+/// smaller
+///
+/// Like [`compact`], but a value holding an embedded JSON event is first
+/// tried as an event segment, with the inner event encoded in `format`,
+/// when [`CompactOptions::events`] is enabled.
+pub fn compact_value(value: &str, options: &CompactOptions, format: Format) -> Option<Vec<u8>> {
+    if options.events && embed::is_candidate(value) {
+        if let Some(body) =
+            embed::encode(value, format, options).and_then(|e| whole(SEGMENT_EVENT, e, value))
+        {
+            return Some(body);
+        }
+    }
+    compact(value, options)
+}
+
+/// A single segment replacing all of `value`, if shorter than it
+fn whole(segment: u8, encoded: Vec<u8>, value: &str) -> Option<Vec<u8>> {
+    let mut body = Vec::with_capacity(encoded.len() + 4);
+    body.push(segment);
+    write_chunk(&mut body, &encoded);
+    (body.len() < value.len()).then_some(body)
+}
+
+/// Compact a value, if that makes it smaller
+///
+/// Profile and event compaction only apply through [`compact_content`] and
+/// [`compact_value`].
+///
+/// Returns the segment body, or `None` when nothing was recognized or the
+/// body would not be shorter than the text.
+pub fn compact(value: &str, options: &CompactOptions) -> Option<Vec<u8>> {
+    if !(options.bech32 || options.base64) || is_compressible_hex(value) {
+        return None;
+    }
+
+    // (range, segment type, variant or prefix code, payload)
+    let mut pieces: Vec<(Range<usize>, u8, u8, Vec<u8>)> = Vec::new();
+
+    if options.bech32 {
+        for reference in nip19::scan(value) {
+            // Keep any `nostr:` scheme as text; only the entity is binary
+            let start = if reference.is_uri() {
+                reference.range.start + nip19::URI_SCHEME.len()
+            } else {
+                reference.range.start
+            };
+            if let Some((code, payload)) = bech32_payload(&value[start..reference.range.end]) {
+                pieces.push((start..reference.range.end, SEGMENT_BECH32, code, payload));
+            }
+        }
+    }
+
+    if options.base64 {
+        let bech32_count = pieces.len();
+        for run in base64_runs(value) {
+            let overlaps = pieces[..bech32_count]
+                .iter()
+                .any(|(range, ..)| range.start < run.end && run.start < range.end);
+            if overlaps {
+                continue;
+            }
+            if let Some((variant, bytes)) = base64_payload(&value[run.clone()]) {
+                pieces.push((run, SEGMENT_BASE64, variant, bytes));
+            }
+        }
+        pieces.sort_by_key(|(range, ..)| range.start);
+    }
+
+    if pieces.is_empty() {
+        return None;
+    }
+
+    let mut body = Vec::new();
+    let mut text_start = 0;
+    for (range, segment, code, payload) in pieces {
+        push_text(&mut body, &value[text_start..range.start]);
+        body.push(segment);
+        body.push(code);
+        write_chunk(&mut body, &payload);
+        text_start = range.end;
+    }
+    push_text(&mut body, &value[text_start..]);
+    (body.len() < value.len()).then_some(body)
+}
+
+/// Restore the original string from a compacted body
+pub fn expand(body: &[u8]) -> Result<String, CompactError> {
+    let mut out = String::with_capacity(body.len() * 2);
+    let mut reader = Reader::new(body);
+
+    while !reader.is_empty() {
+        match reader.byte()? {
+            SEGMENT_TEXT => {
+                out.push_str(std::str::from_utf8(reader.chunk()?)?);
+            }
+            SEGMENT_BECH32 => {
+                let code = reader.byte()?;
+                let prefix = nip19::PREFIXES
+                    .get(code as usize)
+                    .ok_or(CompactError::UnknownPrefix(code))?;
+                out.push_str(&nip19::encode_payload(prefix, reader.chunk()?)?);
+            }
+            SEGMENT_BASE64 => {
+                let variant = reader.byte()?;
+                let engine = base64_engine(variant).ok_or(CompactError::UnknownVariant(variant))?;
+                engine.encode_string(reader.chunk()?, &mut out);
+            }
+            SEGMENT_PROFILE => {
+                out.push_str(&profile::decode(reader.chunk()?)?);
+            }
+            SEGMENT_EVENT => {
+                out.push_str(&embed::decode(reader.chunk()?)?);
+            }
+            other => return Err(CompactError::UnknownSegment(other)),
+        }
+    }
+
+    Ok(out)
+}
+
+/// Prefix code and payload, if re-encoding reproduces `bech32` exactly
+fn bech32_payload(bech32: &str) -> Option<(u8, Vec<u8>)> {
+    let (prefix, payload) = nip19::decode_payload(bech32).ok()?;
+    if nip19::encode_payload(prefix, &payload).ok()? != bech32 {
+        return None;
+    }
+    let code = nip19::PREFIXES.iter().position(|&p| p == prefix)? as u8;
+    Some((code, payload))
+}
+
+/// Variant and bytes, if `run` is canonical base64 in a single alphabet
+fn base64_payload(run: &str) -> Option<(u8, Vec<u8>)> {
+    let url_safe = run.contains(['-', '_']);
+    if url_safe && run.contains(['+', '/']) {
+        return None;
+    }
+    let mut variant = 0;
+    if url_safe {
+        variant |= BASE64_URL_SAFE;
+    }
+    if run.ends_with('=') {
+        variant |= BASE64_PADDED;
+    }
+
+    let engine = base64_engine(variant)?;
+    let bytes = engine.decode(run).ok()?;
+    (engine.encode(&bytes) == run).then_some((variant, bytes))
+}
+
+fn base64_engine(variant: u8) -> Option<&'static GeneralPurpose> {
+    match variant {
+        0 => Some(&STANDARD_NO_PAD),
+        BASE64_URL_SAFE => Some(&URL_SAFE_NO_PAD),
+        BASE64_PADDED => Some(&STANDARD),
+        v if v == BASE64_URL_SAFE | BASE64_PADDED => Some(&URL_SAFE),
+        _ => None,
+    }
+}
+
+/// Maximal runs of base64 characters (either alphabet) with up to two
+/// trailing `=`, at least [`MIN_BASE64_LEN`] long
+fn base64_runs(value: &str) -> Vec<Range<usize>> {
+    let bytes = value.as_bytes();
+    let mut runs = Vec::new();
+    let mut pos = 0;
+
+    while pos < bytes.len() {
+        if !is_base64_char(bytes[pos]) {
+            pos += 1;
+            continue;
+        }
+        let start = pos;
+        while pos < bytes.len() && is_base64_char(bytes[pos]) {
+            pos += 1;
+        }
+        let data_end = pos;
+        while pos < bytes.len() && bytes[pos] == b'=' && pos - data_end < 2 {
+            pos += 1;
+        }
+        if pos - start >= MIN_BASE64_LEN {
+            runs.push(start..pos);
+        }
+    }
+
+    runs
+}
+
+fn is_base64_char(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || matches!(b, b'+' | b'/' | b'-' | b'_')
+}
+
+fn push_text(body: &mut Vec<u8>, text: &str) {
+    if !text.is_empty() {
+        body.push(SEGMENT_TEXT);
+        write_chunk(body, text.as_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nip19::{EventPointer, Nip19};
+
+    #[test]
+    fn test_bech32_roundtrip() {
+        let npub = Nip19::Pubkey([0x11; 32]).to_string();
+        let nevent = Nip19::Event(EventPointer {
+            id: [0x22; 32],
+            relays: vec!["wss://relay.example.com".to_string()],
+            author: None,
+            kind: Some(1),
+        })
+        .to_string();
+        let value = format!("gm nostr:{} 🤙\nnostr:{}", npub, nevent);
+
+        let body = compact(&value, &CompactOptions::all()).unwrap();
+        assert!(body.len() < value.len() - 60);
+        assert_eq!(expand(&body).unwrap(), value);
+
+        // A bare entity compacts to a single segment
+        let body = compact(&npub, &CompactOptions::all()).unwrap();
+        assert_eq!(body.len(), 3 + 32);
+        assert_eq!(expand(&body).unwrap(), npub);
+    }
+
+    #[test]
+    fn test_base64_roundtrip() {
+        let options = CompactOptions::new().with_base64(true);
+        let ciphertext: Vec<u8> = (0..=255u8).cycle().skip(7).take(100).collect();
+
+        // NIP-04: two padded runs around a text `?iv=`
+        let nip04 = format!(
+            "{}?iv={}",
+            STANDARD.encode(&ciphertext),
+            STANDARD.encode([0x5a; 16])
+        );
+        let body = compact(&nip04, &options).unwrap();
+        assert_eq!(body.len(), (3 + 100) + (2 + 4) + (3 + 16));
+        assert_eq!(expand(&body).unwrap(), nip04);
+
+        // NIP-44 v2 style and URL-safe, unpadded variants
+        for value in [
+            STANDARD.encode(&ciphertext[..98]),
+            URL_SAFE_NO_PAD.encode(&ciphertext),
+            STANDARD_NO_PAD.encode(&ciphertext[..97]),
+        ] {
+            let body = compact(&value, &options).unwrap();
+            assert_eq!(expand(&body).unwrap(), value);
+        }
+
+        // Lowercase hex is left to hex compaction; mixed alphabets and
+        // non-canonical trailing bits stay text
+        assert_eq!(compact(&"ab".repeat(32), &options), None);
+        assert_eq!(compact("abcdefgh+/abcdef-_abcdefgh", &options), None);
+        assert_eq!(compact("abcdefghijklmnopqrstuvwxyz", &options), None);
+    }
+
+    #[test]
+    fn test_profile_content() {
+        let options = CompactOptions::new().with_profile(true);
+        let json = r#"{"name":"alice","about":"hello","picture":"https://example.com/a.jpg"}"#;
+
+        let body = compact_content(0, json, &options, Format::DannyPack).unwrap();
+        assert_eq!(body[0], SEGMENT_PROFILE);
+        assert_eq!(expand(&body).unwrap(), json);
+
+        // Only kind 0, and only through compact_content
+        assert_eq!(compact_content(1, json, &options, Format::DannyPack), None);
+        assert_eq!(compact(json, &options), None);
+        assert_eq!(
+            compact_content(0, r#"{"name": "alice"}"#, &options, Format::DannyPack),
+            None
+        );
+    }
+
+    #[test]
+    fn test_embedded_event() {
+        let options = CompactOptions::new().with_events(true);
+        let inner = crate::event::NostrEvent {
+            id: [0x11; 32],
+            pubkey: [0x22; 32],
+            created_at: 1_700_000_000,
+            kind: 1,
+            tags: vec![vec!["t".to_string(), "nostr".to_string()]],
+            content: "gm".to_string(),
+            sig: [0x33; 64],
+        };
+        let json = crate::json::serialize_string(&inner);
+
+        for format in [Format::DannyPack, Format::CborPacked, Format::ProtoBinary] {
+            let body = compact_value(&json, &options, format).unwrap();
+            assert_eq!(body[0], SEGMENT_EVENT);
+            assert!(body.len() < json.len() / 2);
+            assert_eq!(expand(&body).unwrap(), json);
+        }
+
+        // Only when enabled, and only through compact_value
+        assert_eq!(
+            compact_value(&json, &CompactOptions::new(), Format::DannyPack),
+            None
+        );
+        assert_eq!(compact(&json, &options), None);
+        assert_eq!(
+            compact_value("{\"sig\":1}", &options, Format::DannyPack),
+            None
+        );
+    }
+
+    #[test]
+    fn test_not_compacted() {
+        let npub = Nip19::Pubkey([0x11; 32]).to_string();
+        assert_eq!(compact(&npub, &CompactOptions::new()), None);
+        assert_eq!(compact("just text", &CompactOptions::all()), None);
+
+        // Bad checksums and uppercase entities stay text
+        let mut bad = npub.clone();
+        bad.replace_range(10..11, if &npub[10..11] == "q" { "p" } else { "q" });
+        assert_eq!(compact(&bad, &CompactOptions::all()), None);
+        assert_eq!(compact(&npub.to_uppercase(), &CompactOptions::all()), None);
+    }
+
+    #[test]
+    fn test_expand_errors() {
+        assert!(matches!(
+            expand(&[0x00, 5, b'a']),
+            Err(CompactError::Truncated)
+        ));
+        assert!(matches!(
+            expand(&[0x7f]),
+            Err(CompactError::UnknownSegment(0x7f))
+        ));
+        assert!(matches!(
+            expand(&[0x01, 42, 0]),
+            Err(CompactError::UnknownPrefix(42))
+        ));
+        assert!(matches!(
+            expand(&[0x02, 9, 0]),
+            Err(CompactError::UnknownVariant(9))
+        ));
+    }
+}
@@ -13,12 +13,55 @@
 //! [tag_len: varint] + [tag_data: variable]
 //! [content_header: 1 byte (bit7=is_hex, bits0-6=len or 0x7F for varint)] + [content_data]
 //! ```
-
-use crate::event::NostrEvent;
+//!
+//! A header of `0x80` (hex, zero length) never occurs for plain values and
+//! marks a [compacted](crate::compact) value: `0x80 [body_len: varint] [body]`.
+//! Only [`serialize_with`] writes these; every reader accepts them.
+//!
+//! Likewise a tag count of zero with more tag data after it marks a
+//! [follow block](crate::follows) section:
+//! `0x00 [tags before: count + tags] [block_len: varint] [block] [tags after: count + tags]`.
+//!
+//! Version 2 ([`serialize_v2`]) keeps this layout but codes tags against
+//! [`dictionary::V1`]: per tag `[arity: u8] [name: code, or 0xFF then the
+//! value] [values...]`, where a value header of `0x81` (hex flag with
+//! length 1, never written for hex values) is followed by a relay code.
+//! Bare payloads don't say which version they are; the
+//! [envelope](crate::envelope) records it.
+
+use crate::compact::{self, CompactError, CompactOptions};
+use crate::dictionary::{self, Dictionary};
+use crate::event::{NostrEvent, RawTagValue};
+use crate::follows::{self, FollowTag, FollowTags, FollowsError};
+use crate::stats::Format;
+use crate::tags::is_compressible_hex;
 use std::ptr;
 
 const FIXED_SIZE: usize = 138;
 
+/// Format version written by [`serialize`] and [`serialize_with`]
+pub const VERSION_1: u8 = 1;
+
+/// Format version written by [`serialize_v2`]
+pub const VERSION_2: u8 = 2;
+
+/// Value header for a compacted value (hex flag with zero length)
+const COMPACT_HEADER: u8 = 0x80;
+
+/// Version 2 value header for a dictionary relay code (hex flag, length 1)
+const DICTIONARY_HEADER: u8 = 0x81;
+
+/// Version 2 tag name byte for a name outside the dictionary
+const TAG_NAME_LITERAL: u8 = 0xFF;
+
+/// How a value's bytes are stored
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ValueKind {
+    Text,
+    Hex,
+    Compact,
+}
+
 // Lowercase-only hex decode table - rejects A-F to preserve case through roundtrip
 const HEX_LUT_LOWER: [u8; 256] = {
     let mut t = [0xFFu8; 256];
@@ -135,6 +178,29 @@ unsafe fn read_len_flag_ptr(src: *const u8, max_len: usize) -> (usize, bool, usi
     }
 }
 
+/// Read a value header, resolving the compaction escape;
+/// returns (len, kind, header bytes), with 0 header bytes if truncated
+#[inline(always)]
+unsafe fn read_value_header_ptr(src: *const u8, max_len: usize) -> (usize, ValueKind, usize) {
+    if max_len == 0 {
+        return (0, ValueKind::Text, 0);
+    }
+    if *src == COMPACT_HEADER {
+        let (len, varint_bytes) = read_varint_ptr(src.add(1), max_len - 1);
+        if varint_bytes == 0 {
+            return (0, ValueKind::Text, 0);
+        }
+        return (len as usize, ValueKind::Compact, 1 + varint_bytes);
+    }
+    let (len, is_hex, header_bytes) = read_len_flag_ptr(src, max_len);
+    let kind = if is_hex {
+        ValueKind::Hex
+    } else {
+        ValueKind::Text
+    };
+    (len, kind, header_bytes)
+}
+
 /// is this POSSIBLY hex?
 #[inline(always)]
 unsafe fn might_be_hex(src: &[u8]) -> bool {
@@ -214,7 +280,13 @@ const fn varint_size(mut value: u64) -> usize {
     size
 }
 
-pub fn serialize(event: &NostrEvent, buf: &mut Vec<u8>) {
+pub fn serialize(event: &NostrEvent) -> Vec<u8> {
+    let mut buf = Vec::new();
+    serialize_into(event, &mut buf);
+    buf
+}
+
+pub fn serialize_into(event: &NostrEvent, buf: &mut Vec<u8>) {
     let max_tags_size = calc_max_tags_size(&event.tags);
     let content_len = event.content.len();
     let estimated = FIXED_SIZE + 5 + max_tags_size + 5 + content_len;
@@ -249,37 +321,240 @@ pub fn serialize(event: &NostrEvent, buf: &mut Vec<u8>) {
             ptr = tag_len_ptr.add(varint_len + tag_data_len);
         }
 
-        let content_bytes = event.content.as_bytes();
+        ptr = pack_value(ptr, event.content.as_bytes());
 
-        if might_be_hex(content_bytes) {
-            let header_ptr = ptr;
-            ptr = ptr.add(5);
-            let decoded_len = hex_decode_checked(content_bytes, ptr);
-            if decoded_len > 0 {
-                let header_len = write_len_flag_ptr(header_ptr, decoded_len, true);
-                if header_len < 5 {
-                    ptr::copy(ptr, header_ptr.add(header_len), decoded_len);
-                }
-                ptr = header_ptr.add(header_len + decoded_len);
-            } else {
-                ptr = header_ptr;
-                let len = content_bytes.len();
-                let header_len = write_len_flag_ptr(ptr, len, false);
-                ptr = ptr.add(header_len);
-                ptr::copy_nonoverlapping(content_bytes.as_ptr(), ptr, len);
-                ptr = ptr.add(len);
+        let written = ptr.offset_from(base) as usize;
+        buf.set_len(original_len + written);
+    }
+}
+
+/// Exact length of [`serialize`] output, computed without writing it
+pub fn encoded_len(event: &NostrEvent) -> usize {
+    let tag_data_len = varint_size(event.tags.len() as u64)
+        + event
+            .tags
+            .iter()
+            .map(|tag| 1 + tag.iter().map(|v| value_len(v)).sum::<usize>())
+            .sum::<usize>();
+    FIXED_SIZE + varint_size(tag_data_len as u64) + tag_data_len + value_len(&event.content)
+}
+
+/// Length of one value as [`pack_value`] writes it
+fn value_len(value: &str) -> usize {
+    let len = if value.len() >= 8 && is_compressible_hex(value) {
+        value.len() / 2
+    } else {
+        value.len()
+    };
+    let header_len = if len < 0x7F {
+        1
+    } else {
+        1 + varint_size(len as u64)
+    };
+    header_len + len
+}
+
+/// Serialize with [`compact`](crate::compact) value compaction
+///
+/// The output is read by [`deserialize`]; with no compaction enabled it is
+/// identical to [`serialize`].
+pub fn serialize_with(event: &NostrEvent, options: &CompactOptions) -> Vec<u8> {
+    if options.is_empty() {
+        return serialize(event);
+    }
+
+    let mut buf = Vec::with_capacity(FIXED_SIZE + calc_max_tags_size(&event.tags) + 10);
+    write_fixed(&mut buf, event);
+
+    let mut tag_data = Vec::new();
+    match options
+        .follows
+        .then(|| follows::encode(&event.tags))
+        .flatten()
+    {
+        Some((run, block)) => {
+            tag_data.push(0);
+            write_compact_tags(&mut tag_data, &event.tags[..run.start], options);
+            write_varint(&mut tag_data, block.len() as u64);
+            tag_data.extend_from_slice(&block);
+            write_compact_tags(&mut tag_data, &event.tags[run.end..], options);
+        }
+        None => write_compact_tags(&mut tag_data, &event.tags, options),
+    }
+    write_varint(&mut buf, tag_data.len() as u64);
+    buf.extend_from_slice(&tag_data);
+
+    let content = compact::compact_content(event.kind, &event.content, options, Format::DannyPack);
+    write_compact_value(&mut buf, &event.content, content);
+    buf
+}
+
+fn write_compact_tags(buf: &mut Vec<u8>, tags: &[Vec<String>], options: &CompactOptions) {
+    write_varint(buf, tags.len() as u64);
+    for tag in tags {
+        buf.push(tag.len() as u8);
+        for value in tag {
+            write_compact_value(
+                buf,
+                value,
+                compact::compact_value(value, options, Format::DannyPack),
+            );
+        }
+    }
+}
+
+fn write_compact_value(buf: &mut Vec<u8>, value: &str, compacted: Option<Vec<u8>>) {
+    match compacted {
+        Some(body) => {
+            buf.push(COMPACT_HEADER);
+            write_varint(buf, body.len() as u64);
+            buf.extend_from_slice(&body);
+        }
+        None => write_value(buf, value),
+    }
+}
+
+/// Serialize with every value stored as text, without hex compression
+pub fn serialize_plain(event: &NostrEvent) -> Vec<u8> {
+    let mut buf =
+        Vec::with_capacity(FIXED_SIZE + calc_max_tags_size(&event.tags) + 10 + event.content.len());
+    write_fixed(&mut buf, event);
+
+    let mut tag_data = Vec::with_capacity(calc_max_tags_size(&event.tags));
+    write_varint(&mut tag_data, event.tags.len() as u64);
+    for tag in &event.tags {
+        tag_data.push(tag.len() as u8);
+        for value in tag {
+            write_text_value(&mut tag_data, value);
+        }
+    }
+    write_varint(&mut buf, tag_data.len() as u64);
+    buf.extend_from_slice(&tag_data);
+
+    write_text_value(&mut buf, &event.content);
+    buf
+}
+
+/// Serialize as format version 2, with tag names and relay URLs stored as
+/// [`dictionary::V1`] codes
+///
+/// Read with [`deserialize_v2`].
+pub fn serialize_v2(event: &NostrEvent) -> Vec<u8> {
+    let mut buf =
+        Vec::with_capacity(FIXED_SIZE + calc_max_tags_size(&event.tags) + 10 + event.content.len());
+    write_fixed(&mut buf, event);
+
+    let mut tag_data = Vec::new();
+    write_dictionary_tags(&mut tag_data, &event.tags, &dictionary::V1);
+    write_varint(&mut buf, tag_data.len() as u64);
+    buf.extend_from_slice(&tag_data);
+
+    write_value(&mut buf, &event.content);
+    buf
+}
+
+/// Deserialize format version 2, as written by [`serialize_v2`]
+pub fn deserialize_v2(data: &[u8]) -> Result<NostrEvent, DannyPackError> {
+    let fixed = fixed_data(data)?;
+    let rest = &data[FIXED_SIZE..];
+    let (tag_len, varint_bytes) = read_varint_slice(rest).ok_or(DannyPackError::TooShort)?;
+    let tag_data = rest
+        .get(varint_bytes..varint_bytes.saturating_add(tag_len as usize))
+        .ok_or(DannyPackError::TooShort)?;
+    let (content, _) = read_value(&rest[varint_bytes + tag_data.len()..])?;
+
+    Ok(NostrEvent {
+        id: fixed[0..32].try_into().unwrap(),
+        pubkey: fixed[32..64].try_into().unwrap(),
+        sig: fixed[64..128].try_into().unwrap(),
+        created_at: i64::from_le_bytes(fixed[128..136].try_into().unwrap()),
+        kind: u16::from_le_bytes([fixed[136], fixed[137]]),
+        tags: read_dictionary_tags(tag_data, &dictionary::V1)?,
+        content,
+    })
+}
+
+/// Deserialize a payload of the given format version
+pub fn deserialize_version(data: &[u8], version: u8) -> Result<NostrEvent, DannyPackError> {
+    match version {
+        VERSION_1 => deserialize(data),
+        VERSION_2 => deserialize_v2(data),
+        other => Err(DannyPackError::UnsupportedVersion(other)),
+    }
+}
+
+fn write_fixed(buf: &mut Vec<u8>, event: &NostrEvent) {
+    buf.extend_from_slice(&event.id);
+    buf.extend_from_slice(&event.pubkey);
+    buf.extend_from_slice(&event.sig);
+    buf.extend_from_slice(&event.created_at.to_le_bytes());
+    buf.extend_from_slice(&event.kind.to_le_bytes());
+}
+
+fn write_dictionary_tags(buf: &mut Vec<u8>, tags: &[Vec<String>], dictionary: &Dictionary) {
+    write_varint(buf, tags.len() as u64);
+    for tag in tags {
+        buf.push(tag.len() as u8);
+        let Some((name, values)) = tag.split_first() else {
+            continue;
+        };
+        match dictionary.tag_name_code(name) {
+            Some(code) => buf.push(code),
+            None => {
+                buf.push(TAG_NAME_LITERAL);
+                write_value(buf, name);
+            }
+        }
+        for value in values {
+            match dictionary.relay_code(value) {
+                Some(code) => buf.extend_from_slice(&[DICTIONARY_HEADER, code]),
+                None => write_value(buf, value),
             }
-        } else {
-            let len = content_bytes.len();
-            let header_len = write_len_flag_ptr(ptr, len, false);
-            ptr = ptr.add(header_len);
-            ptr::copy_nonoverlapping(content_bytes.as_ptr(), ptr, len);
-            ptr = ptr.add(len);
         }
+    }
+}
 
-        let written = ptr.offset_from(base) as usize;
-        buf.set_len(original_len + written);
+fn read_dictionary_tags(
+    data: &[u8],
+    dictionary: &Dictionary,
+) -> Result<Vec<Vec<String>>, DannyPackError> {
+    let (count, mut pos) = read_varint_slice(data).ok_or(DannyPackError::InvalidTagData)?;
+    let mut tags = Vec::with_capacity((count as usize).min(data.len()));
+    for _ in 0..count {
+        let arity = *data.get(pos).ok_or(DannyPackError::InvalidTagData)? as usize;
+        pos += 1;
+        let mut tag = Vec::with_capacity(arity);
+        if arity > 0 {
+            let code = *data.get(pos).ok_or(DannyPackError::InvalidTagData)?;
+            pos += 1;
+            if code == TAG_NAME_LITERAL {
+                let (name, len) = read_value(&data[pos..])?;
+                pos += len;
+                tag.push(name);
+            } else {
+                let name = dictionary
+                    .tag_name(code)
+                    .ok_or(DannyPackError::UnknownDictionaryCode(code))?;
+                tag.push(name.to_string());
+            }
+        }
+        for _ in 1..arity {
+            if data.get(pos) == Some(&DICTIONARY_HEADER) {
+                let code = *data.get(pos + 1).ok_or(DannyPackError::InvalidTagData)?;
+                let relay = dictionary
+                    .relay(code)
+                    .ok_or(DannyPackError::UnknownDictionaryCode(code))?;
+                tag.push(relay.to_string());
+                pos += 2;
+            } else {
+                let (value, len) = read_value(&data[pos..])?;
+                pos += len;
+                tag.push(value);
+            }
+        }
+        tags.push(tag);
     }
+    Ok(tags)
 }
 
 #[inline(always)]
@@ -309,38 +584,38 @@ unsafe fn pack_tags_fast(mut dst: *mut u8, tags: &[Vec<String>]) -> *mut u8 {
         dst = dst.add(1);
 
         for value in tag {
-            let bytes = value.as_bytes();
-            let len = bytes.len();
-
-            if might_be_hex(bytes) {
-                let header_ptr = dst;
-                dst = dst.add(5);
-                let decoded_len = hex_decode_checked(bytes, dst);
-                if decoded_len > 0 {
-                    let header_len = write_len_flag_ptr(header_ptr, decoded_len, true);
-                    if header_len < 5 {
-                        ptr::copy(dst, header_ptr.add(header_len), decoded_len);
-                    }
-                    dst = header_ptr.add(header_len + decoded_len);
-                } else {
-                    dst = header_ptr;
-                    let header_len = write_len_flag_ptr(dst, len, false);
-                    dst = dst.add(header_len);
-                    ptr::copy_nonoverlapping(bytes.as_ptr(), dst, len);
-                    dst = dst.add(len);
-                }
-            } else {
-                let header_len = write_len_flag_ptr(dst, len, false);
-                dst = dst.add(header_len);
-                ptr::copy_nonoverlapping(bytes.as_ptr(), dst, len);
-                dst = dst.add(len);
-            }
+            dst = pack_value(dst, value.as_bytes());
         }
     }
 
     dst
 }
 
+/// Write one length/flag-prefixed value, hex-compressed when possible
+#[inline(always)]
+unsafe fn pack_value(mut dst: *mut u8, bytes: &[u8]) -> *mut u8 {
+    let len = bytes.len();
+
+    if might_be_hex(bytes) {
+        let header_ptr = dst;
+        dst = dst.add(5);
+        let decoded_len = hex_decode_checked(bytes, dst);
+        if decoded_len > 0 {
+            let header_len = write_len_flag_ptr(header_ptr, decoded_len, true);
+            if header_len < 5 {
+                ptr::copy(dst, header_ptr.add(header_len), decoded_len);
+            }
+            return header_ptr.add(header_len + decoded_len);
+        }
+        dst = header_ptr;
+    }
+
+    let header_len = write_len_flag_ptr(dst, len, false);
+    dst = dst.add(header_len);
+    ptr::copy_nonoverlapping(bytes.as_ptr(), dst, len);
+    dst.add(len)
+}
+
 pub fn deserialize(data: &[u8]) -> Result<NostrEvent, DannyPackError> {
     let mut event = NostrEvent {
         id: [0u8; 32],
@@ -396,7 +671,10 @@ pub fn deserialize_into(data: &[u8], event: &mut NostrEvent) -> Result<(), Danny
         ptr = ptr.add(tag_len);
 
         let remaining = len - (ptr.offset_from(base) as usize);
-        let (content_len, content_is_hex, header_bytes) = read_len_flag_ptr(ptr, remaining);
+        let (content_len, content_kind, header_bytes) = read_value_header_ptr(ptr, remaining);
+        if header_bytes == 0 {
+            return Err(DannyPackError::TooShort);
+        }
         ptr = ptr.add(header_bytes);
 
         let remaining = len - (ptr.offset_from(base) as usize);
@@ -404,7 +682,9 @@ pub fn deserialize_into(data: &[u8], event: &mut NostrEvent) -> Result<(), Danny
             return Err(DannyPackError::TooShort);
         }
 
-        if content_is_hex {
+        if content_kind == ValueKind::Compact {
+            event.content = compact::expand(std::slice::from_raw_parts(ptr, content_len))?;
+        } else if content_kind == ValueKind::Hex {
             let required = content_len * 2;
             event.content.clear();
             event.content.reserve(required);
@@ -446,19 +726,39 @@ unsafe fn unpack_tags_into(
     pos += varint_bytes;
     let tag_count = tag_count as usize;
 
+    if tag_count == 0 && pos < max_len {
+        let section = std::slice::from_raw_parts(ptr.add(pos), max_len - pos);
+        return unpack_follow_section(section, tags);
+    }
+
     if tags.capacity() < tag_count {
         tags.reserve(tag_count - tags.len());
     }
     // We don't clear tags yet, we overwrite them.
     // But since tags is Vec<Vec<String>>, we want to reuse the inner Vecs.
+    unpack_tag_run(ptr, max_len, &mut pos, tag_count, tags, 0)?;
+    tags.truncate(tag_count);
 
-    for i in 0..tag_count {
-        if pos >= max_len {
+    Ok(())
+}
+
+/// Decode `tag_count` plain tags into `tags[offset..]`, reusing allocations
+#[inline(always)]
+unsafe fn unpack_tag_run(
+    ptr: *const u8,
+    max_len: usize,
+    pos: &mut usize,
+    tag_count: usize,
+    tags: &mut Vec<Vec<String>>,
+    offset: usize,
+) -> Result<(), DannyPackError> {
+    for i in offset..offset + tag_count {
+        if *pos >= max_len {
             return Err(DannyPackError::InvalidTagData);
         }
 
-        let value_count = *ptr.add(pos) as usize;
-        pos += 1;
+        let value_count = *ptr.add(*pos) as usize;
+        *pos += 1;
 
         if i >= tags.len() {
             tags.push(Vec::with_capacity(value_count));
@@ -472,43 +772,92 @@ unsafe fn unpack_tags_into(
             values.resize(value_count, String::new());
         }
         for j in 0..value_count {
-            let remaining = max_len - pos;
-            let (len, is_hex, header_bytes) = read_len_flag_ptr(ptr.add(pos), remaining);
-            pos += header_bytes;
+            let remaining = max_len - *pos;
+            let (len, kind, header_bytes) = read_value_header_ptr(ptr.add(*pos), remaining);
+            if header_bytes == 0 {
+                return Err(DannyPackError::InvalidTagData);
+            }
+            *pos += header_bytes;
 
-            if pos + len > max_len {
+            if *pos + len > max_len {
                 return Err(DannyPackError::InvalidTagData);
             }
 
             let s = values.get_unchecked_mut(j);
 
-            if is_hex {
+            if kind == ValueKind::Compact {
+                *s = compact::expand(std::slice::from_raw_parts(ptr.add(*pos), len))?;
+            } else if kind == ValueKind::Hex {
                 let required = len * 2;
                 s.clear();
                 s.reserve(required);
                 let vec = s.as_mut_vec();
                 vec.set_len(required);
                 hex_encode_fast(
-                    std::slice::from_raw_parts(ptr.add(pos), len),
+                    std::slice::from_raw_parts(ptr.add(*pos), len),
                     vec.as_mut_ptr(),
                 );
             } else {
                 s.clear();
                 s.reserve(len);
                 let vec = s.as_mut_vec();
-                ptr::copy_nonoverlapping(ptr.add(pos), vec.as_mut_ptr(), len);
+                ptr::copy_nonoverlapping(ptr.add(*pos), vec.as_mut_ptr(), len);
                 vec.set_len(len);
             }
 
-            pos += len;
+            *pos += len;
         }
         values.truncate(value_count);
     }
-    tags.truncate(tag_count);
 
     Ok(())
 }
 
+/// Decode a follow section (after its zero tag count)
+unsafe fn unpack_follow_section(
+    section: &[u8],
+    tags: &mut Vec<Vec<String>>,
+) -> Result<(), DannyPackError> {
+    let ptr = section.as_ptr();
+    let max_len = section.len();
+    let mut pos = 0;
+
+    let (before, varint_bytes) =
+        read_varint_slice(section).ok_or(DannyPackError::InvalidTagData)?;
+    pos += varint_bytes;
+    let before = before as usize;
+    unpack_tag_run(ptr, max_len, &mut pos, before, tags, 0)?;
+    tags.truncate(before);
+
+    let (block, block_end) = follow_block(section, pos)?;
+    pos = block_end;
+    for tag in FollowTags::new(block)? {
+        tags.push(tag?.to_tag());
+    }
+
+    let offset = tags.len();
+    let (after, varint_bytes) =
+        read_varint_slice(&section[pos..]).ok_or(DannyPackError::InvalidTagData)?;
+    pos += varint_bytes;
+    unpack_tag_run(ptr, max_len, &mut pos, after as usize, tags, offset)?;
+
+    Ok(())
+}
+
+/// The length-prefixed follow block at `pos`, and the position after it
+fn follow_block(section: &[u8], pos: usize) -> Result<(&[u8], usize), DannyPackError> {
+    let rest = section.get(pos..).ok_or(DannyPackError::InvalidTagData)?;
+    let (len, varint_bytes) = read_varint_slice(rest).ok_or(DannyPackError::InvalidTagData)?;
+    let start = pos + varint_bytes;
+    let end = start
+        .checked_add(len as usize)
+        .ok_or(DannyPackError::InvalidTagData)?;
+    let block = section
+        .get(start..end)
+        .ok_or(DannyPackError::InvalidTagData)?;
+    Ok((block, end))
+}
+
 pub fn serialize_batch(events: &[NostrEvent]) -> Vec<u8> {
     let mut buf = Vec::with_capacity(events.len() * 200 + 4);
     buf.extend_from_slice(&(events.len() as u32).to_le_bytes());
@@ -517,7 +866,7 @@ pub fn serialize_batch(events: &[NostrEvent]) -> Vec<u8> {
         let len_pos = buf.len();
         buf.extend_from_slice(&[0u8; 4]);
 
-        serialize(event, &mut buf);
+        serialize_into(event, &mut buf);
 
         let event_len = buf.len() - len_pos - 4;
         let len_bytes = (event_len as u32).to_le_bytes();
@@ -565,6 +914,408 @@ pub fn deserialize_batch(data: &[u8]) -> Result<Vec<NostrEvent>, DannyPackError>
     }
 }
 
+// ============================================
+// Lazy field access
+// ============================================
+
+/// Read only the `id` field without deserializing the event
+pub fn read_id(data: &[u8]) -> Result<[u8; 32], DannyPackError> {
+    Ok(fixed_data(data)?[0..32].try_into().unwrap())
+}
+
+/// Read only the `pubkey` field without deserializing the event
+pub fn read_pubkey(data: &[u8]) -> Result<[u8; 32], DannyPackError> {
+    Ok(fixed_data(data)?[32..64].try_into().unwrap())
+}
+
+/// Read only the `created_at` field without deserializing the event
+pub fn read_created_at(data: &[u8]) -> Result<i64, DannyPackError> {
+    Ok(i64::from_le_bytes(
+        fixed_data(data)?[128..136].try_into().unwrap(),
+    ))
+}
+
+/// Read only the `kind` field without deserializing the event
+pub fn read_kind(data: &[u8]) -> Result<u16, DannyPackError> {
+    let fixed = fixed_data(data)?;
+    Ok(u16::from_le_bytes([fixed[136], fixed[137]]))
+}
+
+/// Iterate over the tags without decoding them into strings
+pub fn read_tags(data: &[u8]) -> Result<RawTags<'_>, DannyPackError> {
+    let rest = data.get(FIXED_SIZE..).ok_or(DannyPackError::TooShort)?;
+    let (tag_len, varint_bytes) = read_varint_slice(rest).ok_or(DannyPackError::TooShort)?;
+    let tag_data = rest
+        .get(varint_bytes..varint_bytes.saturating_add(tag_len as usize))
+        .ok_or(DannyPackError::TooShort)?;
+
+    if tag_data.is_empty() {
+        return Ok(RawTags {
+            data: tag_data,
+            pos: 0,
+            remaining: 0,
+            follows: FollowStage::Done,
+        });
+    }
+    let (count, varint_bytes) =
+        read_varint_slice(tag_data).ok_or(DannyPackError::InvalidTagData)?;
+    if count == 0 && varint_bytes < tag_data.len() {
+        let pos = varint_bytes;
+        let (before, varint_bytes) =
+            read_varint_slice(&tag_data[pos..]).ok_or(DannyPackError::InvalidTagData)?;
+        return Ok(RawTags {
+            data: tag_data,
+            pos: pos + varint_bytes,
+            remaining: before as usize,
+            follows: FollowStage::Before,
+        });
+    }
+    Ok(RawTags {
+        data: tag_data,
+        pos: varint_bytes,
+        remaining: count as usize,
+        follows: FollowStage::Done,
+    })
+}
+
+#[inline]
+fn fixed_data(data: &[u8]) -> Result<&[u8], DannyPackError> {
+    data.get(..FIXED_SIZE).ok_or(DannyPackError::TooShort)
+}
+
+/// Bounds-checked varint read; returns (value, bytes read)
+pub(crate) fn read_varint_slice(data: &[u8]) -> Option<(u64, usize)> {
+    let mut result: u64 = 0;
+    for (i, &byte) in data.iter().enumerate().take(10) {
+        result |= ((byte & 0x7F) as u64) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Some((result, i + 1));
+        }
+    }
+    None
+}
+
+/// A read past the end of a [`Reader`]'s data; modules convert it into
+/// their own `Truncated` error
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Truncated;
+
+/// Bounds-checked cursor over the varint-framed bodies that formats built
+/// on DannyPack primitives write
+#[derive(Debug, Clone)]
+pub(crate) struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    /// The bytes not read yet
+    pub(crate) fn remaining(&self) -> &'a [u8] {
+        &self.data[self.pos..]
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.pos == self.data.len()
+    }
+
+    pub(crate) fn byte(&mut self) -> Result<u8, Truncated> {
+        let byte = *self.data.get(self.pos).ok_or(Truncated)?;
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    pub(crate) fn varint(&mut self) -> Result<u64, Truncated> {
+        let (value, len) = read_varint_slice(self.remaining()).ok_or(Truncated)?;
+        self.pos += len;
+        Ok(value)
+    }
+
+    pub(crate) fn bytes(&mut self, len: usize) -> Result<&'a [u8], Truncated> {
+        let end = self.pos.checked_add(len).ok_or(Truncated)?;
+        let bytes = self.data.get(self.pos..end).ok_or(Truncated)?;
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    /// A fixed-size field, e.g. a little-endian integer
+    pub(crate) fn array<const N: usize>(&mut self) -> Result<[u8; N], Truncated> {
+        Ok(self.bytes(N)?.try_into().unwrap())
+    }
+
+    /// Offset of the next byte from the start of the data
+    pub(crate) fn position(&self) -> usize {
+        self.pos
+    }
+
+    /// Bytes written by [`write_chunk`]
+    pub(crate) fn chunk(&mut self) -> Result<&'a [u8], Truncated> {
+        let len = self.varint()?;
+        self.bytes(usize::try_from(len).map_err(|_| Truncated)?)
+    }
+}
+
+/// Append `[len: varint] [bytes]`, read back by [`Reader::chunk`]
+pub(crate) fn write_chunk(buf: &mut Vec<u8>, bytes: &[u8]) {
+    write_varint(buf, bytes.len() as u64);
+    buf.extend_from_slice(bytes);
+}
+
+/// Map signed values to unsigned so small magnitudes make short varints
+pub(crate) fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+pub(crate) fn unzigzag(value: u64) -> i64 {
+    ((value >> 1) as i64) ^ -((value & 1) as i64)
+}
+
+/// Bounds-checked value header read; returns (len, kind, header bytes)
+fn read_value_header_slice(data: &[u8]) -> Option<(usize, ValueKind, usize)> {
+    let header = *data.first()?;
+    if header == COMPACT_HEADER {
+        let (len, varint_bytes) = read_varint_slice(&data[1..])?;
+        return Some((len as usize, ValueKind::Compact, 1 + varint_bytes));
+    }
+    let kind = if header & 0x80 != 0 {
+        ValueKind::Hex
+    } else {
+        ValueKind::Text
+    };
+    let len = (header & 0x7F) as usize;
+    if len < 0x7F {
+        Some((len, kind, 1))
+    } else {
+        let (len, varint_bytes) = read_varint_slice(&data[1..])?;
+        Some((len as usize, kind, 1 + varint_bytes))
+    }
+}
+
+/// Append a varint, for formats built on DannyPack primitives
+pub(crate) fn write_varint(buf: &mut Vec<u8>, value: u64) {
+    buf.reserve(10);
+    unsafe {
+        let len = buf.len();
+        let written = write_varint_ptr(buf.as_mut_ptr().add(len), value);
+        buf.set_len(len + written);
+    }
+}
+
+/// Append a length/flag-prefixed string, hex-compressed like tag values
+pub(crate) fn write_value(buf: &mut Vec<u8>, value: &str) {
+    let bytes = value.as_bytes();
+    buf.reserve(bytes.len() + 10);
+    unsafe {
+        let len = buf.len();
+        let base = buf.as_mut_ptr().add(len);
+        let end = pack_value(base, bytes);
+        buf.set_len(len + end.offset_from(base) as usize);
+    }
+}
+
+/// Append a length-prefixed string without hex compression
+fn write_text_value(buf: &mut Vec<u8>, value: &str) {
+    buf.reserve(value.len() + 10);
+    unsafe {
+        let len = buf.len();
+        let header_len = write_len_flag_ptr(buf.as_mut_ptr().add(len), value.len(), false);
+        buf.set_len(len + header_len);
+    }
+    buf.extend_from_slice(value.as_bytes());
+}
+
+/// Read a value written by [`write_value`]; returns (value, bytes read)
+pub(crate) fn read_value(data: &[u8]) -> Result<(String, usize), DannyPackError> {
+    let (len, kind, header_bytes) =
+        read_value_header_slice(data).ok_or(DannyPackError::TooShort)?;
+    let end = header_bytes
+        .checked_add(len)
+        .ok_or(DannyPackError::TooShort)?;
+    let bytes = data
+        .get(header_bytes..end)
+        .ok_or(DannyPackError::TooShort)?;
+    let value = match kind {
+        ValueKind::Text => std::str::from_utf8(bytes)?.to_string(),
+        ValueKind::Hex => hex::encode(bytes),
+        ValueKind::Compact => compact::expand(bytes)?,
+    };
+    Ok((value, end))
+}
+
+/// Iterator over the tags of an encoded event
+pub struct RawTags<'a> {
+    data: &'a [u8],
+    pos: usize,
+    remaining: usize,
+    follows: FollowStage<'a>,
+}
+
+/// Progress through a follow section
+enum FollowStage<'a> {
+    /// Reading the plain tags before the block
+    Before,
+    /// Reading the block, with the tag count after it
+    Block(FollowTags<'a>, usize),
+    /// Plain tags only
+    Done,
+}
+
+impl<'a> RawTags<'a> {
+    fn fail(&mut self) -> Option<Result<RawTag<'a>, DannyPackError>> {
+        self.remaining = 0;
+        self.follows = FollowStage::Done;
+        Some(Err(DannyPackError::InvalidTagData))
+    }
+
+    /// Enter the follow block once the tags before it are read
+    fn start_block(&mut self) -> Result<(), DannyPackError> {
+        let (block, end) = follow_block(self.data, self.pos)?;
+        let (after, varint_bytes) =
+            read_varint_slice(&self.data[end..]).ok_or(DannyPackError::InvalidTagData)?;
+        self.pos = end + varint_bytes;
+        self.follows = FollowStage::Block(FollowTags::new(block)?, after as usize);
+        Ok(())
+    }
+}
+
+impl<'a> Iterator for RawTags<'a> {
+    type Item = Result<RawTag<'a>, DannyPackError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            match &mut self.follows {
+                FollowStage::Done => return None,
+                FollowStage::Before => {
+                    if let Err(e) = self.start_block() {
+                        self.follows = FollowStage::Done;
+                        return Some(Err(e));
+                    }
+                    return self.next();
+                }
+                FollowStage::Block(tags, after) => match tags.next() {
+                    Some(Ok(tag)) => return Some(Ok(RawTag(TagRepr::Follow(tag)))),
+                    Some(Err(e)) => {
+                        self.follows = FollowStage::Done;
+                        return Some(Err(e.into()));
+                    }
+                    None => {
+                        self.remaining = *after;
+                        self.follows = FollowStage::Done;
+                        return self.next();
+                    }
+                },
+            }
+        }
+        self.remaining -= 1;
+
+        let Some(&value_count) = self.data.get(self.pos) else {
+            return self.fail();
+        };
+        let start = self.pos + 1;
+        let mut pos = start;
+        for _ in 0..value_count {
+            let value_end = read_value_header_slice(&self.data[pos..])
+                .and_then(|(len, _, header)| pos.checked_add(header + len))
+                .filter(|&end| end <= self.data.len());
+            match value_end {
+                Some(end) => pos = end,
+                None => return self.fail(),
+            }
+        }
+        self.pos = pos;
+
+        Some(Ok(RawTag(TagRepr::Encoded {
+            data: &self.data[start..pos],
+            count: value_count as usize,
+        })))
+    }
+}
+
+/// A single tag borrowed from an encoded event
+#[derive(Debug, Clone, Copy)]
+pub struct RawTag<'a>(TagRepr<'a>);
+
+#[derive(Debug, Clone, Copy)]
+enum TagRepr<'a> {
+    /// Values stored one by one
+    Encoded { data: &'a [u8], count: usize },
+    /// A `p` tag from a follow block
+    Follow(FollowTag<'a>),
+}
+
+impl<'a> RawTag<'a> {
+    /// Number of values in the tag, including its name
+    pub fn len(&self) -> usize {
+        match self.0 {
+            TagRepr::Encoded { count, .. } => count,
+            TagRepr::Follow(tag) => tag.len(),
+        }
+    }
+
+    /// Check if the tag has no values
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The `index`-th value (0 is the tag name)
+    pub fn get(&self, index: usize) -> Option<RawTagValue<'a>> {
+        self.values().nth(index)
+    }
+
+    /// Iterate over the values
+    pub fn values(&self) -> RawTagValues<'a> {
+        RawTagValues {
+            tag: *self,
+            pos: 0,
+            index: 0,
+        }
+    }
+}
+
+/// Iterator over the values of a [`RawTag`]
+pub struct RawTagValues<'a> {
+    tag: RawTag<'a>,
+    pos: usize,
+    index: usize,
+}
+
+impl<'a> Iterator for RawTagValues<'a> {
+    type Item = RawTagValue<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.index == self.tag.len() {
+            return None;
+        }
+        self.index += 1;
+
+        match self.tag.0 {
+            TagRepr::Encoded { data, .. } => {
+                // Bounds were validated when the tag was read
+                let (len, kind, header) = read_value_header_slice(&data[self.pos..])?;
+                let bytes = &data[self.pos + header..self.pos + header + len];
+                self.pos += header + len;
+                Some(match kind {
+                    ValueKind::Text => RawTagValue::Text(bytes),
+                    ValueKind::Hex => RawTagValue::Hex(bytes),
+                    ValueKind::Compact => RawTagValue::Compact(bytes),
+                })
+            }
+            TagRepr::Follow(tag) => match self.index {
+                1 => Some(RawTagValue::Text(b"p")),
+                2 => Some(RawTagValue::Pubkey(tag.pubkey)),
+                3 => tag
+                    .relay
+                    .or(tag.petname)
+                    .map(|s| RawTagValue::Text(s.as_bytes())),
+                _ => tag.petname.map(|s| RawTagValue::Text(s.as_bytes())),
+            },
+        }
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum DannyPackError {
     #[error("Data too short")]
@@ -581,6 +1332,18 @@ pub enum DannyPackError {
 
     #[error("Hex decode error: {0}")]
     Hex(#[from] hex::FromHexError),
+
+    #[error("Compacted value error: {0}")]
+    Compact(#[from] CompactError),
+
+    #[error("Follow block error: {0}")]
+    Follows(#[from] FollowsError),
+
+    #[error("Unknown dictionary code: {0}")]
+    UnknownDictionaryCode(u8),
+
+    #[error("Unsupported format version: {0}")]
+    UnsupportedVersion(u8),
 }
 
 #[cfg(test)]
@@ -617,8 +1380,7 @@ mod tests {
     #[test]
     fn test_roundtrip() {
         let event = sample_event();
-        let mut bytes = Vec::new();
-        serialize(&event, &mut bytes);
+        let bytes = serialize(&event);
         let back = deserialize(&bytes).unwrap();
         assert_eq!(event, back);
     }
@@ -626,18 +1388,24 @@ mod tests {
     #[test]
     fn test_roundtrip_hex_content() {
         let event = sample_event_hex_content();
-        let mut bytes = Vec::new();
-        serialize(&event, &mut bytes);
+        let bytes = serialize(&event);
         let back = deserialize(&bytes).unwrap();
         assert_eq!(event, back);
 
         let non_hex = sample_event();
-        let mut non_hex_bytes = Vec::new();
-        serialize(&non_hex, &mut non_hex_bytes);
+        let non_hex_bytes = serialize(&non_hex);
         println!("Normal content: {} bytes", non_hex_bytes.len());
         println!("Hex content:    {} bytes", bytes.len());
     }
 
+    #[test]
+    fn test_plain_roundtrip() {
+        let event = sample_event_hex_content();
+        let plain = serialize_plain(&event);
+        assert_eq!(deserialize(&plain).unwrap(), event);
+        assert!(plain.len() > serialize(&event).len());
+    }
+
     #[test]
     fn test_batch_roundtrip() {
         let events = vec![sample_event(), sample_event_hex_content()];
@@ -646,13 +1414,169 @@ mod tests {
         assert_eq!(events, back);
     }
 
+    #[test]
+    fn test_lazy_field_access() {
+        let event = sample_event();
+        let bytes = serialize(&event);
+
+        assert_eq!(read_id(&bytes).unwrap(), event.id);
+        assert_eq!(read_pubkey(&bytes).unwrap(), event.pubkey);
+        assert_eq!(read_created_at(&bytes).unwrap(), event.created_at);
+        assert_eq!(read_kind(&bytes).unwrap(), event.kind);
+
+        let tags: Vec<Vec<String>> = read_tags(&bytes)
+            .unwrap()
+            .map(|tag| tag.unwrap().values().map(|v| v.to_string_lossy()).collect())
+            .collect();
+        assert_eq!(tags, event.tags);
+        let no_tags = serialize(&sample_event_hex_content());
+        assert!(read_tags(&no_tags).unwrap().next().is_none());
+        assert!(read_kind(&bytes[..100]).is_err());
+    }
+
+    #[test]
+    fn test_compact_roundtrip() {
+        use crate::compact::CompactOptions;
+        use crate::nip19::Nip19;
+
+        let npub = Nip19::Pubkey([0x11; 32]).to_string();
+        let mut event = sample_event();
+        event.content = format!("gm nostr:{}", npub);
+        event.tags.push(vec!["q".to_string(), npub.clone()]);
+
+        let plain = serialize(&event);
+        let bytes = serialize_with(&event, &CompactOptions::all());
+        assert!(bytes.len() < plain.len());
+        assert_eq!(deserialize(&bytes).unwrap(), event);
+        assert_eq!(serialize_with(&event, &CompactOptions::new()), plain);
+
+        let last = read_tags(&bytes).unwrap().last().unwrap().unwrap();
+        let value = last.values().nth(1).unwrap();
+        assert!(matches!(value, RawTagValue::Compact(_)));
+        assert!(value.eq_str(&npub));
+        assert_eq!(value.to_string_lossy(), npub);
+    }
+
+    #[test]
+    fn test_v2_roundtrip() {
+        let mut event = sample_event();
+        event.tags = vec![
+            vec![
+                "e".to_string(),
+                "ab".repeat(32),
+                "wss://relay.damus.io".to_string(),
+            ],
+            vec![
+                "p".to_string(),
+                "cd".repeat(32),
+                "wss://relay.example.com".to_string(),
+            ],
+            vec!["r".to_string(), "wss://nos.lol".to_string()],
+            vec!["custom".to_string(), "wss://nos.lol".to_string()],
+            vec![],
+        ];
+
+        let bytes = serialize_v2(&event);
+        assert!(bytes.len() < serialize(&event).len() - 30);
+        assert_eq!(deserialize_v2(&bytes).unwrap(), event);
+        assert_eq!(deserialize_version(&bytes, VERSION_2).unwrap(), event);
+        assert_eq!(
+            deserialize_version(&serialize(&event), VERSION_1).unwrap(),
+            event
+        );
+        assert!(matches!(
+            deserialize_version(&bytes, 3),
+            Err(DannyPackError::UnsupportedVersion(3))
+        ));
+
+        // An unknown relay code is rejected
+        let mut relay_only = sample_event();
+        relay_only.tags = vec![vec!["r".to_string(), "wss://nos.lol".to_string()]];
+        let mut corrupt = serialize_v2(&relay_only);
+        // tag_len, count, arity, name code, then the relay value
+        let at = FIXED_SIZE + 4;
+        assert_eq!(corrupt[at], DICTIONARY_HEADER);
+        corrupt[at + 1] = 0xFE;
+        assert!(matches!(
+            deserialize_v2(&corrupt),
+            Err(DannyPackError::UnknownDictionaryCode(0xFE))
+        ));
+    }
+
+    #[test]
+    fn test_embedded_event_roundtrip() {
+        use crate::compact::CompactOptions;
+
+        // A repost of a repost, and a zap receipt carrying its request
+        let note = sample_event();
+        let mut repost = sample_event();
+        repost.kind = 6;
+        repost.content = crate::json::serialize_string(&note);
+        let mut outer = sample_event();
+        outer.kind = 6;
+        outer.content = crate::json::serialize_string(&repost);
+        let mut request = sample_event();
+        request.kind = 9734;
+        outer.tags.push(vec![
+            "description".to_string(),
+            crate::json::serialize_string(&request),
+        ]);
+
+        let options = CompactOptions::new().with_events(true);
+        let bytes = serialize_with(&outer, &options);
+        assert!(bytes.len() < serialize(&outer).len() * 2 / 3);
+        assert_eq!(deserialize(&bytes).unwrap(), outer);
+
+        let last = read_tags(&bytes).unwrap().last().unwrap().unwrap();
+        let value = last.values().nth(1).unwrap();
+        assert!(matches!(value, RawTagValue::Compact(_)));
+        assert_eq!(value.to_string_lossy(), outer.tags.last().unwrap()[1]);
+    }
+
+    #[test]
+    fn test_follow_block_roundtrip() {
+        use crate::compact::CompactOptions;
+
+        let mut event = sample_event();
+        event.kind = 3;
+        event.content = String::new();
+        for i in 0..50u8 {
+            let mut tag = vec!["p".to_string(), hex::encode([i; 32])];
+            if i % 2 == 0 {
+                tag.push("wss://relay.example.com".to_string());
+            }
+            event.tags.push(tag);
+        }
+        event
+            .tags
+            .push(vec!["t".to_string(), "follows".to_string()]);
+
+        let options = CompactOptions::new().with_follows(true);
+        let bytes = serialize_with(&event, &options);
+        assert!(bytes.len() < serialize(&event).len() * 3 / 4);
+        assert_eq!(deserialize(&bytes).unwrap(), event);
+
+        // Decoding into an event with more tags reuses and trims them
+        let mut reused = sample_event();
+        reused.tags = vec![vec!["x".to_string(); 3]; 60];
+        deserialize_into(&bytes, &mut reused).unwrap();
+        assert_eq!(reused, event);
+
+        let tags: Vec<Vec<String>> = read_tags(&bytes)
+            .unwrap()
+            .map(|tag| tag.unwrap().values().map(|v| v.to_string_lossy()).collect())
+            .collect();
+        assert_eq!(tags, event.tags);
+        let follow = read_tags(&bytes).unwrap().nth(5).unwrap().unwrap();
+        assert_eq!(follow.len(), 2);
+        assert!(follow.get(1).unwrap().eq_str(&hex::encode([3u8; 32])));
+    }
+
     #[test]
     fn test_size_comparison() {
         let event = sample_event();
 
-        let mut dannypack_buf = Vec::new();
-        serialize(&event, &mut dannypack_buf);
-        let dannypack_size = dannypack_buf.len();
+        let dannypack_size = serialize(&event).len();
         let json_size = crate::json::serialize(&event).len();
 
         println!("DannyPack: {} bytes", dannypack_size);
@@ -0,0 +1,220 @@
+//! Static dictionary of common tag names and relay URLs
+//!
+//! Single-letter tag names and a few dozen popular relay URLs make up a
+//! large share of the non-hex bytes in tags. A [`Dictionary`] gives each
+//! of them a one-byte code; formats that use it store the code instead of
+//! the text (see [`dannypack::serialize_v2`](crate::dannypack::serialize_v2)).
+//!
+//! Dictionaries are versioned and a published version never changes, since
+//! its codes are on disk; each table holds at most 255 entries, leaving
+//! code `0xFF` free as an escape. Tables are kept sorted so lookups can binary
+//! search; a code is the index into its table.
+//!
+//! New versions are generated from the dataset by
+//! `cargo run --release --example gen_dictionary -- --version N`, which
+//! prints the tables to append here, headed by the arguments used, and
+//! registered in [`get`]. `-- --check N` regenerates a published version
+//! from the dataset and lists the entries that differ from it.
+
+/// One version of the dictionary
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Dictionary {
+    pub version: u8,
+    /// Tag names (first tag element), sorted
+    pub tag_names: &'static [&'static str],
+    /// Relay URLs in any tag position, sorted
+    pub relays: &'static [&'static str],
+}
+
+impl Dictionary {
+    /// Code for a tag name, if it is in the dictionary
+    pub fn tag_name_code(&self, name: &str) -> Option<u8> {
+        self.tag_names.binary_search(&name).ok().map(|i| i as u8)
+    }
+
+    /// Code for a relay URL, if it is in the dictionary
+    pub fn relay_code(&self, url: &str) -> Option<u8> {
+        if !url.starts_with("ws") {
+            return None;
+        }
+        self.relays.binary_search(&url).ok().map(|i| i as u8)
+    }
+
+    pub fn tag_name(&self, code: u8) -> Option<&'static str> {
+        self.tag_names.get(code as usize).copied()
+    }
+
+    pub fn relay(&self, code: u8) -> Option<&'static str> {
+        self.relays.get(code as usize).copied()
+    }
+}
+
+/// Version 1
+pub const V1: Dictionary = Dictionary {
+    version: 1,
+    tag_names: &TAG_NAMES_V1,
+    relays: &RELAYS_V1,
+};
+
+/// Look up a dictionary by version
+pub fn get(version: u8) -> Option<&'static Dictionary> {
+    match version {
+        1 => Some(&V1),
+        _ => None,
+    }
+}
+
+// Version 1 tables. Not yet in generator form: until V1 ships, replace them
+// with the output of `gen_dictionary -- --version 1 --tag-names 52 --relays 60`
+// run on the full dataset (with V1 unregistered from `get`), keeping its
+// header and per-entry counts.
+const TAG_NAMES_V1: [&str; 52] = [
+    "-",
+    "A",
+    "E",
+    "I",
+    "K",
+    "L",
+    "P",
+    "a",
+    "alt",
+    "amount",
+    "bolt11",
+    "challenge",
+    "client",
+    "content-warning",
+    "d",
+    "description",
+    "dim",
+    "e",
+    "emoji",
+    "expiration",
+    "f",
+    "g",
+    "h",
+    "i",
+    "image",
+    "imeta",
+    "k",
+    "l",
+    "m",
+    "name",
+    "nonce",
+    "ox",
+    "p",
+    "preimage",
+    "proxy",
+    "published_at",
+    "q",
+    "r",
+    "relay",
+    "relays",
+    "server",
+    "size",
+    "subject",
+    "summary",
+    "t",
+    "thumb",
+    "title",
+    "u",
+    "url",
+    "x",
+    "y",
+    "zap",
+];
+
+const RELAYS_V1: [&str; 60] = [
+    "wss://a.nos.lol",
+    "wss://atlas.nostr.land",
+    "wss://brb.io",
+    "wss://christpill.nostr1.com",
+    "wss://eden.nostr.land",
+    "wss://filter.nostr.wine",
+    "wss://inbox.nostr.wine",
+    "wss://lightningrelay.com",
+    "wss://nos.lol",
+    "wss://nos.lol/",
+    "wss://nostr-01.yakihonne.com",
+    "wss://nostr-pub.wellorder.net",
+    "wss://nostr-relay.wlvs.space",
+    "wss://nostr.bitcoiner.social",
+    "wss://nostr.data.haus",
+    "wss://nostr.einundzwanzig.space",
+    "wss://nostr.fmt.wiz.biz",
+    "wss://nostr.land",
+    "wss://nostr.mom",
+    "wss://nostr.mom/",
+    "wss://nostr.mutinywallet.com",
+    "wss://nostr.orangepill.dev",
+    "wss://nostr.oxtr.dev",
+    "wss://nostr.stakey.net",
+    "wss://nostr.wine",
+    "wss://nostr.wine/",
+    "wss://nostr.zebedee.cloud",
+    "wss://nostr21.com",
+    "wss://nostrue.com",
+    "wss://offchain.pub",
+    "wss://offchain.pub/",
+    "wss://purplepag.es",
+    "wss://purplepag.es/",
+    "wss://pyramid.fiatjaf.com",
+    "wss://relay.0xchat.com",
+    "wss://relay.coinos.io",
+    "wss://relay.current.fyi",
+    "wss://relay.damus.io",
+    "wss://relay.damus.io/",
+    "wss://relay.getalby.com/v1",
+    "wss://relay.highlighter.com",
+    "wss://relay.mostr.pub",
+    "wss://relay.nos.social",
+    "wss://relay.nostr.band",
+    "wss://relay.nostr.band/",
+    "wss://relay.nostr.bg",
+    "wss://relay.nostr.info",
+    "wss://relay.nostr.net",
+    "wss://relay.nostrcheck.me",
+    "wss://relay.nostrplebs.com",
+    "wss://relay.noswhere.com",
+    "wss://relay.nsec.app",
+    "wss://relay.orangepill.dev",
+    "wss://relay.plebstr.com",
+    "wss://relay.primal.net",
+    "wss://relay.primal.net/",
+    "wss://relay.snort.social",
+    "wss://relay.snort.social/",
+    "wss://relay.utxo.one",
+    "wss://relayable.org",
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tables() {
+        for version in 1..=u8::MAX {
+            let Some(dictionary) = get(version) else {
+                break;
+            };
+            assert_eq!(dictionary.version, version);
+            for table in [dictionary.tag_names, dictionary.relays] {
+                assert!(table.len() < 0xFF);
+                assert!(table.windows(2).all(|w| w[0] < w[1]), "sorted and unique");
+            }
+        }
+    }
+
+    #[test]
+    fn test_lookup() {
+        let code = V1.tag_name_code("e").unwrap();
+        assert_eq!(V1.tag_name(code), Some("e"));
+        let code = V1.relay_code("wss://relay.damus.io").unwrap();
+        assert_eq!(V1.relay(code), Some("wss://relay.damus.io"));
+
+        assert_eq!(V1.tag_name_code("E2"), None);
+        assert_eq!(V1.relay_code("wss://relay.example.com"), None);
+        assert_eq!(V1.relay_code("e"), None);
+        assert_eq!(V1.relay(0xFF), None);
+        assert_eq!(get(0), None);
+    }
+}
@@ -0,0 +1,348 @@
+//! Patches between versions of replaceable events
+//!
+//! Clients re-publish follow lists (kind 3), relay lists (kind 10002) and
+//! other replaceable events with small changes. A patch against the
+//! previous version carries only what changed:
+//!
+//! ```text
+//! [created_at delta: zigzag varint] [id: 32 bytes] [sig: 64 bytes]
+//! [tag op count: varint]
+//!   0x00 copy:   [old index: varint] [count: varint]
+//!   0x01 insert: [count: varint] per tag: [arity: varint] [values (DannyPack value encoding)...]
+//! [content: common prefix len: varint] [common suffix len: varint] [middle len: varint] [middle]
+//! ```
+//!
+//! Tag copies cover additions, removals and reorderings alike. [`apply`]
+//! rebuilds the new event from the old one and checks that it hashes to
+//! the id carried in the patch, so a patch applied to the wrong base fails
+//! instead of producing a different event.
+
+use std::collections::HashMap;
+
+use thiserror::Error;
+
+use crate::dannypack::{self, unzigzag, write_varint, zigzag, DannyPackError, Reader, Truncated};
+use crate::event::{KindClass, NostrEvent};
+
+const OP_COPY: u8 = 0x00;
+const OP_INSERT: u8 = 0x01;
+
+/// Candidate positions checked per tag when looking for a copy
+const MAX_CANDIDATES: usize = 8;
+
+#[derive(Debug, Error)]
+pub enum DiffError {
+    #[error("Events are not versions of the same replaceable event")]
+    NotVersions,
+
+    #[error("Patch is truncated")]
+    Truncated,
+
+    #[error("Unknown tag op: {0:#04x}")]
+    UnknownOp(u8),
+
+    #[error("Tag copy out of range: {start}+{count}")]
+    InvalidCopy { start: usize, count: usize },
+
+    #[error("Content edit out of range")]
+    InvalidContent,
+
+    #[error("UTF-8 error: {0}")]
+    Utf8(#[from] std::string::FromUtf8Error),
+
+    #[error("Tag value error: {0}")]
+    Value(#[from] DannyPackError),
+
+    #[error("Patched event does not match its id")]
+    IdMismatch,
+}
+
+impl From<Truncated> for DiffError {
+    fn from(_: Truncated) -> Self {
+        DiffError::Truncated
+    }
+}
+
+/// Identity shared by every version of a replaceable event:
+/// kind, pubkey and `d` tag (empty for non-addressable kinds)
+///
+/// Returns `None` for regular and ephemeral events.
+pub fn version_key(event: &NostrEvent) -> Option<(u16, [u8; 32], &str)> {
+    match event.kind_class() {
+        KindClass::Replaceable => Some((event.kind, event.pubkey, "")),
+        KindClass::Addressable => Some((event.kind, event.pubkey, event.d_tag().unwrap_or(""))),
+        KindClass::Regular | KindClass::Ephemeral => None,
+    }
+}
+
+/// Compute the patch that turns `old` into `new`
+pub fn diff(old: &NostrEvent, new: &NostrEvent) -> Result<Vec<u8>, DiffError> {
+    if version_key(old).is_none() || version_key(old) != version_key(new) {
+        return Err(DiffError::NotVersions);
+    }
+
+    let mut patch = Vec::with_capacity(128);
+    write_varint(
+        &mut patch,
+        zigzag(new.created_at.wrapping_sub(old.created_at)),
+    );
+    patch.extend_from_slice(&new.id);
+    patch.extend_from_slice(&new.sig);
+
+    let ops = tag_ops(&old.tags, &new.tags);
+    write_varint(&mut patch, ops.len() as u64);
+    for op in ops {
+        match op {
+            TagOp::Copy { start, count } => {
+                patch.push(OP_COPY);
+                write_varint(&mut patch, start as u64);
+                write_varint(&mut patch, count as u64);
+            }
+            TagOp::Insert(tags) => {
+                patch.push(OP_INSERT);
+                write_varint(&mut patch, tags.len() as u64);
+                for tag in tags {
+                    write_varint(&mut patch, tag.len() as u64);
+                    for value in tag {
+                        dannypack::write_value(&mut patch, value);
+                    }
+                }
+            }
+        }
+    }
+
+    let (old_content, new_content) = (old.content.as_bytes(), new.content.as_bytes());
+    let prefix = old_content
+        .iter()
+        .zip(new_content)
+        .take_while(|(a, b)| a == b)
+        .count();
+    let max_suffix = old_content.len().min(new_content.len()) - prefix;
+    let suffix = old_content
+        .iter()
+        .rev()
+        .zip(new_content.iter().rev())
+        .take(max_suffix)
+        .take_while(|(a, b)| a == b)
+        .count();
+    let middle = &new_content[prefix..new_content.len() - suffix];
+    write_varint(&mut patch, prefix as u64);
+    write_varint(&mut patch, suffix as u64);
+    write_varint(&mut patch, middle.len() as u64);
+    patch.extend_from_slice(middle);
+
+    Ok(patch)
+}
+
+/// Rebuild the new version from `old` and a patch, verifying its id
+pub fn apply(old: &NostrEvent, patch: &[u8]) -> Result<NostrEvent, DiffError> {
+    let mut reader = Reader::new(patch);
+
+    let created_at = old.created_at.wrapping_add(unzigzag(reader.varint()?));
+    let id: [u8; 32] = reader.bytes(32)?.try_into().unwrap();
+    let sig: [u8; 64] = reader.bytes(64)?.try_into().unwrap();
+
+    let mut tags = Vec::with_capacity(old.tags.len());
+    for _ in 0..reader.varint()? {
+        match reader.byte()? {
+            OP_COPY => {
+                let start = reader.varint()? as usize;
+                let count = reader.varint()? as usize;
+                let copied = start
+                    .checked_add(count)
+                    .and_then(|end| old.tags.get(start..end))
+                    .ok_or(DiffError::InvalidCopy { start, count })?;
+                tags.extend_from_slice(copied);
+            }
+            OP_INSERT => {
+                for _ in 0..reader.varint()? {
+                    let arity = reader.varint()? as usize;
+                    let mut tag = Vec::with_capacity(arity.min(64));
+                    for _ in 0..arity {
+                        let (value, len) = dannypack::read_value(reader.remaining())?;
+                        reader.bytes(len)?;
+                        tag.push(value);
+                    }
+                    tags.push(tag);
+                }
+            }
+            other => return Err(DiffError::UnknownOp(other)),
+        }
+    }
+
+    let old_content = old.content.as_bytes();
+    let prefix = reader.varint()? as usize;
+    let suffix = reader.varint()? as usize;
+    let middle_len = reader.varint()? as usize;
+    let middle = reader.bytes(middle_len)?;
+    if prefix
+        .checked_add(suffix)
+        .is_none_or(|n| n > old_content.len())
+    {
+        return Err(DiffError::InvalidContent);
+    }
+    let mut content = Vec::with_capacity(prefix + middle.len() + suffix);
+    content.extend_from_slice(&old_content[..prefix]);
+    content.extend_from_slice(middle);
+    content.extend_from_slice(&old_content[old_content.len() - suffix..]);
+
+    let event = NostrEvent {
+        id,
+        pubkey: old.pubkey,
+        created_at,
+        kind: old.kind,
+        tags,
+        content: String::from_utf8(content)?,
+        sig,
+    };
+    if !event.verify_id() {
+        return Err(DiffError::IdMismatch);
+    }
+    Ok(event)
+}
+
+enum TagOp<'a> {
+    Copy { start: usize, count: usize },
+    Insert(Vec<&'a Vec<String>>),
+}
+
+/// Cover `new` with runs copied from `old`, inserting whatever is missing
+fn tag_ops<'a>(old: &[Vec<String>], new: &'a [Vec<String>]) -> Vec<TagOp<'a>> {
+    let mut positions: HashMap<&[String], Vec<usize>> = HashMap::new();
+    for (i, tag) in old.iter().enumerate() {
+        positions.entry(tag.as_slice()).or_default().push(i);
+    }
+
+    let mut ops = Vec::new();
+    let mut inserts = Vec::new();
+    let mut i = 0;
+    while i < new.len() {
+        let best = positions
+            .get(new[i].as_slice())
+            .into_iter()
+            .flatten()
+            .take(MAX_CANDIDATES)
+            .map(|&start| {
+                let count = old[start..]
+                    .iter()
+                    .zip(&new[i..])
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                (start, count)
+            })
+            .max_by_key(|&(_, count)| count);
+
+        match best {
+            Some((start, count)) => {
+                if !inserts.is_empty() {
+                    ops.push(TagOp::Insert(std::mem::take(&mut inserts)));
+                }
+                ops.push(TagOp::Copy { start, count });
+                i += count;
+            }
+            None => {
+                inserts.push(&new[i]);
+                i += 1;
+            }
+        }
+    }
+    if !inserts.is_empty() {
+        ops.push(TagOp::Insert(inserts));
+    }
+    ops
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn follow_list(created_at: i64, follows: &[u8], content: &str) -> NostrEvent {
+        let mut event = NostrEvent {
+            id: [0; 32],
+            pubkey: [0xcd; 32],
+            created_at,
+            kind: 3,
+            tags: follows
+                .iter()
+                .map(|&i| vec!["p".to_string(), hex::encode([i; 32])])
+                .collect(),
+            content: content.to_string(),
+            sig: [0xef; 64],
+        };
+        event.id = event.compute_id();
+        event
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let follows: Vec<u8> = (0..100).collect();
+        let old = follow_list(
+            1_700_000_000,
+            &follows,
+            r#"{"wss://a.example":{"read":true}}"#,
+        );
+
+        // Remove one, add two, move one to the end, edit the content
+        let mut changed: Vec<u8> = follows.iter().copied().filter(|&i| i != 40).collect();
+        changed.insert(10, 200);
+        changed.push(201);
+        let moved = changed.remove(0);
+        changed.push(moved);
+        let new = follow_list(
+            1_700_000_500,
+            &changed,
+            r#"{"wss://b.example":{"read":true}}"#,
+        );
+
+        let patch = diff(&old, &new).unwrap();
+        assert!(patch.len() < 32 + 64 + 120);
+        assert_eq!(apply(&old, &patch).unwrap(), new);
+
+        // Older versions patch forward from newer ones too
+        let back = diff(&new, &old).unwrap();
+        assert_eq!(apply(&new, &back).unwrap(), old);
+    }
+
+    #[test]
+    fn test_apply_verifies_id() {
+        let old = follow_list(100, &[1, 2, 3], "");
+        let new = follow_list(200, &[1, 2, 3, 4], "");
+        let patch = diff(&old, &new).unwrap();
+
+        let other_base = follow_list(100, &[1, 2, 5], "");
+        assert!(matches!(
+            apply(&other_base, &patch),
+            Err(DiffError::IdMismatch)
+        ));
+        assert!(matches!(
+            apply(&old, &patch[..50]),
+            Err(DiffError::Truncated)
+        ));
+    }
+
+    #[test]
+    fn test_not_versions() {
+        let old = follow_list(100, &[1], "");
+        let mut note = old.clone();
+        note.kind = 1;
+        assert!(matches!(diff(&note, &note), Err(DiffError::NotVersions)));
+
+        let mut other_author = old.clone();
+        other_author.pubkey = [0xab; 32];
+        assert!(matches!(
+            diff(&old, &other_author),
+            Err(DiffError::NotVersions)
+        ));
+
+        let mut article = old.clone();
+        article.kind = 30023;
+        article.tags = vec![vec!["d".to_string(), "a".to_string()]];
+        let mut other_article = article.clone();
+        other_article.tags[0][1] = "b".to_string();
+        assert!(diff(&article, &article).is_ok());
+        assert!(matches!(
+            diff(&article, &other_article),
+            Err(DiffError::NotVersions)
+        ));
+    }
+}
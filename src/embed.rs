@@ -0,0 +1,476 @@
+//! Recursive encoding of embedded NIP-01 events
+//!
+//! Some events carry another event as JSON text: kind 6 and 16 reposts in
+//! their content, kind 9735 zap receipts in the `description` tag (the kind
+//! 9734 zap request), and unwrapped NIP-59 seals and rumors once decrypted.
+//! Kind 1059 and 13 payloads themselves are ciphertext; their base64 is
+//! handled by [`compact`](crate::compact) base64 runs.
+//!
+//! The embedded event is stored in the outer event's format, followed by
+//! what is needed to rebuild the exact JSON text:
+//!
+//! ```text
+//! [format: u8, Format id] [order len: u8, 0 for NIP-01 order] [field indices...]
+//! [whitespace run count: varint] per run: [offset delta: varint] [len: varint] [whitespace]
+//! [event len: varint] [encoded event]
+//! ```
+//!
+//! Fields are re-emitted in the stored order with `serde_json` escaping,
+//! and whitespace between tokens is re-inserted at its offset in that
+//! compact rendering. [`encode`] checks the roundtrip and returns `None`
+//! when the text can't be reproduced (extra or duplicate keys, uppercase
+//! hex, `\/` escapes, numbers in other notations), leaving the caller to
+//! store text.
+//!
+//! Nesting is limited to [`MAX_EMBED_DEPTH`] levels: deeper events are
+//! left as text by [`encode`] and rejected by [`decode`], so a crafted
+//! payload can't recurse through the format decoders until the stack
+//! overflows.
+
+use std::cell::Cell;
+
+use thiserror::Error;
+
+use crate::cbor;
+use crate::compact::CompactOptions;
+use crate::dannypack::{self, write_chunk, write_varint, Reader, Truncated};
+use crate::event::NostrEvent;
+use crate::profile::Fields;
+use crate::stats::{self, Format, FormatError};
+
+/// Event fields in NIP-01 order; field indices refer to this list
+pub const FIELDS: [&str; 7] = [
+    "id",
+    "pubkey",
+    "created_at",
+    "kind",
+    "tags",
+    "content",
+    "sig",
+];
+
+/// Deepest nesting of embedded events [`encode`] writes and [`decode`] reads
+pub const MAX_EMBED_DEPTH: usize = 4;
+
+thread_local! {
+    /// Embedded events being encoded or decoded on this thread. The format
+    /// serializers between two nesting levels are the public, depth-unaware
+    /// ones, so the depth is carried here rather than as an argument.
+    static DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+#[derive(Debug, Error)]
+pub enum EmbedError {
+    #[error("Embedded event body is truncated")]
+    Truncated,
+
+    #[error("Unknown embedded event format: {0}")]
+    UnknownFormat(u8),
+
+    #[error("Invalid embedded event field order")]
+    InvalidOrder,
+
+    #[error("Whitespace run out of range")]
+    InvalidWhitespace,
+
+    #[error("Embedded events nested deeper than {} levels", MAX_EMBED_DEPTH)]
+    TooDeep,
+
+    #[error("UTF-8 error: {0}")]
+    Utf8(#[from] std::str::Utf8Error),
+
+    #[error("Embedded event error: {0}")]
+    Event(#[from] Box<FormatError>),
+}
+
+impl From<Truncated> for EmbedError {
+    fn from(_: Truncated) -> Self {
+        EmbedError::Truncated
+    }
+}
+
+/// Whether `value` looks like an embedded JSON object, before parsing it
+pub fn is_candidate(value: &str) -> bool {
+    let trimmed = value.trim_start();
+    trimmed.starts_with('{') && value.contains("\"sig\"")
+}
+
+/// Encode embedded event JSON in `format`, if it decodes back byte for byte
+///
+/// The inner event is encoded with the same `options`, so events nested
+/// inside it are encoded recursively.
+pub fn encode(json: &str, format: Format, options: &CompactOptions) -> Option<Vec<u8>> {
+    let depth = Depth::enter()?;
+    let (stripped, whitespace) = strip_whitespace(json);
+    let Fields(fields) = serde_json::from_str(&stripped).ok()?;
+    let (event, order) = to_event(fields)?;
+
+    let encoded = match format {
+        Format::DannyPack => dannypack::serialize_with(&event, options),
+        Format::CborPacked => cbor::packed::serialize_with(&event, options),
+        other => stats::serialize(&event, other),
+    };
+
+    let mut body = Vec::with_capacity(encoded.len() + 16);
+    body.push(format.id());
+    if order == NIP01_ORDER {
+        body.push(0);
+    } else {
+        body.push(order.len() as u8);
+        body.extend_from_slice(&order);
+    }
+    write_varint(&mut body, whitespace.len() as u64);
+    let mut last = 0;
+    for (offset, run) in &whitespace {
+        write_varint(&mut body, (offset - last) as u64);
+        write_chunk(&mut body, run.as_bytes());
+        last = *offset;
+    }
+    write_chunk(&mut body, &encoded);
+
+    // The check decodes this level again, so it runs at the caller's depth
+    drop(depth);
+    (decode(&body).ok()? == json).then_some(body)
+}
+
+/// Re-emit the embedded event JSON from an encoded body
+pub fn decode(body: &[u8]) -> Result<String, EmbedError> {
+    let _depth = Depth::enter().ok_or(EmbedError::TooDeep)?;
+    let mut reader = Reader::new(body);
+    let format_id = reader.byte()?;
+    let format = Format::from_id(format_id).ok_or(EmbedError::UnknownFormat(format_id))?;
+
+    let order = match reader.byte()? {
+        0 => NIP01_ORDER,
+        7 => {
+            let indices = reader.bytes(7)?;
+            let mut order = [0; 7];
+            order.copy_from_slice(indices);
+            let mut seen = [false; 7];
+            for &index in &order {
+                let slot = seen
+                    .get_mut(index as usize)
+                    .ok_or(EmbedError::InvalidOrder)?;
+                if std::mem::replace(slot, true) {
+                    return Err(EmbedError::InvalidOrder);
+                }
+            }
+            order
+        }
+        _ => return Err(EmbedError::InvalidOrder),
+    };
+
+    let run_count = reader.varint()?;
+    let mut whitespace = Vec::with_capacity(run_count.min(64) as usize);
+    let mut offset = 0usize;
+    for _ in 0..run_count {
+        offset = offset
+            .checked_add(reader.varint()? as usize)
+            .ok_or(EmbedError::InvalidWhitespace)?;
+        whitespace.push((offset, std::str::from_utf8(reader.chunk()?)?));
+    }
+
+    let event = stats::deserialize(reader.chunk()?, format).map_err(Box::new)?;
+
+    let compact = render(&event, &order);
+    let mut out = String::with_capacity(
+        compact.len() + whitespace.iter().map(|(_, w)| w.len()).sum::<usize>(),
+    );
+    let mut last = 0;
+    for (offset, run) in whitespace {
+        let text = compact
+            .get(last..offset)
+            .ok_or(EmbedError::InvalidWhitespace)?;
+        out.push_str(text);
+        out.push_str(run);
+        last = offset;
+    }
+    out.push_str(&compact[last..]);
+
+    Ok(out)
+}
+
+const NIP01_ORDER: [u8; 7] = [0, 1, 2, 3, 4, 5, 6];
+
+/// One nesting level held in [`DEPTH`] until dropped
+struct Depth;
+
+impl Depth {
+    /// Enter a level, unless that would exceed [`MAX_EMBED_DEPTH`]
+    fn enter() -> Option<Self> {
+        DEPTH.with(|depth| {
+            let level = depth.get() + 1;
+            (level <= MAX_EMBED_DEPTH).then(|| {
+                depth.set(level);
+                Depth
+            })
+        })
+    }
+}
+
+impl Drop for Depth {
+    fn drop(&mut self) {
+        DEPTH.with(|depth| depth.set(depth.get() - 1));
+    }
+}
+
+/// The event and its field order, if `fields` are exactly the NIP-01 fields
+fn to_event(fields: Vec<(String, serde_json::Value)>) -> Option<(NostrEvent, [u8; 7])> {
+    if fields.len() != FIELDS.len() {
+        return None;
+    }
+
+    let mut event = NostrEvent {
+        id: [0; 32],
+        pubkey: [0; 32],
+        created_at: 0,
+        kind: 0,
+        tags: Vec::new(),
+        content: String::new(),
+        sig: [0; 64],
+    };
+    let mut order = [0u8; 7];
+    let mut seen = [false; 7];
+
+    for (i, (key, value)) in fields.into_iter().enumerate() {
+        let index = FIELDS.iter().position(|&f| f == key)?;
+        if std::mem::replace(&mut seen[index], true) {
+            return None;
+        }
+        order[i] = index as u8;
+
+        match index {
+            0 => hex::decode_to_slice(value.as_str()?, &mut event.id).ok()?,
+            1 => hex::decode_to_slice(value.as_str()?, &mut event.pubkey).ok()?,
+            2 => event.created_at = value.as_i64()?,
+            3 => event.kind = value.as_u64()?.try_into().ok()?,
+            4 => event.tags = serde_json::from_value(value).ok()?,
+            5 => event.content = value.as_str()?.to_string(),
+            _ => hex::decode_to_slice(value.as_str()?, &mut event.sig).ok()?,
+        }
+    }
+
+    Some((event, order))
+}
+
+/// Compact JSON for `event` with fields in `order`
+fn render(event: &NostrEvent, order: &[u8; 7]) -> String {
+    let mut out = String::with_capacity(256 + event.content.len());
+    out.push('{');
+    for (i, &index) in order.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        out.push('"');
+        out.push_str(FIELDS[index as usize]);
+        out.push_str("\":");
+        match index {
+            0 => push_quoted_hex(&mut out, &event.id),
+            1 => push_quoted_hex(&mut out, &event.pubkey),
+            2 => out.push_str(&event.created_at.to_string()),
+            3 => out.push_str(&event.kind.to_string()),
+            4 => out.push_str(&to_json(&event.tags)),
+            5 => out.push_str(&to_json(&event.content)),
+            _ => push_quoted_hex(&mut out, &event.sig),
+        }
+    }
+    out.push('}');
+    out
+}
+
+fn push_quoted_hex(out: &mut String, bytes: &[u8]) {
+    out.push('"');
+    out.push_str(&hex::encode(bytes));
+    out.push('"');
+}
+
+fn to_json<T: serde::Serialize + ?Sized>(value: &T) -> String {
+    serde_json::to_string(value).expect("JSON serialization should not fail")
+}
+
+/// Remove whitespace outside strings, keeping each run and its offset in
+/// the stripped text
+fn strip_whitespace(json: &str) -> (String, Vec<(usize, &str)>) {
+    let bytes = json.as_bytes();
+    let mut stripped = String::with_capacity(json.len());
+    let mut whitespace = Vec::new();
+    let mut in_string = false;
+    let mut escaped = false;
+    let mut copied = 0;
+    let mut pos = 0;
+
+    while pos < bytes.len() {
+        let b = bytes[pos];
+        if in_string {
+            if escaped {
+                escaped = false;
+            } else if b == b'\\' {
+                escaped = true;
+            } else if b == b'"' {
+                in_string = false;
+            }
+            pos += 1;
+        } else if matches!(b, b' ' | b'\t' | b'\n' | b'\r') {
+            stripped.push_str(&json[copied..pos]);
+            let start = pos;
+            while pos < bytes.len() && matches!(bytes[pos], b' ' | b'\t' | b'\n' | b'\r') {
+                pos += 1;
+            }
+            whitespace.push((stripped.len(), &json[start..pos]));
+            copied = pos;
+        } else {
+            in_string = b == b'"';
+            pos += 1;
+        }
+    }
+    stripped.push_str(&json[copied..]);
+
+    (stripped, whitespace)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::NostrEventJson;
+
+    fn sample_event() -> NostrEvent {
+        NostrEvent {
+            id: [0x11; 32],
+            pubkey: [0x22; 32],
+            created_at: 1_700_000_000,
+            kind: 1,
+            tags: vec![
+                vec!["e".to_string(), "ab".repeat(32), "".to_string()],
+                vec!["t".to_string(), "nostr".to_string()],
+            ],
+            content: "hello \"world\"\nnostr:npub1 🤙".to_string(),
+            sig: [0x33; 64],
+        }
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let json = crate::json::serialize_string(&sample_event());
+        for format in Format::all() {
+            let body = encode(&json, *format, &CompactOptions::new()).unwrap();
+            assert_eq!(decode(&body).unwrap(), json, "{}", format.name());
+        }
+
+        let body = encode(&json, Format::DannyPack, &CompactOptions::new()).unwrap();
+        assert!(body.len() < json.len() / 2);
+    }
+
+    #[test]
+    fn test_key_order_and_whitespace() {
+        let event = sample_event();
+        let pretty = serde_json::to_string_pretty(&NostrEventJson::from(&event)).unwrap();
+        let reordered = format!(
+            "{{\"kind\":1,\"content\":\"hi\",\"tags\":[],\"created_at\":5,\"pubkey\":\"{}\",\"id\":\"{}\",\"sig\":\"{}\"}}",
+            hex::encode(event.pubkey),
+            hex::encode(event.id),
+            hex::encode(event.sig)
+        );
+        let spaced = reordered.replace(",\"", ", \"").replace("{", "{ \n\t");
+
+        for json in [pretty, reordered, spaced] {
+            let body = encode(&json, Format::DannyPack, &CompactOptions::new()).unwrap();
+            assert_eq!(decode(&body).unwrap(), json);
+        }
+    }
+
+    #[test]
+    fn test_not_reproducible() {
+        let json = crate::json::serialize_string(&sample_event());
+        for json in [
+            json.replace("\"kind\":1", "\"kind\":1.0"),
+            json.replace(&"11".repeat(32), &"AB".repeat(32)),
+            json.replace("}", ",\"extra\":1}"),
+            json.replace("\\n", "\\u000a"),
+            json[..json.len() - 1].to_string(),
+            "{\"sig\":1}".to_string(),
+        ] {
+            assert_eq!(
+                encode(&json, Format::DannyPack, &CompactOptions::new()),
+                None,
+                "{}",
+                json
+            );
+        }
+    }
+
+    /// Kind 6 repost of `json`, as JSON text
+    fn repost(json: String) -> String {
+        let mut event = sample_event();
+        event.kind = 6;
+        event.content = json;
+        crate::json::serialize_string(&event)
+    }
+
+    #[test]
+    fn test_nesting_limit() {
+        let options = CompactOptions::new().with_events(true);
+        let contains_sig = |body: &[u8]| body.windows(3).any(|w| w == b"sig");
+
+        let mut json = crate::json::serialize_string(&sample_event());
+        for nested in 2..=MAX_EMBED_DEPTH + 2 {
+            json = repost(json);
+            let body = encode(&json, Format::DannyPack, &options).unwrap();
+            assert_eq!(decode(&body).unwrap(), json);
+
+            // Events past the limit stay JSON text inside the innermost one
+            assert_eq!(contains_sig(&body), nested > MAX_EMBED_DEPTH, "{}", nested);
+        }
+    }
+
+    #[test]
+    fn test_decode_too_deep() {
+        // Hand-built bodies, since encode never nests past the limit
+        let mut body = encode(
+            &crate::json::serialize_string(&sample_event()),
+            Format::DannyPack,
+            &CompactOptions::new(),
+        )
+        .unwrap();
+        for level in 2..=MAX_EMBED_DEPTH + 1 {
+            let mut segment = vec![0x04];
+            write_chunk(&mut segment, &body);
+
+            let mut event = sample_event();
+            event.kind = 6;
+            event.content.clear();
+            let mut encoded = dannypack::serialize(&event);
+            encoded.pop(); // empty content header
+            encoded.push(0x80);
+            write_chunk(&mut encoded, &segment);
+
+            body = vec![Format::DannyPack.id(), 0, 0];
+            write_chunk(&mut body, &encoded);
+
+            let result = decode(&body);
+            if level <= MAX_EMBED_DEPTH {
+                assert!(result.is_ok(), "level {}", level);
+            } else {
+                let err = result.unwrap_err().to_string();
+                assert!(err.contains("nested deeper"), "{}", err);
+            }
+        }
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        assert!(matches!(decode(&[]), Err(EmbedError::Truncated)));
+        assert!(matches!(
+            decode(&[0xee, 0]),
+            Err(EmbedError::UnknownFormat(0xee))
+        ));
+        let dannypack = Format::DannyPack.id();
+        assert!(matches!(
+            decode(&[dannypack, 3]),
+            Err(EmbedError::InvalidOrder)
+        ));
+        assert!(matches!(
+            decode(&[dannypack, 7, 0, 0, 1, 2, 3, 4, 5]),
+            Err(EmbedError::InvalidOrder)
+        ));
+    }
+}
@@ -0,0 +1,497 @@
+//! Self-describing envelope for encoded events
+//!
+//! A bare DannyPack or Proto Binary payload does not say which format
+//! produced it. The envelope prefixes a small header naming the format and
+//! schema version, so stores can hold a mix of formats and be migrated one
+//! record at a time. Use [`decode_any`] to read any enveloped event.
+//!
+//! Layout:
+//! ```text
+//! [magic: 0xB1 'N'][format id: u8][version: u8][flags: u8][payload][checksum]
+//! ```
+//!
+//! - format id is [`Format::id`]
+//! - version is [`SCHEMA_VERSION`], or a newer format version where one
+//!   exists (DannyPack 2, see [`max_version`])
+//! - flags bits 0-1: compression (0 none, 1 gzip, 2 zstd)
+//! - flags bits 2-3: checksum (0 none, 1 CRC32C, 2 xxHash3-64)
+//! - payload is the encoded event, compressed if flagged; readers reject
+//!   payloads that decompress past [`MAX_PAYLOAD_LEN`]
+//! - checksum (little-endian, 4 or 8 bytes) covers header and payload
+//!
+//! The first magic byte is not valid UTF-8 on its own, so an envelope is never
+//! mistaken for a JSON event.
+
+use std::borrow::Cow;
+use std::io::{Read, Write};
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+
+use crate::dannypack;
+use crate::event::NostrEvent;
+use crate::stats::{self, Format, FormatError};
+
+const MAGIC: [u8; 2] = [0xB1, b'N'];
+const HEADER_LEN: usize = 5;
+
+const COMPRESSION_MASK: u8 = 0b0000_0011;
+const CHECKSUM_SHIFT: u8 = 2;
+const CHECKSUM_MASK: u8 = 0b0000_1100;
+
+/// Schema version written for every format
+///
+/// Bump this when an encoding changes incompatibly; readers reject versions
+/// newer than they understand.
+pub const SCHEMA_VERSION: u8 = 1;
+
+/// Newest schema version readers understand for `format`
+pub fn max_version(format: Format) -> u8 {
+    match format {
+        Format::DannyPack => dannypack::VERSION_2,
+        _ => SCHEMA_VERSION,
+    }
+}
+
+/// zstd level used for compressed payloads
+const ZSTD_LEVEL: i32 = 3;
+
+/// Largest payload [`unwrap`] will decompress
+pub const MAX_PAYLOAD_LEN: usize = 16 * 1024 * 1024;
+
+#[derive(Debug, thiserror::Error)]
+pub enum EnvelopeError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Decode error: {0}")]
+    Format(#[from] FormatError),
+
+    #[error("Not an envelope (bad magic)")]
+    BadMagic,
+
+    #[error("Data too short")]
+    TooShort,
+
+    #[error("Unknown format id: {0}")]
+    UnknownFormat(u8),
+
+    #[error("Unsupported schema version {version} for {format:?}")]
+    UnsupportedVersion { format: Format, version: u8 },
+
+    #[error("Unknown flags: {0:#04x}")]
+    UnknownFlags(u8),
+
+    #[error("Checksum mismatch")]
+    ChecksumMismatch,
+
+    #[error("Decompressed payload exceeds {} bytes", MAX_PAYLOAD_LEN)]
+    TooLarge,
+}
+
+/// Payload compression
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Compression {
+    #[default]
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    fn bits(self) -> u8 {
+        match self {
+            Compression::None => 0,
+            Compression::Gzip => 1,
+            Compression::Zstd => 2,
+        }
+    }
+
+    fn from_bits(bits: u8) -> Option<Self> {
+        match bits {
+            0 => Some(Compression::None),
+            1 => Some(Compression::Gzip),
+            2 => Some(Compression::Zstd),
+            _ => None,
+        }
+    }
+}
+
+/// Integrity trailer
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Checksum {
+    #[default]
+    None,
+    /// 4-byte CRC32C (hardware-accelerated on most CPUs)
+    Crc32c,
+    /// 8-byte xxHash3-64
+    Xxh3,
+}
+
+impl Checksum {
+    fn bits(self) -> u8 {
+        match self {
+            Checksum::None => 0,
+            Checksum::Crc32c => 1,
+            Checksum::Xxh3 => 2,
+        }
+    }
+
+    fn from_bits(bits: u8) -> Option<Self> {
+        match bits {
+            0 => Some(Checksum::None),
+            1 => Some(Checksum::Crc32c),
+            2 => Some(Checksum::Xxh3),
+            _ => None,
+        }
+    }
+
+    /// Size of the trailer in bytes
+    pub fn trailer_len(self) -> usize {
+        match self {
+            Checksum::None => 0,
+            Checksum::Crc32c => 4,
+            Checksum::Xxh3 => 8,
+        }
+    }
+
+    fn compute(self, data: &[u8]) -> Vec<u8> {
+        match self {
+            Checksum::None => Vec::new(),
+            Checksum::Crc32c => crc32c::crc32c(data).to_le_bytes().to_vec(),
+            Checksum::Xxh3 => xxhash_rust::xxh3::xxh3_64(data).to_le_bytes().to_vec(),
+        }
+    }
+}
+
+/// How to wrap a payload
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EnvelopeOptions {
+    pub compression: Compression,
+    pub checksum: Checksum,
+}
+
+impl EnvelopeOptions {
+    /// No compression and no checksum: 5 bytes of overhead
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    pub fn with_checksum(mut self, checksum: Checksum) -> Self {
+        self.checksum = checksum;
+        self
+    }
+}
+
+/// Decoded envelope header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Header {
+    pub format: Format,
+    pub version: u8,
+    pub compression: Compression,
+    pub checksum: Checksum,
+}
+
+impl Header {
+    /// Parse the header without verifying or decompressing the payload
+    pub fn parse(data: &[u8]) -> Result<Self, EnvelopeError> {
+        if data.len() < MAGIC.len() || data[..2] != MAGIC {
+            return Err(EnvelopeError::BadMagic);
+        }
+        if data.len() < HEADER_LEN {
+            return Err(EnvelopeError::TooShort);
+        }
+
+        let format = Format::from_id(data[2]).ok_or(EnvelopeError::UnknownFormat(data[2]))?;
+        let version = data[3];
+        if version == 0 || version > max_version(format) {
+            return Err(EnvelopeError::UnsupportedVersion { format, version });
+        }
+
+        let flags = data[4];
+        let unknown = || EnvelopeError::UnknownFlags(flags);
+        if flags & !(COMPRESSION_MASK | CHECKSUM_MASK) != 0 {
+            return Err(unknown());
+        }
+        let compression = Compression::from_bits(flags & COMPRESSION_MASK).ok_or_else(unknown)?;
+        let checksum =
+            Checksum::from_bits((flags & CHECKSUM_MASK) >> CHECKSUM_SHIFT).ok_or_else(unknown)?;
+
+        Ok(Self {
+            format,
+            version,
+            compression,
+            checksum,
+        })
+    }
+
+    fn flags(&self) -> u8 {
+        self.compression.bits() | (self.checksum.bits() << CHECKSUM_SHIFT)
+    }
+}
+
+/// Check for the envelope magic
+pub fn is_envelope(data: &[u8]) -> bool {
+    data.starts_with(&MAGIC)
+}
+
+/// Wrap an already-encoded payload
+pub fn wrap(payload: &[u8], format: Format, options: EnvelopeOptions) -> Vec<u8> {
+    wrap_version(payload, format, SCHEMA_VERSION, options)
+}
+
+/// Wrap a payload encoded with a specific format version, such as
+/// [`dannypack::serialize_v2`] output with [`dannypack::VERSION_2`]
+pub fn wrap_version(
+    payload: &[u8],
+    format: Format,
+    version: u8,
+    options: EnvelopeOptions,
+) -> Vec<u8> {
+    let header = Header {
+        format,
+        version,
+        compression: options.compression,
+        checksum: options.checksum,
+    };
+
+    let mut buf = Vec::with_capacity(HEADER_LEN + payload.len() + options.checksum.trailer_len());
+    buf.extend_from_slice(&MAGIC);
+    buf.extend_from_slice(&[format.id(), header.version, header.flags()]);
+
+    match options.compression {
+        Compression::None => buf.extend_from_slice(payload),
+        Compression::Gzip => {
+            let mut encoder = GzEncoder::new(buf, flate2::Compression::default());
+            encoder
+                .write_all(payload)
+                .expect("gzip to memory should not fail");
+            buf = encoder.finish().expect("gzip to memory should not fail");
+        }
+        Compression::Zstd => {
+            let compressed =
+                zstd::encode_all(payload, ZSTD_LEVEL).expect("zstd to memory should not fail");
+            buf.extend_from_slice(&compressed);
+        }
+    }
+
+    let checksum = options.checksum.compute(&buf);
+    buf.extend_from_slice(&checksum);
+    buf
+}
+
+/// Encode an event in `format` and wrap it
+pub fn encode(event: &NostrEvent, format: Format, options: EnvelopeOptions) -> Vec<u8> {
+    wrap(&stats::serialize(event, format), format, options)
+}
+
+/// Verify and unwrap an envelope, returning the header and raw payload
+///
+/// The payload is borrowed unless it had to be decompressed.
+pub fn unwrap(data: &[u8]) -> Result<(Header, Cow<'_, [u8]>), EnvelopeError> {
+    let header = Header::parse(data)?;
+
+    let body_end = data
+        .len()
+        .checked_sub(header.checksum.trailer_len())
+        .filter(|&end| end >= HEADER_LEN)
+        .ok_or(EnvelopeError::TooShort)?;
+    let (body, trailer) = data.split_at(body_end);
+    if header.checksum.compute(body) != trailer {
+        return Err(EnvelopeError::ChecksumMismatch);
+    }
+
+    let payload = &body[HEADER_LEN..];
+    let payload = match header.compression {
+        Compression::None => Cow::Borrowed(payload),
+        Compression::Gzip => Cow::Owned(decompress(GzDecoder::new(payload))?),
+        Compression::Zstd => Cow::Owned(decompress(zstd::Decoder::new(payload)?)?),
+    };
+
+    Ok((header, payload))
+}
+
+/// Read a decoder to the end, stopping past [`MAX_PAYLOAD_LEN`]
+fn decompress(decoder: impl Read) -> Result<Vec<u8>, EnvelopeError> {
+    let mut out = Vec::new();
+    decoder
+        .take(MAX_PAYLOAD_LEN as u64 + 1)
+        .read_to_end(&mut out)?;
+    if out.len() > MAX_PAYLOAD_LEN {
+        return Err(EnvelopeError::TooLarge);
+    }
+    Ok(out)
+}
+
+/// Decode an enveloped event in whatever format it was written
+pub fn decode_any(data: &[u8]) -> Result<NostrEvent, EnvelopeError> {
+    let (header, payload) = unwrap(data)?;
+    match header.format {
+        Format::DannyPack => {
+            Ok(dannypack::deserialize_version(&payload, header.version)
+                .map_err(FormatError::from)?)
+        }
+        format => Ok(stats::deserialize(&payload, format)?),
+    }
+}
+
+/// Re-encode an enveloped event in another format
+///
+/// Records already in `target` are returned unchanged, so a store can be
+/// migrated incrementally by running every record through this.
+pub fn transcode(
+    data: &[u8],
+    target: Format,
+    options: EnvelopeOptions,
+) -> Result<Vec<u8>, EnvelopeError> {
+    let header = Header::parse(data)?;
+    if header.format == target
+        && header.version == SCHEMA_VERSION
+        && header.compression == options.compression
+        && header.checksum == options.checksum
+    {
+        // Still verify, so migration never copies a corrupt record
+        unwrap(data)?;
+        return Ok(data.to_vec());
+    }
+    Ok(encode(&decode_any(data)?, target, options))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_event() -> NostrEvent {
+        NostrEvent {
+            id: [0xab; 32],
+            pubkey: [0xcd; 32],
+            created_at: 1234567890,
+            kind: 1,
+            tags: vec![
+                vec!["p".to_string(), "cd".repeat(32)],
+                vec!["t".to_string(), "nostr".to_string()],
+            ],
+            content: "Hello, Nostr! ".repeat(10),
+            sig: [0xef; 64],
+        }
+    }
+
+    #[test]
+    fn test_decode_any_all_formats_and_options() {
+        let event = sample_event();
+        let options = [
+            EnvelopeOptions::new(),
+            EnvelopeOptions::new().with_checksum(Checksum::Crc32c),
+            EnvelopeOptions::new()
+                .with_compression(Compression::Gzip)
+                .with_checksum(Checksum::Xxh3),
+            EnvelopeOptions::new().with_compression(Compression::Zstd),
+        ];
+
+        for &format in Format::all() {
+            for options in options {
+                let data = encode(&event, format, options);
+                assert!(is_envelope(&data));
+
+                let header = Header::parse(&data).unwrap();
+                assert_eq!(header.format, format);
+                assert_eq!(header.compression, options.compression);
+                assert_eq!(header.checksum, options.checksum);
+
+                assert_eq!(decode_any(&data).unwrap(), event, "{}", format.name());
+            }
+        }
+
+        // Uncompressed, unchecked envelopes cost exactly the header
+        let bare = stats::serialize(&event, Format::DannyPack);
+        let wrapped = encode(&event, Format::DannyPack, EnvelopeOptions::new());
+        assert_eq!(wrapped.len(), bare.len() + HEADER_LEN);
+    }
+
+    #[test]
+    fn test_rejects_corruption() {
+        let event = sample_event();
+        let options = EnvelopeOptions::new().with_checksum(Checksum::Crc32c);
+        let mut data = encode(&event, Format::ProtoBinary, options);
+
+        let mid = data.len() / 2;
+        data[mid] ^= 0x01;
+        assert!(matches!(
+            decode_any(&data),
+            Err(EnvelopeError::ChecksumMismatch)
+        ));
+
+        // JSON is never mistaken for an envelope
+        let json = stats::serialize(&event, Format::Json);
+        assert!(!is_envelope(&json));
+        assert!(matches!(decode_any(&json), Err(EnvelopeError::BadMagic)));
+
+        let mut data = encode(&event, Format::DannyPack, EnvelopeOptions::new());
+        data[3] = max_version(Format::DannyPack) + 1;
+        assert!(matches!(
+            decode_any(&data),
+            Err(EnvelopeError::UnsupportedVersion { .. })
+        ));
+        data[3] = SCHEMA_VERSION;
+        data[2] = 200;
+        assert!(matches!(
+            decode_any(&data),
+            Err(EnvelopeError::UnknownFormat(200))
+        ));
+    }
+
+    #[test]
+    fn test_payload_limit() {
+        for compression in [Compression::Gzip, Compression::Zstd] {
+            let options = EnvelopeOptions::new().with_compression(compression);
+            let data = wrap(&vec![0; MAX_PAYLOAD_LEN], Format::DannyPack, options);
+            assert_eq!(unwrap(&data).unwrap().1.len(), MAX_PAYLOAD_LEN);
+
+            let data = wrap(&vec![0; MAX_PAYLOAD_LEN + 1], Format::DannyPack, options);
+            assert!(matches!(unwrap(&data), Err(EnvelopeError::TooLarge)));
+        }
+    }
+
+    #[test]
+    fn test_dannypack_v2() {
+        let event = sample_event();
+        let payload = dannypack::serialize_v2(&event);
+        let data = wrap_version(
+            &payload,
+            Format::DannyPack,
+            dannypack::VERSION_2,
+            EnvelopeOptions::new(),
+        );
+        assert_eq!(Header::parse(&data).unwrap().version, dannypack::VERSION_2);
+        assert_eq!(decode_any(&data).unwrap(), event);
+
+        // Other formats have no version 2
+        let data = wrap_version(&payload, Format::ProtoBinary, 2, EnvelopeOptions::new());
+        assert!(matches!(
+            decode_any(&data),
+            Err(EnvelopeError::UnsupportedVersion { .. })
+        ));
+    }
+
+    #[test]
+    fn test_transcode() {
+        let event = sample_event();
+        let options = EnvelopeOptions::new().with_checksum(Checksum::Xxh3);
+        let proto = encode(&event, Format::ProtoBinary, options);
+
+        let migrated = transcode(&proto, Format::DannyPack, options).unwrap();
+        assert_eq!(Header::parse(&migrated).unwrap().format, Format::DannyPack);
+        assert_eq!(decode_any(&migrated).unwrap(), event);
+
+        // Already in the target format: passed through untouched
+        assert_eq!(
+            transcode(&migrated, Format::DannyPack, options).unwrap(),
+            migrated
+        );
+    }
+}
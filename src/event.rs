@@ -4,6 +4,9 @@
 //! that all serializers convert to/from.
 
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::stats::{self, Format};
 
 /// A Nostr event as defined in NIP-01
 ///
@@ -80,51 +83,64 @@ impl NostrEvent {
         hex::encode(self.sig)
     }
 
-    /// Calculate the total number of tags
-    pub fn tag_count(&self) -> usize {
-        self.tags.len()
+    /// The NIP-01 serialization whose SHA-256 is the event id:
+    /// `[0,<pubkey>,<created_at>,<kind>,<tags>,<content>]`
+    pub fn id_preimage(&self) -> String {
+        let mut out = String::with_capacity(self.encoded_len(Format::Json));
+        out.push_str("[0,\"");
+        out.push_str(&self.pubkey_hex());
+        out.push_str(&format!("\",{},{},[", self.created_at, self.kind));
+        for (i, tag) in self.tags.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            out.push('[');
+            for (j, value) in tag.iter().enumerate() {
+                if j > 0 {
+                    out.push(',');
+                }
+                push_nip01_string(&mut out, value);
+            }
+            out.push(']');
+        }
+        out.push_str("],");
+        push_nip01_string(&mut out, &self.content);
+        out.push(']');
+        out
     }
 
-    /// Calculate approximate JSON size (for categorization)
-    pub fn estimated_json_size(&self) -> usize {
-        // Base structure overhead
-        let base = 100; // {"id":"","pubkey":"","created_at":,"kind":,"tags":[],"content":"","sig":""}
-
-        // Hex-encoded fields
-        let id_size = 64;
-        let pubkey_size = 64;
-        let sig_size = 128;
+    /// Compute the event id from the other fields
+    pub fn compute_id(&self) -> [u8; 32] {
+        Sha256::digest(self.id_preimage().as_bytes()).into()
+    }
 
-        // Timestamp and kind (variable, estimate)
-        let timestamp_size = 10;
-        let kind_size = 5;
+    /// Whether `id` is the hash of the event's contents
+    ///
+    /// Only the id is checked; verifying `sig` needs secp256k1.
+    pub fn verify_id(&self) -> bool {
+        self.compute_id() == self.id
+    }
 
-        // Content (with some escaping overhead estimate)
-        let content_size = self.content.len() + self.content.len() / 10;
+    /// Calculate the total number of tags
+    pub fn tag_count(&self) -> usize {
+        self.tags.len()
+    }
 
-        // Tags
-        let tags_size: usize = self
-            .tags
-            .iter()
-            .map(|tag| {
-                // ["tag_name", "value1", ...] with quotes and commas
-                4 + tag.iter().map(|s| s.len() + 3).sum::<usize>()
-            })
-            .sum();
+    /// Exact serialized size in `format`, computed without serializing
+    /// where the format allows; see [`stats::encoded_len`]
+    pub fn encoded_len(&self, format: Format) -> usize {
+        stats::encoded_len(self, format)
+    }
 
-        base + id_size
-            + pubkey_size
-            + sig_size
-            + timestamp_size
-            + kind_size
-            + content_size
-            + tags_size
+    /// Calculate JSON size (for categorization)
+    #[deprecated(note = "use encoded_len(Format::Json)")]
+    pub fn estimated_json_size(&self) -> usize {
+        self.encoded_len(Format::Json)
     }
 
     /// Categorize event by size
     pub fn size_category(&self) -> SizeCategory {
-        let size = self.estimated_json_size();
-        match size {
+        match self.encoded_len(Format::Json) {
             0..=500 => SizeCategory::Tiny,
             501..=2000 => SizeCategory::Small,
             2001..=10000 => SizeCategory::Medium,
@@ -143,6 +159,29 @@ impl NostrEvent {
             _ => TagCategory::Massive,
         }
     }
+
+    /// Classify the event by its kind range (NIP-01)
+    pub fn kind_class(&self) -> KindClass {
+        KindClass::from_kind(self.kind)
+    }
+
+    /// Value of the first `d` tag, if any
+    pub fn d_tag(&self) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|t| t.first().is_some_and(|name| name == "d"))
+            .and_then(|t| t.get(1))
+            .map(String::as_str)
+    }
+
+    /// `kind:pubkey:d-tag` coordinate as used in `a` tags
+    ///
+    /// Returns `None` for regular and ephemeral events. Replaceable events
+    /// have an empty d-tag part, as does an addressable event without a `d`
+    /// tag.
+    pub fn addressable_coordinate(&self) -> Option<String> {
+        crate::tags::Coordinate::of(self).map(|c| c.to_string())
+    }
 }
 
 /// Size category for events
@@ -189,6 +228,121 @@ impl std::fmt::Display for TagCategory {
     }
 }
 
+/// Storage class of an event kind (NIP-01)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum KindClass {
+    Regular,     // 1-9999 except 3, and 40000+
+    Replaceable, // 0, 3, 10000-19999
+    Ephemeral,   // 20000-29999
+    Addressable, // 30000-39999
+}
+
+impl KindClass {
+    pub const ALL: [KindClass; 4] = [
+        KindClass::Regular,
+        KindClass::Replaceable,
+        KindClass::Ephemeral,
+        KindClass::Addressable,
+    ];
+
+    pub fn from_kind(kind: u16) -> Self {
+        match kind {
+            0 | 3 | 10000..=19999 => KindClass::Replaceable,
+            20000..=29999 => KindClass::Ephemeral,
+            30000..=39999 => KindClass::Addressable,
+            _ => KindClass::Regular,
+        }
+    }
+
+    /// Whether relays store events of this class at all
+    pub fn is_stored(&self) -> bool {
+        *self != KindClass::Ephemeral
+    }
+
+    /// Whether a newer event replaces older ones with the same coordinate
+    pub fn is_replaceable(&self) -> bool {
+        matches!(self, KindClass::Replaceable | KindClass::Addressable)
+    }
+}
+
+impl std::fmt::Display for KindClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KindClass::Regular => write!(f, "regular"),
+            KindClass::Replaceable => write!(f, "replaceable"),
+            KindClass::Ephemeral => write!(f, "ephemeral"),
+            KindClass::Addressable => write!(f, "addressable"),
+        }
+    }
+}
+
+/// A tag value as stored by the binary formats, borrowed from the encoded bytes
+///
+/// Formats that compress hex strings store them as raw bytes; `Hex` holds
+/// those bytes, which decode to their lowercase hex encoding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RawTagValue<'a> {
+    /// UTF-8 bytes of the value
+    Text(&'a [u8]),
+    /// Hex-decoded bytes of the value
+    Hex(&'a [u8]),
+    /// A [compacted](crate::compact) value body
+    Compact(&'a [u8]),
+    /// A pubkey rebuilt from a [follow block](crate::follows)
+    Pubkey([u8; 32]),
+}
+
+impl RawTagValue<'_> {
+    /// Compare against a decoded tag value without allocating
+    pub fn eq_str(&self, value: &str) -> bool {
+        const DIGITS: &[u8; 16] = b"0123456789abcdef";
+        match *self {
+            RawTagValue::Text(bytes) => bytes == value.as_bytes(),
+            RawTagValue::Pubkey(ref key) => RawTagValue::Hex(key).eq_str(value),
+            RawTagValue::Hex(bytes) => {
+                let value = value.as_bytes();
+                value.len() == bytes.len() * 2
+                    && bytes.iter().zip(value.chunks_exact(2)).all(|(b, pair)| {
+                        pair[0] == DIGITS[(b >> 4) as usize]
+                            && pair[1] == DIGITS[(b & 0xF) as usize]
+                    })
+            }
+            RawTagValue::Compact(body) => {
+                crate::compact::expand(body).is_ok_and(|expanded| expanded == value)
+            }
+        }
+    }
+
+    /// Decode into an owned string
+    pub fn to_string_lossy(&self) -> String {
+        match *self {
+            RawTagValue::Text(bytes) => String::from_utf8_lossy(bytes).into_owned(),
+            RawTagValue::Hex(bytes) => hex::encode(bytes),
+            RawTagValue::Compact(body) => crate::compact::expand(body).unwrap_or_default(),
+            RawTagValue::Pubkey(key) => hex::encode(key),
+        }
+    }
+}
+
+/// Write a JSON string with NIP-01 escaping: only `"`, `\` and the
+/// `\b \t \n \f \r` control characters are escaped, everything else verbatim
+fn push_nip01_string(out: &mut String, value: &str) {
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            '\u{08}' => out.push_str("\\b"),
+            '\u{0c}' => out.push_str("\\f"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
 /// JSON-compatible representation for serde
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NostrEventJson {
@@ -262,10 +416,47 @@ mod tests {
         assert_eq!(event, back);
     }
 
+    #[test]
+    fn test_compute_id() {
+        let mut event = sample_event();
+        event.pubkey = [0xcd; 32];
+        event.tags = vec![
+            vec!["p".to_string(), "abcd1234".to_string()],
+            vec!["e".to_string(), "deadbeef".to_string()],
+        ];
+        // Quotes and newlines are escaped, other control characters are not
+        event.content = "Hello, \"Nostr\"!\n\u{1}".to_string();
+
+        assert_eq!(
+            event.id_preimage(),
+            format!(
+                "[0,\"{}\",1234567890,1,[[\"p\",\"abcd1234\"],[\"e\",\"deadbeef\"]],\"Hello, \\\"Nostr\\\"!\\n\u{1}\"]",
+                "cd".repeat(32)
+            )
+        );
+        assert_eq!(
+            hex::encode(event.compute_id()),
+            "cc9343ffee254ffe1ac2f763942076716028de84430030775f9c076094803afe"
+        );
+        assert!(!event.verify_id());
+        event.id = event.compute_id();
+        assert!(event.verify_id());
+    }
+
     #[test]
     fn test_size_category() {
         let event = sample_event();
         assert_eq!(event.size_category(), SizeCategory::Tiny);
+
+        // Boundaries use the exact JSON size, escapes included
+        let mut event = sample_event();
+        let padding = 500 - event.encoded_len(Format::Json);
+        event.content.push_str(&"\n".repeat(padding / 2));
+        event.content.push_str(&"x".repeat(padding % 2));
+        assert_eq!(event.encoded_len(Format::Json), 500);
+        assert_eq!(event.size_category(), SizeCategory::Tiny);
+        event.content.push('x');
+        assert_eq!(event.size_category(), SizeCategory::Small);
     }
 
     #[test]
@@ -273,4 +464,45 @@ mod tests {
         let event = sample_event();
         assert_eq!(event.tag_category(), TagCategory::Few);
     }
+
+    #[test]
+    fn test_kind_class() {
+        assert_eq!(KindClass::from_kind(0), KindClass::Replaceable);
+        assert_eq!(KindClass::from_kind(1), KindClass::Regular);
+        assert_eq!(KindClass::from_kind(3), KindClass::Replaceable);
+        assert_eq!(KindClass::from_kind(9999), KindClass::Regular);
+        assert_eq!(KindClass::from_kind(10002), KindClass::Replaceable);
+        assert_eq!(KindClass::from_kind(20000), KindClass::Ephemeral);
+        assert_eq!(KindClass::from_kind(30023), KindClass::Addressable);
+        assert_eq!(KindClass::from_kind(40000), KindClass::Regular);
+
+        let mut event = sample_event();
+        assert_eq!(event.kind_class(), KindClass::Regular);
+        assert_eq!(event.addressable_coordinate(), None);
+
+        event.kind = 10002;
+        let pubkey = event.pubkey_hex();
+        assert_eq!(
+            event.addressable_coordinate(),
+            Some(format!("10002:{}:", pubkey))
+        );
+
+        event.kind = 30023;
+        event
+            .tags
+            .push(vec!["d".to_string(), "my-article".to_string()]);
+        assert_eq!(
+            event.addressable_coordinate(),
+            Some(format!("30023:{}:my-article", pubkey))
+        );
+    }
+
+    #[test]
+    fn test_raw_tag_value_eq_str() {
+        assert!(RawTagValue::Text(b"nostr").eq_str("nostr"));
+        assert!(!RawTagValue::Text(b"nostr").eq_str("nost"));
+        assert!(RawTagValue::Hex(&[0xde, 0xad, 0xbe, 0xef]).eq_str("deadbeef"));
+        assert!(!RawTagValue::Hex(&[0xde, 0xad, 0xbe, 0xef]).eq_str("DEADBEEF"));
+        assert!(!RawTagValue::Hex(&[0xde, 0xad]).eq_str("deadbeef"));
+    }
 }
@@ -0,0 +1,434 @@
+//! NIP-01 subscription filters
+//!
+//! A [`Filter`] can be evaluated on a decoded [`NostrEvent`], or directly on
+//! encoded bytes. Cap'n Proto and DannyPack are matched lazily: the fixed
+//! fields are checked first and tags are walked without building strings, so
+//! most non-matching events are rejected without decoding anything else.
+//! Every other format falls back to a full decode.
+
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+
+use serde_json::{Map, Value};
+
+use crate::event::{NostrEvent, RawTagValue};
+use crate::stats::{self, Format, FormatError};
+use crate::{capnp, dannypack};
+
+#[derive(Debug, thiserror::Error)]
+pub enum FilterError {
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("Filter must be a JSON object")]
+    ExpectedObject,
+
+    #[error("Invalid value for field: {0}")]
+    InvalidField(String),
+}
+
+/// A NIP-01 filter
+///
+/// Every present condition must hold for an event to match. Within a list,
+/// any element may match. `None` means the condition is absent; `Some` of an
+/// empty list matches nothing.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Filter {
+    pub ids: Option<Vec<[u8; 32]>>,
+    pub authors: Option<Vec<[u8; 32]>>,
+    pub kinds: Option<Vec<u16>>,
+    /// Generic tag conditions (`#e`, `#p`, `#t`, ...) keyed by tag letter
+    pub tags: BTreeMap<char, Vec<String>>,
+    pub since: Option<i64>,
+    pub until: Option<i64>,
+    pub limit: Option<usize>,
+}
+
+impl Filter {
+    /// An empty filter, which matches every event
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn ids<I: IntoIterator<Item = [u8; 32]>>(mut self, ids: I) -> Self {
+        self.ids = Some(ids.into_iter().collect());
+        self
+    }
+
+    pub fn authors<I: IntoIterator<Item = [u8; 32]>>(mut self, authors: I) -> Self {
+        self.authors = Some(authors.into_iter().collect());
+        self
+    }
+
+    pub fn kinds<I: IntoIterator<Item = u16>>(mut self, kinds: I) -> Self {
+        self.kinds = Some(kinds.into_iter().collect());
+        self
+    }
+
+    /// Require a `letter` tag whose first value is one of `values`
+    pub fn tag<I, S>(mut self, letter: char, values: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.tags
+            .insert(letter, values.into_iter().map(Into::into).collect());
+        self
+    }
+
+    pub fn since(mut self, since: i64) -> Self {
+        self.since = Some(since);
+        self
+    }
+
+    pub fn until(mut self, until: i64) -> Self {
+        self.until = Some(until);
+        self
+    }
+
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Parse a filter from its JSON form
+    pub fn from_json(json: &str) -> Result<Self, FilterError> {
+        Self::from_value(&serde_json::from_str(json)?)
+    }
+
+    /// Parse a filter from a JSON value
+    ///
+    /// Fields this crate does not know (e.g. NIP-50 `search`) are ignored.
+    pub fn from_value(value: &Value) -> Result<Self, FilterError> {
+        let object = value.as_object().ok_or(FilterError::ExpectedObject)?;
+        let mut filter = Filter::new();
+
+        for (key, value) in object {
+            match key.as_str() {
+                "ids" => filter.ids = Some(parse_hex32_list(key, value)?),
+                "authors" => filter.authors = Some(parse_hex32_list(key, value)?),
+                "kinds" => {
+                    filter.kinds = Some(
+                        as_array(key, value)?
+                            .iter()
+                            .map(|v| {
+                                v.as_u64()
+                                    .and_then(|k| u16::try_from(k).ok())
+                                    .ok_or_else(|| invalid(key))
+                            })
+                            .collect::<Result<_, _>>()?,
+                    )
+                }
+                "since" => filter.since = Some(value.as_i64().ok_or_else(|| invalid(key))?),
+                "until" => filter.until = Some(value.as_i64().ok_or_else(|| invalid(key))?),
+                "limit" => {
+                    filter.limit = Some(
+                        value
+                            .as_u64()
+                            .and_then(|l| usize::try_from(l).ok())
+                            .ok_or_else(|| invalid(key))?,
+                    )
+                }
+                _ => {
+                    let mut chars = key.chars();
+                    if let (Some('#'), Some(letter), None) =
+                        (chars.next(), chars.next(), chars.next())
+                    {
+                        if !letter.is_ascii_alphabetic() {
+                            return Err(invalid(key));
+                        }
+                        let values = as_array(key, value)?
+                            .iter()
+                            .map(|v| v.as_str().map(str::to_string).ok_or_else(|| invalid(key)))
+                            .collect::<Result<_, _>>()?;
+                        filter.tags.insert(letter, values);
+                    }
+                }
+            }
+        }
+
+        Ok(filter)
+    }
+
+    /// Serialize to a JSON value
+    pub fn to_value(&self) -> Value {
+        let mut object = Map::new();
+        if let Some(ids) = &self.ids {
+            object.insert("ids".into(), ids.iter().map(hex::encode).collect());
+        }
+        if let Some(authors) = &self.authors {
+            object.insert("authors".into(), authors.iter().map(hex::encode).collect());
+        }
+        if let Some(kinds) = &self.kinds {
+            object.insert("kinds".into(), kinds.iter().copied().collect());
+        }
+        for (letter, values) in &self.tags {
+            object.insert(format!("#{}", letter), values.iter().cloned().collect());
+        }
+        if let Some(since) = self.since {
+            object.insert("since".into(), since.into());
+        }
+        if let Some(until) = self.until {
+            object.insert("until".into(), until.into());
+        }
+        if let Some(limit) = self.limit {
+            object.insert("limit".into(), limit.into());
+        }
+        Value::Object(object)
+    }
+
+    /// Serialize to a JSON string
+    pub fn to_json(&self) -> String {
+        self.to_value().to_string()
+    }
+
+    /// Check a decoded event against the filter
+    pub fn matches(&self, event: &NostrEvent) -> bool {
+        if !self.matches_fixed(&event.id, &event.pubkey, event.created_at, event.kind) {
+            return false;
+        }
+        let tags = event.tags.iter().filter_map(|tag| {
+            let name = RawTagValue::Text(tag.first()?.as_bytes());
+            let value = RawTagValue::Text(tag.get(1)?.as_bytes());
+            Some(Ok::<_, Infallible>((name, value)))
+        });
+        self.matches_tags(tags).unwrap_or(false)
+    }
+
+    /// Check a DannyPack-encoded event without deserializing it
+    pub fn matches_dannypack(&self, data: &[u8]) -> Result<bool, dannypack::DannyPackError> {
+        if !self.matches_fixed(
+            &dannypack::read_id(data)?,
+            &dannypack::read_pubkey(data)?,
+            dannypack::read_created_at(data)?,
+            dannypack::read_kind(data)?,
+        ) {
+            return Ok(false);
+        }
+        if self.tags.is_empty() {
+            return Ok(true);
+        }
+        self.matches_tags(dannypack::read_tags(data)?.filter_map(|tag| match tag {
+            Ok(tag) => Some(Ok((tag.get(0)?, tag.get(1)?))),
+            Err(e) => Some(Err(e)),
+        }))
+    }
+
+    /// Check a Cap'n Proto-encoded event without deserializing it
+    pub fn matches_capnp(&self, data: &[u8]) -> Result<bool, capnp::CapnpError> {
+        capnp::with_raw_event(data, |raw| {
+            if !self.matches_fixed(&raw.id(), &raw.pubkey(), raw.created_at(), raw.kind()) {
+                return Ok(false);
+            }
+            if self.tags.is_empty() {
+                return Ok(true);
+            }
+            self.matches_tags(raw.tags().filter_map(|tag| match tag {
+                Ok(tag) => Some(Ok((tag.get(0)?, tag.get(1)?))),
+                Err(e) => Some(Err(e)),
+            }))
+        })?
+    }
+
+    /// Check an event encoded in any format
+    ///
+    /// Cap'n Proto and DannyPack are matched lazily; other formats are decoded.
+    pub fn matches_encoded(&self, data: &[u8], format: Format) -> Result<bool, FormatError> {
+        match format {
+            Format::CapnProto => Ok(self.matches_capnp(data)?),
+            Format::DannyPack => Ok(self.matches_dannypack(data)?),
+            _ => Ok(self.matches(&stats::deserialize(data, format)?)),
+        }
+    }
+
+    /// Apply the filter to a slice of events, honouring `limit`
+    ///
+    /// As in NIP-01, the limit keeps the newest matching events, newest first.
+    pub fn apply<'a>(&self, events: &'a [NostrEvent]) -> Vec<&'a NostrEvent> {
+        let mut matched: Vec<&NostrEvent> = events.iter().filter(|e| self.matches(e)).collect();
+        if let Some(limit) = self.limit {
+            matched.sort_by(|a, b| b.created_at.cmp(&a.created_at).then(a.id.cmp(&b.id)));
+            matched.truncate(limit);
+        }
+        matched
+    }
+
+    fn matches_fixed(&self, id: &[u8; 32], pubkey: &[u8; 32], created_at: i64, kind: u16) -> bool {
+        self.kinds.as_ref().is_none_or(|k| k.contains(&kind))
+            && self.since.is_none_or(|since| created_at >= since)
+            && self.until.is_none_or(|until| created_at <= until)
+            && self.authors.as_ref().is_none_or(|a| a.contains(pubkey))
+            && self.ids.as_ref().is_none_or(|ids| ids.contains(id))
+    }
+
+    /// Check the tag conditions in a single pass over (name, first value) pairs
+    fn matches_tags<'v, E, I>(&self, tags: I) -> Result<bool, E>
+    where
+        I: Iterator<Item = Result<(RawTagValue<'v>, RawTagValue<'v>), E>>,
+    {
+        if self.tags.is_empty() {
+            return Ok(true);
+        }
+
+        let mut satisfied = vec![false; self.tags.len()];
+        let mut remaining = self.tags.len();
+        for tag in tags {
+            let (name, value) = tag?;
+            let RawTagValue::Text(&[letter]) = name else {
+                continue;
+            };
+            for (i, (filter_letter, values)) in self.tags.iter().enumerate() {
+                if !satisfied[i]
+                    && *filter_letter as u32 == letter as u32
+                    && values.iter().any(|v| value.eq_str(v))
+                {
+                    satisfied[i] = true;
+                    remaining -= 1;
+                    if remaining == 0 {
+                        return Ok(true);
+                    }
+                }
+            }
+        }
+        Ok(false)
+    }
+}
+
+fn invalid(field: &str) -> FilterError {
+    FilterError::InvalidField(field.to_string())
+}
+
+fn as_array<'a>(field: &str, value: &'a Value) -> Result<&'a Vec<Value>, FilterError> {
+    value.as_array().ok_or_else(|| invalid(field))
+}
+
+fn parse_hex32_list(field: &str, value: &Value) -> Result<Vec<[u8; 32]>, FilterError> {
+    as_array(field, value)?
+        .iter()
+        .map(|v| {
+            let mut out = [0u8; 32];
+            let s = v.as_str().ok_or_else(|| invalid(field))?;
+            hex::decode_to_slice(s, &mut out).map_err(|_| invalid(field))?;
+            Ok(out)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_event() -> NostrEvent {
+        NostrEvent {
+            id: [0xab; 32],
+            pubkey: [0xcd; 32],
+            created_at: 1234567890,
+            kind: 1,
+            tags: vec![
+                vec!["p".to_string(), hex::encode([0x11; 32])],
+                vec![
+                    "e".to_string(),
+                    hex::encode([0x22; 32]),
+                    "wss://r.example".to_string(),
+                ],
+                vec!["t".to_string(), "nostr".to_string()],
+            ],
+            content: "Hello, Nostr!".to_string(),
+            sig: [0xef; 64],
+        }
+    }
+
+    fn check_all_formats(filter: &Filter, event: &NostrEvent, expected: bool) {
+        assert_eq!(
+            filter.matches(event),
+            expected,
+            "decoded: {}",
+            filter.to_json()
+        );
+        for &format in Format::all() {
+            let data = stats::serialize(event, format);
+            assert_eq!(
+                filter.matches_encoded(&data, format).unwrap(),
+                expected,
+                "{}: {}",
+                format.name(),
+                filter.to_json()
+            );
+        }
+    }
+
+    #[test]
+    fn test_json_roundtrip() {
+        let json = format!(
+            r##"{{"ids":["{}"],"kinds":[1,7],"#p":["{}"],"#t":["nostr"],"since":100,"until":200,"limit":10,"search":"ignored"}}"##,
+            hex::encode([0xab; 32]),
+            hex::encode([0x11; 32])
+        );
+        let filter = Filter::from_json(&json).unwrap();
+
+        assert_eq!(filter.ids, Some(vec![[0xab; 32]]));
+        assert_eq!(filter.kinds, Some(vec![1, 7]));
+        assert_eq!(filter.tags.get(&'t'), Some(&vec!["nostr".to_string()]));
+        assert_eq!(filter.limit, Some(10));
+        assert_eq!(Filter::from_json(&filter.to_json()).unwrap(), filter);
+
+        assert!(Filter::from_json(r#"{"kinds":[70000]}"#).is_err());
+        assert!(Filter::from_json(r#"{"authors":["abcd"]}"#).is_err());
+        assert!(Filter::from_json(r#"[]"#).is_err());
+    }
+
+    #[test]
+    fn test_matches_across_formats() {
+        let event = sample_event();
+
+        check_all_formats(&Filter::new(), &event, true);
+        check_all_formats(&Filter::new().kinds([1, 7]), &event, true);
+        check_all_formats(&Filter::new().kinds([7]), &event, false);
+        check_all_formats(&Filter::new().kinds([]), &event, false);
+        check_all_formats(&Filter::new().authors([[0xcd; 32]]), &event, true);
+        check_all_formats(&Filter::new().ids([[0x00; 32]]), &event, false);
+        check_all_formats(
+            &Filter::new().since(1234567890).until(1234567890),
+            &event,
+            true,
+        );
+        check_all_formats(&Filter::new().since(1234567891), &event, false);
+        check_all_formats(
+            &Filter::new().tag('p', [hex::encode([0x11; 32])]),
+            &event,
+            true,
+        );
+        check_all_formats(
+            &Filter::new().tag('e', [hex::encode([0x11; 32])]),
+            &event,
+            false,
+        );
+        check_all_formats(
+            &Filter::new()
+                .tag('t', ["bitcoin", "nostr"])
+                .tag('e', [hex::encode([0x22; 32])]),
+            &event,
+            true,
+        );
+        check_all_formats(
+            &Filter::new().tag('t', ["nostr"]).tag('x', ["y"]),
+            &event,
+            false,
+        );
+    }
+
+    #[test]
+    fn test_apply_limit_keeps_newest() {
+        let events: Vec<NostrEvent> = (0..5)
+            .map(|i| NostrEvent {
+                id: [i as u8; 32],
+                created_at: 1000 + i,
+                ..sample_event()
+            })
+            .collect();
+
+        let newest = Filter::new().limit(2).apply(&events);
+        let times: Vec<_> = newest.iter().map(|e| e.created_at).collect();
+        assert_eq!(times, vec![1004, 1003]);
+    }
+}
@@ -0,0 +1,620 @@
+//! Cached, memory-mapped benchmark fixtures
+//!
+//! Loading a benchmark sample means inflating gzip protobuf and re-sampling,
+//! which dominates bench startup. The fixture cache stores the sampled events
+//! once in an uncompressed, indexed DannyPack file and memory-maps it on later
+//! runs, so every run decodes the exact same events in milliseconds.
+//!
+//! File layout (all integers little-endian):
+//! ```text
+//! [magic: "BNFX"][version: u8][reserved: 3 bytes][key_hash: u64]
+//! [section_count: u32]
+//!   per section: [name_len: u16][name][first_record: u32][record_count: u32]
+//! [record_count: u32][offsets: (record_count + 1) x u64, relative to data start]
+//! [data: concatenated DannyPack records]
+//! ```
+
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use memmap2::Mmap;
+
+use crate::dannypack::{self, DannyPackError, Reader, Truncated};
+use crate::event::NostrEvent;
+use crate::loader::{self, LoadError};
+use crate::sampler::{BenchmarkSets, EventSampler};
+
+const MAGIC: &[u8; 4] = b"BNFX";
+const VERSION: u8 = 1;
+
+/// Environment variable overriding the default cache directory
+pub const FIXTURE_DIR_ENV: &str = "BINOSTR_FIXTURE_DIR";
+
+/// Default cache directory, relative to the crate root
+pub const DEFAULT_FIXTURE_DIR: &str = "target/binostr-fixtures";
+
+#[derive(Debug, thiserror::Error)]
+pub enum FixtureError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Load error: {0}")]
+    Load(#[from] LoadError),
+
+    #[error("DannyPack error: {0}")]
+    DannyPack(#[from] DannyPackError),
+
+    #[error("Invalid fixture file: {0}")]
+    Invalid(&'static str),
+
+    #[error("Missing fixture section: {0}")]
+    MissingSection(String),
+}
+
+impl From<Truncated> for FixtureError {
+    fn from(_: Truncated) -> Self {
+        FixtureError::Invalid("header truncated")
+    }
+}
+
+/// Identifies one cached fixture: what was sampled, from which data, how
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FixtureKey {
+    /// Free-form label for what the fixture contains (e.g. "sample-1000")
+    pub label: String,
+    /// Hash of the source dataset (see [`dataset_hash`])
+    pub dataset_hash: u64,
+    /// RNG seed used for sampling
+    pub seed: u64,
+    /// Number of events loaded from the dataset before sampling
+    pub limit: usize,
+}
+
+impl FixtureKey {
+    /// Build a key for a data directory, hashing its current contents
+    pub fn for_directory<P: AsRef<Path>>(
+        data_dir: P,
+        label: &str,
+        seed: u64,
+        limit: usize,
+    ) -> Result<Self, FixtureError> {
+        Ok(Self {
+            label: label.to_string(),
+            dataset_hash: dataset_hash(data_dir)?,
+            seed,
+            limit,
+        })
+    }
+
+    /// Combined hash of every key field, stored in the file header
+    pub fn hash(&self) -> u64 {
+        let mut h = Fnv64::new();
+        h.write(self.label.as_bytes());
+        h.write(&self.dataset_hash.to_le_bytes());
+        h.write(&self.seed.to_le_bytes());
+        h.write(&(self.limit as u64).to_le_bytes());
+        h.finish()
+    }
+
+    /// File name of this fixture inside the cache directory
+    pub fn file_name(&self) -> String {
+        let label: String = self
+            .label
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        format!(
+            "{}-{:016x}-s{}-n{}.bnfx",
+            label, self.dataset_hash, self.seed, self.limit
+        )
+    }
+}
+
+/// Hash a dataset directory by the names, sizes and modification times of its
+/// .gz files. Cheap enough to run on every bench start; any change to the data
+/// files produces a different hash.
+pub fn dataset_hash<P: AsRef<Path>>(data_dir: P) -> Result<u64, FixtureError> {
+    let mut h = Fnv64::new();
+    for path in loader::list_data_files(data_dir)? {
+        let meta = fs::metadata(&path)?;
+        let modified = meta
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0);
+
+        if let Some(name) = path.file_name() {
+            h.write(name.to_string_lossy().as_bytes());
+        }
+        h.write(&meta.len().to_le_bytes());
+        h.write(&modified.to_le_bytes());
+    }
+    Ok(h.finish())
+}
+
+/// Write named event sections to an indexed fixture file
+pub fn write_fixture<P: AsRef<Path>>(
+    path: P,
+    key_hash: u64,
+    sections: &[(&str, &[NostrEvent])],
+) -> Result<(), FixtureError> {
+    let record_count: usize = sections.iter().map(|(_, events)| events.len()).sum();
+
+    let mut data = Vec::new();
+    let mut offsets = Vec::with_capacity(record_count + 1);
+    for (_, events) in sections {
+        for event in events.iter() {
+            offsets.push(data.len() as u64);
+            dannypack::serialize_into(event, &mut data);
+        }
+    }
+    offsets.push(data.len() as u64);
+
+    let mut buf = Vec::with_capacity(data.len() + offsets.len() * 8 + 256);
+    buf.extend_from_slice(MAGIC);
+    buf.push(VERSION);
+    buf.extend_from_slice(&[0u8; 3]);
+    buf.extend_from_slice(&key_hash.to_le_bytes());
+
+    buf.extend_from_slice(&(sections.len() as u32).to_le_bytes());
+    let mut first_record = 0u32;
+    for (name, events) in sections {
+        buf.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        buf.extend_from_slice(name.as_bytes());
+        buf.extend_from_slice(&first_record.to_le_bytes());
+        buf.extend_from_slice(&(events.len() as u32).to_le_bytes());
+        first_record += events.len() as u32;
+    }
+
+    buf.extend_from_slice(&(record_count as u32).to_le_bytes());
+    for offset in &offsets {
+        buf.extend_from_slice(&offset.to_le_bytes());
+    }
+    buf.extend_from_slice(&data);
+
+    // Write to a temporary file first so a crashed run never leaves a torn fixture
+    let path = path.as_ref();
+    let tmp = path.with_extension("tmp");
+    {
+        let mut file = File::create(&tmp)?;
+        file.write_all(&buf)?;
+        file.sync_all()?;
+    }
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+struct SectionEntry {
+    name: String,
+    first_record: usize,
+    record_count: usize,
+}
+
+/// A memory-mapped fixture file
+pub struct FixtureFile {
+    mmap: Mmap,
+    key_hash: u64,
+    sections: Vec<SectionEntry>,
+    record_count: usize,
+    offsets_start: usize,
+    data_start: usize,
+}
+
+impl FixtureFile {
+    /// Open and validate a fixture file
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, FixtureError> {
+        let file = File::open(path)?;
+        // SAFETY: fixture files are only written through a temp-file rename,
+        // never modified in place, so the mapping stays valid while we hold it.
+        let mmap = unsafe { Mmap::map(&file)? };
+
+        let mut reader = Reader::new(&mmap);
+        if reader.bytes(4)? != MAGIC {
+            return Err(FixtureError::Invalid("bad magic"));
+        }
+        if reader.byte()? != VERSION {
+            return Err(FixtureError::Invalid("unsupported version"));
+        }
+        reader.bytes(3)?;
+        let key_hash = u64::from_le_bytes(reader.array()?);
+
+        let section_count = u32::from_le_bytes(reader.array()?) as usize;
+        // Every entry takes at least 10 bytes, so a corrupt count cannot over-allocate
+        let mut sections = Vec::with_capacity(section_count.min(reader.remaining().len() / 10));
+        for _ in 0..section_count {
+            let name_len = u16::from_le_bytes(reader.array()?) as usize;
+            let name = std::str::from_utf8(reader.bytes(name_len)?)
+                .map_err(|_| FixtureError::Invalid("section name is not UTF-8"))?
+                .to_string();
+            let first_record = u32::from_le_bytes(reader.array()?) as usize;
+            let record_count = u32::from_le_bytes(reader.array()?) as usize;
+            sections.push(SectionEntry {
+                name,
+                first_record,
+                record_count,
+            });
+        }
+
+        let record_count = u32::from_le_bytes(reader.array()?) as usize;
+        let offsets_start = reader.position();
+        reader.bytes((record_count + 1) * 8)?;
+        let data_start = reader.position();
+
+        let fixture = Self {
+            mmap,
+            key_hash,
+            sections,
+            record_count,
+            offsets_start,
+            data_start,
+        };
+
+        if fixture
+            .sections
+            .iter()
+            .any(|s| s.first_record + s.record_count > record_count)
+        {
+            return Err(FixtureError::Invalid("section out of range"));
+        }
+        if fixture.data_start + fixture.offset(record_count) as usize > fixture.mmap.len() {
+            return Err(FixtureError::Invalid("data truncated"));
+        }
+
+        Ok(fixture)
+    }
+
+    /// Key hash recorded when the fixture was written
+    pub fn key_hash(&self) -> u64 {
+        self.key_hash
+    }
+
+    /// Names of the sections in file order
+    pub fn section_names(&self) -> impl Iterator<Item = &str> {
+        self.sections.iter().map(|s| s.name.as_str())
+    }
+
+    /// Total number of records across all sections
+    pub fn len(&self) -> usize {
+        self.record_count
+    }
+
+    /// Check if the file holds no records
+    pub fn is_empty(&self) -> bool {
+        self.record_count == 0
+    }
+
+    /// Raw DannyPack bytes of record `index` (global across sections)
+    pub fn record(&self, index: usize) -> Option<&[u8]> {
+        if index >= self.record_count {
+            return None;
+        }
+        let start = self.offset(index) as usize;
+        let end = self.offset(index + 1) as usize;
+        if start > end {
+            return None;
+        }
+        self.mmap
+            .get(self.data_start + start..self.data_start + end)
+    }
+
+    /// Look up a section by name
+    pub fn section(&self, name: &str) -> Option<FixtureSection<'_>> {
+        self.sections
+            .iter()
+            .find(|s| s.name == name)
+            .map(|entry| FixtureSection {
+                file: self,
+                first_record: entry.first_record,
+                record_count: entry.record_count,
+            })
+    }
+
+    /// Decode every event of a section
+    pub fn decode_section(&self, name: &str) -> Result<Vec<NostrEvent>, FixtureError> {
+        self.section(name)
+            .ok_or_else(|| FixtureError::MissingSection(name.to_string()))?
+            .decode_all()
+    }
+
+    fn offset(&self, index: usize) -> u64 {
+        let pos = self.offsets_start + index * 8;
+        u64::from_le_bytes(self.mmap[pos..pos + 8].try_into().unwrap())
+    }
+}
+
+/// A named run of records inside a [`FixtureFile`]
+pub struct FixtureSection<'a> {
+    file: &'a FixtureFile,
+    first_record: usize,
+    record_count: usize,
+}
+
+impl<'a> FixtureSection<'a> {
+    /// Number of events in the section
+    pub fn len(&self) -> usize {
+        self.record_count
+    }
+
+    /// Check if the section is empty
+    pub fn is_empty(&self) -> bool {
+        self.record_count == 0
+    }
+
+    /// Raw DannyPack bytes of the `index`-th event, borrowed from the mapping
+    pub fn raw(&self, index: usize) -> Option<&'a [u8]> {
+        if index >= self.record_count {
+            return None;
+        }
+        self.file.record(self.first_record + index)
+    }
+
+    /// Decode the `index`-th event
+    pub fn decode(&self, index: usize) -> Result<NostrEvent, FixtureError> {
+        let raw = self
+            .raw(index)
+            .ok_or(FixtureError::Invalid("record out of range"))?;
+        Ok(dannypack::deserialize(raw)?)
+    }
+
+    /// Decode every event in the section
+    pub fn decode_all(&self) -> Result<Vec<NostrEvent>, FixtureError> {
+        (0..self.record_count).map(|i| self.decode(i)).collect()
+    }
+}
+
+/// Directory of fixture files keyed by [`FixtureKey`]
+pub struct FixtureCache {
+    dir: PathBuf,
+}
+
+impl FixtureCache {
+    /// Use the given directory for fixture files
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Use `$BINOSTR_FIXTURE_DIR`, or `target/binostr-fixtures` when unset
+    pub fn from_env() -> Self {
+        match std::env::var_os(FIXTURE_DIR_ENV) {
+            Some(dir) => Self::new(dir),
+            None => Self::new(DEFAULT_FIXTURE_DIR),
+        }
+    }
+
+    /// Cache directory
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Path a fixture with this key is stored at
+    pub fn path_for(&self, key: &FixtureKey) -> PathBuf {
+        self.dir.join(key.file_name())
+    }
+
+    /// Open the fixture for `key`, building and writing it first if it is
+    /// missing or was written for a different key.
+    pub fn get_or_build<F>(&self, key: &FixtureKey, build: F) -> Result<FixtureFile, FixtureError>
+    where
+        F: FnOnce() -> Result<Vec<(String, Vec<NostrEvent>)>, FixtureError>,
+    {
+        let path = self.path_for(key);
+        if let Ok(file) = FixtureFile::open(&path) {
+            if file.key_hash() == key.hash() {
+                return Ok(file);
+            }
+        }
+
+        let sections = build()?;
+        let borrowed: Vec<(&str, &[NostrEvent])> = sections
+            .iter()
+            .map(|(name, events)| (name.as_str(), events.as_slice()))
+            .collect();
+
+        fs::create_dir_all(&self.dir)?;
+        write_fixture(&path, key.hash(), &borrowed)?;
+        FixtureFile::open(&path)
+    }
+
+    /// Cached events built from a seeded sampler over `limit` loaded events
+    ///
+    /// `label` must uniquely describe what `select` picks, since it is part of
+    /// the cache key.
+    pub fn events<P, F>(
+        &self,
+        data_dir: P,
+        label: &str,
+        seed: u64,
+        limit: usize,
+        select: F,
+    ) -> Result<Vec<NostrEvent>, FixtureError>
+    where
+        P: AsRef<Path>,
+        F: FnOnce(&mut EventSampler) -> Vec<NostrEvent>,
+    {
+        let data_dir = data_dir.as_ref();
+        let key = FixtureKey::for_directory(data_dir, label, seed, limit)?;
+        let file = self.get_or_build(&key, || {
+            let mut sampler = seeded_sampler(data_dir, seed, limit)?;
+            Ok(vec![(label.to_string(), select(&mut sampler))])
+        })?;
+        file.decode_section(label)
+    }
+
+    /// Cached random sample of `size` events from `limit` loaded events
+    pub fn sample<P: AsRef<Path>>(
+        &self,
+        data_dir: P,
+        seed: u64,
+        limit: usize,
+        size: usize,
+    ) -> Result<Vec<NostrEvent>, FixtureError> {
+        self.events(data_dir, &format!("sample-{}", size), seed, limit, |s| {
+            s.random_sample(size).into_iter().cloned().collect()
+        })
+    }
+
+    /// Cached [`BenchmarkSets`] built from `limit` loaded events
+    pub fn benchmark_sets<P: AsRef<Path>>(
+        &self,
+        data_dir: P,
+        seed: u64,
+        limit: usize,
+    ) -> Result<BenchmarkSets, FixtureError> {
+        let data_dir = data_dir.as_ref();
+        let key = FixtureKey::for_directory(data_dir, "benchmark-sets", seed, limit)?;
+        let file = self.get_or_build(&key, || {
+            let mut sampler = seeded_sampler(data_dir, seed, limit)?;
+            let sets = sampler.create_benchmark_sets();
+            Ok(sets
+                .named_sets()
+                .into_iter()
+                .map(|(name, events)| (name.to_string(), events.to_vec()))
+                .collect())
+        })?;
+
+        BenchmarkSets::from_named_sets(|name| file.decode_section(name))
+    }
+}
+
+fn seeded_sampler(data_dir: &Path, seed: u64, limit: usize) -> Result<EventSampler, FixtureError> {
+    let events = loader::load_limited_from_directory_parallel(
+        data_dir,
+        limit,
+        loader::default_load_threads(),
+    )?;
+    let mut sampler = EventSampler::with_seed(events, seed);
+    sampler.filter_excluded_kinds();
+    Ok(sampler)
+}
+
+/// 64-bit FNV-1a; stable across Rust releases, unlike `DefaultHasher`
+struct Fnv64(u64);
+
+impl Fnv64 {
+    fn new() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 ^= b as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_events(n: usize) -> Vec<NostrEvent> {
+        (0..n)
+            .map(|i| NostrEvent {
+                id: [i as u8; 32],
+                pubkey: [0xcd; 32],
+                created_at: 1700000000 + i as i64,
+                kind: (i % 3) as u16,
+                tags: vec![vec!["p".to_string(), hex::encode([0xab; 32])]],
+                content: format!("event {}", i),
+                sig: [0xef; 64],
+            })
+            .collect()
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("binostr-fixture-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_write_and_open() {
+        let dir = temp_dir("roundtrip");
+        let path = dir.join("test.bnfx");
+        let a = sample_events(5);
+        let b = sample_events(3);
+
+        write_fixture(&path, 7, &[("a", &a), ("b", &b), ("empty", &[])]).unwrap();
+        let file = FixtureFile::open(&path).unwrap();
+
+        assert_eq!(file.key_hash(), 7);
+        assert_eq!(file.len(), 8);
+        assert_eq!(
+            file.section_names().collect::<Vec<_>>(),
+            vec!["a", "b", "empty"]
+        );
+        assert_eq!(file.decode_section("a").unwrap(), a);
+        assert_eq!(file.decode_section("b").unwrap(), b);
+        assert!(file.section("empty").unwrap().is_empty());
+        assert_eq!(file.section("b").unwrap().decode(2).unwrap(), b[2]);
+        assert!(matches!(
+            file.decode_section("missing"),
+            Err(FixtureError::MissingSection(_))
+        ));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_cache_builds_once() {
+        let dir = temp_dir("cache");
+        let cache = FixtureCache::new(&dir);
+        let key = FixtureKey {
+            label: "sample".to_string(),
+            dataset_hash: 1,
+            seed: 42,
+            limit: 10,
+        };
+        let events = sample_events(4);
+
+        let mut builds = 0;
+        for _ in 0..2 {
+            let file = cache
+                .get_or_build(&key, || {
+                    builds += 1;
+                    Ok(vec![("sample".to_string(), events.clone())])
+                })
+                .unwrap();
+            assert_eq!(file.decode_section("sample").unwrap(), events);
+        }
+        assert_eq!(builds, 1);
+
+        // A different key must not reuse the file
+        let other = FixtureKey { seed: 43, ..key };
+        cache
+            .get_or_build(&other, || {
+                builds += 1;
+                Ok(vec![("sample".to_string(), Vec::new())])
+            })
+            .unwrap();
+        assert_eq!(builds, 2);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_rejects_corrupt_file() {
+        let dir = temp_dir("corrupt");
+        let path = dir.join("bad.bnfx");
+        fs::write(&path, b"BNFX\x01").unwrap();
+        assert!(matches!(
+            FixtureFile::open(&path),
+            Err(FixtureError::Invalid(_))
+        ));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
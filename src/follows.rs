@@ -0,0 +1,571 @@
+//! Follow-list tag blocks
+//!
+//! Kind 3 events (and other NIP-51 style lists) carry thousands of
+//! `["p", <pubkey>]` and `["p", <pubkey>, <relay>, <petname>]` tags. Stored
+//! tag by tag, every one repeats its name, arity and value headers, and the
+//! same handful of relay hints appear over and over. This module encodes a
+//! run of such tags as one block:
+//!
+//! ```text
+//! [flags: u8, bit 0 sorted, bit 1 shaped, bit 2 permuted] [count: varint]
+//! if shaped:
+//!   [relay_count: varint] [relays: (len: varint, UTF-8)...]
+//!   per tag: [shape: varint, relay code << 1 | has_petname] [petname if any]
+//! if permuted:
+//!   [sorted index per tag: bits for count - 1 each, packed MSB first]
+//! if sorted:
+//!   [shared prefix lengths: 4 bits each, capped at 15] [key suffixes...]
+//! else:
+//!   [keys: 32 bytes each]
+//! ```
+//!
+//! Relay code 0 means a two-value tag, otherwise an index (plus one) into
+//! the per-event relay dictionary. Blocks without relays or petnames omit
+//! the shape section. Sorted keys are stored with prefix compression; when
+//! the tags are not already in key order, each tag's index into the sorted
+//! keys restores the original order exactly. Whichever key layout is
+//! smallest is used. For random pubkeys the indices cost about what the
+//! prefixes save, so the permuted layout mostly pays off for lists whose
+//! keys share long prefixes.
+
+use std::ops::Range;
+
+use thiserror::Error;
+
+use crate::dannypack::{write_chunk, write_varint, Reader, Truncated};
+
+/// Shortest run of follow tags worth a block
+pub const MIN_RUN: usize = 8;
+
+const FLAG_SORTED: u8 = 0x01;
+const FLAG_SHAPED: u8 = 0x02;
+const FLAG_PERMUTED: u8 = 0x04;
+const MAX_SHARED: usize = 15;
+
+#[derive(Debug, Error)]
+pub enum FollowsError {
+    #[error("Follow block is truncated")]
+    Truncated,
+
+    #[error("Unknown follow block flags: {0:#04x}")]
+    UnknownFlags(u8),
+
+    #[error("Invalid relay code: {0}")]
+    InvalidRelay(u64),
+
+    #[error("Sorted key index out of range: {0}")]
+    InvalidIndex(usize),
+
+    #[error("UTF-8 error: {0}")]
+    Utf8(#[from] std::str::Utf8Error),
+}
+
+impl From<Truncated> for FollowsError {
+    fn from(_: Truncated) -> Self {
+        FollowsError::Truncated
+    }
+}
+
+/// One `p` tag decoded from a block
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FollowTag<'a> {
+    pub pubkey: [u8; 32],
+    pub relay: Option<&'a str>,
+    pub petname: Option<&'a str>,
+}
+
+impl FollowTag<'_> {
+    /// Number of values in the tag, including its name
+    pub fn len(&self) -> usize {
+        2 + self.relay.is_some() as usize + self.petname.is_some() as usize
+    }
+
+    /// Always false: a follow tag has at least a name and a pubkey
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+
+    /// The tag as stored in [`NostrEvent::tags`](crate::NostrEvent)
+    pub fn to_tag(&self) -> Vec<String> {
+        let mut tag = Vec::with_capacity(self.len());
+        tag.push("p".to_string());
+        tag.push(hex::encode(self.pubkey));
+        tag.extend(self.relay.map(str::to_string));
+        tag.extend(self.petname.map(str::to_string));
+        tag
+    }
+}
+
+/// Pubkey, relay hint and petname of a tag that fits in a block
+fn follow_parts(tag: &[String]) -> Option<([u8; 32], Option<&str>, Option<&str>)> {
+    if !(2..=4).contains(&tag.len()) || tag[0] != "p" || tag[1].len() != 64 {
+        return None;
+    }
+    // Lowercase only, so the hex round trip is exact
+    if !tag[1]
+        .bytes()
+        .all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+    {
+        return None;
+    }
+    let mut pubkey = [0u8; 32];
+    hex::decode_to_slice(&tag[1], &mut pubkey).ok()?;
+    Some((
+        pubkey,
+        tag.get(2).map(String::as_str),
+        tag.get(3).map(String::as_str),
+    ))
+}
+
+/// Find the longest run of follow tags and encode it
+///
+/// Returns the run's range in `tags` and the block, or `None` when no run
+/// reaches [`MIN_RUN`] tags.
+pub fn encode(tags: &[Vec<String>]) -> Option<(Range<usize>, Vec<u8>)> {
+    let mut best = 0..0;
+    let mut start = 0;
+    for (i, tag) in tags.iter().enumerate() {
+        if follow_parts(tag).is_none() {
+            start = i + 1;
+        } else if i + 1 - start > best.len() {
+            best = start..i + 1;
+        }
+    }
+    if best.len() < MIN_RUN {
+        return None;
+    }
+
+    let parts: Vec<_> = tags[best.clone()]
+        .iter()
+        .filter_map(|tag| follow_parts(tag))
+        .collect();
+    let shaped = parts.iter().any(|(_, relay, _)| relay.is_some());
+    let keys: Vec<[u8; 32]> = parts.iter().map(|(key, _, _)| *key).collect();
+
+    // Tag positions in key order, and each tag's index into that order
+    let mut order: Vec<usize> = (0..keys.len()).collect();
+    order.sort_by_key(|&i| keys[i]);
+    let sorted_keys: Vec<[u8; 32]> = order.iter().map(|&i| keys[i]).collect();
+    let in_order = order.iter().enumerate().all(|(k, &i)| keys[k] == keys[i]);
+    let mut indices = vec![0; keys.len()];
+    for (k, &i) in order.iter().enumerate() {
+        indices[i] = k;
+    }
+
+    let plain_len = keys.len() * 32;
+    let sorted_len = prefix_block_len(&sorted_keys);
+    let width = index_width(keys.len());
+    let (sorted, permuted) = if in_order {
+        (sorted_len < plain_len, false)
+    } else {
+        let permuted = (keys.len() * width).div_ceil(8) + sorted_len < plain_len;
+        (permuted, permuted)
+    };
+
+    let mut block = Vec::with_capacity(parts.len() * 34);
+    let mut flags = 0;
+    if sorted {
+        flags |= FLAG_SORTED;
+    }
+    if shaped {
+        flags |= FLAG_SHAPED;
+    }
+    if permuted {
+        flags |= FLAG_PERMUTED;
+    }
+    block.push(flags);
+    write_varint(&mut block, parts.len() as u64);
+
+    if shaped {
+        let mut relays: Vec<&str> = Vec::new();
+        for (_, relay, _) in &parts {
+            if let Some(relay) = relay {
+                if !relays.contains(relay) {
+                    relays.push(relay);
+                }
+            }
+        }
+        write_varint(&mut block, relays.len() as u64);
+        for relay in &relays {
+            write_chunk(&mut block, relay.as_bytes());
+        }
+        for (_, relay, petname) in &parts {
+            let code = relay.map_or(0, |r| relays.iter().position(|&x| x == r).unwrap() + 1);
+            write_varint(&mut block, ((code as u64) << 1) | petname.is_some() as u64);
+            if let Some(petname) = petname {
+                write_chunk(&mut block, petname.as_bytes());
+            }
+        }
+    }
+
+    if permuted {
+        write_indices(&mut block, &indices, width);
+    }
+
+    if sorted {
+        let mut prev = [0u8; 32];
+        let shared: Vec<usize> = sorted_keys
+            .iter()
+            .map(|key| {
+                let n = shared_prefix(&prev, key);
+                prev = *key;
+                n
+            })
+            .collect();
+        for pair in shared.chunks(2) {
+            block.push(((pair[0] as u8) << 4) | pair.get(1).map_or(0, |&n| n as u8));
+        }
+        for (key, &n) in sorted_keys.iter().zip(&shared) {
+            block.extend_from_slice(&key[n..]);
+        }
+    } else {
+        for key in &keys {
+            block.extend_from_slice(key);
+        }
+    }
+
+    Some((best, block))
+}
+
+/// Bits per sorted key index in a block of `count` tags
+fn index_width(count: usize) -> usize {
+    (usize::BITS - count.saturating_sub(1).leading_zeros()) as usize
+}
+
+fn write_indices(block: &mut Vec<u8>, indices: &[usize], width: usize) {
+    let start = block.len();
+    block.resize(start + (indices.len() * width).div_ceil(8), 0);
+    for (i, &index) in indices.iter().enumerate() {
+        for b in 0..width {
+            if (index >> (width - 1 - b)) & 1 != 0 {
+                let bit = i * width + b;
+                block[start + bit / 8] |= 0x80 >> (bit % 8);
+            }
+        }
+    }
+}
+
+fn read_index(indices: &[u8], i: usize, width: usize) -> usize {
+    (i * width..(i + 1) * width).fold(0, |value, bit| {
+        (value << 1) | ((indices[bit / 8] >> (7 - bit % 8)) & 1) as usize
+    })
+}
+
+/// Decode a block back into tags
+pub fn decode(block: &[u8]) -> Result<Vec<Vec<String>>, FollowsError> {
+    FollowTags::new(block)?
+        .map(|tag| tag.map(|tag| tag.to_tag()))
+        .collect()
+}
+
+/// Size of the sorted key section for `keys`, which are in sorted order
+fn prefix_block_len(keys: &[[u8; 32]]) -> usize {
+    let mut prev = [0u8; 32];
+    let suffixes: usize = keys
+        .iter()
+        .map(|key| {
+            let n = shared_prefix(&prev, key);
+            prev = *key;
+            32 - n
+        })
+        .sum();
+    keys.len().div_ceil(2) + suffixes
+}
+
+fn shared_prefix(a: &[u8; 32], b: &[u8; 32]) -> usize {
+    a.iter()
+        .zip(b)
+        .take(MAX_SHARED)
+        .take_while(|(x, y)| x == y)
+        .count()
+}
+
+/// Lazy iterator over the tags of a block
+///
+/// Relay hints and petnames borrow from the block; keys are rebuilt one at
+/// a time, except in permuted blocks, whose sorted keys are all rebuilt
+/// up front.
+#[derive(Debug, Clone)]
+pub struct FollowTags<'a> {
+    remaining: usize,
+    index: usize,
+    relays: Vec<&'a str>,
+    /// Reader at the next shape, if the block is shaped
+    shapes: Option<Reader<'a>>,
+    /// Reader at the next key (or suffix)
+    keys: Reader<'a>,
+    /// Shared prefix lengths, if the block is sorted
+    shared: Option<&'a [u8]>,
+    prev: [u8; 32],
+    permutation: Option<Permutation<'a>>,
+}
+
+/// Key order of a permuted block
+#[derive(Debug, Clone)]
+struct Permutation<'a> {
+    /// Sorted key index per tag, packed
+    indices: &'a [u8],
+    width: usize,
+    sorted_keys: Vec<[u8; 32]>,
+}
+
+impl<'a> FollowTags<'a> {
+    pub fn new(block: &'a [u8]) -> Result<Self, FollowsError> {
+        let mut reader = Reader::new(block);
+        let flags = reader.byte()?;
+        let permuted = flags & FLAG_PERMUTED != 0;
+        if flags & !(FLAG_SORTED | FLAG_SHAPED | FLAG_PERMUTED) != 0
+            || (permuted && flags & FLAG_SORTED == 0)
+        {
+            return Err(FollowsError::UnknownFlags(flags));
+        }
+        let count = reader.varint()? as usize;
+
+        let mut relays = Vec::new();
+        let mut shapes = None;
+        if flags & FLAG_SHAPED != 0 {
+            let relay_count = reader.varint()?;
+            for _ in 0..relay_count {
+                relays.push(std::str::from_utf8(reader.chunk()?)?);
+            }
+            shapes = Some(reader.clone());
+            // Skip the shapes to find the keys
+            for _ in 0..count {
+                if reader.varint()? & 1 != 0 {
+                    reader.chunk()?;
+                }
+            }
+        }
+
+        let mut indices = None;
+        if permuted {
+            let width = index_width(count);
+            let len = count
+                .checked_mul(width)
+                .ok_or(FollowsError::Truncated)?
+                .div_ceil(8);
+            indices = Some((reader.bytes(len)?, width));
+        }
+
+        let mut shared = None;
+        if flags & FLAG_SORTED != 0 {
+            shared = Some(reader.bytes(count.div_ceil(2))?);
+        }
+
+        let mut tags = Self {
+            remaining: count,
+            index: 0,
+            relays,
+            shapes,
+            keys: reader,
+            shared,
+            prev: [0u8; 32],
+            permutation: None,
+        };
+        if let Some((indices, width)) = indices {
+            let mut sorted_keys = Vec::with_capacity(count.min(block.len()));
+            for i in 0..count {
+                sorted_keys.push(tags.read_key(i)?);
+            }
+            tags.permutation = Some(Permutation {
+                indices,
+                width,
+                sorted_keys,
+            });
+        }
+        Ok(tags)
+    }
+
+    /// Read the `i`th stored key (or suffix), which follows key `i - 1`
+    fn read_key(&mut self, i: usize) -> Result<[u8; 32], FollowsError> {
+        let shared = match self.shared {
+            Some(lengths) => {
+                let byte = lengths[i / 2];
+                let n = if i.is_multiple_of(2) {
+                    byte >> 4
+                } else {
+                    byte & 0x0F
+                };
+                n as usize
+            }
+            None => 0,
+        };
+        let suffix = self.keys.bytes(32 - shared)?;
+        let mut key = self.prev;
+        key[shared..].copy_from_slice(suffix);
+        self.prev = key;
+        Ok(key)
+    }
+
+    fn next_tag(&mut self) -> Result<FollowTag<'a>, FollowsError> {
+        let (relay, petname) = match &mut self.shapes {
+            Some(shapes) => {
+                let shape = shapes.varint()?;
+                let relay = match shape >> 1 {
+                    0 if shape & 1 != 0 => return Err(FollowsError::InvalidRelay(0)),
+                    0 => None,
+                    code => Some(
+                        *self
+                            .relays
+                            .get(code as usize - 1)
+                            .ok_or(FollowsError::InvalidRelay(code))?,
+                    ),
+                };
+                let petname = if shape & 1 != 0 {
+                    Some(std::str::from_utf8(shapes.chunk()?)?)
+                } else {
+                    None
+                };
+                (relay, petname)
+            }
+            None => (None, None),
+        };
+
+        let pubkey = match &self.permutation {
+            Some(permutation) => {
+                let index = read_index(permutation.indices, self.index, permutation.width);
+                *permutation
+                    .sorted_keys
+                    .get(index)
+                    .ok_or(FollowsError::InvalidIndex(index))?
+            }
+            None => self.read_key(self.index)?,
+        };
+        self.index += 1;
+
+        Ok(FollowTag {
+            pubkey,
+            relay,
+            petname,
+        })
+    }
+}
+
+impl<'a> Iterator for FollowTags<'a> {
+    type Item = Result<FollowTag<'a>, FollowsError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        let tag = self.next_tag();
+        if tag.is_err() {
+            self.remaining = 0;
+        }
+        Some(tag)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sha2::{Digest, Sha256};
+
+    fn p(key: u8, extra: &[&str]) -> Vec<String> {
+        let mut tag = vec!["p".to_string(), hex::encode([key; 32])];
+        tag.extend(extra.iter().map(|s| s.to_string()));
+        tag
+    }
+
+    #[test]
+    fn test_roundtrip_shapes() {
+        let relay = "wss://relay.example.com";
+        let mut tags = vec![vec!["t".to_string(), "nostr".to_string()]];
+        for i in 0..20u8 {
+            tags.push(match i % 3 {
+                0 => p(i.wrapping_mul(37), &[]),
+                1 => p(i.wrapping_mul(37), &[relay]),
+                _ => p(i.wrapping_mul(37), &[relay, "alice"]),
+            });
+        }
+        tags.push(vec!["p".to_string(), "not hex".to_string()]);
+
+        let (range, block) = encode(&tags).unwrap();
+        assert_eq!(range, 1..21);
+        assert_eq!(block[0], FLAG_SHAPED);
+        assert_eq!(decode(&block).unwrap(), tags[range]);
+
+        let raw_size: usize = tags[1..21].iter().flatten().map(String::len).sum();
+        assert!(block.len() < raw_size / 2);
+    }
+
+    #[test]
+    fn test_sorted_prefix_compression() {
+        // Sorted keys sharing prefixes use the sorted layout
+        let tags: Vec<_> = (0..64u8)
+            .map(|i| {
+                let mut key = [0x42; 32];
+                key[2] = i;
+                vec!["p".to_string(), hex::encode(key)]
+            })
+            .collect();
+        let (range, block) = encode(&tags).unwrap();
+        assert_eq!(range, 0..64);
+        assert_eq!(block[0], FLAG_SORTED);
+        assert!(block.len() < 64 * 32 - 64);
+        assert_eq!(decode(&block).unwrap(), tags);
+
+        // The same keys out of order are stored sorted with their indices
+        let mut shuffled = tags.clone();
+        shuffled.swap(3, 40);
+        shuffled.reverse();
+        let (_, permuted) = encode(&shuffled).unwrap();
+        assert_eq!(permuted[0], FLAG_SORTED | FLAG_PERMUTED);
+        assert_eq!(permuted.len(), block.len() + 64 * 6 / 8);
+        assert_eq!(decode(&permuted).unwrap(), shuffled);
+
+        // Random keys share too little for the indices to pay off
+        let random: Vec<_> = (0..64u32)
+            .map(|i| {
+                let key: [u8; 32] = Sha256::digest(i.to_le_bytes()).into();
+                vec!["p".to_string(), hex::encode(key)]
+            })
+            .collect();
+        let (_, block) = encode(&random).unwrap();
+        assert_eq!(block[0], 0);
+        assert_eq!(decode(&block).unwrap(), random);
+    }
+
+    #[test]
+    fn test_indices() {
+        for count in [1, 2, 8, 9, 300] {
+            let width = index_width(count);
+            let indices: Vec<usize> = (0..count).rev().collect();
+            let mut packed = Vec::new();
+            write_indices(&mut packed, &indices, width);
+            assert_eq!(packed.len(), (count * width).div_ceil(8));
+            for (i, &index) in indices.iter().enumerate() {
+                assert_eq!(read_index(&packed, i, width), index);
+            }
+        }
+        assert_eq!(index_width(8), 3);
+        assert_eq!(index_width(9), 4);
+    }
+
+    #[test]
+    fn test_not_encoded() {
+        let short: Vec<_> = (0..MIN_RUN as u8 - 1).map(|i| p(i, &[])).collect();
+        assert!(encode(&short).is_none());
+
+        let upper = vec!["p".to_string(), hex::encode_upper([0xab; 32])];
+        assert!(encode(&vec![upper; MIN_RUN]).is_none());
+
+        assert!(matches!(
+            FollowTags::new(&[0x80, 0]),
+            Err(FollowsError::UnknownFlags(0x80))
+        ));
+        assert!(matches!(
+            FollowTags::new(&[FLAG_PERMUTED, 0]),
+            Err(FollowsError::UnknownFlags(FLAG_PERMUTED))
+        ));
+        assert!(matches!(
+            decode(&[0, 1, 0xaa]),
+            Err(FollowsError::Truncated)
+        ));
+    }
+}
@@ -4,10 +4,52 @@
 
 use crate::event::{NostrEvent, NostrEventJson};
 
+/// Everything in the serialized event but the field values
+const SKELETON: &str = r#"{"id":"","pubkey":"","created_at":,"kind":,"tags":,"content":,"sig":""}"#;
+
 /// Serialize a NostrEvent to JSON bytes
 pub fn serialize(event: &NostrEvent) -> Vec<u8> {
     let json_event = NostrEventJson::from(event);
-    serde_json::to_vec(&json_event).expect("JSON serialization should not fail")
+    let mut buf = Vec::with_capacity(encoded_len(event));
+    serde_json::to_writer(&mut buf, &json_event).expect("JSON serialization should not fail");
+    buf
+}
+
+/// Exact length of [`serialize`] output, computed without serializing
+pub fn encoded_len(event: &NostrEvent) -> usize {
+    let tags: usize = event
+        .tags
+        .iter()
+        .map(|tag| {
+            2 + tag.len().saturating_sub(1) + tag.iter().map(|v| string_len(v)).sum::<usize>()
+        })
+        .sum();
+
+    SKELETON.len()
+        + 2 * (event.id.len() + event.pubkey.len() + event.sig.len())
+        + integer_len(event.created_at)
+        + integer_len(event.kind.into())
+        + 2
+        + event.tags.len().saturating_sub(1)
+        + tags
+        + string_len(&event.content)
+}
+
+/// Length of a JSON string literal as serde_json escapes it
+fn string_len(value: &str) -> usize {
+    2 + value
+        .bytes()
+        .map(|b| match b {
+            b'"' | b'\\' | b'\x08' | b'\t' | b'\n' | b'\x0c' | b'\r' => 2,
+            0x00..=0x1f => 6,
+            _ => 1,
+        })
+        .sum::<usize>()
+}
+
+fn integer_len(value: i64) -> usize {
+    let digits = value.unsigned_abs().checked_ilog10().unwrap_or(0) as usize + 1;
+    digits + usize::from(value < 0)
 }
 
 /// Serialize a NostrEvent to a JSON string
@@ -39,7 +81,10 @@ pub fn deserialize_str(data: &str) -> Result<NostrEvent, JsonError> {
 /// Serialize a batch of events to JSON array
 pub fn serialize_batch(events: &[NostrEvent]) -> Vec<u8> {
     let json_events: Vec<NostrEventJson> = events.iter().map(NostrEventJson::from).collect();
-    serde_json::to_vec(&json_events).expect("JSON serialization should not fail")
+    let len = 2 + events.len().saturating_sub(1) + events.iter().map(encoded_len).sum::<usize>();
+    let mut buf = Vec::with_capacity(len);
+    serde_json::to_writer(&mut buf, &json_events).expect("JSON serialization should not fail");
+    buf
 }
 
 /// Deserialize a batch of events from JSON array
@@ -88,6 +133,24 @@ mod tests {
         assert_eq!(event, back);
     }
 
+    #[test]
+    fn test_encoded_len() {
+        let mut event = sample_event();
+        assert_eq!(encoded_len(&event), serialize(&event).len());
+
+        event.created_at = -42;
+        event.kind = 0;
+        event.content = "\"quoted\" \\ tab\t nul\0 bell\u{7} del\u{7f} ünïcödé 🤙".to_string();
+        event.tags.push(vec![]);
+        event
+            .tags
+            .push(vec!["line\nbreak".to_string(), String::new()]);
+        assert_eq!(encoded_len(&event), serialize(&event).len());
+
+        event.tags.clear();
+        assert_eq!(encoded_len(&event), serialize(&event).len());
+    }
+
     #[test]
     fn test_batch_roundtrip() {
         let events = vec![sample_event(), sample_event()];
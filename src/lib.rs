@@ -3,20 +3,42 @@
 //! This library provides tools for benchmarking different serialization
 //! formats for Nostr events: JSON, CBOR, Protocol Buffers, Cap'n Proto, and DannyPack.
 
+pub mod archive;
+pub mod auto;
 pub mod capnp;
 pub mod cbor;
+#[cfg(feature = "codec")]
+pub mod codec;
+pub mod compact;
 pub mod dannypack;
+pub mod dictionary;
+pub mod diff;
+pub mod embed;
+pub mod envelope;
 pub mod event;
+pub mod filter;
+pub mod fixture;
+pub mod follows;
 pub mod json;
 pub mod loader;
+pub mod loopback;
+pub mod message;
+pub mod negotiate;
+pub mod nip19;
 pub mod notepack;
+pub mod pipeline;
+pub mod profile;
 pub mod proto;
 pub mod sampler;
 pub mod stats;
+pub mod store;
+pub mod tags;
+pub mod writer;
 
 pub use event::NostrEvent;
 pub use loader::EventLoader;
 pub use sampler::{EventSampler, EXCLUDED_KINDS};
+pub use writer::EventWriter;
 
 // Re-export generated protobuf types
 pub mod proto_gen {
@@ -26,4 +48,7 @@ pub mod proto_gen {
     pub mod nostr_binary {
         include!(concat!(env!("OUT_DIR"), "/nostr_binary.rs"));
     }
+    pub mod nostr_message {
+        include!(concat!(env!("OUT_DIR"), "/nostr_message.rs"));
+    }
 }
@@ -4,15 +4,21 @@
 
 use std::fs::File;
 use std::io::{BufReader, Read};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
 
-use flate2::read::GzDecoder;
+use flate2::read::MultiGzDecoder;
 use prost::Message;
 use thiserror::Error;
 
 use crate::event::NostrEvent;
 use crate::proto_gen::nostr::ProtoEvent;
 
+/// Largest record accepted, far above any real event; a longer length
+/// prefix means the stream is corrupt
+pub const MAX_RECORD_LEN: usize = 16 * 1024 * 1024;
+
 #[derive(Error, Debug)]
 pub enum LoadError {
     #[error("IO error: {0}")]
@@ -28,35 +34,149 @@ pub enum LoadError {
     InvalidData(String),
 }
 
+/// How the loader reacts to a record it cannot decode
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LoadMode {
+    /// Return an error on the first invalid record (default)
+    #[default]
+    Strict,
+    /// Skip invalid records and record them in the [`LoadReport`]
+    Lenient,
+}
+
+/// A record skipped by a lenient load
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkippedRecord {
+    /// Byte offset of the record's length prefix in the decompressed stream
+    pub offset: u64,
+    /// Why the record was skipped
+    pub reason: String,
+}
+
+/// Summary of a load: how many events were decoded and which records were skipped
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LoadReport {
+    pub loaded: usize,
+    pub skipped: Vec<SkippedRecord>,
+}
+
+impl LoadReport {
+    /// Number of records that were skipped
+    pub fn skipped_count(&self) -> usize {
+        self.skipped.len()
+    }
+
+    /// Check if every record decoded cleanly
+    pub fn is_clean(&self) -> bool {
+        self.skipped.is_empty()
+    }
+
+    /// Fold another report into this one (e.g. when loading several files)
+    pub fn merge(&mut self, other: LoadReport) {
+        self.loaded += other.loaded;
+        self.skipped.extend(other.skipped);
+    }
+}
+
 /// Loader for .pb.gz event files
 pub struct EventLoader {
-    reader: BufReader<GzDecoder<File>>,
+    reader: BufReader<MultiGzDecoder<File>>,
     buffer: Vec<u8>,
+    mode: LoadMode,
+    position: u64,
+    report: LoadReport,
 }
 
 impl EventLoader {
     /// Open a .pb.gz file for reading
     pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, LoadError> {
         let file = File::open(path)?;
-        let decoder = GzDecoder::new(file);
+        // Files appended to in several gzip members (see ShardedWriter) read
+        // as one stream
+        let decoder = MultiGzDecoder::new(file);
         let reader = BufReader::with_capacity(1024 * 1024, decoder); // 1MB buffer
 
         Ok(Self {
             reader,
             buffer: Vec::with_capacity(64 * 1024), // 64KB initial capacity
+            mode: LoadMode::Strict,
+            position: 0,
+            report: LoadReport::default(),
         })
     }
 
+    /// Open a .pb.gz file in lenient mode, skipping invalid records
+    pub fn open_lenient<P: AsRef<Path>>(path: P) -> Result<Self, LoadError> {
+        Ok(Self::open(path)?.with_mode(LoadMode::Lenient))
+    }
+
+    /// Set the load mode
+    pub fn with_mode(mut self, mode: LoadMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Get the load mode
+    pub fn mode(&self) -> LoadMode {
+        self.mode
+    }
+
+    /// Get the report of everything loaded and skipped so far
+    pub fn report(&self) -> &LoadReport {
+        &self.report
+    }
+
     /// Read the next event from the file
     ///
-    /// Returns None when EOF is reached
+    /// Returns None when EOF is reached. In lenient mode, invalid records are
+    /// skipped and recorded in the report instead of returning an error.
     pub fn next_event(&mut self) -> Result<Option<NostrEvent>, LoadError> {
+        loop {
+            let offset = self.position;
+            match self.next_record() {
+                Ok(Some(event)) => {
+                    self.report.loaded += 1;
+                    return Ok(Some(event));
+                }
+                Ok(None) => return Ok(None),
+                Err(RecordError::Fatal(e)) => return Err(e),
+                Err(RecordError::Skip(e)) if self.mode == LoadMode::Strict => return Err(e),
+                Err(RecordError::Skip(e)) => {
+                    self.report.skipped.push(SkippedRecord {
+                        offset,
+                        reason: e.to_string(),
+                    });
+                }
+                Err(RecordError::Truncated(e)) if self.mode == LoadMode::Strict => return Err(e),
+                Err(RecordError::Truncated(e)) => {
+                    // Nothing can follow a truncated record, so record it and stop
+                    self.report.skipped.push(SkippedRecord {
+                        offset,
+                        reason: e.to_string(),
+                    });
+                    return Ok(None);
+                }
+            }
+        }
+    }
+
+    /// Read and decode a single length-prefixed record
+    fn next_record(&mut self) -> Result<Option<NostrEvent>, RecordError> {
         // Read varint length prefix
         let len = match self.read_varint() {
-            Ok(Some(len)) => len as usize,
+            Ok(Some(len)) => len,
             Ok(None) => return Ok(None), // EOF
             Err(e) => return Err(e),
         };
+        if len > MAX_RECORD_LEN as u64 {
+            // Don't allocate for a corrupt prefix; the stream is no longer
+            // aligned to record boundaries either
+            return Err(RecordError::Truncated(LoadError::InvalidData(format!(
+                "Record length {} exceeds {} bytes",
+                len, MAX_RECORD_LEN
+            ))));
+        }
+        let len = len as usize;
 
         // Resize buffer if needed
         if self.buffer.len() < len {
@@ -64,34 +184,46 @@ impl EventLoader {
         }
 
         // Read the message bytes
-        self.reader.read_exact(&mut self.buffer[..len])?;
+        match self.reader.read_exact(&mut self.buffer[..len]) {
+            Ok(()) => self.position += len as u64,
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                return Err(RecordError::Truncated(LoadError::InvalidData(format!(
+                    "Truncated record: expected {} bytes",
+                    len
+                ))));
+            }
+            Err(e) => return Err(RecordError::Fatal(e.into())),
+        }
 
         // Decode protobuf
-        let proto_event = ProtoEvent::decode(&self.buffer[..len])?;
+        let proto_event =
+            ProtoEvent::decode(&self.buffer[..len]).map_err(|e| RecordError::Skip(e.into()))?;
 
         // Convert to NostrEvent
-        let event = proto_to_event(proto_event)?;
+        let event = proto_to_event(proto_event).map_err(RecordError::Skip)?;
 
         Ok(Some(event))
     }
 
     /// Read a varint from the stream
-    fn read_varint(&mut self) -> Result<Option<u64>, LoadError> {
+    fn read_varint(&mut self) -> Result<Option<u64>, RecordError> {
         let mut result: u64 = 0;
         let mut shift = 0;
         let mut byte = [0u8; 1];
 
         loop {
             match self.reader.read_exact(&mut byte) {
-                Ok(()) => {}
+                Ok(()) => self.position += 1,
                 Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
                     if shift == 0 {
                         return Ok(None); // Clean EOF
                     } else {
-                        return Err(LoadError::InvalidData("Truncated varint".to_string()));
+                        return Err(RecordError::Truncated(LoadError::InvalidData(
+                            "Truncated varint".to_string(),
+                        )));
                     }
                 }
-                Err(e) => return Err(e.into()),
+                Err(e) => return Err(RecordError::Fatal(e.into())),
             }
 
             result |= ((byte[0] & 0x7F) as u64) << shift;
@@ -102,7 +234,10 @@ impl EventLoader {
 
             shift += 7;
             if shift >= 64 {
-                return Err(LoadError::InvalidData("Varint too long".to_string()));
+                // The stream is no longer aligned to record boundaries
+                return Err(RecordError::Truncated(LoadError::InvalidData(
+                    "Varint too long".to_string(),
+                )));
             }
         }
 
@@ -118,6 +253,15 @@ impl EventLoader {
         Ok(events)
     }
 
+    /// Load all events and return them together with the load report
+    pub fn load_all_with_report(mut self) -> Result<(Vec<NostrEvent>, LoadReport), LoadError> {
+        let mut events = Vec::new();
+        while let Some(event) = self.next_event()? {
+            events.push(event);
+        }
+        Ok((events, self.report))
+    }
+
     /// Load up to `limit` events from the file
     pub fn load_limited(mut self, limit: usize) -> Result<Vec<NostrEvent>, LoadError> {
         let mut events = Vec::with_capacity(limit);
@@ -131,6 +275,16 @@ impl EventLoader {
     }
 }
 
+/// Per-record failure, classified by whether the loader can continue past it
+enum RecordError {
+    /// The record is invalid but the stream is still aligned
+    Skip(LoadError),
+    /// The stream ended mid-record or lost alignment
+    Truncated(LoadError),
+    /// An I/O failure unrelated to the data itself
+    Fatal(LoadError),
+}
+
 impl Iterator for EventLoader {
     type Item = Result<NostrEvent, LoadError>;
 
@@ -159,13 +313,18 @@ fn proto_to_event(proto: ProtoEvent) -> Result<NostrEvent, LoadError> {
         .try_into()
         .map_err(|_| LoadError::InvalidData("Invalid sig length".to_string()))?;
 
+    let kind: u16 = proto
+        .kind
+        .try_into()
+        .map_err(|_| LoadError::InvalidData(format!("Kind out of range: {}", proto.kind)))?;
+
     let tags: Vec<Vec<String>> = proto.tags.into_iter().map(|t| t.values).collect();
 
     Ok(NostrEvent {
         id,
         pubkey,
         created_at: proto.created_at,
-        kind: proto.kind as u16,
+        kind,
         tags,
         content: proto.content,
         sig,
@@ -176,14 +335,9 @@ fn proto_to_event(proto: ProtoEvent) -> Result<NostrEvent, LoadError> {
 pub fn load_from_directory<P: AsRef<Path>>(dir: P) -> Result<Vec<NostrEvent>, LoadError> {
     let mut events = Vec::new();
 
-    for entry in std::fs::read_dir(dir)? {
-        let entry = entry?;
-        let path = entry.path();
-
-        if path.extension().is_some_and(|ext| ext == "gz") {
-            let loader = EventLoader::open(&path)?;
-            events.extend(loader.load_all()?);
-        }
+    for path in list_data_files(dir)? {
+        let loader = EventLoader::open(&path)?;
+        events.extend(loader.load_all()?);
     }
 
     Ok(events)
@@ -195,13 +349,10 @@ pub fn load_limited_from_directory<P: AsRef<Path>>(
     limit: usize,
 ) -> Result<Vec<NostrEvent>, LoadError> {
     let mut events = Vec::with_capacity(limit);
-    let mut files: Vec<_> = std::fs::read_dir(&dir)?
-        .filter_map(|e| e.ok())
-        .filter(|e| e.path().extension().is_some_and(|ext| ext == "gz"))
-        .map(|e| e.path())
-        .collect();
-
-    files.sort();
+    let files = list_data_files(dir)?;
+    if files.is_empty() {
+        return Ok(events);
+    }
 
     let per_file = (limit / files.len()).max(1);
 
@@ -220,9 +371,165 @@ pub fn load_limited_from_directory<P: AsRef<Path>>(
     Ok(events)
 }
 
+/// List the .gz data files in a directory, sorted by path
+pub fn list_data_files<P: AsRef<Path>>(dir: P) -> Result<Vec<PathBuf>, LoadError> {
+    let mut files: Vec<_> = std::fs::read_dir(&dir)?
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().extension().is_some_and(|ext| ext == "gz"))
+        .map(|e| e.path())
+        .collect();
+
+    files.sort();
+    Ok(files)
+}
+
+/// Number of loader threads to use when none is given
+pub fn default_load_threads() -> usize {
+    std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(4)
+}
+
+/// Load events from multiple .pb.gz files, decoding files concurrently
+///
+/// Files are processed in sorted path order and results are concatenated in
+/// that order, so the output does not depend on thread scheduling.
+/// `threads` bounds the number of files decoded at once.
+pub fn load_from_directory_parallel<P: AsRef<Path>>(
+    dir: P,
+    threads: usize,
+) -> Result<Vec<NostrEvent>, LoadError> {
+    let files = list_data_files(dir)?;
+    let per_file =
+        load_files_parallel(&files, threads, |path| EventLoader::open(path)?.load_all())?;
+    Ok(per_file.into_iter().flatten().collect())
+}
+
+/// Parallel version of [`load_limited_from_directory`]
+///
+/// Each file contributes at most `limit / file_count` events (minimum 1), in
+/// sorted file order, and the result is cut at `limit`. This yields exactly
+/// the same events in the same order as the sequential round-robin loader.
+pub fn load_limited_from_directory_parallel<P: AsRef<Path>>(
+    dir: P,
+    limit: usize,
+    threads: usize,
+) -> Result<Vec<NostrEvent>, LoadError> {
+    let files = list_data_files(dir)?;
+    if files.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let per_file = (limit / files.len()).max(1);
+
+    let loaded = load_files_parallel(&files, threads, |path| {
+        EventLoader::open(path)?.load_limited(per_file)
+    })?;
+
+    let mut events = Vec::with_capacity(limit);
+    for file_events in loaded {
+        let remaining = limit - events.len();
+        events.extend(file_events.into_iter().take(remaining));
+        if events.len() >= limit {
+            break;
+        }
+    }
+
+    Ok(events)
+}
+
+type FileResult = Result<Vec<NostrEvent>, LoadError>;
+
+/// Run `load` over every file on a bounded pool of scoped threads
+///
+/// Workers pull the next file index from a shared counter; results are
+/// stored by index so they come back in input order.
+fn load_files_parallel<F>(
+    files: &[PathBuf],
+    threads: usize,
+    load: F,
+) -> Result<Vec<Vec<NostrEvent>>, LoadError>
+where
+    F: Fn(&Path) -> Result<Vec<NostrEvent>, LoadError> + Sync,
+{
+    let workers = threads.max(1).min(files.len());
+    let next = AtomicUsize::new(0);
+    let results: Vec<Mutex<Option<FileResult>>> = files.iter().map(|_| Mutex::new(None)).collect();
+
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                if i >= files.len() {
+                    break;
+                }
+                let result = load(&files[i]);
+                *results[i].lock().unwrap() = Some(result);
+            });
+        }
+    });
+
+    results
+        .into_iter()
+        .map(|slot| {
+            slot.into_inner()
+                .unwrap()
+                .expect("every file index is claimed by a worker")
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::proto_gen::nostr::Tag;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    fn proto_event(id: &str, kind: i32) -> ProtoEvent {
+        ProtoEvent {
+            id: id.to_string(),
+            pubkey: "cd".repeat(32),
+            created_at: 1700000000,
+            kind,
+            tags: vec![Tag {
+                values: vec!["t".to_string(), "nostr".to_string()],
+            }],
+            content: "hello".to_string(),
+            sig: "ef".repeat(64),
+        }
+    }
+
+    fn push_record(raw: &mut Vec<u8>, proto: &ProtoEvent) {
+        let buf = proto.encode_to_vec();
+        prost::encoding::encode_varint(buf.len() as u64, raw);
+        raw.extend_from_slice(&buf);
+    }
+
+    /// Write a .pb.gz file with two valid records around three corrupt ones
+    fn write_corrupt_file(name: &str) -> (std::path::PathBuf, Vec<u64>) {
+        let mut raw = Vec::new();
+        let mut offsets = Vec::new();
+
+        push_record(&mut raw, &proto_event(&"ab".repeat(32), 1));
+        offsets.push(raw.len() as u64);
+        push_record(&mut raw, &proto_event(&"zz".repeat(32), 1)); // bad hex
+        offsets.push(raw.len() as u64);
+        push_record(&mut raw, &proto_event(&"ab".repeat(16), 1)); // short id
+        offsets.push(raw.len() as u64);
+        push_record(&mut raw, &proto_event(&"ab".repeat(32), 70000)); // kind > u16
+        push_record(&mut raw, &proto_event(&"ab".repeat(32), 7));
+        offsets.push(raw.len() as u64);
+        raw.push(0x80); // truncated varint
+
+        let path =
+            std::env::temp_dir().join(format!("binostr-{}-{}.pb.gz", name, std::process::id()));
+        let mut encoder = GzEncoder::new(File::create(&path).unwrap(), Compression::fast());
+        encoder.write_all(&raw).unwrap();
+        encoder.finish().unwrap();
+        (path, offsets)
+    }
 
     #[test]
     fn test_load_events() {
@@ -236,4 +543,104 @@ mod tests {
             assert!(!event.sig_hex().is_empty());
         }
     }
+
+    #[test]
+    fn test_strict_mode_stops_on_corrupt_record() {
+        let (path, _) = write_corrupt_file("strict");
+        let mut loader = EventLoader::open(&path).unwrap();
+        assert_eq!(loader.mode(), LoadMode::Strict);
+        assert!(loader.next_event().unwrap().is_some());
+        assert!(matches!(loader.next_event(), Err(LoadError::Hex(_))));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_lenient_mode_skips_and_reports() {
+        let (path, offsets) = write_corrupt_file("lenient");
+        let loader = EventLoader::open_lenient(&path).unwrap();
+        let (events, report) = loader.load_all_with_report().unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[1].kind, 7);
+        assert_eq!(report.loaded, 2);
+        assert_eq!(report.skipped_count(), 4);
+
+        let skipped_offsets: Vec<u64> = report.skipped.iter().map(|s| s.offset).collect();
+        assert_eq!(skipped_offsets, offsets);
+        assert!(report.skipped[1].reason.contains("id length"));
+        assert!(report.skipped[2].reason.contains("Kind out of range"));
+        assert!(report.skipped[3].reason.contains("Truncated varint"));
+    }
+
+    #[test]
+    fn test_oversized_record_length() {
+        let mut raw = Vec::new();
+        push_record(&mut raw, &proto_event(&"ab".repeat(32), 1));
+        let offset = raw.len() as u64;
+        prost::encoding::encode_varint(1 << 62, &mut raw);
+        push_record(&mut raw, &proto_event(&"ab".repeat(32), 7));
+
+        let path =
+            std::env::temp_dir().join(format!("binostr-oversized-{}.pb.gz", std::process::id()));
+        let mut encoder = GzEncoder::new(File::create(&path).unwrap(), Compression::fast());
+        encoder.write_all(&raw).unwrap();
+        encoder.finish().unwrap();
+
+        let mut strict = EventLoader::open(&path).unwrap();
+        assert!(strict.next_event().unwrap().is_some());
+        assert!(matches!(
+            strict.next_event(),
+            Err(LoadError::InvalidData(reason)) if reason.contains("exceeds")
+        ));
+
+        let (events, report) = EventLoader::open_lenient(&path)
+            .unwrap()
+            .load_all_with_report()
+            .unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(report.skipped.len(), 1);
+        assert_eq!(report.skipped[0].offset, offset);
+        assert!(report.skipped[0].reason.contains("exceeds"));
+    }
+
+    fn write_events_file(dir: &Path, name: &str, count: usize) {
+        let mut raw = Vec::new();
+        for i in 0..count {
+            let mut proto = proto_event(&"ab".repeat(32), 1);
+            proto.content = format!("{} #{}", name, i);
+            push_record(&mut raw, &proto);
+        }
+        let mut encoder = GzEncoder::new(
+            File::create(dir.join(format!("{}.pb.gz", name))).unwrap(),
+            Compression::fast(),
+        );
+        encoder.write_all(&raw).unwrap();
+        encoder.finish().unwrap();
+    }
+
+    #[test]
+    fn test_parallel_matches_sequential() {
+        let dir = std::env::temp_dir().join(format!("binostr-parallel-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        write_events_file(&dir, "2025_09_01", 5);
+        write_events_file(&dir, "2025_09_02", 2);
+        write_events_file(&dir, "2025_09_03", 7);
+
+        let all = load_from_directory(&dir).unwrap();
+        assert_eq!(all, load_from_directory_parallel(&dir, 2).unwrap());
+        assert_eq!(all.len(), 14);
+
+        for limit in [1, 2, 9, 14, 100] {
+            let sequential = load_limited_from_directory(&dir, limit).unwrap();
+            for threads in [1, 3, 8] {
+                let parallel = load_limited_from_directory_parallel(&dir, limit, threads).unwrap();
+                assert_eq!(sequential, parallel, "limit={} threads={}", limit, threads);
+            }
+        }
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
 }
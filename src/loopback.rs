@@ -0,0 +1,406 @@
+//! Loopback TCP relay stand-in for end-to-end throughput
+//!
+//! Microbenchmarks of `serialize`/`deserialize` leave out framing, syscalls
+//! and buffering. This module runs a minimal "relay" on `127.0.0.1` that
+//! streams a pre-encoded event set to a client over a real socket, and a
+//! client that reads, decompresses and fully decodes every event.
+//!
+//! Frames are `[len: u32 LE][payload]`. Compression is either applied to
+//! each payload on its own ([`WireCompression::PerMessage`], like
+//! permessage-deflate without context takeover) or to the whole connection
+//! ([`WireCompression::Streaming`], one context shared by all messages and
+//! flushed once at the end: the best case for compression ratio).
+
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+
+use crate::envelope::Compression;
+use crate::event::NostrEvent;
+use crate::stats::{self, Format, FormatError};
+
+/// zstd level used on the wire
+const ZSTD_LEVEL: i32 = 3;
+
+/// Socket buffer size on both ends
+const BUFFER_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, thiserror::Error)]
+pub enum LoopbackError {
+    #[error("IO error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("Decode error: {0}")]
+    Format(#[from] FormatError),
+
+    #[error("Relay thread panicked")]
+    RelayPanicked,
+
+    #[error("Expected {expected} events, received {received}")]
+    CountMismatch { expected: usize, received: usize },
+}
+
+/// How the relay compresses the stream
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WireCompression {
+    #[default]
+    None,
+    /// Each frame payload compressed independently
+    PerMessage(Compression),
+    /// The whole framed stream compressed with one shared context
+    Streaming(Compression),
+}
+
+impl WireCompression {
+    pub fn name(&self) -> &'static str {
+        match self {
+            WireCompression::None => "none",
+            WireCompression::PerMessage(Compression::None) => "none",
+            WireCompression::PerMessage(Compression::Gzip) => "msg-gzip",
+            WireCompression::PerMessage(Compression::Zstd) => "msg-zstd",
+            WireCompression::Streaming(Compression::None) => "none",
+            WireCompression::Streaming(Compression::Gzip) => "stream-gzip",
+            WireCompression::Streaming(Compression::Zstd) => "stream-zstd",
+        }
+    }
+}
+
+/// Result of one loopback run
+#[derive(Debug, Clone)]
+pub struct LoopbackReport {
+    pub format: Format,
+    pub compression: WireCompression,
+    pub events: usize,
+    /// Encoded payload bytes before framing and compression
+    pub payload_bytes: u64,
+    /// Bytes that crossed the socket
+    pub wire_bytes: u64,
+    /// Wall time from connect until the last event was decoded
+    pub elapsed: Duration,
+    /// CPU time of the client thread (read, decompress, decode)
+    pub client_cpu: Option<Duration>,
+}
+
+impl LoopbackReport {
+    pub fn events_per_sec(&self) -> f64 {
+        self.events as f64 / self.elapsed.as_secs_f64()
+    }
+
+    pub fn wire_bytes_per_event(&self) -> f64 {
+        self.wire_bytes as f64 / self.events.max(1) as f64
+    }
+
+    /// Client CPU time per event, in microseconds
+    pub fn client_cpu_per_event_us(&self) -> Option<f64> {
+        self.client_cpu
+            .map(|cpu| cpu.as_secs_f64() * 1e6 / self.events.max(1) as f64)
+    }
+}
+
+/// A relay stand-in serving one connection on an ephemeral loopback port
+pub struct LoopbackRelay {
+    addr: std::net::SocketAddr,
+    handle: JoinHandle<io::Result<u64>>,
+}
+
+impl LoopbackRelay {
+    /// Bind to `127.0.0.1:0` and serve `payloads` to the first client
+    pub fn start(payloads: Vec<Vec<u8>>, compression: WireCompression) -> io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?;
+
+        let handle = thread::spawn(move || {
+            let (stream, _) = listener.accept()?;
+            stream.set_nodelay(true)?;
+            serve(stream, &payloads, compression)
+        });
+
+        Ok(Self { addr, handle })
+    }
+
+    pub fn addr(&self) -> std::net::SocketAddr {
+        self.addr
+    }
+
+    /// Wait for the relay to finish; returns bytes written to the socket
+    pub fn join(self) -> Result<u64, LoopbackError> {
+        Ok(self
+            .handle
+            .join()
+            .map_err(|_| LoopbackError::RelayPanicked)??)
+    }
+}
+
+/// Stream `events` through a loopback relay in `format` and decode them
+pub fn run(
+    events: &[NostrEvent],
+    format: Format,
+    compression: WireCompression,
+) -> Result<LoopbackReport, LoopbackError> {
+    // The relay serves stored events, so encoding is not part of the run
+    let payloads: Vec<Vec<u8>> = events.iter().map(|e| stats::serialize(e, format)).collect();
+    let payload_bytes = payloads.iter().map(|p| p.len() as u64).sum();
+
+    let relay = LoopbackRelay::start(payloads, compression)?;
+
+    let start = Instant::now();
+    let cpu_start = thread_cpu_time();
+    let stream = TcpStream::connect(relay.addr())?;
+    let received = receive(stream, format, compression)?;
+    let client_cpu = thread_cpu_time()
+        .zip(cpu_start)
+        .map(|(end, start)| end.saturating_sub(start));
+    let elapsed = start.elapsed();
+
+    let wire_bytes = relay.join()?;
+    if received != events.len() {
+        return Err(LoopbackError::CountMismatch {
+            expected: events.len(),
+            received,
+        });
+    }
+
+    Ok(LoopbackReport {
+        format,
+        compression,
+        events: received,
+        payload_bytes,
+        wire_bytes,
+        elapsed,
+        client_cpu,
+    })
+}
+
+fn serve(stream: TcpStream, payloads: &[Vec<u8>], compression: WireCompression) -> io::Result<u64> {
+    let writer = BufWriter::with_capacity(BUFFER_SIZE, CountingWriter::new(stream));
+
+    let writer = match compression {
+        WireCompression::None
+        | WireCompression::PerMessage(Compression::None)
+        | WireCompression::Streaming(Compression::None) => {
+            let mut writer = writer;
+            for payload in payloads {
+                write_frame(&mut writer, payload)?;
+            }
+            writer
+        }
+        WireCompression::PerMessage(algorithm) => {
+            let mut writer = writer;
+            for payload in payloads {
+                write_frame(&mut writer, &compress(algorithm, payload)?)?;
+            }
+            writer
+        }
+        WireCompression::Streaming(Compression::Gzip) => {
+            let mut encoder = GzEncoder::new(writer, flate2::Compression::default());
+            for payload in payloads {
+                write_frame(&mut encoder, payload)?;
+            }
+            encoder.finish()?
+        }
+        WireCompression::Streaming(Compression::Zstd) => {
+            let mut encoder = zstd::stream::write::Encoder::new(writer, ZSTD_LEVEL)?;
+            for payload in payloads {
+                write_frame(&mut encoder, payload)?;
+            }
+            encoder.finish()?
+        }
+    };
+
+    let counting = writer.into_inner().map_err(|e| e.into_error())?;
+    Ok(counting.count)
+}
+
+fn receive(
+    stream: TcpStream,
+    format: Format,
+    compression: WireCompression,
+) -> Result<usize, LoopbackError> {
+    let reader = BufReader::with_capacity(BUFFER_SIZE, stream);
+
+    match compression {
+        WireCompression::Streaming(Compression::Gzip) => {
+            read_frames(GzDecoder::new(reader), format, None)
+        }
+        WireCompression::Streaming(Compression::Zstd) => read_frames(
+            zstd::stream::read::Decoder::with_buffer(reader)?,
+            format,
+            None,
+        ),
+        WireCompression::PerMessage(algorithm) if algorithm != Compression::None => {
+            read_frames(reader, format, Some(algorithm))
+        }
+        _ => read_frames(reader, format, None),
+    }
+}
+
+fn read_frames<R: Read>(
+    mut reader: R,
+    format: Format,
+    per_message: Option<Compression>,
+) -> Result<usize, LoopbackError> {
+    let mut buf = Vec::new();
+    let mut count = 0;
+
+    while read_frame(&mut reader, &mut buf)? {
+        let event = match per_message {
+            Some(algorithm) => stats::deserialize(&decompress(algorithm, &buf)?, format)?,
+            None => stats::deserialize(&buf, format)?,
+        };
+        std::hint::black_box(event);
+        count += 1;
+    }
+
+    Ok(count)
+}
+
+fn write_frame<W: Write>(writer: &mut W, payload: &[u8]) -> io::Result<()> {
+    writer.write_all(&(payload.len() as u32).to_le_bytes())?;
+    writer.write_all(payload)
+}
+
+/// Read one frame into `buf`; returns false on a clean end of stream
+fn read_frame<R: Read>(reader: &mut R, buf: &mut Vec<u8>) -> io::Result<bool> {
+    let mut len = [0u8; 4];
+    match reader.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(false),
+        Err(e) => return Err(e),
+    }
+    buf.resize(u32::from_le_bytes(len) as usize, 0);
+    reader.read_exact(buf)?;
+    Ok(true)
+}
+
+fn compress(algorithm: Compression, data: &[u8]) -> io::Result<Vec<u8>> {
+    match algorithm {
+        Compression::None => Ok(data.to_vec()),
+        Compression::Gzip => {
+            let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(data)?;
+            encoder.finish()
+        }
+        Compression::Zstd => zstd::encode_all(data, ZSTD_LEVEL),
+    }
+}
+
+fn decompress(algorithm: Compression, data: &[u8]) -> io::Result<Vec<u8>> {
+    match algorithm {
+        Compression::None => Ok(data.to_vec()),
+        Compression::Gzip => {
+            let mut out = Vec::new();
+            GzDecoder::new(data).read_to_end(&mut out)?;
+            Ok(out)
+        }
+        Compression::Zstd => zstd::decode_all(data),
+    }
+}
+
+/// Counts bytes written to the socket
+struct CountingWriter<W> {
+    inner: W,
+    count: u64,
+}
+
+impl<W> CountingWriter<W> {
+    fn new(inner: W) -> Self {
+        Self { inner, count: 0 }
+    }
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.count += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// CPU time consumed by the calling thread, where the platform exposes it
+#[cfg(unix)]
+pub fn thread_cpu_time() -> Option<Duration> {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    // SAFETY: `ts` is a valid, writable timespec
+    let rc = unsafe { libc::clock_gettime(libc::CLOCK_THREAD_CPUTIME_ID, &mut ts) };
+    (rc == 0).then(|| Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32))
+}
+
+/// CPU time consumed by the calling thread, where the platform exposes it
+#[cfg(not(unix))]
+pub fn thread_cpu_time() -> Option<Duration> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_event() -> NostrEvent {
+        NostrEvent {
+            id: [0xab; 32],
+            pubkey: [0xcd; 32],
+            created_at: 1234567890,
+            kind: 1,
+            tags: vec![
+                vec!["p".to_string(), "cd".repeat(32)],
+                vec!["t".to_string(), "nostr".to_string()],
+            ],
+            content: "Hello, Nostr! ".repeat(20),
+            sig: [0xef; 64],
+        }
+    }
+
+    #[test]
+    fn test_loopback_all_compression_modes() {
+        let events = vec![sample_event(); 200];
+        let modes = [
+            WireCompression::None,
+            WireCompression::PerMessage(Compression::Gzip),
+            WireCompression::PerMessage(Compression::Zstd),
+            WireCompression::Streaming(Compression::Gzip),
+            WireCompression::Streaming(Compression::Zstd),
+        ];
+
+        for format in [Format::Json, Format::ProtoBinary, Format::DannyPack] {
+            let mut uncompressed = 0;
+            for compression in modes {
+                let report = run(&events, format, compression).unwrap();
+                assert_eq!(report.events, events.len());
+
+                match compression {
+                    WireCompression::None => {
+                        // Exactly the payloads plus a 4-byte length per frame
+                        uncompressed = report.wire_bytes;
+                        assert_eq!(report.wire_bytes, report.payload_bytes + 4 * 200);
+                    }
+                    // Identical events compress extremely well across messages
+                    WireCompression::Streaming(_) => {
+                        assert!(report.wire_bytes * 10 < uncompressed)
+                    }
+                    _ => assert!(report.wire_bytes < uncompressed),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_truncated_stream_is_an_error() {
+        let payload = stats::serialize(&sample_event(), Format::DannyPack);
+        let mut data = Vec::new();
+        write_frame(&mut data, &payload).unwrap();
+        data.truncate(data.len() - 1);
+
+        assert!(read_frames(data.as_slice(), Format::DannyPack, None).is_err());
+    }
+}
@@ -0,0 +1,1317 @@
+//! NIP-01 relay protocol messages
+//!
+//! Events are only part of what crosses a relay connection: subscriptions,
+//! acknowledgements and notices are framed around them. This module models
+//! every client and relay message and encodes each one four ways:
+//!
+//! - `json`: the standard NIP-01 wire format, e.g. `["EVENT", "sub", {...}]`
+//! - `cbor`: a packed array led by an integer type code; events use `cbor::packed`
+//! - `proto`: `docs/nostr_message.proto`, one `oneof` per direction; events use
+//!   the binary-optimized schema
+//! - `dannypack`: a type byte followed by DannyPack-style fields; events are
+//!   embedded as raw DannyPack
+//!
+//! All binary encodings store ids and pubkeys as raw bytes and use the same
+//! type codes (see [`MessageType`]).
+
+use crate::cbor::CborError;
+use crate::dannypack::{DannyPackError, Truncated};
+use crate::event::NostrEvent;
+use crate::filter::{Filter, FilterError};
+use crate::proto::ProtoError;
+
+/// Message verbs and their type codes in the binary encodings
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum MessageType {
+    Event = 1,
+    Req = 2,
+    Close = 3,
+    Eose = 4,
+    Ok = 5,
+    Notice = 6,
+    Closed = 7,
+    Auth = 8,
+    Count = 9,
+}
+
+impl MessageType {
+    pub const ALL: [MessageType; 9] = [
+        MessageType::Event,
+        MessageType::Req,
+        MessageType::Close,
+        MessageType::Eose,
+        MessageType::Ok,
+        MessageType::Notice,
+        MessageType::Closed,
+        MessageType::Auth,
+        MessageType::Count,
+    ];
+
+    /// The JSON verb, e.g. `"EVENT"`
+    pub fn verb(&self) -> &'static str {
+        match self {
+            MessageType::Event => "EVENT",
+            MessageType::Req => "REQ",
+            MessageType::Close => "CLOSE",
+            MessageType::Eose => "EOSE",
+            MessageType::Ok => "OK",
+            MessageType::Notice => "NOTICE",
+            MessageType::Closed => "CLOSED",
+            MessageType::Auth => "AUTH",
+            MessageType::Count => "COUNT",
+        }
+    }
+
+    pub fn from_verb(verb: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|t| t.verb() == verb)
+    }
+
+    /// The type code used by the binary encodings
+    pub fn code(&self) -> u8 {
+        *self as u8
+    }
+
+    pub fn from_code(code: u8) -> Option<Self> {
+        Self::ALL.into_iter().find(|t| t.code() == code)
+    }
+}
+
+/// Messages sent from a client to a relay
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClientMessage {
+    /// Publish an event
+    Event(NostrEvent),
+    /// Open a subscription
+    Req {
+        subscription_id: String,
+        filters: Vec<Filter>,
+    },
+    /// Close a subscription
+    Close(String),
+    /// NIP-42 authentication event
+    Auth(NostrEvent),
+    /// NIP-45 count request
+    Count {
+        subscription_id: String,
+        filters: Vec<Filter>,
+    },
+}
+
+impl ClientMessage {
+    pub fn message_type(&self) -> MessageType {
+        match self {
+            ClientMessage::Event(_) => MessageType::Event,
+            ClientMessage::Req { .. } => MessageType::Req,
+            ClientMessage::Close(_) => MessageType::Close,
+            ClientMessage::Auth(_) => MessageType::Auth,
+            ClientMessage::Count { .. } => MessageType::Count,
+        }
+    }
+}
+
+/// Messages sent from a relay to a client
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RelayMessage {
+    /// An event matching a subscription
+    Event {
+        subscription_id: String,
+        event: NostrEvent,
+    },
+    /// Result of publishing an event
+    Ok {
+        event_id: [u8; 32],
+        accepted: bool,
+        message: String,
+    },
+    /// End of stored events for a subscription
+    Eose(String),
+    /// The relay closed a subscription
+    Closed {
+        subscription_id: String,
+        message: String,
+    },
+    /// Human-readable notice
+    Notice(String),
+    /// NIP-42 authentication challenge
+    Auth(String),
+    /// NIP-45 count result
+    Count { subscription_id: String, count: u64 },
+}
+
+impl RelayMessage {
+    pub fn message_type(&self) -> MessageType {
+        match self {
+            RelayMessage::Event { .. } => MessageType::Event,
+            RelayMessage::Ok { .. } => MessageType::Ok,
+            RelayMessage::Eose(_) => MessageType::Eose,
+            RelayMessage::Closed { .. } => MessageType::Closed,
+            RelayMessage::Notice(_) => MessageType::Notice,
+            RelayMessage::Auth(_) => MessageType::Auth,
+            RelayMessage::Count { .. } => MessageType::Count,
+        }
+    }
+}
+
+// ============================================
+// JSON (NIP-01 wire format)
+// ============================================
+
+pub mod json {
+    use super::*;
+    use crate::event::NostrEventJson;
+    use serde_json::{json, Value};
+
+    pub fn serialize_client(message: &ClientMessage) -> Vec<u8> {
+        let value = match message {
+            ClientMessage::Event(event) => json!(["EVENT", event_to_value(event)]),
+            ClientMessage::Req {
+                subscription_id,
+                filters,
+            } => subscription_value("REQ", subscription_id, filters),
+            ClientMessage::Close(subscription_id) => json!(["CLOSE", subscription_id]),
+            ClientMessage::Auth(event) => json!(["AUTH", event_to_value(event)]),
+            ClientMessage::Count {
+                subscription_id,
+                filters,
+            } => subscription_value("COUNT", subscription_id, filters),
+        };
+        serde_json::to_vec(&value).expect("JSON serialization should not fail")
+    }
+
+    pub fn deserialize_client(data: &[u8]) -> Result<ClientMessage, MessageError> {
+        let (message_type, mut fields) = split(serde_json::from_slice(data)?)?;
+
+        Ok(match message_type {
+            MessageType::Event => ClientMessage::Event(event_from_value(next(&mut fields)?)?),
+            MessageType::Req => ClientMessage::Req {
+                subscription_id: next_string(&mut fields)?,
+                filters: filters_from_values(fields)?,
+            },
+            MessageType::Close => ClientMessage::Close(next_string(&mut fields)?),
+            MessageType::Auth => ClientMessage::Auth(event_from_value(next(&mut fields)?)?),
+            MessageType::Count => ClientMessage::Count {
+                subscription_id: next_string(&mut fields)?,
+                filters: filters_from_values(fields)?,
+            },
+            other => return Err(MessageError::WrongDirection(other.verb())),
+        })
+    }
+
+    pub fn serialize_relay(message: &RelayMessage) -> Vec<u8> {
+        let value = match message {
+            RelayMessage::Event {
+                subscription_id,
+                event,
+            } => json!(["EVENT", subscription_id, event_to_value(event)]),
+            RelayMessage::Ok {
+                event_id,
+                accepted,
+                message,
+            } => json!(["OK", hex::encode(event_id), accepted, message]),
+            RelayMessage::Eose(subscription_id) => json!(["EOSE", subscription_id]),
+            RelayMessage::Closed {
+                subscription_id,
+                message,
+            } => json!(["CLOSED", subscription_id, message]),
+            RelayMessage::Notice(message) => json!(["NOTICE", message]),
+            RelayMessage::Auth(challenge) => json!(["AUTH", challenge]),
+            RelayMessage::Count {
+                subscription_id,
+                count,
+            } => json!(["COUNT", subscription_id, { "count": count }]),
+        };
+        serde_json::to_vec(&value).expect("JSON serialization should not fail")
+    }
+
+    pub fn deserialize_relay(data: &[u8]) -> Result<RelayMessage, MessageError> {
+        let (message_type, mut fields) = split(serde_json::from_slice(data)?)?;
+
+        Ok(match message_type {
+            MessageType::Event => RelayMessage::Event {
+                subscription_id: next_string(&mut fields)?,
+                event: event_from_value(next(&mut fields)?)?,
+            },
+            MessageType::Ok => {
+                let event_id = hex::decode(next_string(&mut fields)?)?;
+                RelayMessage::Ok {
+                    event_id: event_id
+                        .try_into()
+                        .map_err(|_| MessageError::Invalid("event id"))?,
+                    accepted: next(&mut fields)?
+                        .as_bool()
+                        .ok_or(MessageError::Invalid("accepted"))?,
+                    message: next_string(&mut fields)?,
+                }
+            }
+            MessageType::Eose => RelayMessage::Eose(next_string(&mut fields)?),
+            MessageType::Closed => RelayMessage::Closed {
+                subscription_id: next_string(&mut fields)?,
+                message: next_string(&mut fields)?,
+            },
+            MessageType::Notice => RelayMessage::Notice(next_string(&mut fields)?),
+            MessageType::Auth => RelayMessage::Auth(next_string(&mut fields)?),
+            MessageType::Count => RelayMessage::Count {
+                subscription_id: next_string(&mut fields)?,
+                count: next(&mut fields)?
+                    .get("count")
+                    .and_then(Value::as_u64)
+                    .ok_or(MessageError::Invalid("count"))?,
+            },
+            other => return Err(MessageError::WrongDirection(other.verb())),
+        })
+    }
+
+    fn event_to_value(event: &NostrEvent) -> Value {
+        serde_json::to_value(NostrEventJson::from(event))
+            .expect("JSON serialization should not fail")
+    }
+
+    fn event_from_value(value: Value) -> Result<NostrEvent, MessageError> {
+        let json: NostrEventJson = serde_json::from_value(value)?;
+        Ok(NostrEvent::try_from(json)?)
+    }
+
+    fn subscription_value(verb: &str, subscription_id: &str, filters: &[Filter]) -> Value {
+        let mut values = vec![Value::from(verb), Value::from(subscription_id)];
+        values.extend(filters.iter().map(Filter::to_value));
+        Value::Array(values)
+    }
+
+    fn filters_from_values(
+        fields: impl Iterator<Item = Value>,
+    ) -> Result<Vec<Filter>, MessageError> {
+        fields
+            .map(|value| Ok(Filter::from_value(&value)?))
+            .collect()
+    }
+
+    /// Split `[verb, fields...]` into the message type and remaining fields
+    fn split(value: Value) -> Result<(MessageType, std::vec::IntoIter<Value>), MessageError> {
+        let Value::Array(values) = value else {
+            return Err(MessageError::Invalid("message must be a JSON array"));
+        };
+        let mut fields = values.into_iter();
+        let verb = next_string(&mut fields)?;
+        let message_type = MessageType::from_verb(&verb).ok_or(MessageError::UnknownType(verb))?;
+        Ok((message_type, fields))
+    }
+
+    fn next(fields: &mut impl Iterator<Item = Value>) -> Result<Value, MessageError> {
+        fields.next().ok_or(MessageError::Invalid("missing field"))
+    }
+
+    fn next_string(fields: &mut impl Iterator<Item = Value>) -> Result<String, MessageError> {
+        match next(fields)? {
+            Value::String(s) => Ok(s),
+            _ => Err(MessageError::Invalid("expected string")),
+        }
+    }
+}
+
+// ============================================
+// CBOR (packed arrays)
+// ============================================
+
+// Messages: [type_code, fields...], with events as `cbor::packed` arrays.
+// Filters are integer-keyed maps so absent and empty lists stay distinct:
+// {0: ids, 1: authors, 2: kinds, 3: {letter: values}, 4: since, 5: until, 6: limit}
+
+pub mod cbor {
+    use super::*;
+    use crate::cbor::{decode_tag_value_cbor, extract_bytes, extract_i64, extract_string, packed};
+    use crate::tags::is_compressible_hex;
+    use ciborium::Value;
+
+    pub fn serialize_client(message: &ClientMessage) -> Vec<u8> {
+        let mut values = vec![type_value(message.message_type())];
+        match message {
+            ClientMessage::Event(event) | ClientMessage::Auth(event) => {
+                values.push(packed::to_value(event))
+            }
+            ClientMessage::Req {
+                subscription_id,
+                filters,
+            }
+            | ClientMessage::Count {
+                subscription_id,
+                filters,
+            } => {
+                values.push(Value::Text(subscription_id.clone()));
+                values.extend(filters.iter().map(filter_to_value));
+            }
+            ClientMessage::Close(subscription_id) => {
+                values.push(Value::Text(subscription_id.clone()))
+            }
+        }
+        to_vec(Value::Array(values))
+    }
+
+    pub fn deserialize_client(data: &[u8]) -> Result<ClientMessage, MessageError> {
+        let value: Value = ciborium::from_reader(data).map_err(CborError::from)?;
+        let (message_type, fields) = split(&value)?;
+
+        Ok(match message_type {
+            MessageType::Event => ClientMessage::Event(packed::from_value(field(fields, 0)?)?),
+            MessageType::Req => ClientMessage::Req {
+                subscription_id: extract_string(field(fields, 0)?, "subscription_id")?,
+                filters: filters_from_values(&fields[1..])?,
+            },
+            MessageType::Close => {
+                ClientMessage::Close(extract_string(field(fields, 0)?, "subscription_id")?)
+            }
+            MessageType::Auth => ClientMessage::Auth(packed::from_value(field(fields, 0)?)?),
+            MessageType::Count => ClientMessage::Count {
+                subscription_id: extract_string(field(fields, 0)?, "subscription_id")?,
+                filters: filters_from_values(&fields[1..])?,
+            },
+            other => return Err(MessageError::WrongDirection(other.verb())),
+        })
+    }
+
+    pub fn serialize_relay(message: &RelayMessage) -> Vec<u8> {
+        let mut values = vec![type_value(message.message_type())];
+        match message {
+            RelayMessage::Event {
+                subscription_id,
+                event,
+            } => {
+                values.push(Value::Text(subscription_id.clone()));
+                values.push(packed::to_value(event));
+            }
+            RelayMessage::Ok {
+                event_id,
+                accepted,
+                message,
+            } => {
+                values.push(Value::Bytes(event_id.to_vec()));
+                values.push(Value::Bool(*accepted));
+                values.push(Value::Text(message.clone()));
+            }
+            RelayMessage::Eose(text) | RelayMessage::Notice(text) | RelayMessage::Auth(text) => {
+                values.push(Value::Text(text.clone()))
+            }
+            RelayMessage::Closed {
+                subscription_id,
+                message,
+            } => {
+                values.push(Value::Text(subscription_id.clone()));
+                values.push(Value::Text(message.clone()));
+            }
+            RelayMessage::Count {
+                subscription_id,
+                count,
+            } => {
+                values.push(Value::Text(subscription_id.clone()));
+                values.push(Value::Integer((*count).into()));
+            }
+        }
+        to_vec(Value::Array(values))
+    }
+
+    pub fn deserialize_relay(data: &[u8]) -> Result<RelayMessage, MessageError> {
+        let value: Value = ciborium::from_reader(data).map_err(CborError::from)?;
+        let (message_type, fields) = split(&value)?;
+
+        Ok(match message_type {
+            MessageType::Event => RelayMessage::Event {
+                subscription_id: extract_string(field(fields, 0)?, "subscription_id")?,
+                event: packed::from_value(field(fields, 1)?)?,
+            },
+            MessageType::Ok => RelayMessage::Ok {
+                event_id: extract_bytes(field(fields, 0)?, "event_id")?
+                    .try_into()
+                    .map_err(|_| CborError::InvalidLength("event_id"))?,
+                accepted: field(fields, 1)?
+                    .as_bool()
+                    .ok_or(MessageError::Invalid("accepted"))?,
+                message: extract_string(field(fields, 2)?, "message")?,
+            },
+            MessageType::Eose => {
+                RelayMessage::Eose(extract_string(field(fields, 0)?, "subscription_id")?)
+            }
+            MessageType::Closed => RelayMessage::Closed {
+                subscription_id: extract_string(field(fields, 0)?, "subscription_id")?,
+                message: extract_string(field(fields, 1)?, "message")?,
+            },
+            MessageType::Notice => {
+                RelayMessage::Notice(extract_string(field(fields, 0)?, "message")?)
+            }
+            MessageType::Auth => {
+                RelayMessage::Auth(extract_string(field(fields, 0)?, "challenge")?)
+            }
+            MessageType::Count => RelayMessage::Count {
+                subscription_id: extract_string(field(fields, 0)?, "subscription_id")?,
+                count: extract_u64(field(fields, 1)?, "count")?,
+            },
+            other => return Err(MessageError::WrongDirection(other.verb())),
+        })
+    }
+
+    fn filter_to_value(filter: &Filter) -> Value {
+        let key = |k: u8| Value::Integer(k.into());
+        let byte_list = |list: &[[u8; 32]]| {
+            Value::Array(list.iter().map(|b| Value::Bytes(b.to_vec())).collect())
+        };
+
+        let mut entries = Vec::new();
+        if let Some(ids) = &filter.ids {
+            entries.push((key(0), byte_list(ids)));
+        }
+        if let Some(authors) = &filter.authors {
+            entries.push((key(1), byte_list(authors)));
+        }
+        if let Some(kinds) = &filter.kinds {
+            let kinds = kinds.iter().map(|&k| Value::Integer(k.into())).collect();
+            entries.push((key(2), Value::Array(kinds)));
+        }
+        if !filter.tags.is_empty() {
+            let tags = filter
+                .tags
+                .iter()
+                .map(|(letter, values)| {
+                    let values = values.iter().map(|v| filter_tag_value(v)).collect();
+                    (Value::Text(letter.to_string()), Value::Array(values))
+                })
+                .collect();
+            entries.push((key(3), Value::Map(tags)));
+        }
+        if let Some(since) = filter.since {
+            entries.push((key(4), Value::Integer(since.into())));
+        }
+        if let Some(until) = filter.until {
+            entries.push((key(5), Value::Integer(until.into())));
+        }
+        if let Some(limit) = filter.limit {
+            entries.push((key(6), Value::Integer((limit as u64).into())));
+        }
+        Value::Map(entries)
+    }
+
+    /// Lowercase hex as bytes, anything else as text: relays match filter
+    /// values case-sensitively, so uppercase hex must come back unchanged
+    fn filter_tag_value(value: &str) -> Value {
+        if is_compressible_hex(value) {
+            if let Ok(bytes) = hex::decode(value) {
+                return Value::Bytes(bytes);
+            }
+        }
+        Value::Text(value.to_string())
+    }
+
+    fn filter_from_value(value: &Value) -> Result<Filter, MessageError> {
+        let map = value.as_map().ok_or(CborError::ExpectedMap)?;
+        let mut filter = Filter::new();
+
+        for (key, value) in map {
+            let key = key
+                .as_integer()
+                .and_then(|k| u8::try_from(k).ok())
+                .ok_or(CborError::ExpectedInteger("filter key"))?;
+            match key {
+                0 => filter.ids = Some(hash_list(value, "ids")?),
+                1 => filter.authors = Some(hash_list(value, "authors")?),
+                2 => {
+                    let kinds = value.as_array().ok_or(CborError::ExpectedArray)?;
+                    filter.kinds = Some(
+                        kinds
+                            .iter()
+                            .map(|k| {
+                                k.as_integer()
+                                    .and_then(|k| u16::try_from(k).ok())
+                                    .ok_or(CborError::ExpectedInteger("kinds"))
+                            })
+                            .collect::<Result<_, _>>()?,
+                    );
+                }
+                3 => {
+                    let tags = value.as_map().ok_or(CborError::ExpectedMap)?;
+                    for (letter, values) in tags {
+                        let letter = tag_letter(&extract_string(letter, "tag letter")?)?;
+                        let values = values
+                            .as_array()
+                            .ok_or(CborError::ExpectedArray)?
+                            .iter()
+                            .map(decode_tag_value_cbor)
+                            .collect::<Result<_, _>>()?;
+                        filter.tags.insert(letter, values);
+                    }
+                }
+                4 => filter.since = Some(extract_i64(value, "since")?),
+                5 => filter.until = Some(extract_i64(value, "until")?),
+                6 => filter.limit = Some(extract_u64(value, "limit")? as usize),
+                _ => {}
+            }
+        }
+
+        Ok(filter)
+    }
+
+    fn filters_from_values(values: &[Value]) -> Result<Vec<Filter>, MessageError> {
+        values.iter().map(filter_from_value).collect()
+    }
+
+    fn hash_list(value: &Value, field: &'static str) -> Result<Vec<[u8; 32]>, MessageError> {
+        let list = value.as_array().ok_or(CborError::ExpectedArray)?;
+        list.iter()
+            .map(|v| {
+                extract_bytes(v, field)?
+                    .try_into()
+                    .map_err(|_| CborError::InvalidLength(field).into())
+            })
+            .collect()
+    }
+
+    fn extract_u64(value: &Value, field: &'static str) -> Result<u64, CborError> {
+        value
+            .as_integer()
+            .and_then(|i| u64::try_from(i).ok())
+            .ok_or(CborError::ExpectedInteger(field))
+    }
+
+    fn type_value(message_type: MessageType) -> Value {
+        Value::Integer(message_type.code().into())
+    }
+
+    /// Split `[type_code, fields...]` into the message type and remaining fields
+    fn split(value: &Value) -> Result<(MessageType, &[Value]), MessageError> {
+        let arr = value.as_array().ok_or(CborError::ExpectedArray)?;
+        let (code, fields) = arr
+            .split_first()
+            .ok_or(CborError::MissingField("message type"))?;
+        let code = code
+            .as_integer()
+            .and_then(|c| u8::try_from(c).ok())
+            .ok_or(CborError::ExpectedInteger("message type"))?;
+        let message_type =
+            MessageType::from_code(code).ok_or(MessageError::UnknownType(code.to_string()))?;
+        Ok((message_type, fields))
+    }
+
+    fn field(fields: &[Value], index: usize) -> Result<&Value, CborError> {
+        fields
+            .get(index)
+            .ok_or(CborError::MissingField("message field"))
+    }
+
+    fn to_vec(value: Value) -> Vec<u8> {
+        let mut buf = Vec::new();
+        ciborium::into_writer(&value, &mut buf).expect("CBOR serialization should not fail");
+        buf
+    }
+}
+
+// ============================================
+// Protocol Buffers (nostr_message.proto)
+// ============================================
+
+pub mod proto {
+    use super::*;
+    use crate::proto::binary::{event_to_proto_binary, proto_binary_to_event};
+    use crate::proto_gen::nostr_message::{
+        proto_client_message, proto_relay_message, ProtoClientMessage, ProtoClosed, ProtoCount,
+        ProtoFilter, ProtoOk, ProtoRelayMessage, ProtoSubscription, ProtoSubscriptionEvent,
+        ProtoTagFilter,
+    };
+    use prost::Message;
+
+    pub fn serialize_client(message: &ClientMessage) -> Vec<u8> {
+        use proto_client_message::Message as M;
+
+        let message = match message {
+            ClientMessage::Event(event) => M::Event(event_to_proto_binary(event)),
+            ClientMessage::Req {
+                subscription_id,
+                filters,
+            } => M::Req(subscription_to_proto(subscription_id, filters)),
+            ClientMessage::Close(subscription_id) => M::Close(subscription_id.clone()),
+            ClientMessage::Auth(event) => M::Auth(event_to_proto_binary(event)),
+            ClientMessage::Count {
+                subscription_id,
+                filters,
+            } => M::Count(subscription_to_proto(subscription_id, filters)),
+        };
+        ProtoClientMessage {
+            message: Some(message),
+        }
+        .encode_to_vec()
+    }
+
+    pub fn deserialize_client(data: &[u8]) -> Result<ClientMessage, MessageError> {
+        use proto_client_message::Message as M;
+
+        let proto = ProtoClientMessage::decode(data).map_err(ProtoError::from)?;
+        Ok(
+            match proto
+                .message
+                .ok_or(MessageError::Invalid("empty message"))?
+            {
+                M::Event(event) => ClientMessage::Event(proto_binary_to_event(event)?),
+                M::Req(sub) => ClientMessage::Req {
+                    filters: filters_from_proto(sub.filters)?,
+                    subscription_id: sub.subscription_id,
+                },
+                M::Close(subscription_id) => ClientMessage::Close(subscription_id),
+                M::Auth(event) => ClientMessage::Auth(proto_binary_to_event(event)?),
+                M::Count(sub) => ClientMessage::Count {
+                    filters: filters_from_proto(sub.filters)?,
+                    subscription_id: sub.subscription_id,
+                },
+            },
+        )
+    }
+
+    pub fn serialize_relay(message: &RelayMessage) -> Vec<u8> {
+        use proto_relay_message::Message as M;
+
+        let message = match message {
+            RelayMessage::Event {
+                subscription_id,
+                event,
+            } => M::Event(ProtoSubscriptionEvent {
+                subscription_id: subscription_id.clone(),
+                event: Some(event_to_proto_binary(event)),
+            }),
+            RelayMessage::Ok {
+                event_id,
+                accepted,
+                message,
+            } => M::Ok(ProtoOk {
+                event_id: event_id.to_vec(),
+                accepted: *accepted,
+                message: message.clone(),
+            }),
+            RelayMessage::Eose(subscription_id) => M::Eose(subscription_id.clone()),
+            RelayMessage::Closed {
+                subscription_id,
+                message,
+            } => M::Closed(ProtoClosed {
+                subscription_id: subscription_id.clone(),
+                message: message.clone(),
+            }),
+            RelayMessage::Notice(message) => M::Notice(message.clone()),
+            RelayMessage::Auth(challenge) => M::Auth(challenge.clone()),
+            RelayMessage::Count {
+                subscription_id,
+                count,
+            } => M::Count(ProtoCount {
+                subscription_id: subscription_id.clone(),
+                count: *count,
+            }),
+        };
+        ProtoRelayMessage {
+            message: Some(message),
+        }
+        .encode_to_vec()
+    }
+
+    pub fn deserialize_relay(data: &[u8]) -> Result<RelayMessage, MessageError> {
+        use proto_relay_message::Message as M;
+
+        let proto = ProtoRelayMessage::decode(data).map_err(ProtoError::from)?;
+        Ok(
+            match proto
+                .message
+                .ok_or(MessageError::Invalid("empty message"))?
+            {
+                M::Event(sub) => RelayMessage::Event {
+                    subscription_id: sub.subscription_id,
+                    event: proto_binary_to_event(
+                        sub.event.ok_or(MessageError::Invalid("missing event"))?,
+                    )?,
+                },
+                M::Ok(ok) => RelayMessage::Ok {
+                    event_id: ok
+                        .event_id
+                        .try_into()
+                        .map_err(|_| ProtoError::InvalidLength("event_id"))?,
+                    accepted: ok.accepted,
+                    message: ok.message,
+                },
+                M::Eose(subscription_id) => RelayMessage::Eose(subscription_id),
+                M::Closed(closed) => RelayMessage::Closed {
+                    subscription_id: closed.subscription_id,
+                    message: closed.message,
+                },
+                M::Notice(message) => RelayMessage::Notice(message),
+                M::Auth(challenge) => RelayMessage::Auth(challenge),
+                M::Count(count) => RelayMessage::Count {
+                    subscription_id: count.subscription_id,
+                    count: count.count,
+                },
+            },
+        )
+    }
+
+    fn subscription_to_proto(subscription_id: &str, filters: &[Filter]) -> ProtoSubscription {
+        ProtoSubscription {
+            subscription_id: subscription_id.to_string(),
+            filters: filters.iter().map(filter_to_proto).collect(),
+        }
+    }
+
+    fn filter_to_proto(filter: &Filter) -> ProtoFilter {
+        let byte_list =
+            |list: &Option<Vec<[u8; 32]>>| list.iter().flatten().map(|b| b.to_vec()).collect();
+
+        ProtoFilter {
+            ids: byte_list(&filter.ids),
+            authors: byte_list(&filter.authors),
+            kinds: filter.kinds.iter().flatten().map(|&k| k as u32).collect(),
+            tags: filter
+                .tags
+                .iter()
+                .map(|(letter, values)| ProtoTagFilter {
+                    letter: letter.to_string(),
+                    values: values.clone(),
+                })
+                .collect(),
+            since: filter.since,
+            until: filter.until,
+            limit: filter.limit.map(|l| l as u64),
+            has_ids: filter.ids.is_some(),
+            has_authors: filter.authors.is_some(),
+            has_kinds: filter.kinds.is_some(),
+        }
+    }
+
+    fn filters_from_proto(filters: Vec<ProtoFilter>) -> Result<Vec<Filter>, MessageError> {
+        filters.into_iter().map(filter_from_proto).collect()
+    }
+
+    fn filter_from_proto(proto: ProtoFilter) -> Result<Filter, MessageError> {
+        let hash_list = |present: bool, list: Vec<Vec<u8>>, field: &'static str| {
+            present
+                .then(|| {
+                    list.into_iter()
+                        .map(|b| b.try_into().map_err(|_| ProtoError::InvalidLength(field)))
+                        .collect::<Result<Vec<_>, _>>()
+                })
+                .transpose()
+        };
+
+        let mut tags = std::collections::BTreeMap::new();
+        for tag in proto.tags {
+            let letter = tag_letter(&tag.letter)?;
+            tags.insert(letter, tag.values);
+        }
+
+        Ok(Filter {
+            ids: hash_list(proto.has_ids, proto.ids, "ids")?,
+            authors: hash_list(proto.has_authors, proto.authors, "authors")?,
+            kinds: proto
+                .has_kinds
+                .then(|| {
+                    proto
+                        .kinds
+                        .into_iter()
+                        .map(|k| u16::try_from(k).map_err(|_| MessageError::Invalid("kind")))
+                        .collect::<Result<Vec<_>, _>>()
+                })
+                .transpose()?,
+            tags,
+            since: proto.since,
+            until: proto.until,
+            limit: proto.limit.map(|l| l as usize),
+        })
+    }
+}
+
+// ============================================
+// DannyPack style (type byte + compact fields)
+// ============================================
+
+// Layout: [type: u8] followed by the fields of each message type.
+// Strings use DannyPack's length/flag header, so hex subscription ids and
+// tag values are stored as raw bytes. Events come last and are raw
+// DannyPack, so they need no length prefix.
+//
+// Filter: [flags: u8] [ids] [authors] [kinds] [tags] [since] [until] [limit]
+//   flags: bit 0 ids, 1 authors, 2 kinds, 3 since, 4 until, 5 limit
+//   ids/authors: varint count + 32 bytes each; kinds: varint count + u16 LE
+//   tags (always present): varint count + (letter, varint count, values)
+//   since/until: i64 LE; limit: varint
+
+pub mod dannypack {
+    use super::*;
+    use crate::dannypack::{self as dp, read_value, write_value, write_varint, Reader};
+
+    const HAS_IDS: u8 = 0x01;
+    const HAS_AUTHORS: u8 = 0x02;
+    const HAS_KINDS: u8 = 0x04;
+    const HAS_SINCE: u8 = 0x08;
+    const HAS_UNTIL: u8 = 0x10;
+    const HAS_LIMIT: u8 = 0x20;
+
+    pub fn serialize_client(message: &ClientMessage) -> Vec<u8> {
+        let mut buf = vec![message.message_type().code()];
+        match message {
+            ClientMessage::Event(event) | ClientMessage::Auth(event) => {
+                dp::serialize_into(event, &mut buf)
+            }
+            ClientMessage::Req {
+                subscription_id,
+                filters,
+            }
+            | ClientMessage::Count {
+                subscription_id,
+                filters,
+            } => {
+                write_value(&mut buf, subscription_id);
+                write_varint(&mut buf, filters.len() as u64);
+                for filter in filters {
+                    write_filter(&mut buf, filter);
+                }
+            }
+            ClientMessage::Close(subscription_id) => write_value(&mut buf, subscription_id),
+        }
+        buf
+    }
+
+    pub fn deserialize_client(data: &[u8]) -> Result<ClientMessage, MessageError> {
+        let (message_type, mut reader) = split(data)?;
+
+        Ok(match message_type {
+            MessageType::Event => ClientMessage::Event(dp::deserialize(reader.remaining())?),
+            MessageType::Req => ClientMessage::Req {
+                subscription_id: read_string(&mut reader)?,
+                filters: read_filters(&mut reader)?,
+            },
+            MessageType::Close => ClientMessage::Close(read_string(&mut reader)?),
+            MessageType::Auth => ClientMessage::Auth(dp::deserialize(reader.remaining())?),
+            MessageType::Count => ClientMessage::Count {
+                subscription_id: read_string(&mut reader)?,
+                filters: read_filters(&mut reader)?,
+            },
+            other => return Err(MessageError::WrongDirection(other.verb())),
+        })
+    }
+
+    pub fn serialize_relay(message: &RelayMessage) -> Vec<u8> {
+        let mut buf = vec![message.message_type().code()];
+        match message {
+            RelayMessage::Event {
+                subscription_id,
+                event,
+            } => {
+                write_value(&mut buf, subscription_id);
+                dp::serialize_into(event, &mut buf);
+            }
+            RelayMessage::Ok {
+                event_id,
+                accepted,
+                message,
+            } => {
+                buf.extend_from_slice(event_id);
+                buf.push(*accepted as u8);
+                write_value(&mut buf, message);
+            }
+            RelayMessage::Eose(text) | RelayMessage::Notice(text) | RelayMessage::Auth(text) => {
+                write_value(&mut buf, text)
+            }
+            RelayMessage::Closed {
+                subscription_id,
+                message,
+            } => {
+                write_value(&mut buf, subscription_id);
+                write_value(&mut buf, message);
+            }
+            RelayMessage::Count {
+                subscription_id,
+                count,
+            } => {
+                write_value(&mut buf, subscription_id);
+                write_varint(&mut buf, *count);
+            }
+        }
+        buf
+    }
+
+    pub fn deserialize_relay(data: &[u8]) -> Result<RelayMessage, MessageError> {
+        let (message_type, mut reader) = split(data)?;
+
+        Ok(match message_type {
+            MessageType::Event => RelayMessage::Event {
+                subscription_id: read_string(&mut reader)?,
+                event: dp::deserialize(reader.remaining())?,
+            },
+            MessageType::Ok => RelayMessage::Ok {
+                event_id: read_hash(&mut reader)?,
+                accepted: reader.byte()? != 0,
+                message: read_string(&mut reader)?,
+            },
+            MessageType::Eose => RelayMessage::Eose(read_string(&mut reader)?),
+            MessageType::Closed => RelayMessage::Closed {
+                subscription_id: read_string(&mut reader)?,
+                message: read_string(&mut reader)?,
+            },
+            MessageType::Notice => RelayMessage::Notice(read_string(&mut reader)?),
+            MessageType::Auth => RelayMessage::Auth(read_string(&mut reader)?),
+            MessageType::Count => RelayMessage::Count {
+                subscription_id: read_string(&mut reader)?,
+                count: reader.varint()?,
+            },
+            other => return Err(MessageError::WrongDirection(other.verb())),
+        })
+    }
+
+    fn write_filter(buf: &mut Vec<u8>, filter: &Filter) {
+        let mut flags = 0;
+        for (present, flag) in [
+            (filter.ids.is_some(), HAS_IDS),
+            (filter.authors.is_some(), HAS_AUTHORS),
+            (filter.kinds.is_some(), HAS_KINDS),
+            (filter.since.is_some(), HAS_SINCE),
+            (filter.until.is_some(), HAS_UNTIL),
+            (filter.limit.is_some(), HAS_LIMIT),
+        ] {
+            if present {
+                flags |= flag;
+            }
+        }
+        buf.push(flags);
+
+        for list in [&filter.ids, &filter.authors].into_iter().flatten() {
+            write_varint(buf, list.len() as u64);
+            for hash in list {
+                buf.extend_from_slice(hash);
+            }
+        }
+        if let Some(kinds) = &filter.kinds {
+            write_varint(buf, kinds.len() as u64);
+            for kind in kinds {
+                buf.extend_from_slice(&kind.to_le_bytes());
+            }
+        }
+
+        write_varint(buf, filter.tags.len() as u64);
+        for (letter, values) in &filter.tags {
+            write_value(buf, letter.encode_utf8(&mut [0; 4]));
+            write_varint(buf, values.len() as u64);
+            for value in values {
+                write_value(buf, value);
+            }
+        }
+
+        for time in [filter.since, filter.until].into_iter().flatten() {
+            buf.extend_from_slice(&time.to_le_bytes());
+        }
+        if let Some(limit) = filter.limit {
+            write_varint(buf, limit as u64);
+        }
+    }
+
+    fn split(data: &[u8]) -> Result<(MessageType, Reader<'_>), MessageError> {
+        let (&code, rest) = data.split_first().ok_or(DannyPackError::TooShort)?;
+        let message_type =
+            MessageType::from_code(code).ok_or(MessageError::UnknownType(code.to_string()))?;
+        Ok((message_type, Reader::new(rest)))
+    }
+
+    fn read_string(reader: &mut Reader) -> Result<String, MessageError> {
+        let (value, len) = read_value(reader.remaining())?;
+        reader.bytes(len)?;
+        Ok(value)
+    }
+
+    fn read_hash(reader: &mut Reader) -> Result<[u8; 32], MessageError> {
+        Ok(reader.bytes(32)?.try_into().unwrap())
+    }
+
+    fn read_hashes(reader: &mut Reader) -> Result<Vec<[u8; 32]>, MessageError> {
+        let count = reader.varint()? as usize;
+        // Every entry takes 32 bytes, so a corrupt count cannot over-allocate
+        if count > reader.remaining().len() / 32 {
+            return Err(Truncated.into());
+        }
+        (0..count).map(|_| read_hash(reader)).collect()
+    }
+
+    fn read_filters(reader: &mut Reader) -> Result<Vec<Filter>, MessageError> {
+        let count = reader.varint()? as usize;
+        let mut filters = Vec::with_capacity(count.min(reader.remaining().len()));
+        for _ in 0..count {
+            filters.push(read_filter(reader)?);
+        }
+        Ok(filters)
+    }
+
+    fn read_filter(reader: &mut Reader) -> Result<Filter, MessageError> {
+        let flags = reader.byte()?;
+        let mut filter = Filter::new();
+
+        if flags & HAS_IDS != 0 {
+            filter.ids = Some(read_hashes(reader)?);
+        }
+        if flags & HAS_AUTHORS != 0 {
+            filter.authors = Some(read_hashes(reader)?);
+        }
+        if flags & HAS_KINDS != 0 {
+            let count = reader.varint()? as usize;
+            let kinds = reader.bytes(count.checked_mul(2).ok_or(Truncated)?)?;
+            filter.kinds = Some(
+                kinds
+                    .chunks_exact(2)
+                    .map(|k| u16::from_le_bytes([k[0], k[1]]))
+                    .collect(),
+            );
+        }
+
+        let tag_count = reader.varint()?;
+        for _ in 0..tag_count {
+            let letter = tag_letter(&read_string(reader)?)?;
+            let value_count = reader.varint()?;
+            let values = (0..value_count)
+                .map(|_| read_string(reader))
+                .collect::<Result<_, _>>()?;
+            filter.tags.insert(letter, values);
+        }
+
+        if flags & HAS_SINCE != 0 {
+            filter.since = Some(i64::from_le_bytes(reader.bytes(8)?.try_into().unwrap()));
+        }
+        if flags & HAS_UNTIL != 0 {
+            filter.until = Some(i64::from_le_bytes(reader.bytes(8)?.try_into().unwrap()));
+        }
+        if flags & HAS_LIMIT != 0 {
+            filter.limit = Some(reader.varint()? as usize);
+        }
+
+        Ok(filter)
+    }
+}
+
+/// Parse a filter tag key, which must be a single character
+fn tag_letter(s: &str) -> Result<char, MessageError> {
+    let mut chars = s.chars();
+    match (chars.next(), chars.next()) {
+        (Some(letter), None) => Ok(letter),
+        _ => Err(MessageError::Invalid("tag letter")),
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum MessageError {
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("CBOR error: {0}")]
+    Cbor(#[from] CborError),
+
+    #[error("Protobuf error: {0}")]
+    Proto(#[from] ProtoError),
+
+    #[error("DannyPack error: {0}")]
+    DannyPack(#[from] DannyPackError),
+
+    #[error("Filter error: {0}")]
+    Filter(#[from] FilterError),
+
+    #[error("Hex decode error: {0}")]
+    Hex(#[from] hex::FromHexError),
+
+    #[error("Unknown message type: {0}")]
+    UnknownType(String),
+
+    #[error("{0} is not valid in this direction")]
+    WrongDirection(&'static str),
+
+    #[error("Invalid message: {0}")]
+    Invalid(&'static str),
+}
+
+impl From<Truncated> for MessageError {
+    fn from(_: Truncated) -> Self {
+        MessageError::DannyPack(DannyPackError::TooShort)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_event() -> NostrEvent {
+        NostrEvent {
+            id: [0xab; 32],
+            pubkey: [0xcd; 32],
+            created_at: 1234567890,
+            kind: 7,
+            tags: vec![
+                vec!["e".to_string(), "ab".repeat(32)],
+                vec!["p".to_string(), "cd".repeat(32)],
+            ],
+            content: "+".to_string(),
+            sig: [0xef; 64],
+        }
+    }
+
+    fn sample_filter() -> Filter {
+        Filter::new()
+            .authors([[0xcd; 32]])
+            .kinds([1, 7])
+            .tag('e', ["ab".repeat(32), "not hex".to_string()])
+            .since(1_700_000_000)
+            .limit(100)
+    }
+
+    fn client_messages() -> Vec<ClientMessage> {
+        vec![
+            ClientMessage::Event(sample_event()),
+            ClientMessage::Req {
+                subscription_id: "sub1".to_string(),
+                filters: vec![sample_filter(), Filter::new().ids(Vec::new())],
+            },
+            ClientMessage::Close("sub1".to_string()),
+            ClientMessage::Auth(sample_event()),
+            ClientMessage::Count {
+                subscription_id: "0123456789abcdef".to_string(),
+                filters: vec![Filter::new().kinds([3])],
+            },
+        ]
+    }
+
+    fn relay_messages() -> Vec<RelayMessage> {
+        vec![
+            RelayMessage::Event {
+                subscription_id: "sub1".to_string(),
+                event: sample_event(),
+            },
+            RelayMessage::Ok {
+                event_id: [0xab; 32],
+                accepted: false,
+                message: "blocked: spam".to_string(),
+            },
+            RelayMessage::Eose("sub1".to_string()),
+            RelayMessage::Closed {
+                subscription_id: "sub1".to_string(),
+                message: "error: shutting down".to_string(),
+            },
+            RelayMessage::Notice("hello".to_string()),
+            RelayMessage::Auth("challenge-string".to_string()),
+            RelayMessage::Count {
+                subscription_id: "sub1".to_string(),
+                count: 4242,
+            },
+        ]
+    }
+
+    type Codec<M> = (
+        &'static str,
+        fn(&M) -> Vec<u8>,
+        fn(&[u8]) -> Result<M, MessageError>,
+    );
+
+    fn client_codecs() -> [Codec<ClientMessage>; 4] {
+        [
+            ("json", json::serialize_client, json::deserialize_client),
+            ("cbor", cbor::serialize_client, cbor::deserialize_client),
+            ("proto", proto::serialize_client, proto::deserialize_client),
+            (
+                "dannypack",
+                dannypack::serialize_client,
+                dannypack::deserialize_client,
+            ),
+        ]
+    }
+
+    fn relay_codecs() -> [Codec<RelayMessage>; 4] {
+        [
+            ("json", json::serialize_relay, json::deserialize_relay),
+            ("cbor", cbor::serialize_relay, cbor::deserialize_relay),
+            ("proto", proto::serialize_relay, proto::deserialize_relay),
+            (
+                "dannypack",
+                dannypack::serialize_relay,
+                dannypack::deserialize_relay,
+            ),
+        ]
+    }
+
+    #[test]
+    fn test_client_roundtrip() {
+        for (name, serialize, deserialize) in client_codecs() {
+            for message in client_messages() {
+                let data = serialize(&message);
+                let back = deserialize(&data).unwrap();
+                assert_eq!(message, back, "{} {:?}", name, message.message_type());
+            }
+        }
+    }
+
+    #[test]
+    fn test_relay_roundtrip() {
+        for (name, serialize, deserialize) in relay_codecs() {
+            for message in relay_messages() {
+                let data = serialize(&message);
+                let back = deserialize(&data).unwrap();
+                assert_eq!(message, back, "{} {:?}", name, message.message_type());
+            }
+        }
+    }
+
+    #[test]
+    fn test_json_wire_format() {
+        let data = json::serialize_relay(&RelayMessage::Eose("sub1".to_string()));
+        assert_eq!(data, br#"["EOSE","sub1"]"#);
+
+        let req = br##"["REQ","s",{"kinds":[1],"#t":["nostr"]},{"limit":5}]"##;
+        let ClientMessage::Req { filters, .. } = json::deserialize_client(req).unwrap() else {
+            panic!("expected REQ");
+        };
+        assert_eq!(filters[0], Filter::new().kinds([1]).tag('t', ["nostr"]));
+        assert_eq!(filters[1], Filter::new().limit(5));
+
+        // Relay-only verbs are rejected from clients and vice versa
+        assert!(matches!(
+            json::deserialize_client(br#"["EOSE","s"]"#),
+            Err(MessageError::WrongDirection("EOSE"))
+        ));
+        assert!(matches!(
+            json::deserialize_relay(br#"["PING"]"#),
+            Err(MessageError::UnknownType(_))
+        ));
+    }
+
+    #[test]
+    fn test_filter_tag_value_case() {
+        let message = ClientMessage::Req {
+            subscription_id: "sub1".to_string(),
+            filters: vec![Filter::new()
+                .tag('t', ["BEEF", "beef", "AbCd"])
+                .tag('e', ["AB".repeat(32)])],
+        };
+        for (name, serialize, deserialize) in client_codecs() {
+            let back = deserialize(&serialize(&message)).unwrap();
+            assert_eq!(back, message, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_binary_framing_is_smaller() {
+        let message = RelayMessage::Event {
+            subscription_id: "sub1".to_string(),
+            event: sample_event(),
+        };
+        let json_len = json::serialize_relay(&message).len();
+
+        for (name, serialize, _) in relay_codecs().into_iter().skip(1) {
+            let len = serialize(&message).len();
+            assert!(len < json_len, "{} ({}) >= json ({})", name, len, json_len);
+        }
+
+        // DannyPack framing is the type byte plus the subscription id
+        let event_len = crate::dannypack::serialize(&sample_event()).len();
+        assert_eq!(
+            dannypack::serialize_relay(&message).len(),
+            event_len + 1 + 5
+        );
+    }
+
+    #[test]
+    fn test_truncated_binary_fails() {
+        for (_, serialize, deserialize) in relay_codecs().into_iter().skip(1) {
+            let data = serialize(&relay_messages()[1]);
+            assert!(deserialize(&data[..data.len() / 2]).is_err());
+        }
+    }
+}
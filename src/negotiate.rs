@@ -0,0 +1,439 @@
+//! Format negotiation over WebSocket subprotocols
+//!
+//! Each [`Format`] has a stable `Sec-WebSocket-Protocol` token such as
+//! `nostr.cbor-packed.v1` and a MIME type. A client offers its formats in
+//! preference order; the relay picks one it supports and echoes its token; the
+//! client checks the echo and both sides use the same [`Codec`].
+//!
+//! A connection without a subprotocol is plain NIP-01, i.e. JSON.
+//!
+//! ```text
+//! client                                   relay
+//!   offer_header(prefs) ──────────────────▶ select(offer, supported)
+//!   accept(response, prefs) ◀───────────── Selection::response_header()
+//! ```
+//!
+//! [`loopback`] runs both sides over in-process channels, so client and
+//! relay implementations can test against the same reference exchange.
+
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+use crate::event::NostrEvent;
+use crate::stats::{self, Format, FormatError};
+
+#[derive(Debug, thiserror::Error)]
+pub enum NegotiateError {
+    #[error("No mutually supported format")]
+    NoCommonFormat,
+
+    #[error("Relay selected a subprotocol that was not offered: {0}")]
+    NotOffered(String),
+
+    #[error("Connection closed unexpectedly")]
+    Disconnected,
+
+    #[error("Decode error: {0}")]
+    Format(#[from] FormatError),
+}
+
+/// Stable subprotocol token for a format
+///
+/// The `.vN` suffix is the [`envelope::SCHEMA_VERSION`](crate::envelope::SCHEMA_VERSION)
+/// of the encoding.
+pub fn subprotocol(format: Format) -> &'static str {
+    match format {
+        Format::Json => "nostr.json.v1",
+        Format::CborSchemaless => "nostr.cbor-schemaless.v1",
+        Format::CborPacked => "nostr.cbor-packed.v1",
+        Format::CborIntKey => "nostr.cbor-intkey.v1",
+        Format::ProtoString => "nostr.proto-string.v1",
+        Format::ProtoBinary => "nostr.proto-binary.v1",
+        Format::CapnProto => "nostr.capnp.v1",
+        Format::CapnProtoPacked => "nostr.capnp-packed.v1",
+        Format::DannyPack => "nostr.dannypack.v1",
+        Format::Notepack => "nostr.notepack.v1",
+    }
+}
+
+/// MIME type for a format, e.g. for HTTP `Content-Type`
+pub fn mime_type(format: Format) -> &'static str {
+    match format {
+        Format::Json => "application/nostr+json",
+        Format::CborSchemaless => "application/vnd.nostr.cbor-schemaless+cbor",
+        Format::CborPacked => "application/vnd.nostr.cbor-packed+cbor",
+        Format::CborIntKey => "application/vnd.nostr.cbor-intkey+cbor",
+        Format::ProtoString => "application/vnd.nostr.proto-string+protobuf",
+        Format::ProtoBinary => "application/vnd.nostr.proto-binary+protobuf",
+        Format::CapnProto => "application/vnd.nostr.capnp",
+        Format::CapnProtoPacked => "application/vnd.nostr.capnp-packed",
+        Format::DannyPack => "application/vnd.nostr.dannypack",
+        Format::Notepack => "application/vnd.nostr.notepack",
+    }
+}
+
+/// Look up a format by subprotocol token
+///
+/// Tokens for other schema versions are not recognized.
+pub fn from_subprotocol(token: &str) -> Option<Format> {
+    let token = token.trim();
+    Format::all()
+        .iter()
+        .copied()
+        .find(|&f| subprotocol(f) == token)
+}
+
+/// Look up a format by MIME type, ignoring parameters such as `; charset=`
+pub fn from_mime_type(mime: &str) -> Option<Format> {
+    let essence = mime.split(';').next().unwrap_or_default().trim();
+    Format::all()
+        .iter()
+        .copied()
+        .find(|&f| mime_type(f).eq_ignore_ascii_case(essence))
+}
+
+/// Parse a comma-separated `Sec-WebSocket-Protocol` header, in order
+///
+/// Unknown tokens (other protocols, other versions) are skipped.
+pub fn parse_header(header: &str) -> Vec<Format> {
+    header.split(',').filter_map(from_subprotocol).collect()
+}
+
+/// Build the client's `Sec-WebSocket-Protocol` header from its preferences
+pub fn offer_header(preferences: &[Format]) -> String {
+    preferences
+        .iter()
+        .map(|&f| subprotocol(f))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Which side's preference order wins when both support several formats
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Priority {
+    /// First format in the client's list that the relay supports
+    #[default]
+    Client,
+    /// First format in the relay's list that the client offered
+    Relay,
+}
+
+/// Pick the best mutually supported format from two ordered lists
+pub fn choose(client: &[Format], relay: &[Format], priority: Priority) -> Option<Format> {
+    let (first, second) = match priority {
+        Priority::Client => (client, relay),
+        Priority::Relay => (relay, client),
+    };
+    first.iter().copied().find(|f| second.contains(f))
+}
+
+/// Encoder/decoder for the negotiated format
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Codec {
+    pub format: Format,
+}
+
+impl Codec {
+    pub fn new(format: Format) -> Self {
+        Self { format }
+    }
+
+    pub fn subprotocol(&self) -> &'static str {
+        subprotocol(self.format)
+    }
+
+    pub fn mime_type(&self) -> &'static str {
+        mime_type(self.format)
+    }
+
+    /// Whether messages are sent as text frames rather than binary frames
+    pub fn is_text(&self) -> bool {
+        self.format == Format::Json
+    }
+
+    pub fn serialize(&self, event: &NostrEvent) -> Vec<u8> {
+        stats::serialize(event, self.format)
+    }
+
+    pub fn deserialize(&self, data: &[u8]) -> Result<NostrEvent, FormatError> {
+        stats::deserialize(data, self.format)
+    }
+}
+
+/// Relay-side result of negotiation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Selection {
+    pub codec: Codec,
+    /// False for the plain NIP-01 fallback, in which case the relay must not
+    /// send a `Sec-WebSocket-Protocol` response header
+    pub via_subprotocol: bool,
+}
+
+impl Selection {
+    /// Value for the relay's `Sec-WebSocket-Protocol` response header
+    pub fn response_header(&self) -> Option<&'static str> {
+        self.via_subprotocol.then(|| self.codec.subprotocol())
+    }
+}
+
+/// Relay side: choose a format from the client's offer header
+///
+/// When the client offers no subprotocol, or none the relay supports, the
+/// connection falls back to plain NIP-01 JSON if the relay supports it.
+pub fn select(
+    offer: Option<&str>,
+    supported: &[Format],
+    priority: Priority,
+) -> Result<Selection, NegotiateError> {
+    let offered = offer.map(parse_header).unwrap_or_default();
+
+    if let Some(format) = choose(&offered, supported, priority) {
+        return Ok(Selection {
+            codec: Codec::new(format),
+            via_subprotocol: true,
+        });
+    }
+
+    supported
+        .contains(&Format::Json)
+        .then_some(Selection {
+            codec: Codec::new(Format::Json),
+            via_subprotocol: false,
+        })
+        .ok_or(NegotiateError::NoCommonFormat)
+}
+
+/// Client side: validate the relay's response header against what was offered
+///
+/// No response header means the relay speaks plain NIP-01 JSON.
+pub fn accept(response: Option<&str>, offered: &[Format]) -> Result<Codec, NegotiateError> {
+    let Some(token) = response.map(str::trim).filter(|t| !t.is_empty()) else {
+        return Ok(Codec::new(Format::Json));
+    };
+    match from_subprotocol(token) {
+        Some(format) if offered.contains(&format) => Ok(Codec::new(format)),
+        _ => Err(NegotiateError::NotOffered(token.to_string())),
+    }
+}
+
+/// Outcome of a [`loopback`] connection
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Session {
+    /// Codec the client accepted
+    pub client: Codec,
+    /// Codec the relay selected
+    pub relay: Codec,
+    /// Events as the client decoded them from the relay's echoes
+    pub echoed: Vec<NostrEvent>,
+}
+
+/// Messages exchanged over the simulated connection
+enum Frame {
+    Handshake(Option<String>),
+    Data(Vec<u8>),
+}
+
+/// Simulate a full connection with the relay on its own thread: the
+/// handshake, then the client publishes `events` and the relay echoes each
+/// one back re-encoded
+///
+/// A relay that finds no common format closes the connection, as a real
+/// relay refuses the upgrade, and its error is returned.
+pub fn loopback(
+    client_preferences: &[Format],
+    relay_supported: &[Format],
+    priority: Priority,
+    events: &[NostrEvent],
+) -> Result<Session, NegotiateError> {
+    let (to_relay, relay_rx) = mpsc::channel();
+    let (to_client, client_rx) = mpsc::channel();
+    let relay_supported = relay_supported.to_vec();
+    let relay = thread::spawn(move || run_relay(&relay_rx, &to_client, &relay_supported, priority));
+
+    let client = run_client(&to_relay, &client_rx, client_preferences, events);
+    drop(to_relay);
+    let relay = relay.join().map_err(|_| NegotiateError::Disconnected)?;
+
+    // The relay's error explains the client seeing a closed connection
+    let relay = relay?;
+    let (client, echoed) = client?;
+    Ok(Session {
+        client,
+        relay,
+        echoed,
+    })
+}
+
+fn run_relay(
+    rx: &Receiver<Frame>,
+    tx: &Sender<Frame>,
+    supported: &[Format],
+    priority: Priority,
+) -> Result<Codec, NegotiateError> {
+    let Ok(Frame::Handshake(offer)) = rx.recv() else {
+        return Err(NegotiateError::Disconnected);
+    };
+    let selection = select(offer.as_deref(), supported, priority)?;
+    let response = selection.response_header().map(str::to_string);
+    tx.send(Frame::Handshake(response))
+        .map_err(|_| NegotiateError::Disconnected)?;
+
+    let codec = selection.codec;
+    while let Ok(Frame::Data(data)) = rx.recv() {
+        let event = codec.deserialize(&data)?;
+        if tx.send(Frame::Data(codec.serialize(&event))).is_err() {
+            break;
+        }
+    }
+    Ok(codec)
+}
+
+fn run_client(
+    tx: &Sender<Frame>,
+    rx: &Receiver<Frame>,
+    preferences: &[Format],
+    events: &[NostrEvent],
+) -> Result<(Codec, Vec<NostrEvent>), NegotiateError> {
+    let offer = (!preferences.is_empty()).then(|| offer_header(preferences));
+    tx.send(Frame::Handshake(offer))
+        .map_err(|_| NegotiateError::Disconnected)?;
+    let Ok(Frame::Handshake(response)) = rx.recv() else {
+        return Err(NegotiateError::Disconnected);
+    };
+    let codec = accept(response.as_deref(), preferences)?;
+
+    let mut echoed = Vec::with_capacity(events.len());
+    for event in events {
+        tx.send(Frame::Data(codec.serialize(event)))
+            .map_err(|_| NegotiateError::Disconnected)?;
+        let Ok(Frame::Data(data)) = rx.recv() else {
+            return Err(NegotiateError::Disconnected);
+        };
+        echoed.push(codec.deserialize(&data)?);
+    }
+    Ok((codec, echoed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_event() -> NostrEvent {
+        NostrEvent {
+            id: [0xab; 32],
+            pubkey: [0xcd; 32],
+            created_at: 1234567890,
+            kind: 1,
+            tags: vec![vec!["p".to_string(), "cd".repeat(32)]],
+            content: "Hello, Nostr!".to_string(),
+            sig: [0xef; 64],
+        }
+    }
+
+    #[test]
+    fn test_tokens_are_unique_and_roundtrip() {
+        for &format in Format::all() {
+            assert_eq!(from_subprotocol(subprotocol(format)), Some(format));
+            assert_eq!(from_mime_type(mime_type(format)), Some(format));
+        }
+        assert_eq!(
+            from_mime_type("application/vnd.nostr.dannypack; q=0.9"),
+            Some(Format::DannyPack)
+        );
+        assert_eq!(from_subprotocol("nostr.cbor-packed.v2"), None);
+
+        let header = "chat, nostr.dannypack.v1,nostr.cbor-packed.v1 , nostr.json.v9";
+        assert_eq!(
+            parse_header(header),
+            vec![Format::DannyPack, Format::CborPacked]
+        );
+    }
+
+    #[test]
+    fn test_choose_priority() {
+        let client = [Format::DannyPack, Format::ProtoBinary, Format::Json];
+        let relay = [Format::Json, Format::ProtoBinary, Format::DannyPack];
+
+        assert_eq!(
+            choose(&client, &relay, Priority::Client),
+            Some(Format::DannyPack)
+        );
+        assert_eq!(choose(&client, &relay, Priority::Relay), Some(Format::Json));
+        assert_eq!(choose(&[Format::Notepack], &relay, Priority::Client), None);
+    }
+
+    #[test]
+    fn test_accept_rejects_unoffered() {
+        let offered = [Format::DannyPack];
+        assert!(matches!(
+            accept(Some("nostr.capnp.v1"), &offered),
+            Err(NegotiateError::NotOffered(_))
+        ));
+        assert_eq!(accept(None, &offered).unwrap().format, Format::Json);
+    }
+
+    #[test]
+    fn test_loopback() {
+        let events = vec![sample_event(); 3];
+
+        // Both sides agree on the client's favourite
+        let session = loopback(
+            &[Format::DannyPack, Format::CborPacked],
+            &[Format::Json, Format::CborPacked, Format::DannyPack],
+            Priority::Client,
+            &events,
+        )
+        .unwrap();
+        assert_eq!(session.client.format, Format::DannyPack);
+        assert_eq!(session.client, session.relay);
+        assert_eq!(session.echoed, events);
+
+        // Or on the relay's, if it has priority
+        let session = loopback(
+            &[Format::DannyPack, Format::CborPacked],
+            &[Format::Json, Format::CborPacked, Format::DannyPack],
+            Priority::Relay,
+            &events,
+        )
+        .unwrap();
+        assert_eq!(session.client.format, Format::CborPacked);
+        assert_eq!(session.echoed, events);
+
+        // Legacy client without subprotocols gets JSON
+        let session = loopback(
+            &[],
+            &[Format::Json, Format::DannyPack],
+            Priority::Client,
+            &events,
+        )
+        .unwrap();
+        assert_eq!(session.client.format, Format::Json);
+        assert!(session.client.is_text());
+        assert_eq!(session.echoed, events);
+
+        // Nothing in common falls back to JSON when the relay allows it
+        let session = loopback(
+            &[Format::Notepack],
+            &[Format::Json],
+            Priority::Client,
+            &events,
+        )
+        .unwrap();
+        assert_eq!(session.client.format, Format::Json);
+
+        // Binary-only relay and a client without subprotocols cannot talk
+        assert!(matches!(
+            loopback(&[], &[Format::DannyPack], Priority::Client, &events),
+            Err(NegotiateError::NoCommonFormat)
+        ));
+        assert!(matches!(
+            loopback(
+                &[Format::Notepack],
+                &[Format::DannyPack],
+                Priority::Client,
+                &events
+            ),
+            Err(NegotiateError::NoCommonFormat)
+        ));
+    }
+}
@@ -0,0 +1,510 @@
+//! NIP-19 bech32 entities
+//!
+//! Encodes and decodes `npub`, `nsec`, `note`, `nevent`, `nprofile` and
+//! `naddr`. The last three carry TLV records:
+//!
+//! | Type | Name    | Value                                         |
+//! |------|---------|-----------------------------------------------|
+//! | 0    | special | event id, pubkey or `d` tag (per entity)      |
+//! | 1    | relay   | relay URL, ASCII, may repeat                  |
+//! | 2    | author  | 32-byte pubkey                                |
+//! | 3    | kind    | 32-bit big-endian kind                        |
+//!
+//! Unknown TLV types are skipped, as NIP-19 requires. [`scan`] finds
+//! entities embedded in content (`nostr:npub1…` or bare), which every
+//! format currently stores as text although they are bech32-wrapped bytes.
+
+use std::fmt;
+use std::ops::Range;
+use std::str::FromStr;
+
+use bech32::primitives::decode::{CheckedHrpstring, CheckedHrpstringError};
+use bech32::{Bech32, Hrp};
+use thiserror::Error;
+
+use crate::tags::Coordinate;
+
+/// URI scheme prefix from NIP-21
+pub const URI_SCHEME: &str = "nostr:";
+
+/// Entity prefixes (bech32 human-readable parts)
+pub const PREFIXES: [&str; 6] = ["npub", "nsec", "note", "nevent", "nprofile", "naddr"];
+
+const TLV_SPECIAL: u8 = 0;
+const TLV_RELAY: u8 = 1;
+const TLV_AUTHOR: u8 = 2;
+const TLV_KIND: u8 = 3;
+
+#[derive(Debug, Error)]
+pub enum Nip19Error {
+    #[error("Bech32 error: {0}")]
+    Bech32(#[from] CheckedHrpstringError),
+
+    #[error("Encoded entity is too long: {0}")]
+    TooLong(#[from] bech32::EncodeError),
+
+    #[error("Unknown prefix: {0}")]
+    UnknownPrefix(String),
+
+    #[error("Invalid {prefix} payload length: {len}")]
+    InvalidLength { prefix: &'static str, len: usize },
+
+    #[error("TLV value of type {0} exceeds 255 bytes")]
+    ValueTooLong(u8),
+
+    #[error("Truncated TLV record")]
+    TruncatedTlv,
+
+    #[error("Invalid TLV record of type {0}")]
+    InvalidTlv(u8),
+
+    #[error("Missing TLV record of type {0}")]
+    MissingTlv(u8),
+}
+
+/// `nevent`: an event id with optional relays, author and kind
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EventPointer {
+    pub id: [u8; 32],
+    pub relays: Vec<String>,
+    pub author: Option<[u8; 32]>,
+    pub kind: Option<u32>,
+}
+
+/// `nprofile`: a pubkey with optional relays
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProfilePointer {
+    pub pubkey: [u8; 32],
+    pub relays: Vec<String>,
+}
+
+/// `naddr`: an addressable event coordinate with optional relays
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AddressPointer {
+    pub coordinate: Coordinate,
+    pub relays: Vec<String>,
+}
+
+/// A decoded NIP-19 entity
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Nip19 {
+    Pubkey([u8; 32]),
+    SecretKey([u8; 32]),
+    Note([u8; 32]),
+    Event(EventPointer),
+    Profile(ProfilePointer),
+    Address(AddressPointer),
+}
+
+impl Nip19 {
+    /// Bech32 human-readable part
+    pub fn prefix(&self) -> &'static str {
+        match self {
+            Nip19::Pubkey(_) => "npub",
+            Nip19::SecretKey(_) => "nsec",
+            Nip19::Note(_) => "note",
+            Nip19::Event(_) => "nevent",
+            Nip19::Profile(_) => "nprofile",
+            Nip19::Address(_) => "naddr",
+        }
+    }
+
+    /// Binary payload (raw key/id, or TLV records)
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Nip19Error> {
+        let mut out = Vec::new();
+        match self {
+            Nip19::Pubkey(bytes) | Nip19::SecretKey(bytes) | Nip19::Note(bytes) => {
+                out.extend_from_slice(bytes)
+            }
+            Nip19::Event(pointer) => {
+                write_tlv(&mut out, TLV_SPECIAL, &pointer.id)?;
+                write_relays(&mut out, &pointer.relays)?;
+                if let Some(author) = &pointer.author {
+                    write_tlv(&mut out, TLV_AUTHOR, author)?;
+                }
+                if let Some(kind) = pointer.kind {
+                    write_tlv(&mut out, TLV_KIND, &kind.to_be_bytes())?;
+                }
+            }
+            Nip19::Profile(pointer) => {
+                write_tlv(&mut out, TLV_SPECIAL, &pointer.pubkey)?;
+                write_relays(&mut out, &pointer.relays)?;
+            }
+            Nip19::Address(pointer) => {
+                let coordinate = &pointer.coordinate;
+                write_tlv(&mut out, TLV_SPECIAL, coordinate.identifier.as_bytes())?;
+                write_relays(&mut out, &pointer.relays)?;
+                write_tlv(&mut out, TLV_AUTHOR, &coordinate.pubkey)?;
+                write_tlv(&mut out, TLV_KIND, &(coordinate.kind as u32).to_be_bytes())?;
+            }
+        }
+        Ok(out)
+    }
+
+    /// Parse a binary payload for the given prefix
+    pub fn from_bytes(prefix: &str, data: &[u8]) -> Result<Self, Nip19Error> {
+        match prefix {
+            "npub" => Ok(Nip19::Pubkey(fixed("npub", data)?)),
+            "nsec" => Ok(Nip19::SecretKey(fixed("nsec", data)?)),
+            "note" => Ok(Nip19::Note(fixed("note", data)?)),
+            "nevent" => {
+                let tlv = Tlv::parse(data)?;
+                Ok(Nip19::Event(EventPointer {
+                    id: tlv.special32()?,
+                    relays: tlv.relays,
+                    author: tlv.author,
+                    kind: tlv.kind,
+                }))
+            }
+            "nprofile" => {
+                let tlv = Tlv::parse(data)?;
+                Ok(Nip19::Profile(ProfilePointer {
+                    pubkey: tlv.special32()?,
+                    relays: tlv.relays,
+                }))
+            }
+            "naddr" => {
+                let tlv = Tlv::parse(data)?;
+                let special = tlv.special.ok_or(Nip19Error::MissingTlv(TLV_SPECIAL))?;
+                let kind = tlv.kind.ok_or(Nip19Error::MissingTlv(TLV_KIND))?;
+                Ok(Nip19::Address(AddressPointer {
+                    coordinate: Coordinate {
+                        kind: u16::try_from(kind).map_err(|_| Nip19Error::InvalidTlv(TLV_KIND))?,
+                        pubkey: tlv.author.ok_or(Nip19Error::MissingTlv(TLV_AUTHOR))?,
+                        identifier: String::from_utf8(special)
+                            .map_err(|_| Nip19Error::InvalidTlv(TLV_SPECIAL))?,
+                    },
+                    relays: tlv.relays,
+                }))
+            }
+            _ => Err(Nip19Error::UnknownPrefix(prefix.to_string())),
+        }
+    }
+
+    /// Bech32 string, e.g. `npub1…`
+    pub fn encode(&self) -> Result<String, Nip19Error> {
+        encode_payload(self.prefix(), &self.to_bytes()?)
+    }
+
+    /// Decode a bech32 string, with or without the `nostr:` scheme
+    pub fn decode(s: &str) -> Result<Self, Nip19Error> {
+        let (prefix, data) = decode_payload(s)?;
+        Self::from_bytes(prefix, &data)
+    }
+}
+
+/// Bech32-encode a raw payload under a NIP-19 prefix
+pub fn encode_payload(prefix: &str, data: &[u8]) -> Result<String, Nip19Error> {
+    let hrp = Hrp::parse(prefix).map_err(|_| Nip19Error::UnknownPrefix(prefix.to_string()))?;
+    Ok(bech32::encode::<Bech32>(hrp, data)?)
+}
+
+/// Decode a bech32 string into its prefix and raw payload, without
+/// interpreting the payload
+pub fn decode_payload(s: &str) -> Result<(&'static str, Vec<u8>), Nip19Error> {
+    let s = s.strip_prefix(URI_SCHEME).unwrap_or(s);
+    let checked = CheckedHrpstring::new::<Bech32>(s)?;
+    let hrp = checked.hrp().to_lowercase();
+    let prefix = PREFIXES
+        .iter()
+        .find(|&&p| p == hrp)
+        .ok_or(Nip19Error::UnknownPrefix(hrp))?;
+    Ok((prefix, checked.byte_iter().collect()))
+}
+
+impl fmt::Display for Nip19 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let encoded = self.encode().map_err(|_| fmt::Error)?;
+        f.write_str(&encoded)
+    }
+}
+
+impl FromStr for Nip19 {
+    type Err = Nip19Error;
+
+    fn from_str(s: &str) -> Result<Self, Nip19Error> {
+        Self::decode(s)
+    }
+}
+
+/// A NIP-19 entity found in text by [`scan`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reference<'a> {
+    /// Byte range in the scanned text, including any `nostr:` scheme
+    pub range: Range<usize>,
+    pub text: &'a str,
+    pub entity: Nip19,
+}
+
+impl Reference<'_> {
+    /// Whether the reference is a `nostr:` URI rather than a bare entity
+    pub fn is_uri(&self) -> bool {
+        self.text.starts_with(URI_SCHEME)
+    }
+
+    /// Size of the binary payload the text stands for
+    pub fn binary_len(&self) -> usize {
+        // Scanned entities were decoded from valid TLVs, so they re-encode
+        self.entity.to_bytes().map_or(0, |bytes| bytes.len())
+    }
+}
+
+/// Find all valid NIP-19 entities in a string
+///
+/// Matches start at a word boundary; candidates that fail to decode
+/// (bad checksum, unknown TLV layout) are skipped.
+pub fn scan(text: &str) -> Vec<Reference<'_>> {
+    let bytes = text.as_bytes();
+    let mut references = Vec::new();
+    let mut pos = 0;
+
+    while pos < bytes.len() {
+        let at_boundary = pos == 0 || !bytes[pos - 1].is_ascii_alphanumeric();
+        let Some(prefix) = PREFIXES
+            .iter()
+            .find(|p| at_boundary && bytes[pos..].starts_with(p.as_bytes()))
+        else {
+            pos += 1;
+            continue;
+        };
+
+        let data_start = pos + prefix.len();
+        if bytes.get(data_start) != Some(&b'1') {
+            pos += 1;
+            continue;
+        }
+        let end = data_start
+            + 1
+            + bytes[data_start + 1..]
+                .iter()
+                .take_while(|&&b| is_bech32_char(b))
+                .count();
+
+        match Nip19::decode(&text[pos..end]) {
+            Ok(entity) => {
+                let start = if text[..pos].ends_with(URI_SCHEME) {
+                    pos - URI_SCHEME.len()
+                } else {
+                    pos
+                };
+                references.push(Reference {
+                    range: start..end,
+                    text: &text[start..end],
+                    entity,
+                });
+                pos = end;
+            }
+            Err(_) => pos += 1,
+        }
+    }
+
+    references
+}
+
+fn is_bech32_char(b: u8) -> bool {
+    matches!(b, b'0' | b'2'..=b'9' | b'a'..=b'z') && !matches!(b, b'b' | b'i' | b'o')
+}
+
+fn fixed(prefix: &'static str, data: &[u8]) -> Result<[u8; 32], Nip19Error> {
+    data.try_into().map_err(|_| Nip19Error::InvalidLength {
+        prefix,
+        len: data.len(),
+    })
+}
+
+fn write_tlv(out: &mut Vec<u8>, ty: u8, value: &[u8]) -> Result<(), Nip19Error> {
+    let len = u8::try_from(value.len()).map_err(|_| Nip19Error::ValueTooLong(ty))?;
+    out.push(ty);
+    out.push(len);
+    out.extend_from_slice(value);
+    Ok(())
+}
+
+fn write_relays(out: &mut Vec<u8>, relays: &[String]) -> Result<(), Nip19Error> {
+    relays
+        .iter()
+        .try_for_each(|relay| write_tlv(out, TLV_RELAY, relay.as_bytes()))
+}
+
+/// Parsed TLV records; first occurrence wins except for relays
+#[derive(Default)]
+struct Tlv {
+    special: Option<Vec<u8>>,
+    relays: Vec<String>,
+    author: Option<[u8; 32]>,
+    kind: Option<u32>,
+}
+
+impl Tlv {
+    fn parse(mut data: &[u8]) -> Result<Self, Nip19Error> {
+        let mut tlv = Tlv::default();
+        while !data.is_empty() {
+            let [ty, len, rest @ ..] = data else {
+                return Err(Nip19Error::TruncatedTlv);
+            };
+            let (value, rest) = rest
+                .split_at_checked(*len as usize)
+                .ok_or(Nip19Error::TruncatedTlv)?;
+            data = rest;
+
+            let invalid = || Nip19Error::InvalidTlv(*ty);
+            match *ty {
+                TLV_SPECIAL if tlv.special.is_none() => tlv.special = Some(value.to_vec()),
+                TLV_RELAY => tlv
+                    .relays
+                    .push(String::from_utf8(value.to_vec()).map_err(|_| invalid())?),
+                TLV_AUTHOR if tlv.author.is_none() => {
+                    tlv.author = Some(value.try_into().map_err(|_| invalid())?)
+                }
+                TLV_KIND if tlv.kind.is_none() => {
+                    tlv.kind = Some(u32::from_be_bytes(value.try_into().map_err(|_| invalid())?))
+                }
+                _ => {}
+            }
+        }
+        Ok(tlv)
+    }
+
+    fn special32(&self) -> Result<[u8; 32], Nip19Error> {
+        self.special
+            .as_deref()
+            .ok_or(Nip19Error::MissingTlv(TLV_SPECIAL))?
+            .try_into()
+            .map_err(|_| Nip19Error::InvalidTlv(TLV_SPECIAL))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test vectors from NIP-19
+    const NPUB: &str = "npub10elfcs4fr0l0r8af98jlmgdh9c8tcxjvz9qkw038js35mp4dma8qzvjptg";
+    const NPUB_HEX: &str = "7e7e9c42a91bfef19fa929e5fda1b72e0ebc1a4c1141673e2794234d86addf4e";
+    const NPROFILE: &str = "nprofile1qqsrhuxx8l9ex335q7he0f09aej04zpazpl0ne2cgukyawd24mayt8gpp4mhxue69uhhytnc9e3k7mgpz4mhxue69uhkg6nzv9ejuumpv34kytnrdaksjlyr9p";
+    const NPROFILE_HEX: &str = "3bf0c63fcb93463407af97a5e5ee64fa883d107ef9e558472c4eb9aaaefa459d";
+
+    #[test]
+    fn test_nip19_vectors() {
+        let npub = Nip19::decode(NPUB).unwrap();
+        assert_eq!(
+            npub,
+            Nip19::Pubkey(hex::decode(NPUB_HEX).unwrap().try_into().unwrap())
+        );
+        assert_eq!(npub.encode().unwrap(), NPUB);
+
+        let Nip19::Profile(profile) = Nip19::decode(NPROFILE).unwrap() else {
+            panic!("expected nprofile");
+        };
+        assert_eq!(hex::encode(profile.pubkey), NPROFILE_HEX);
+        assert_eq!(
+            profile.relays,
+            vec!["wss://r.x.com", "wss://djbas.sadkb.com"]
+        );
+        assert_eq!(Nip19::Profile(profile).encode().unwrap(), NPROFILE);
+    }
+
+    #[test]
+    fn test_tlv_roundtrip() {
+        let entities = [
+            Nip19::SecretKey([0x11; 32]),
+            Nip19::Note([0x22; 32]),
+            Nip19::Event(EventPointer {
+                id: [0x33; 32],
+                relays: vec!["wss://relay.example.com".to_string()],
+                author: Some([0x44; 32]),
+                kind: Some(1),
+            }),
+            Nip19::Event(EventPointer {
+                id: [0x33; 32],
+                relays: vec![],
+                author: None,
+                kind: None,
+            }),
+            Nip19::Address(AddressPointer {
+                coordinate: Coordinate {
+                    kind: 30023,
+                    pubkey: [0x55; 32],
+                    identifier: "my-article".to_string(),
+                },
+                relays: vec!["wss://a.example".to_string(), "wss://b.example".to_string()],
+            }),
+        ];
+
+        for entity in entities {
+            let encoded = entity.to_string();
+            assert!(encoded.starts_with(entity.prefix()));
+            assert_eq!(encoded.parse::<Nip19>().unwrap(), entity);
+            assert_eq!(
+                Nip19::decode(&format!("nostr:{}", encoded)).unwrap(),
+                entity
+            );
+        }
+
+        // Unknown TLV types are ignored
+        let mut data = Nip19::Note([0x22; 32]).to_bytes().unwrap();
+        data.splice(0..0, [TLV_SPECIAL, 32]);
+        data.extend_from_slice(&[9, 2, 0xaa, 0xbb]);
+        let profile = Nip19::from_bytes("nprofile", &data).unwrap();
+        assert!(matches!(profile, Nip19::Profile(p) if p.pubkey == [0x22; 32]));
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        let mut bad = NPUB.to_string();
+        bad.pop();
+        bad.push('q');
+        assert!(matches!(Nip19::decode(&bad), Err(Nip19Error::Bech32(_))));
+
+        let short = bech32::encode::<Bech32>(Hrp::parse_unchecked("npub"), &[1, 2, 3]).unwrap();
+        assert!(matches!(
+            Nip19::decode(&short),
+            Err(Nip19Error::InvalidLength {
+                prefix: "npub",
+                len: 3
+            })
+        ));
+
+        let other = bech32::encode::<Bech32>(Hrp::parse_unchecked("lnurl"), &[1]).unwrap();
+        assert!(matches!(
+            Nip19::decode(&other),
+            Err(Nip19Error::UnknownPrefix(_))
+        ));
+
+        assert!(matches!(
+            Nip19::from_bytes("nevent", &[TLV_SPECIAL, 32, 1, 2]),
+            Err(Nip19Error::TruncatedTlv)
+        ));
+        assert!(matches!(
+            Nip19::from_bytes("naddr", &[TLV_SPECIAL, 1, b'x']),
+            Err(Nip19Error::MissingTlv(_))
+        ));
+
+        let long_relay = Nip19::Profile(ProfilePointer {
+            pubkey: [0x11; 32],
+            relays: vec!["x".repeat(256)],
+        });
+        assert!(matches!(
+            long_relay.encode(),
+            Err(Nip19Error::ValueTooLong(TLV_RELAY))
+        ));
+    }
+
+    #[test]
+    fn test_scan() {
+        let note = Nip19::Note([0x22; 32]).to_string();
+        let text = format!(
+            "gm nostr:{} see {}, not x{} or {}zz",
+            NPUB, note, NPUB, "npub1qqqq"
+        );
+
+        let refs = scan(&text);
+        assert_eq!(refs.len(), 2);
+        assert!(refs[0].is_uri());
+        assert_eq!(refs[0].text, format!("nostr:{}", NPUB));
+        assert_eq!(&text[refs[0].range.clone()], refs[0].text);
+        assert_eq!(refs[0].binary_len(), 32);
+        assert!(!refs[1].is_uri());
+        assert_eq!(refs[1].entity, Nip19::Note([0x22; 32]));
+    }
+}
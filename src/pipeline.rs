@@ -0,0 +1,702 @@
+//! Composable encoding optimizations for ablation studies
+//!
+//! Each [`Optimization`] is a reversible transform over a batch of events:
+//! it moves what it can compact into a side stream and leaves a residual
+//! event behind (an emptied value, a zeroed field) for the base format to
+//! carry. A [`Pipeline`] applies any subset of them in [`Optimization::ALL`]
+//! order on top of any base [`Format`], written with
+//! [`stats::serialize_plain`] so the format's own hex compression doesn't
+//! mask the transform's:
+//!
+//! ```text
+//! [optimizations: u8 bitmask] [base format id: u8] [event count: varint]
+//! per enabled optimization: [side stream len: varint] [side stream]
+//! per event: [residual len: varint] [residual in the base format]
+//! ```
+//!
+//! Side streams address values by slot: every event's content, then the
+//! values of each tag after its name, counted across the batch.
+//!
+//! Zeroed `created_at` and pubkey fields only shrink in formats that store
+//! them as varints, or under compression, so a [`Measurement`] records the
+//! zstd size alongside the raw one. [`Ablation`] measures each optimization
+//! alone and left out of the full set, so its marginal size and speed
+//! contribution can be read off.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use thiserror::Error;
+
+use crate::compact::{self, CompactError, CompactOptions};
+use crate::dannypack::{unzigzag, write_varint, zigzag, Reader, Truncated};
+use crate::dictionary;
+use crate::event::NostrEvent;
+use crate::stats::{self, Format, FormatError};
+use crate::tags::is_compressible_hex;
+
+/// Shortest hex value worth moving to the side stream
+const MIN_HEX_LEN: usize = 8;
+
+/// Encodes and decodes timed per pipeline by [`Pipeline::measure`]
+const MEASURE_RUNS: usize = 3;
+
+#[derive(Debug, Error)]
+pub enum PipelineError {
+    #[error("Data is truncated")]
+    Truncated,
+
+    #[error("Unknown format id: {0}")]
+    UnknownFormat(u8),
+
+    #[error("Unknown optimization bits: {0:#04x}")]
+    UnknownOptimization(u8),
+
+    #[error("Unknown dictionary version: {0}")]
+    UnknownDictionary(u8),
+
+    #[error("Unknown dictionary code: {0}")]
+    UnknownDictionaryCode(u8),
+
+    #[error("Index out of range in {0} side stream")]
+    InvalidIndex(&'static str),
+
+    #[error("Decoded events differ from the input")]
+    Mismatch,
+
+    #[error("Compacted value error: {0}")]
+    Compact(#[from] CompactError),
+
+    #[error(transparent)]
+    Format(#[from] FormatError),
+}
+
+impl From<Truncated> for PipelineError {
+    fn from(_: Truncated) -> Self {
+        PipelineError::Truncated
+    }
+}
+
+/// One reversible transform
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Optimization {
+    /// Each distinct pubkey, whether an author or a `p` tag value, stored
+    /// once; events and tags refer to it by index
+    PubkeyInterning,
+    /// Tag names and relay URLs as [`dictionary::V1`] codes
+    TagDictionary,
+    /// Lowercase hex values as raw bytes
+    HexCompaction,
+    /// Base64 runs (NIP-04 and NIP-44 payloads) as raw bytes, via
+    /// [`compact`]
+    Base64Compaction,
+    /// `created_at` as a zigzag varint delta from the previous event
+    CreatedAtDelta,
+}
+
+impl Optimization {
+    /// Every optimization, in the order a pipeline applies them
+    pub const ALL: [Optimization; 5] = [
+        Optimization::PubkeyInterning,
+        Optimization::TagDictionary,
+        Optimization::HexCompaction,
+        Optimization::Base64Compaction,
+        Optimization::CreatedAtDelta,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Optimization::PubkeyInterning => "Pubkey interning",
+            Optimization::TagDictionary => "Tag dictionary",
+            Optimization::HexCompaction => "Hex compaction",
+            Optimization::Base64Compaction => "Base64 compaction",
+            Optimization::CreatedAtDelta => "created_at delta",
+        }
+    }
+
+    fn bit(&self) -> u8 {
+        match self {
+            Optimization::PubkeyInterning => 0x01,
+            Optimization::TagDictionary => 0x02,
+            Optimization::HexCompaction => 0x04,
+            Optimization::Base64Compaction => 0x08,
+            Optimization::CreatedAtDelta => 0x10,
+        }
+    }
+
+    /// Move what this optimization compacts out of `events`, returning the
+    /// side stream
+    fn forward(&self, events: &mut [NostrEvent]) -> Vec<u8> {
+        let mut side = Vec::new();
+        match self {
+            Optimization::PubkeyInterning => {
+                let mut table: HashMap<[u8; 32], u64> = HashMap::new();
+                let mut keys = Vec::new();
+                let mut intern = |key: [u8; 32]| {
+                    let next = table.len() as u64;
+                    *table.entry(key).or_insert_with(|| {
+                        keys.push(key);
+                        next
+                    })
+                };
+                let authors: Vec<u64> = events
+                    .iter_mut()
+                    .map(|event| intern(std::mem::take(&mut event.pubkey)))
+                    .collect();
+                let refs: Vec<_> = pubkey_values(events)
+                    .filter_map(|(i, value)| {
+                        let mut key = [0; 32];
+                        hex::decode_to_slice(&*value, &mut key).ok()?;
+                        if hex::encode(key) != *value {
+                            return None;
+                        }
+                        value.clear();
+                        Some((i, intern(key)))
+                    })
+                    .collect();
+
+                write_varint(&mut side, keys.len() as u64);
+                for key in &keys {
+                    side.extend_from_slice(key);
+                }
+                for index in authors {
+                    write_varint(&mut side, index);
+                }
+                write_varint(&mut side, refs.len() as u64);
+                let mut previous = 0;
+                for (i, index) in refs {
+                    write_varint(&mut side, (i - previous) as u64);
+                    write_varint(&mut side, index);
+                    previous = i;
+                }
+            }
+            Optimization::TagDictionary => {
+                let dictionary = &dictionary::V1;
+                side.push(dictionary.version);
+                let names: Vec<_> = tag_names(events)
+                    .filter_map(|(i, name)| {
+                        let code = dictionary.tag_name_code(name)?;
+                        name.clear();
+                        Some((i, code))
+                    })
+                    .collect();
+                write_codes(&mut side, &names);
+                let relays: Vec<_> = slots(events)
+                    .filter_map(|(i, value)| {
+                        let code = dictionary.relay_code(value)?;
+                        value.clear();
+                        Some((i, code))
+                    })
+                    .collect();
+                write_codes(&mut side, &relays);
+            }
+            Optimization::HexCompaction => {
+                let entries: Vec<_> = slots(events)
+                    .filter(|(_, value)| value.len() >= MIN_HEX_LEN && is_compressible_hex(value))
+                    .map(|(i, value)| {
+                        let bytes = hex::decode(&*value).expect("checked hex");
+                        value.clear();
+                        (i, bytes)
+                    })
+                    .collect();
+                write_entries(&mut side, &entries);
+            }
+            Optimization::Base64Compaction => {
+                let options = CompactOptions::new().with_base64(true);
+                let entries: Vec<_> = slots(events)
+                    .filter_map(|(i, value)| {
+                        let body = compact::compact(value, &options)?;
+                        value.clear();
+                        Some((i, body))
+                    })
+                    .collect();
+                write_entries(&mut side, &entries);
+            }
+            Optimization::CreatedAtDelta => {
+                let mut previous = 0i64;
+                for event in events {
+                    write_varint(&mut side, zigzag(event.created_at.wrapping_sub(previous)));
+                    previous = event.created_at;
+                    event.created_at = 0;
+                }
+            }
+        }
+        side
+    }
+
+    /// Restore what [`forward`](Self::forward) moved into `side`
+    fn inverse(&self, events: &mut [NostrEvent], side: &[u8]) -> Result<(), PipelineError> {
+        let mut reader = Reader::new(side);
+        match self {
+            Optimization::PubkeyInterning => {
+                let count = reader.varint()? as usize;
+                let keys = reader.bytes(count.checked_mul(32).ok_or(PipelineError::Truncated)?)?;
+                let key = |index: u64| {
+                    usize::try_from(index)
+                        .ok()
+                        .and_then(|i| keys.chunks_exact(32).nth(i))
+                        .ok_or(PipelineError::InvalidIndex("pubkey"))
+                };
+                for event in events.iter_mut() {
+                    event.pubkey.copy_from_slice(key(reader.varint()?)?);
+                }
+                let count = reader.varint()? as usize;
+                let mut refs = Vec::with_capacity(count.min(side.len()));
+                let mut i = 0usize;
+                for _ in 0..count {
+                    i = i
+                        .checked_add(reader.varint()? as usize)
+                        .ok_or(PipelineError::Truncated)?;
+                    refs.push((i, reader.varint()?));
+                }
+                restore(pubkey_values(events), refs, "pubkey", |index| {
+                    Ok(hex::encode(key(index)?))
+                })?;
+            }
+            Optimization::TagDictionary => {
+                let version = reader.byte()?;
+                let dictionary =
+                    dictionary::get(version).ok_or(PipelineError::UnknownDictionary(version))?;
+                let names = read_codes(&mut reader)?;
+                let relays = read_codes(&mut reader)?;
+                restore(tag_names(events), names, "tag name", |code| {
+                    dictionary
+                        .tag_name(code)
+                        .map(str::to_string)
+                        .ok_or(PipelineError::UnknownDictionaryCode(code))
+                })?;
+                restore(slots(events), relays, "relay", |code| {
+                    dictionary
+                        .relay(code)
+                        .map(str::to_string)
+                        .ok_or(PipelineError::UnknownDictionaryCode(code))
+                })?;
+            }
+            Optimization::HexCompaction => {
+                let entries = read_entries(&mut reader)?;
+                restore(slots(events), entries, "hex", |bytes| {
+                    Ok(hex::encode(bytes))
+                })?;
+            }
+            Optimization::Base64Compaction => {
+                let entries = read_entries(&mut reader)?;
+                restore(slots(events), entries, "base64", |body| {
+                    Ok(compact::expand(body)?)
+                })?;
+            }
+            Optimization::CreatedAtDelta => {
+                let mut previous = 0i64;
+                for event in events {
+                    event.created_at = previous.wrapping_add(unzigzag(reader.varint()?));
+                    previous = event.created_at;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A base format with a set of optimizations on top
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pipeline {
+    pub base: Format,
+    mask: u8,
+}
+
+impl Pipeline {
+    /// `base` alone, with no optimizations
+    pub fn new(base: Format) -> Self {
+        Self { base, mask: 0 }
+    }
+
+    /// `base` with every optimization
+    pub fn all(base: Format) -> Self {
+        Optimization::ALL
+            .iter()
+            .fold(Self::new(base), |pipeline, &opt| pipeline.with(opt))
+    }
+
+    pub fn with(mut self, optimization: Optimization) -> Self {
+        self.mask |= optimization.bit();
+        self
+    }
+
+    pub fn without(mut self, optimization: Optimization) -> Self {
+        self.mask &= !optimization.bit();
+        self
+    }
+
+    pub fn is_enabled(&self, optimization: Optimization) -> bool {
+        self.mask & optimization.bit() != 0
+    }
+
+    /// Enabled optimizations, in the order they are applied
+    pub fn optimizations(&self) -> impl Iterator<Item = Optimization> + '_ {
+        Optimization::ALL
+            .into_iter()
+            .filter(|opt| self.is_enabled(*opt))
+    }
+
+    pub fn encode(&self, events: &[NostrEvent]) -> Vec<u8> {
+        let mut residual = events.to_vec();
+        let sides: Vec<Vec<u8>> = self
+            .optimizations()
+            .map(|opt| opt.forward(&mut residual))
+            .collect();
+
+        let mut buf = vec![self.mask, self.base.id()];
+        write_varint(&mut buf, events.len() as u64);
+        for side in &sides {
+            write_varint(&mut buf, side.len() as u64);
+            buf.extend_from_slice(side);
+        }
+        for event in &residual {
+            let payload = stats::serialize_plain(event, self.base);
+            write_varint(&mut buf, payload.len() as u64);
+            buf.extend_from_slice(&payload);
+        }
+        buf
+    }
+
+    /// Encode and decode `events`, checking that they come back unchanged
+    ///
+    /// Times are the best of several runs.
+    pub fn measure(&self, events: &[NostrEvent]) -> Result<Measurement, PipelineError> {
+        let mut encode = Duration::MAX;
+        let mut decode_time = Duration::MAX;
+        let mut data = Vec::new();
+        for _ in 0..MEASURE_RUNS {
+            let start = Instant::now();
+            data = self.encode(events);
+            encode = encode.min(start.elapsed());
+
+            let start = Instant::now();
+            let decoded = decode(&data)?;
+            decode_time = decode_time.min(start.elapsed());
+
+            if decoded != events {
+                return Err(PipelineError::Mismatch);
+            }
+        }
+        Ok(Measurement {
+            bytes: data.len(),
+            zstd_bytes: stats::zstd_size(&data),
+            encode,
+            decode: decode_time,
+        })
+    }
+}
+
+/// Decode a batch written by [`Pipeline::encode`]
+pub fn decode(data: &[u8]) -> Result<Vec<NostrEvent>, PipelineError> {
+    let mut reader = Reader::new(data);
+    let mask = reader.byte()?;
+    let known = Optimization::ALL
+        .iter()
+        .fold(0, |bits, opt| bits | opt.bit());
+    if mask & !known != 0 {
+        return Err(PipelineError::UnknownOptimization(mask & !known));
+    }
+    let id = reader.byte()?;
+    let base = Format::from_id(id).ok_or(PipelineError::UnknownFormat(id))?;
+    let pipeline = Pipeline { base, mask };
+
+    let count = reader.varint()? as usize;
+    let mut sides = Vec::new();
+    for opt in pipeline.optimizations() {
+        let len = reader.varint()? as usize;
+        sides.push((opt, reader.bytes(len)?));
+    }
+
+    let mut events = Vec::with_capacity(count.min(data.len()));
+    for _ in 0..count {
+        let len = reader.varint()? as usize;
+        events.push(stats::deserialize(reader.bytes(len)?, base)?);
+    }
+    for (opt, side) in sides.into_iter().rev() {
+        opt.inverse(&mut events, side)?;
+    }
+    Ok(events)
+}
+
+/// Size and best-of-runs times of one pipeline over a sample
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Measurement {
+    pub bytes: usize,
+    pub zstd_bytes: usize,
+    pub encode: Duration,
+    pub decode: Duration,
+}
+
+/// Every optimization measured alone and left out of the full set, on one
+/// base format
+#[derive(Debug, Clone)]
+pub struct Ablation {
+    pub base: Format,
+    /// No optimizations
+    pub baseline: Measurement,
+    /// Every optimization
+    pub full: Measurement,
+    /// Each optimization alone, in [`Optimization::ALL`] order
+    pub alone: Vec<(Optimization, Measurement)>,
+    /// Every optimization but one, in [`Optimization::ALL`] order
+    pub without: Vec<(Optimization, Measurement)>,
+}
+
+impl Ablation {
+    pub fn run(base: Format, events: &[NostrEvent]) -> Result<Self, PipelineError> {
+        let full = Pipeline::all(base);
+        let mut alone = Vec::new();
+        let mut without = Vec::new();
+        for opt in Optimization::ALL {
+            alone.push((opt, Pipeline::new(base).with(opt).measure(events)?));
+            without.push((opt, full.without(opt).measure(events)?));
+        }
+        Ok(Self {
+            base,
+            baseline: Pipeline::new(base).measure(events)?,
+            full: full.measure(events)?,
+            alone,
+            without,
+        })
+    }
+
+    /// Raw and zstd bytes saved by `optimization` on its own
+    pub fn alone_savings(&self, optimization: Optimization) -> (i64, i64) {
+        savings(&self.baseline, lookup(&self.alone, optimization))
+    }
+
+    /// Raw and zstd bytes saved by `optimization` on top of all the others
+    pub fn marginal_savings(&self, optimization: Optimization) -> (i64, i64) {
+        savings(lookup(&self.without, optimization), &self.full)
+    }
+
+    /// Encode and decode time `optimization` adds on top of all the others,
+    /// in seconds (negative when leaving it out is slower)
+    pub fn marginal_time(&self, optimization: Optimization) -> (f64, f64) {
+        let without = lookup(&self.without, optimization);
+        (
+            self.full.encode.as_secs_f64() - without.encode.as_secs_f64(),
+            self.full.decode.as_secs_f64() - without.decode.as_secs_f64(),
+        )
+    }
+}
+
+fn savings(before: &Measurement, after: &Measurement) -> (i64, i64) {
+    (
+        before.bytes as i64 - after.bytes as i64,
+        before.zstd_bytes as i64 - after.zstd_bytes as i64,
+    )
+}
+
+fn lookup(
+    measurements: &[(Optimization, Measurement)],
+    optimization: Optimization,
+) -> &Measurement {
+    measurements
+        .iter()
+        .find(|(opt, _)| *opt == optimization)
+        .map(|(_, measurement)| measurement)
+        .expect("every optimization is measured")
+}
+
+/// Content and tag values (not names), numbered across the batch
+fn slots(events: &mut [NostrEvent]) -> impl Iterator<Item = (usize, &mut String)> {
+    events
+        .iter_mut()
+        .flat_map(|event| {
+            std::iter::once(&mut event.content)
+                .chain(event.tags.iter_mut().flat_map(|tag| tag.iter_mut().skip(1)))
+        })
+        .enumerate()
+}
+
+/// Values of `p` tags, numbered by tag across the batch
+fn pubkey_values(events: &mut [NostrEvent]) -> impl Iterator<Item = (usize, &mut String)> {
+    events
+        .iter_mut()
+        .flat_map(|event| event.tags.iter_mut())
+        .enumerate()
+        .filter(|(_, tag)| tag.first().is_some_and(|name| name == "p"))
+        .filter_map(|(i, tag)| tag.get_mut(1).map(|value| (i, value)))
+}
+
+/// Tag names, numbered by tag across the batch
+fn tag_names(events: &mut [NostrEvent]) -> impl Iterator<Item = (usize, &mut String)> {
+    events
+        .iter_mut()
+        .flat_map(|event| event.tags.iter_mut())
+        .enumerate()
+        .filter_map(|(i, tag)| tag.first_mut().map(|name| (i, name)))
+}
+
+/// Put values back at the indexed positions of `targets`
+fn restore<'a, T>(
+    targets: impl Iterator<Item = (usize, &'a mut String)>,
+    entries: Vec<(usize, T)>,
+    stream: &'static str,
+    mut value: impl FnMut(T) -> Result<String, PipelineError>,
+) -> Result<(), PipelineError> {
+    let mut entries = entries.into_iter().peekable();
+    for (i, target) in targets {
+        let Some((index, _)) = entries.peek() else {
+            return Ok(());
+        };
+        if *index == i {
+            let (_, entry) = entries.next().expect("peeked");
+            *target = value(entry)?;
+        }
+    }
+    match entries.next() {
+        Some(_) => Err(PipelineError::InvalidIndex(stream)),
+        None => Ok(()),
+    }
+}
+
+/// `[count: varint]` then per entry `[index delta: varint] [code: u8]`
+fn write_codes(buf: &mut Vec<u8>, codes: &[(usize, u8)]) {
+    write_varint(buf, codes.len() as u64);
+    let mut previous = 0;
+    for &(index, code) in codes {
+        write_varint(buf, (index - previous) as u64);
+        buf.push(code);
+        previous = index;
+    }
+}
+
+fn read_codes(reader: &mut Reader) -> Result<Vec<(usize, u8)>, PipelineError> {
+    let count = reader.varint()? as usize;
+    let mut codes = Vec::with_capacity(count.min(reader.remaining().len()));
+    let mut index = 0usize;
+    for _ in 0..count {
+        index = index
+            .checked_add(reader.varint()? as usize)
+            .ok_or(PipelineError::Truncated)?;
+        codes.push((index, reader.byte()?));
+    }
+    Ok(codes)
+}
+
+/// `[count: varint]` then per entry `[index delta: varint] [len: varint] [bytes]`
+fn write_entries(buf: &mut Vec<u8>, entries: &[(usize, Vec<u8>)]) {
+    write_varint(buf, entries.len() as u64);
+    let mut previous = 0;
+    for (index, bytes) in entries {
+        write_varint(buf, (index - previous) as u64);
+        write_varint(buf, bytes.len() as u64);
+        buf.extend_from_slice(bytes);
+        previous = *index;
+    }
+}
+
+fn read_entries<'a>(reader: &mut Reader<'a>) -> Result<Vec<(usize, &'a [u8])>, PipelineError> {
+    let count = reader.varint()? as usize;
+    let mut entries = Vec::with_capacity(count.min(reader.remaining().len()));
+    let mut index = 0usize;
+    for _ in 0..count {
+        index = index
+            .checked_add(reader.varint()? as usize)
+            .ok_or(PipelineError::Truncated)?;
+        let len = reader.varint()? as usize;
+        entries.push((index, reader.bytes(len)?));
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_event() -> NostrEvent {
+        NostrEvent {
+            id: [0xab; 32],
+            pubkey: [0xcd; 32],
+            created_at: 1234567890,
+            kind: 1,
+            tags: vec![
+                vec![
+                    "e".to_string(),
+                    "ab".repeat(32),
+                    "wss://relay.damus.io".to_string(),
+                ],
+                vec!["p".to_string(), "cd".repeat(32)],
+                vec!["t".to_string(), "nostr".to_string()],
+                vec![],
+            ],
+            content: "Hello, Nostr!".to_string(),
+            sig: [0xef; 64],
+        }
+    }
+
+    fn sample_batch() -> Vec<NostrEvent> {
+        let mut dm = sample_event();
+        dm.kind = 4;
+        dm.created_at += 60;
+        dm.content = "dGhpcyBpcyBhbiBlbmNyeXB0ZWQgbWVzc2FnZQ==?iv=AAECAwQFBgcICQoLDA0ODw==".into();
+        let mut other = sample_event();
+        other.pubkey = [0x11; 32];
+        other.created_at -= 5;
+        other.tags[2][1] = String::new();
+        vec![sample_event(), dm, other, sample_event()]
+    }
+
+    #[test]
+    fn test_roundtrip_every_subset() {
+        let events = sample_batch();
+        for &base in Format::all() {
+            for mask in 0..1u8 << Optimization::ALL.len() {
+                let pipeline = Optimization::ALL
+                    .iter()
+                    .enumerate()
+                    .filter(|(bit, _)| mask & (1 << bit) != 0)
+                    .fold(Pipeline::new(base), |p, (_, &opt)| p.with(opt));
+                let data = pipeline.encode(&events);
+                assert_eq!(
+                    decode(&data).unwrap(),
+                    events,
+                    "{} {:#04x}",
+                    base.name(),
+                    mask
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_each_optimization_saves() {
+        let events = sample_batch();
+        let baseline = Pipeline::new(Format::CborPacked).encode(&events).len();
+        for opt in Optimization::ALL {
+            let size = Pipeline::new(Format::CborPacked)
+                .with(opt)
+                .encode(&events)
+                .len();
+            assert!(size < baseline, "{}: {} >= {}", opt.name(), size, baseline);
+        }
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        let data = Pipeline::all(Format::DannyPack).encode(&sample_batch());
+        assert!(matches!(decode(&[]), Err(PipelineError::Truncated)));
+        assert!(matches!(
+            decode(&[0x80, Format::Json.id(), 0]),
+            Err(PipelineError::UnknownOptimization(0x80))
+        ));
+        assert!(matches!(
+            decode(&[0, 200, 0]),
+            Err(PipelineError::UnknownFormat(200))
+        ));
+        assert!(decode(&data[..data.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_ablation() {
+        let events = sample_batch();
+        let ablation = Ablation::run(Format::ProtoBinary, &events).unwrap();
+        assert!(ablation.full.bytes < ablation.baseline.bytes);
+        assert!(ablation.alone_savings(Optimization::HexCompaction).0 > 0);
+        assert!(ablation.marginal_savings(Optimization::HexCompaction).0 > 0);
+        assert_eq!(ablation.alone.len(), Optimization::ALL.len());
+    }
+}
@@ -0,0 +1,216 @@
+//! Structured binary encoding of kind 0 profile metadata
+//!
+//! Kind 0 content is a stringified JSON object. Instead of storing it as an
+//! opaque string, each field is stored with a one-byte key code (known NIP-01
+//! and NIP-24 keys by index, anything else by name) and its value:
+//!
+//! ```text
+//! [field_count: varint]
+//! per field: [key: u8] [name_len: varint, name if key code is 0] [len: varint] [value]
+//! ```
+//!
+//! Bit 7 of the key byte marks a value stored as raw JSON (numbers, booleans,
+//! nested objects); without it the value is the unescaped string.
+//!
+//! Decoding re-emits compact JSON with `serde_json` string escaping, so only
+//! content already in that form can be encoded. [`encode`] checks the
+//! roundtrip and returns `None` for anything else (whitespace, `\/`,
+//! `\u00e9`, invalid JSON), leaving the caller to store raw text.
+
+use std::marker::PhantomData;
+
+use serde::de::{Deserialize, Deserializer, MapAccess, Visitor};
+use thiserror::Error;
+
+use crate::dannypack::{write_chunk, write_varint, Reader, Truncated};
+
+/// Keys with a one-byte code (code = index + 1); append only
+pub const KNOWN_KEYS: [&str; 15] = [
+    "name",
+    "display_name",
+    "displayName",
+    "about",
+    "picture",
+    "banner",
+    "website",
+    "nip05",
+    "lud16",
+    "lud06",
+    "username",
+    "bot",
+    "birthday",
+    "pronouns",
+    "image",
+];
+
+const KEY_BY_NAME: u8 = 0;
+const RAW_VALUE: u8 = 0x80;
+
+#[derive(Debug, Error)]
+pub enum ProfileError {
+    #[error("Profile body is truncated")]
+    Truncated,
+
+    #[error("Unknown profile key code: {0}")]
+    UnknownKey(u8),
+
+    #[error("UTF-8 error: {0}")]
+    Utf8(#[from] std::str::Utf8Error),
+}
+
+impl From<Truncated> for ProfileError {
+    fn from(_: Truncated) -> Self {
+        ProfileError::Truncated
+    }
+}
+
+/// A top-level field value
+enum FieldValue {
+    Text(String),
+    Raw(String),
+}
+
+/// Top-level fields in document order, duplicates included
+///
+/// Also used by [`embed`](crate::embed), which needs the order of event
+/// fields.
+pub(crate) struct Fields<V>(pub(crate) Vec<(String, V)>);
+
+impl<'de, V: Deserialize<'de>> Deserialize<'de> for Fields<V> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct FieldsVisitor<V>(PhantomData<V>);
+
+        impl<'de, V: Deserialize<'de>> Visitor<'de> for FieldsVisitor<V> {
+            type Value = Fields<V>;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("a JSON object")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Fields<V>, A::Error> {
+                let mut fields = Vec::with_capacity(map.size_hint().unwrap_or(0).min(64));
+                while let Some(entry) = map.next_entry()? {
+                    fields.push(entry);
+                }
+                Ok(Fields(fields))
+            }
+        }
+
+        deserializer.deserialize_map(FieldsVisitor(PhantomData))
+    }
+}
+
+/// Encode profile JSON, if it decodes back byte for byte
+pub fn encode(json: &str) -> Option<Vec<u8>> {
+    let Fields(fields) = serde_json::from_str::<Fields<serde_json::Value>>(json).ok()?;
+    let fields: Vec<(String, FieldValue)> = fields
+        .into_iter()
+        .map(|(key, value)| {
+            let value = match value {
+                serde_json::Value::String(s) => FieldValue::Text(s),
+                other => FieldValue::Raw(other.to_string()),
+            };
+            (key, value)
+        })
+        .collect();
+
+    let mut body = Vec::with_capacity(json.len());
+    write_varint(&mut body, fields.len() as u64);
+    for (key, value) in &fields {
+        let (flag, value) = match value {
+            FieldValue::Text(s) => (0, s),
+            FieldValue::Raw(s) => (RAW_VALUE, s),
+        };
+        match KNOWN_KEYS.iter().position(|&k| k == key) {
+            Some(index) => body.push(flag | (index as u8 + 1)),
+            None => {
+                body.push(flag | KEY_BY_NAME);
+                write_chunk(&mut body, key.as_bytes());
+            }
+        }
+        write_chunk(&mut body, value.as_bytes());
+    }
+
+    (decode(&body).ok()? == json).then_some(body)
+}
+
+/// Re-emit the profile JSON from an encoded body
+pub fn decode(body: &[u8]) -> Result<String, ProfileError> {
+    let mut reader = Reader::new(body);
+    let count = reader.varint()?;
+
+    let mut out = String::with_capacity(body.len() * 2);
+    out.push('{');
+    for i in 0..count {
+        if i > 0 {
+            out.push(',');
+        }
+        let key_byte = reader.byte()?;
+
+        match key_byte & !RAW_VALUE {
+            KEY_BY_NAME => push_json_string(&mut out, std::str::from_utf8(reader.chunk()?)?),
+            code => {
+                let key = KNOWN_KEYS
+                    .get(code as usize - 1)
+                    .ok_or(ProfileError::UnknownKey(code))?;
+                push_json_string(&mut out, key);
+            }
+        }
+        out.push(':');
+
+        let value = std::str::from_utf8(reader.chunk()?)?;
+        if key_byte & RAW_VALUE != 0 {
+            out.push_str(value);
+        } else {
+            push_json_string(&mut out, value);
+        }
+    }
+    out.push('}');
+
+    Ok(out)
+}
+
+fn push_json_string(out: &mut String, s: &str) {
+    out.push_str(&serde_json::to_string(s).expect("string serialization should not fail"));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        let json = r#"{"name":"alice","about":"line one\nline \"two\" 🤙","picture":"https://example.com/a.jpg","nip05":"alice@example.com","custom_field":"x","bot":false,"lud16":"alice@getalby.com"}"#;
+        let body = encode(json).unwrap();
+        assert!(body.len() < json.len() - 50);
+        assert_eq!(decode(&body).unwrap(), json);
+
+        // Duplicate keys and empty objects survive
+        for json in [r#"{"name":"a","name":"b"}"#, "{}"] {
+            assert_eq!(decode(&encode(json).unwrap()).unwrap(), json);
+        }
+    }
+
+    #[test]
+    fn test_not_reproducible() {
+        for json in [
+            r#"{"name": "alice"}"#,
+            r#"{"website":"https:\/\/example.com"}"#,
+            r#"{"name":"caf\u00e9"}"#,
+            r#"{"nested":{"b":1,"a":2}}"#,
+            r#"["name","alice"]"#,
+            "not json",
+        ] {
+            assert_eq!(encode(json), None, "{}", json);
+        }
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        assert!(matches!(decode(&[1, 1]), Err(ProfileError::Truncated)));
+        assert!(matches!(
+            decode(&[1, 0x7f, 0]),
+            Err(ProfileError::UnknownKey(0x7f))
+        ));
+    }
+}
@@ -4,6 +4,7 @@
 //! 1. String - uses hex strings for id/pubkey/sig (compatible with existing schema)
 //! 2. Binary - uses raw bytes for id/pubkey/sig (optimized for size)
 
+use prost::encoding::{encoded_len_varint, key_len};
 use prost::Message;
 
 use crate::event::NostrEvent;
@@ -22,6 +23,13 @@ pub mod string {
         proto.encode_to_vec()
     }
 
+    /// Exact length of [`serialize`] output, computed without building the
+    /// message
+    pub fn encoded_len(event: &NostrEvent) -> usize {
+        // id, pubkey and sig as hex strings
+        fields_len(event, 2 * event.id.len(), 2 * event.sig.len())
+    }
+
     pub fn deserialize(data: &[u8]) -> Result<NostrEvent, ProtoError> {
         let proto = ProtoEvent::decode(data)?;
         proto_to_event(proto)
@@ -43,7 +51,8 @@ pub mod string {
         batch.events.into_iter().map(proto_to_event).collect()
     }
 
-    fn event_to_proto(event: &NostrEvent) -> ProtoEvent {
+    /// Convert to the message type used by the .pb.gz dataset files
+    pub fn event_to_proto(event: &NostrEvent) -> ProtoEvent {
         ProtoEvent {
             id: event.id_hex(),
             pubkey: event.pubkey_hex(),
@@ -92,6 +101,12 @@ pub mod binary {
         proto.encode_to_vec()
     }
 
+    /// Exact length of [`serialize`] output, computed without building the
+    /// message
+    pub fn encoded_len(event: &NostrEvent) -> usize {
+        fields_len(event, event.id.len(), event.sig.len())
+    }
+
     pub fn deserialize(data: &[u8]) -> Result<NostrEvent, ProtoError> {
         let proto = ProtoEventBinary::decode(data)?;
         proto_binary_to_event(proto)
@@ -117,7 +132,7 @@ pub mod binary {
             .collect()
     }
 
-    fn event_to_proto_binary(event: &NostrEvent) -> ProtoEventBinary {
+    pub fn event_to_proto_binary(event: &NostrEvent) -> ProtoEventBinary {
         ProtoEventBinary {
             id: event.id.to_vec(),
             pubkey: event.pubkey.to_vec(),
@@ -133,7 +148,7 @@ pub mod binary {
         }
     }
 
-    fn proto_binary_to_event(proto: ProtoEventBinary) -> Result<NostrEvent, ProtoError> {
+    pub fn proto_binary_to_event(proto: ProtoEventBinary) -> Result<NostrEvent, ProtoError> {
         Ok(NostrEvent {
             id: proto
                 .id
@@ -155,6 +170,50 @@ pub mod binary {
     }
 }
 
+/// Length of a string or bytes field; proto3 omits empty ones
+fn bytes_field_len(tag: u32, len: usize) -> usize {
+    if len == 0 {
+        0
+    } else {
+        key_len(tag) + encoded_len_varint(len as u64) + len
+    }
+}
+
+/// Length of both event messages, which share field numbers and differ only
+/// in how id, pubkey (`id_len` bytes each) and sig (`sig_len`) are stored
+fn fields_len(event: &NostrEvent, id_len: usize, sig_len: usize) -> usize {
+    let tags: usize = event
+        .tags
+        .iter()
+        .map(|tag| {
+            // Repeated fields keep every element, empty or not
+            let body: usize = tag
+                .iter()
+                .map(|v| key_len(1) + encoded_len_varint(v.len() as u64) + v.len())
+                .sum();
+            key_len(5) + encoded_len_varint(body as u64) + body
+        })
+        .sum();
+
+    // int64 and int32 are plain varints; negative values take ten bytes
+    let created_at = match event.created_at {
+        0 => 0,
+        value => key_len(3) + encoded_len_varint(value as u64),
+    };
+    let kind = match event.kind {
+        0 => 0,
+        value => key_len(4) + encoded_len_varint(value.into()),
+    };
+
+    bytes_field_len(1, id_len)
+        + bytes_field_len(2, id_len)
+        + created_at
+        + kind
+        + tags
+        + bytes_field_len(6, event.content.len())
+        + bytes_field_len(7, sig_len)
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum ProtoError {
     #[error("Protobuf decode error: {0}")]
@@ -8,7 +8,7 @@ use std::path::Path;
 use rand::prelude::*;
 use rand::seq::SliceRandom;
 
-use crate::event::{NostrEvent, SizeCategory, TagCategory};
+use crate::event::{KindClass, NostrEvent, SizeCategory, TagCategory};
 use crate::loader::LoadError;
 
 /// Event kinds to exclude from benchmarks.
@@ -20,8 +20,8 @@ use crate::loader::LoadError;
 ///
 /// Excluding these ensures benchmarks focus on representative real-world events.
 ///
-/// Note: Kind numbers and their purposes:
-/// - 0-9999: Regular events (stored by relays)
+/// Note: Kind numbers and their purposes (see [`KindClass`]):
+/// - 0-9999: Regular events (stored by relays; 0 and 3 are replaceable)
 /// - 10000-19999: Replaceable events (only latest stored)
 /// - 20000-29999: Ephemeral events (not stored)
 /// - 30000-39999: Addressable events (identified by kind+pubkey+d-tag)
@@ -65,8 +65,15 @@ impl EventSampler {
 
     /// Load events from a directory and create a sampler
     /// Automatically filters out excluded kinds (see EXCLUDED_KINDS)
+    ///
+    /// Files are decoded in parallel; the loaded events are identical to
+    /// those of `load_limited_from_directory`.
     pub fn from_directory<P: AsRef<Path>>(dir: P, limit: usize) -> Result<Self, LoadError> {
-        let events = crate::loader::load_limited_from_directory(dir, limit)?;
+        let events = crate::loader::load_limited_from_directory_parallel(
+            dir,
+            limit,
+            crate::loader::default_load_threads(),
+        )?;
         let mut sampler = Self::new(events);
         sampler.filter_excluded_kinds();
         Ok(sampler)
@@ -160,6 +167,27 @@ impl EventSampler {
             .collect()
     }
 
+    /// Get events by kind class
+    pub fn by_class(&self, class: KindClass) -> Vec<&NostrEvent> {
+        self.events
+            .iter()
+            .filter(|e| e.kind_class() == class)
+            .collect()
+    }
+
+    /// Get a sample of events by kind class
+    pub fn sample_class(&mut self, class: KindClass, n: usize) -> Vec<&NostrEvent> {
+        let class_events: Vec<_> = self
+            .events
+            .iter()
+            .filter(|e| e.kind_class() == class)
+            .collect();
+        class_events
+            .choose_multiple(&mut self.rng, n.min(class_events.len()))
+            .cloned()
+            .collect()
+    }
+
     /// Get distribution of event kinds
     pub fn kind_distribution(&self) -> HashMap<u16, usize> {
         let mut dist = HashMap::new();
@@ -187,6 +215,15 @@ impl EventSampler {
         dist
     }
 
+    /// Get distribution of kind classes
+    pub fn class_distribution(&self) -> HashMap<KindClass, usize> {
+        let mut dist = HashMap::new();
+        for event in &self.events {
+            *dist.entry(event.kind_class()).or_insert(0) += 1;
+        }
+        dist
+    }
+
     /// Create a stratified sample ensuring representation from key kinds
     ///
     /// Prioritizes kinds: 0, 1, 3, 4, 7, 10002, 30023
@@ -332,6 +369,59 @@ impl BenchmarkSets {
         !self.random_1000.is_empty()
     }
 
+    /// Every set paired with its field name, in declaration order
+    pub fn named_sets(&self) -> Vec<(&'static str, &[NostrEvent])> {
+        vec![
+            ("kind_0_profile", self.kind_0_profile.as_slice()),
+            ("kind_1_notes", self.kind_1_notes.as_slice()),
+            ("kind_3_follows", self.kind_3_follows.as_slice()),
+            ("kind_4_dms", self.kind_4_dms.as_slice()),
+            ("kind_7_reactions", self.kind_7_reactions.as_slice()),
+            ("kind_10002_relays", self.kind_10002_relays.as_slice()),
+            ("kind_30023_articles", self.kind_30023_articles.as_slice()),
+            ("tiny", self.tiny.as_slice()),
+            ("small", self.small.as_slice()),
+            ("medium", self.medium.as_slice()),
+            ("large", self.large.as_slice()),
+            ("huge", self.huge.as_slice()),
+            ("no_tags", self.no_tags.as_slice()),
+            ("few_tags", self.few_tags.as_slice()),
+            ("moderate_tags", self.moderate_tags.as_slice()),
+            ("many_tags", self.many_tags.as_slice()),
+            ("massive_tags", self.massive_tags.as_slice()),
+            ("random_1000", self.random_1000.as_slice()),
+        ]
+    }
+
+    /// Rebuild the sets by loading each one by its field name
+    ///
+    /// Inverse of [`BenchmarkSets::named_sets`].
+    pub fn from_named_sets<F, E>(mut load: F) -> Result<Self, E>
+    where
+        F: FnMut(&str) -> Result<Vec<NostrEvent>, E>,
+    {
+        Ok(Self {
+            kind_0_profile: load("kind_0_profile")?,
+            kind_1_notes: load("kind_1_notes")?,
+            kind_3_follows: load("kind_3_follows")?,
+            kind_4_dms: load("kind_4_dms")?,
+            kind_7_reactions: load("kind_7_reactions")?,
+            kind_10002_relays: load("kind_10002_relays")?,
+            kind_30023_articles: load("kind_30023_articles")?,
+            tiny: load("tiny")?,
+            small: load("small")?,
+            medium: load("medium")?,
+            large: load("large")?,
+            huge: load("huge")?,
+            no_tags: load("no_tags")?,
+            few_tags: load("few_tags")?,
+            moderate_tags: load("moderate_tags")?,
+            many_tags: load("many_tags")?,
+            massive_tags: load("massive_tags")?,
+            random_1000: load("random_1000")?,
+        })
+    }
+
     /// Get summary statistics
     pub fn summary(&self) -> String {
         format!(
@@ -420,4 +510,20 @@ mod tests {
         let dist = sampler.kind_distribution();
         assert_eq!(dist.get(&0), Some(&10)); // 0, 10, 20, ..., 90
     }
+
+    #[test]
+    fn test_by_class() {
+        let events = make_test_events();
+        let mut sampler = EventSampler::with_seed(events, 42);
+
+        // Kinds 0 and 3 are replaceable, the rest regular
+        let dist = sampler.class_distribution();
+        assert_eq!(dist.get(&KindClass::Replaceable), Some(&20));
+        assert_eq!(dist.get(&KindClass::Regular), Some(&80));
+        assert!(sampler.by_class(KindClass::Ephemeral).is_empty());
+
+        let sample = sampler.sample_class(KindClass::Replaceable, 5);
+        assert_eq!(sample.len(), 5);
+        assert!(sample.iter().all(|e| e.kind == 0 || e.kind == 3));
+    }
 }
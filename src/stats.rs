@@ -8,7 +8,12 @@ use std::io::Write;
 use flate2::write::GzEncoder;
 use flate2::Compression;
 
-use crate::event::{NostrEvent, SizeCategory, TagCategory};
+use crate::auto::{AutoOptions, AutoStats};
+use crate::dictionary;
+use crate::diff;
+use crate::event::{KindClass, NostrEvent, SizeCategory, TagCategory};
+use crate::nip19;
+use crate::tags::is_compressible_hex;
 use crate::{capnp, cbor, dannypack, json, notepack, proto};
 
 /// Serialization format identifier
@@ -71,6 +76,57 @@ impl Format {
             Format::Notepack => "notepack",
         }
     }
+
+    /// Stable one-byte identifier, used wherever a format is recorded on disk
+    pub fn id(&self) -> u8 {
+        match self {
+            Format::Json => 0,
+            Format::CborSchemaless => 1,
+            Format::CborPacked => 2,
+            Format::CborIntKey => 3,
+            Format::ProtoString => 4,
+            Format::ProtoBinary => 5,
+            Format::CapnProto => 6,
+            Format::CapnProtoPacked => 7,
+            Format::DannyPack => 8,
+            Format::Notepack => 9,
+        }
+    }
+
+    /// Look up a format by its [`Format::id`]
+    pub fn from_id(id: u8) -> Option<Format> {
+        Format::all().iter().copied().find(|f| f.id() == id)
+    }
+
+    /// Look up a format by its [`Format::short_name`]
+    pub fn from_short_name(name: &str) -> Option<Format> {
+        Format::all()
+            .iter()
+            .copied()
+            .find(|f| f.short_name() == name)
+    }
+}
+
+/// Error from decoding an event in any [`Format`]
+#[derive(Debug, thiserror::Error)]
+pub enum FormatError {
+    #[error(transparent)]
+    Json(#[from] json::JsonError),
+
+    #[error(transparent)]
+    Cbor(#[from] cbor::CborError),
+
+    #[error(transparent)]
+    Proto(#[from] proto::ProtoError),
+
+    #[error(transparent)]
+    Capnp(#[from] capnp::CapnpError),
+
+    #[error(transparent)]
+    DannyPack(#[from] dannypack::DannyPackError),
+
+    #[error(transparent)]
+    Notepack(#[from] notepack::NotepackError),
 }
 
 /// Serialize an event using the specified format
@@ -84,15 +140,65 @@ pub fn serialize(event: &NostrEvent, format: Format) -> Vec<u8> {
         Format::ProtoBinary => proto::binary::serialize(event),
         Format::CapnProto => capnp::serialize_event(event),
         Format::CapnProtoPacked => capnp::serialize_event_packed(event),
-        Format::DannyPack => {
-            let mut buf = Vec::new();
-            dannypack::serialize(event, &mut buf);
-            buf
-        }
+        Format::DannyPack => dannypack::serialize(event),
         Format::Notepack => notepack::serialize(event),
     }
 }
 
+/// Exact length of [`serialize`] output
+///
+/// Computed from the event's fields without allocating for every format
+/// except Cap'n Proto Packed, whose size depends on the zero bytes of each
+/// word, and Notepack, whose encoder lives in the notepack crate; those two
+/// are serialized and measured.
+pub fn encoded_len(event: &NostrEvent, format: Format) -> usize {
+    match format {
+        Format::Json => json::encoded_len(event),
+        Format::CborSchemaless => cbor::schemaless::encoded_len(event),
+        Format::CborPacked => cbor::packed::encoded_len(event),
+        Format::CborIntKey => cbor::intkey::encoded_len(event),
+        Format::ProtoString => proto::string::encoded_len(event),
+        Format::ProtoBinary => proto::binary::encoded_len(event),
+        Format::CapnProto => capnp::encoded_len(event),
+        Format::DannyPack => dannypack::encoded_len(event),
+        Format::CapnProtoPacked | Format::Notepack => serialize(event, format).len(),
+    }
+}
+
+/// Serialize an event without the format's built-in hex compression of
+/// tag values and content
+///
+/// Lets the [pipeline](crate::pipeline) apply hex compaction as a separate
+/// transform. [`deserialize`] reads the output. Formats without built-in
+/// hex compression serialize as usual, and so does Notepack, whose hex
+/// handling lives inside the notepack crate.
+pub fn serialize_plain(event: &NostrEvent, format: Format) -> Vec<u8> {
+    match format {
+        Format::CborPacked => cbor::packed_no_hex_opt::serialize(event),
+        Format::CborIntKey => cbor::intkey::serialize_plain(event),
+        Format::CapnProto => capnp::serialize_event_plain(event),
+        Format::CapnProtoPacked => capnp::serialize_event_packed_plain(event),
+        Format::DannyPack => dannypack::serialize_plain(event),
+        _ => serialize(event, format),
+    }
+}
+
+/// Deserialize an event using the specified format
+pub fn deserialize(data: &[u8], format: Format) -> Result<NostrEvent, FormatError> {
+    Ok(match format {
+        Format::Json => json::deserialize(data)?,
+        Format::CborSchemaless => cbor::schemaless::deserialize(data)?,
+        Format::CborPacked => cbor::packed::deserialize(data)?,
+        Format::CborIntKey => cbor::intkey::deserialize(data)?,
+        Format::ProtoString => proto::string::deserialize(data)?,
+        Format::ProtoBinary => proto::binary::deserialize(data)?,
+        Format::CapnProto => capnp::deserialize_event(data)?,
+        Format::CapnProtoPacked => capnp::deserialize_event_packed(data)?,
+        Format::DannyPack => dannypack::deserialize(data)?,
+        Format::Notepack => notepack::deserialize(data)?,
+    })
+}
+
 /// Serialize a batch of events using the specified format
 pub fn serialize_batch(events: &[NostrEvent], format: Format) -> Vec<u8> {
     match format {
@@ -233,6 +339,9 @@ pub struct DistributionAnalysis {
     pub by_kind: HashMap<u16, usize>,
     pub by_size: HashMap<SizeCategory, usize>,
     pub by_tags: HashMap<TagCategory, usize>,
+    pub by_class: HashMap<KindClass, usize>,
+    /// Total JSON size per kind class
+    pub class_json_bytes: HashMap<KindClass, usize>,
     pub avg_content_len: f64,
     pub avg_tag_count: f64,
 }
@@ -244,6 +353,8 @@ impl DistributionAnalysis {
         let mut by_kind: HashMap<u16, usize> = HashMap::new();
         let mut by_size: HashMap<SizeCategory, usize> = HashMap::new();
         let mut by_tags: HashMap<TagCategory, usize> = HashMap::new();
+        let mut by_class: HashMap<KindClass, usize> = HashMap::new();
+        let mut class_json_bytes: HashMap<KindClass, usize> = HashMap::new();
         let mut total_content_len = 0usize;
         let mut total_tag_count = 0usize;
 
@@ -251,6 +362,9 @@ impl DistributionAnalysis {
             *by_kind.entry(event.kind).or_insert(0) += 1;
             *by_size.entry(event.size_category()).or_insert(0) += 1;
             *by_tags.entry(event.tag_category()).or_insert(0) += 1;
+            *by_class.entry(event.kind_class()).or_insert(0) += 1;
+            *class_json_bytes.entry(event.kind_class()).or_insert(0) +=
+                event.encoded_len(Format::Json);
             total_content_len += event.content.len();
             total_tag_count += event.tag_count();
         }
@@ -272,6 +386,8 @@ impl DistributionAnalysis {
             by_kind,
             by_size,
             by_tags,
+            by_class,
+            class_json_bytes,
             avg_content_len,
             avg_tag_count,
         }
@@ -279,12 +395,343 @@ impl DistributionAnalysis {
 
     pub fn top_kinds(&self, n: usize) -> Vec<(u16, usize)> {
         let mut kinds: Vec<_> = self.by_kind.iter().map(|(&k, &v)| (k, v)).collect();
-        kinds.sort_by(|a, b| b.1.cmp(&a.1));
+        kinds.sort_by_key(|k| std::cmp::Reverse(k.1));
+        kinds.truncate(n);
+        kinds
+    }
+
+    /// Average JSON size of events in a kind class
+    pub fn avg_class_json_size(&self, class: KindClass) -> f64 {
+        match self.by_class.get(&class) {
+            Some(&count) if count > 0 => {
+                self.class_json_bytes.get(&class).copied().unwrap_or(0) as f64 / count as f64
+            }
+            _ => 0.0,
+        }
+    }
+}
+
+/// Statistics for one tag shape: a tag name with a given number of values
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagShape {
+    pub name: String,
+    /// Number of values after the name
+    pub arity: usize,
+    pub count: usize,
+    /// Total bytes of all values (excluding the name)
+    pub value_bytes: usize,
+    /// Values that are lowercase, even-length hex
+    pub hex_values: usize,
+    pub hex_value_bytes: usize,
+    /// Hex-compressible values per position (index 0 is the first value)
+    pub hex_by_position: Vec<usize>,
+}
+
+impl TagShape {
+    /// Bytes saved by storing hex values as raw bytes
+    pub fn hex_savings(&self) -> usize {
+        self.hex_value_bytes / 2
+    }
+}
+
+/// How often each tag shape occurs and how much of it is hex-compressible
+#[derive(Debug, Clone, Default)]
+pub struct TagStats {
+    pub total_tags: usize,
+    pub total_values: usize,
+    pub value_bytes: usize,
+    pub hex_values: usize,
+    pub hex_value_bytes: usize,
+    /// Shapes sorted by count, most common first
+    pub shapes: Vec<TagShape>,
+}
+
+impl TagStats {
+    pub fn from_events(events: &[NostrEvent]) -> Self {
+        let mut shapes: HashMap<(&str, usize), TagShape> = HashMap::new();
+
+        for tag in events.iter().flat_map(|e| &e.tags) {
+            let Some(name) = tag.first() else {
+                continue;
+            };
+            let values = &tag[1..];
+            let shape = shapes
+                .entry((name.as_str(), values.len()))
+                .or_insert_with(|| TagShape {
+                    name: name.clone(),
+                    arity: values.len(),
+                    count: 0,
+                    value_bytes: 0,
+                    hex_values: 0,
+                    hex_value_bytes: 0,
+                    hex_by_position: vec![0; values.len()],
+                });
+            shape.count += 1;
+            for (i, value) in values.iter().enumerate() {
+                shape.value_bytes += value.len();
+                if is_compressible_hex(value) {
+                    shape.hex_values += 1;
+                    shape.hex_value_bytes += value.len();
+                    shape.hex_by_position[i] += 1;
+                }
+            }
+        }
+
+        let mut shapes: Vec<TagShape> = shapes.into_values().collect();
+        shapes.sort_by(|a, b| {
+            b.count
+                .cmp(&a.count)
+                .then_with(|| a.name.cmp(&b.name))
+                .then(a.arity.cmp(&b.arity))
+        });
+
+        Self {
+            total_tags: shapes.iter().map(|s| s.count).sum(),
+            total_values: shapes.iter().map(|s| s.count * s.arity).sum(),
+            value_bytes: shapes.iter().map(|s| s.value_bytes).sum(),
+            hex_values: shapes.iter().map(|s| s.hex_values).sum(),
+            hex_value_bytes: shapes.iter().map(|s| s.hex_value_bytes).sum(),
+            shapes,
+        }
+    }
+
+    /// Fraction of tag values that are hex-compressible
+    pub fn hex_value_ratio(&self) -> f64 {
+        if self.total_values == 0 {
+            return 0.0;
+        }
+        self.hex_values as f64 / self.total_values as f64
+    }
+
+    /// Fraction of tag value bytes saved by hex compression
+    pub fn hex_savings_ratio(&self) -> f64 {
+        if self.value_bytes == 0 {
+            return 0.0;
+        }
+        (self.hex_value_bytes / 2) as f64 / self.value_bytes as f64
+    }
+
+    pub fn top_shapes(&self, n: usize) -> &[TagShape] {
+        &self.shapes[..n.min(self.shapes.len())]
+    }
+}
+
+/// Byte counts for NIP-19 references with one prefix
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Nip19PrefixStats {
+    pub count: usize,
+    /// Bytes of text, including any `nostr:` scheme
+    pub text_bytes: usize,
+    /// Bytes of the decoded binary payloads
+    pub binary_bytes: usize,
+}
+
+/// How much of the dataset is NIP-19 references stored as text
+#[derive(Debug, Clone, Default)]
+pub struct Nip19Stats {
+    pub events_with_references: usize,
+    pub content_bytes: usize,
+    pub tag_value_bytes: usize,
+    pub content: Nip19PrefixStats,
+    pub tags: Nip19PrefixStats,
+    pub by_prefix: HashMap<&'static str, Nip19PrefixStats>,
+}
+
+impl Nip19Stats {
+    /// Scan event content and tag values for embedded entities
+    pub fn from_events(events: &[NostrEvent]) -> Self {
+        let mut stats = Self::default();
+
+        for event in events {
+            stats.content_bytes += event.content.len();
+            let mut found = Self::add(
+                &mut stats.content,
+                &mut stats.by_prefix,
+                nip19::scan(&event.content),
+            );
+            for value in event.tags.iter().flat_map(|t| t.iter().skip(1)) {
+                stats.tag_value_bytes += value.len();
+                found |= Self::add(&mut stats.tags, &mut stats.by_prefix, nip19::scan(value));
+            }
+            if found {
+                stats.events_with_references += 1;
+            }
+        }
+
+        stats
+    }
+
+    fn add(
+        total: &mut Nip19PrefixStats,
+        by_prefix: &mut HashMap<&'static str, Nip19PrefixStats>,
+        references: Vec<nip19::Reference<'_>>,
+    ) -> bool {
+        let found = !references.is_empty();
+        for reference in references {
+            let text_bytes = reference.text.len();
+            let binary_bytes = reference.binary_len();
+            for entry in [
+                &mut *total,
+                by_prefix.entry(reference.entity.prefix()).or_default(),
+            ] {
+                entry.count += 1;
+                entry.text_bytes += text_bytes;
+                entry.binary_bytes += binary_bytes;
+            }
+        }
+        found
+    }
+
+    /// Total references found
+    pub fn references(&self) -> usize {
+        self.content.count + self.tags.count
+    }
+
+    /// Fraction of content bytes taken up by references
+    pub fn content_ratio(&self) -> f64 {
+        if self.content_bytes == 0 {
+            return 0.0;
+        }
+        self.content.text_bytes as f64 / self.content_bytes as f64
+    }
+
+    /// Bytes saved by storing every reference as its binary payload
+    pub fn binary_savings(&self) -> usize {
+        (self.content.text_bytes + self.tags.text_bytes)
+            - (self.content.binary_bytes + self.tags.binary_bytes)
+    }
+}
+
+/// Dictionary hits and DannyPack sizes for one group of events
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DictionaryKindStats {
+    pub events: usize,
+    pub tag_name_hits: usize,
+    pub relay_hits: usize,
+    /// DannyPack version 1 bytes
+    pub v1_bytes: usize,
+    /// DannyPack version 2 bytes
+    pub v2_bytes: usize,
+}
+
+impl DictionaryKindStats {
+    /// Fraction of version 1 bytes saved by version 2
+    pub fn savings_ratio(&self) -> f64 {
+        if self.v1_bytes == 0 {
+            return 0.0;
+        }
+        1.0 - self.v2_bytes as f64 / self.v1_bytes as f64
+    }
+}
+
+/// Effect of the static tag [`dictionary`] (DannyPack version 2) per kind
+#[derive(Debug, Clone, Default)]
+pub struct DictionaryStats {
+    pub total: DictionaryKindStats,
+    pub by_kind: HashMap<u16, DictionaryKindStats>,
+}
+
+impl DictionaryStats {
+    pub fn from_events(events: &[NostrEvent]) -> Self {
+        let mut stats = Self::default();
+        for event in events {
+            let names = event
+                .tags
+                .iter()
+                .filter_map(|t| t.first())
+                .filter(|name| dictionary::V1.tag_name_code(name).is_some())
+                .count();
+            let relays = event
+                .tags
+                .iter()
+                .flat_map(|t| t.iter().skip(1))
+                .filter(|value| dictionary::V1.relay_code(value).is_some())
+                .count();
+            let v1 = dannypack::serialize(event).len();
+            let v2 = dannypack::serialize_v2(event).len();
+
+            for entry in [
+                &mut stats.total,
+                stats.by_kind.entry(event.kind).or_default(),
+            ] {
+                entry.events += 1;
+                entry.tag_name_hits += names;
+                entry.relay_hits += relays;
+                entry.v1_bytes += v1;
+                entry.v2_bytes += v2;
+            }
+        }
+        stats
+    }
+
+    /// The `n` kinds with the most events
+    pub fn top_kinds(&self, n: usize) -> Vec<(u16, DictionaryKindStats)> {
+        let mut kinds: Vec<_> = self.by_kind.iter().map(|(&k, &v)| (k, v)).collect();
+        kinds.sort_by(|a, b| b.1.events.cmp(&a.1.events).then(a.0.cmp(&b.0)));
         kinds.truncate(n);
         kinds
     }
 }
 
+/// Successive versions of replaceable events, and what patches would save
+#[derive(Debug, Clone, Default)]
+pub struct VersionStats {
+    /// Replaceable coordinates with more than one version
+    pub chains: usize,
+    pub patches: usize,
+    /// Patches that apply back with a matching id
+    pub verified: usize,
+    pub patch_bytes: usize,
+    /// Size of the newer versions encoded in full, per format
+    pub full_bytes: HashMap<Format, usize>,
+}
+
+impl VersionStats {
+    /// Group replaceable events into version chains and diff each
+    /// consecutive pair
+    pub fn from_events(events: &[NostrEvent]) -> Self {
+        let mut chains: HashMap<_, Vec<&NostrEvent>> = HashMap::new();
+        for event in events {
+            if let Some(key) = diff::version_key(event) {
+                chains.entry(key).or_default().push(event);
+            }
+        }
+
+        let mut stats = Self::default();
+        for versions in chains.values_mut() {
+            versions.sort_by_key(|e| (e.created_at, e.id));
+            versions.dedup_by_key(|e| e.id);
+            if versions.len() < 2 {
+                continue;
+            }
+            stats.chains += 1;
+
+            for pair in versions.windows(2) {
+                let patch = diff::diff(pair[0], pair[1]).expect("same version key");
+                stats.patches += 1;
+                stats.patch_bytes += patch.len();
+                if diff::apply(pair[0], &patch).is_ok() {
+                    stats.verified += 1;
+                }
+                for &format in Format::all() {
+                    *stats.full_bytes.entry(format).or_default() +=
+                        serialize(pair[1], format).len();
+                }
+            }
+        }
+
+        stats
+    }
+
+    /// Patch size as a fraction of the full encoding in `format`
+    pub fn patch_ratio(&self, format: Format) -> f64 {
+        match self.full_bytes.get(&format) {
+            Some(&full) if full > 0 => self.patch_bytes as f64 / full as f64,
+            _ => 0.0,
+        }
+    }
+}
+
 /// Generate a markdown report of size comparisons
 pub fn generate_size_report(events: &[NostrEvent]) -> String {
     let mut report = String::new();
@@ -314,6 +761,157 @@ pub fn generate_size_report(events: &[NostrEvent]) -> String {
     }
     report.push('\n');
 
+    // Kind classes
+    report.push_str("### Kind Classes\n\n");
+    report.push_str("| Class | Count | Percentage | Avg JSON Size |\n");
+    report.push_str("|-------|-------|------------|---------------|\n");
+    for class in KindClass::ALL {
+        let count = dist.by_class.get(&class).copied().unwrap_or(0);
+        let pct = 100.0 * count as f64 / dist.total_events as f64;
+        report.push_str(&format!(
+            "| {} | {} | {:.1}% | {:.0} |\n",
+            class,
+            count,
+            pct,
+            dist.avg_class_json_size(class)
+        ));
+    }
+    report.push('\n');
+
+    // Tag shapes
+    let tag_stats = TagStats::from_events(events);
+    report.push_str("### Top Tag Shapes\n\n");
+    report.push_str(&format!(
+        "{} tags, {:.1}% of values hex-compressible ({:.1}% of value bytes saved)\n\n",
+        tag_stats.total_tags,
+        100.0 * tag_stats.hex_value_ratio(),
+        100.0 * tag_stats.hex_savings_ratio(),
+    ));
+    report.push_str("| Tag | Values | Count | Avg Value Bytes | Hex Values | Hex Savings |\n");
+    report.push_str("|-----|--------|-------|-----------------|------------|-------------|\n");
+    for shape in tag_stats.top_shapes(15) {
+        let values = (shape.count * shape.arity).max(1);
+        report.push_str(&format!(
+            "| {} | {} | {} | {:.1} | {:.1}% | {} |\n",
+            shape.name,
+            shape.arity,
+            shape.count,
+            shape.value_bytes as f64 / shape.count as f64,
+            100.0 * shape.hex_values as f64 / values as f64,
+            shape.hex_savings(),
+        ));
+    }
+    report.push('\n');
+
+    // NIP-19 references
+    let nip19_stats = Nip19Stats::from_events(events);
+    if nip19_stats.references() > 0 {
+        report.push_str("### NIP-19 References\n\n");
+        report.push_str(&format!(
+            "{} references in {} events; {:.1}% of content bytes, {} bytes saved as binary\n\n",
+            nip19_stats.references(),
+            nip19_stats.events_with_references,
+            100.0 * nip19_stats.content_ratio(),
+            nip19_stats.binary_savings(),
+        ));
+        report.push_str("| Prefix | Count | Text Bytes | Binary Bytes |\n");
+        report.push_str("|--------|-------|------------|--------------|\n");
+        for prefix in nip19::PREFIXES {
+            if let Some(entry) = nip19_stats.by_prefix.get(prefix) {
+                report.push_str(&format!(
+                    "| {} | {} | {} | {} |\n",
+                    prefix, entry.count, entry.text_bytes, entry.binary_bytes
+                ));
+            }
+        }
+        report.push('\n');
+    }
+
+    // Static tag dictionary
+    let dictionary_stats = DictionaryStats::from_events(events);
+    report.push_str("### Tag Dictionary (DannyPack v2)\n\n");
+    report.push_str(&format!(
+        "{} tag names and {} relay URLs coded; {:.1}% smaller than DannyPack\n\n",
+        dictionary_stats.total.tag_name_hits,
+        dictionary_stats.total.relay_hits,
+        100.0 * dictionary_stats.total.savings_ratio(),
+    ));
+    report.push_str(
+        "| Kind | Events | Name Hits | Relay Hits | DannyPack | DannyPack v2 | Saved |\n",
+    );
+    report.push_str(
+        "|------|--------|-----------|------------|-----------|--------------|-------|\n",
+    );
+    for (kind, entry) in dictionary_stats.top_kinds(10) {
+        report.push_str(&format!(
+            "| {} | {} | {} | {} | {} | {} | {:.1}% |\n",
+            kind,
+            entry.events,
+            entry.tag_name_hits,
+            entry.relay_hits,
+            entry.v1_bytes,
+            entry.v2_bytes,
+            100.0 * entry.savings_ratio(),
+        ));
+    }
+    report.push('\n');
+
+    // Replaceable version chains
+    let version_stats = VersionStats::from_events(events);
+    if version_stats.patches > 0 {
+        report.push_str("### Replaceable Version Chains\n\n");
+        report.push_str(&format!(
+            "{} chains, {} patches ({} with verified ids), {} patch bytes\n\n",
+            version_stats.chains,
+            version_stats.patches,
+            version_stats.verified,
+            version_stats.patch_bytes,
+        ));
+        report.push_str("| Format | Full Bytes | Patch / Full |\n");
+        report.push_str("|--------|------------|--------------|\n");
+        for &format in Format::all() {
+            report.push_str(&format!(
+                "| {} | {} | {:.1}% |\n",
+                format.name(),
+                version_stats.full_bytes[&format],
+                100.0 * version_stats.patch_ratio(format),
+            ));
+        }
+        report.push('\n');
+    }
+
+    // Adaptive format selection
+    let auto_options = AutoOptions::new();
+    let auto_stats = AutoStats::from_events(events, &auto_options);
+    if let Some((best, best_bytes)) = auto_stats.best_single() {
+        report.push_str("### Adaptive Format Selection\n\n");
+        report.push_str(&format!(
+            "Smallest-candidate auto encoding: {} bytes, {:.1}% of {} ({} bytes)\n\n",
+            auto_stats.auto_bytes,
+            100.0 * auto_stats.auto_bytes as f64 / best_bytes as f64,
+            best.name(),
+            best_bytes,
+        ));
+        report.push_str("| Kind | Events |");
+        for format in &auto_options.candidates {
+            report.push_str(&format!(" {} Wins |", format.name()));
+        }
+        report.push_str("\n|------|--------|");
+        for _ in &auto_options.candidates {
+            report.push_str("------|");
+        }
+        report.push('\n');
+        for (kind, count) in auto_stats.top_kinds(10) {
+            report.push_str(&format!("| {} | {} |", kind, count));
+            for format in &auto_options.candidates {
+                let wins = auto_stats.wins[&kind].get(format).copied().unwrap_or(0);
+                report.push_str(&format!(" {} |", wins));
+            }
+            report.push('\n');
+        }
+        report.push('\n');
+    }
+
     // Aggregate stats
     let stats = compute_aggregate_stats(events);
 
@@ -369,7 +967,7 @@ fn gzip_size(data: &[u8]) -> usize {
 }
 
 /// Compress data with zstd and return the size
-fn zstd_size(data: &[u8]) -> usize {
+pub(crate) fn zstd_size(data: &[u8]) -> usize {
     zstd::encode_all(data, 3).unwrap().len()
 }
 
@@ -407,6 +1005,90 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_deserialize_all_formats() {
+        let event = sample_event();
+        for &format in Format::all() {
+            assert_eq!(Format::from_id(format.id()), Some(format));
+            let data = serialize(&event, format);
+            assert_eq!(
+                deserialize(&data, format).unwrap(),
+                event,
+                "{}",
+                format.name()
+            );
+        }
+        assert_eq!(Format::from_id(255), None);
+    }
+
+    #[test]
+    fn test_serialize_plain() {
+        let mut event = sample_event();
+        event.tags.push(vec!["p".to_string(), "cd".repeat(32)]);
+        for &format in Format::all() {
+            let data = serialize_plain(&event, format);
+            assert_eq!(
+                deserialize(&data, format).unwrap(),
+                event,
+                "{}",
+                format.name()
+            );
+        }
+        assert!(
+            serialize_plain(&event, Format::CborPacked).len()
+                > serialize(&event, Format::CborPacked).len()
+        );
+    }
+
+    #[test]
+    fn test_encoded_len() {
+        let mut escapes = sample_event();
+        escapes.created_at = -1;
+        escapes.kind = 0;
+        escapes.content = "\"quoted\" \\ \n\t\r\u{1} \u{7f} caf\u{e9} \u{1f600}".to_string();
+        escapes.tags = vec![
+            vec![],
+            vec!["".to_string()],
+            vec![
+                "e".to_string(),
+                "AB".repeat(32),
+                "ab".repeat(4),
+                "abc".to_string(),
+            ],
+            vec!["t".to_string(), "x".repeat(200)],
+        ];
+
+        let mut big = sample_event();
+        big.content = "x".repeat(50_000);
+
+        // Many tags and long content push Cap'n Proto past its first segment
+        let mut follows = sample_event();
+        follows.kind = 3;
+        follows.tags = (0..300)
+            .map(|i| vec!["p".to_string(), format!("{:064x}", i)])
+            .collect();
+        follows.content = "y".repeat(9_000);
+
+        let mut events = vec![sample_event(), escapes, big, follows];
+        for len in (8_000..8_300).step_by(7) {
+            let mut event = sample_event();
+            event.content = "z".repeat(len);
+            events.push(event);
+        }
+
+        for event in &events {
+            for &format in Format::all() {
+                assert_eq!(
+                    encoded_len(event, format),
+                    serialize(event, format).len(),
+                    "{} with {} content bytes",
+                    format.name(),
+                    event.content.len()
+                );
+            }
+        }
+    }
+
     #[test]
     fn test_distribution_analysis() {
         let events: Vec<NostrEvent> = (0..10)
@@ -425,5 +1107,101 @@ mod tests {
 
         assert_eq!(dist.total_events, 10);
         assert_eq!(dist.by_kind.len(), 3);
+        // kinds 0 (replaceable) x4, 1 and 2 (regular) x6
+        assert_eq!(dist.by_class.get(&KindClass::Replaceable), Some(&4));
+        assert_eq!(dist.by_class.get(&KindClass::Regular), Some(&6));
+        assert!(dist.avg_class_json_size(KindClass::Regular) > 0.0);
+        assert_eq!(dist.avg_class_json_size(KindClass::Ephemeral), 0.0);
+    }
+
+    #[test]
+    fn test_tag_stats() {
+        let mut event = sample_event();
+        event.tags = vec![
+            vec!["e".to_string(), "ab".repeat(32)],
+            vec![
+                "e".to_string(),
+                "cd".repeat(32),
+                "wss://r.example".to_string(),
+            ],
+            vec!["e".to_string(), "ef".repeat(32), "".to_string()],
+            vec!["t".to_string(), "Nostr".to_string()],
+        ];
+        let stats = TagStats::from_events(&[event]);
+
+        assert_eq!(stats.total_tags, 4);
+        assert_eq!(stats.total_values, 6);
+        assert_eq!(stats.hex_values, 3);
+        assert_eq!(stats.hex_value_bytes, 192);
+
+        let top = &stats.top_shapes(1)[0];
+        assert_eq!((top.name.as_str(), top.arity, top.count), ("e", 2, 2));
+        assert_eq!(top.hex_by_position, vec![2, 0]);
+        assert_eq!(top.hex_savings(), 64);
+        assert!((stats.hex_value_ratio() - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_nip19_stats() {
+        let npub = nip19::Nip19::Pubkey([0x11; 32]).to_string();
+        let mut event = sample_event();
+        event.content = format!("gm nostr:{} and {}", npub, npub);
+        event.tags = vec![vec!["q".to_string(), npub.clone()]];
+
+        let stats = Nip19Stats::from_events(&[event, sample_event()]);
+        assert_eq!(stats.events_with_references, 1);
+        assert_eq!(stats.references(), 3);
+        assert_eq!(stats.content.text_bytes, 2 * npub.len() + 6);
+        assert_eq!(stats.content.binary_bytes, 64);
+        assert_eq!(stats.by_prefix["npub"].count, 3);
+        assert_eq!(stats.binary_savings(), 3 * npub.len() + 6 - 96);
+    }
+
+    #[test]
+    fn test_version_stats() {
+        let version = |created_at: i64, follows: u8| {
+            let mut event = sample_event();
+            event.kind = 3;
+            event.created_at = created_at;
+            event.tags = (0..follows)
+                .map(|i| vec!["p".to_string(), hex::encode([i; 32])])
+                .collect();
+            event.id = event.compute_id();
+            event
+        };
+        let events = vec![
+            version(300, 52),
+            version(100, 50),
+            version(200, 51),
+            sample_event(),
+        ];
+
+        let stats = VersionStats::from_events(&events);
+        assert_eq!(stats.chains, 1);
+        assert_eq!(stats.patches, 2);
+        assert_eq!(stats.verified, 2);
+        assert!(stats.patch_ratio(Format::Json) < 0.05);
+        assert!(stats.patch_ratio(Format::DannyPack) < 0.15);
+    }
+
+    #[test]
+    fn test_dictionary_stats() {
+        let mut reply = sample_event();
+        reply.tags = vec![
+            vec![
+                "e".to_string(),
+                "ab".repeat(32),
+                "wss://nos.lol".to_string(),
+            ],
+            vec!["custom".to_string(), "value".to_string()],
+        ];
+        let events = vec![sample_event(), reply.clone(), reply];
+
+        let stats = DictionaryStats::from_events(&events);
+        assert_eq!(stats.total.events, 3);
+        assert_eq!(stats.total.relay_hits, 2);
+        assert_eq!(stats.total.tag_name_hits, 4);
+        assert!(stats.total.v2_bytes < stats.total.v1_bytes);
+        assert_eq!(stats.top_kinds(5)[0], (1, stats.total));
     }
 }
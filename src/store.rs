@@ -0,0 +1,536 @@
+//! In-memory event store, as a relay stand-in
+//!
+//! [`EventStore`] keeps events encoded in one [`Format`] and indexes them by
+//! id, author, kind and single-letter tag. Inserts follow NIP-01 storage
+//! rules:
+//!
+//! - Replaceable kinds (0, 3, 10000-19999): only the latest event per
+//!   kind and pubkey is kept
+//! - Addressable kinds (30000-39999): only the latest per kind, pubkey and
+//!   `d` tag value
+//! - Ephemeral kinds (20000-29999): never stored
+//!
+//! For equal `created_at`, the event with the lowest id wins. Queries take a
+//! [`Filter`] and return events newest first; candidate events are matched
+//! on their encoded bytes, so the cost of the chosen format is part of every
+//! query.
+
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, HashMap};
+
+use crate::event::{KindClass, NostrEvent};
+use crate::filter::Filter;
+use crate::stats::{self, Format, FormatError};
+
+/// Index entry, ordered newest first, then by slot
+type Entry = (Reverse<i64>, u32);
+
+/// Result of [`EventStore::insert`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InsertOutcome {
+    /// Stored as a new event
+    Stored,
+    /// Stored, replacing an older version of a replaceable or addressable event
+    Replaced { old_id: [u8; 32] },
+    /// An event with this id is already stored
+    Duplicate,
+    /// A newer version of this replaceable or addressable event is stored
+    Stale,
+    /// Ephemeral kinds are not stored
+    Ephemeral,
+}
+
+impl InsertOutcome {
+    /// Whether the event is now in the store
+    pub fn is_stored(&self) -> bool {
+        matches!(self, InsertOutcome::Stored | InsertOutcome::Replaced { .. })
+    }
+}
+
+/// Key identifying the single stored version of a replaceable event
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum ReplaceKey {
+    Replaceable(u16, [u8; 32]),
+    Addressable(u16, [u8; 32], String),
+}
+
+struct Record {
+    data: Vec<u8>,
+    id: [u8; 32],
+    pubkey: [u8; 32],
+    kind: u16,
+    created_at: i64,
+    tags: Vec<(char, String)>,
+    replace_key: Option<ReplaceKey>,
+}
+
+impl Record {
+    fn entry(&self, slot: u32) -> Entry {
+        (Reverse(self.created_at), slot)
+    }
+}
+
+/// Events encoded in one format, with NIP-01 replacement rules and indexes
+pub struct EventStore {
+    format: Format,
+    slots: Vec<Option<Record>>,
+    free: Vec<u32>,
+    by_id: HashMap<[u8; 32], u32>,
+    by_time: BTreeSet<Entry>,
+    by_author: HashMap<[u8; 32], BTreeSet<Entry>>,
+    by_kind: HashMap<u16, BTreeSet<Entry>>,
+    by_tag: HashMap<(char, String), BTreeSet<Entry>>,
+    replaceable: HashMap<ReplaceKey, u32>,
+    encoded_bytes: usize,
+}
+
+impl EventStore {
+    pub fn new(format: Format) -> Self {
+        Self {
+            format,
+            slots: Vec::new(),
+            free: Vec::new(),
+            by_id: HashMap::new(),
+            by_time: BTreeSet::new(),
+            by_author: HashMap::new(),
+            by_kind: HashMap::new(),
+            by_tag: HashMap::new(),
+            replaceable: HashMap::new(),
+            encoded_bytes: 0,
+        }
+    }
+
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    /// Number of stored events
+    pub fn len(&self) -> usize {
+        self.by_id.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_id.is_empty()
+    }
+
+    /// Total size of the stored encodings
+    pub fn encoded_bytes(&self) -> usize {
+        self.encoded_bytes
+    }
+
+    /// Insert an event, applying the NIP-01 replacement rules
+    pub fn insert(&mut self, event: &NostrEvent) -> InsertOutcome {
+        if !event.kind_class().is_stored() {
+            return InsertOutcome::Ephemeral;
+        }
+        if self.by_id.contains_key(&event.id) {
+            return InsertOutcome::Duplicate;
+        }
+
+        let replace_key = replace_key(event);
+        let mut old_id = None;
+        if let Some(&slot) = replace_key.as_ref().and_then(|k| self.replaceable.get(k)) {
+            let current = self.record(slot);
+            if (current.created_at, Reverse(current.id)) >= (event.created_at, Reverse(event.id)) {
+                return InsertOutcome::Stale;
+            }
+            old_id = Some(current.id);
+            self.remove_slot(slot);
+        }
+
+        let record = Record {
+            data: stats::serialize(event, self.format),
+            id: event.id,
+            pubkey: event.pubkey,
+            kind: event.kind,
+            created_at: event.created_at,
+            tags: index_tags(event),
+            replace_key,
+        };
+        self.insert_record(record);
+
+        match old_id {
+            Some(old_id) => InsertOutcome::Replaced { old_id },
+            None => InsertOutcome::Stored,
+        }
+    }
+
+    /// Insert many events; returns how many ended up stored
+    pub fn extend<'a, I: IntoIterator<Item = &'a NostrEvent>>(&mut self, events: I) -> usize {
+        events
+            .into_iter()
+            .filter(|e| self.insert(e).is_stored())
+            .count()
+    }
+
+    /// Remove an event by id; returns true if it was stored
+    pub fn remove(&mut self, id: &[u8; 32]) -> bool {
+        match self.by_id.get(id) {
+            Some(&slot) => {
+                self.remove_slot(slot);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn contains(&self, id: &[u8; 32]) -> bool {
+        self.by_id.contains_key(id)
+    }
+
+    /// Encoded bytes of an event
+    pub fn get_raw(&self, id: &[u8; 32]) -> Option<&[u8]> {
+        self.by_id
+            .get(id)
+            .map(|&slot| self.record(slot).data.as_slice())
+    }
+
+    /// Decode an event by id
+    pub fn get(&self, id: &[u8; 32]) -> Option<Result<NostrEvent, FormatError>> {
+        self.get_raw(id)
+            .map(|data| stats::deserialize(data, self.format))
+    }
+
+    /// Encoded bytes of matching events, newest first, honouring `limit`
+    pub fn query_raw(&self, filter: &Filter) -> Result<Vec<&[u8]>, FormatError> {
+        let limit = filter.limit.unwrap_or(usize::MAX);
+        let mut matched = Vec::new();
+        if limit == 0 {
+            return Ok(matched);
+        }
+
+        for slot in self.candidates(filter) {
+            let record = self.record(slot);
+            if filter.matches_encoded(&record.data, self.format)? {
+                matched.push(record.data.as_slice());
+                if matched.len() == limit {
+                    break;
+                }
+            }
+        }
+        Ok(matched)
+    }
+
+    /// Decode matching events, newest first, honouring `limit`
+    pub fn query(&self, filter: &Filter) -> Result<Vec<NostrEvent>, FormatError> {
+        self.query_raw(filter)?
+            .into_iter()
+            .map(|data| stats::deserialize(data, self.format))
+            .collect()
+    }
+
+    /// Count matching events (NIP-45), ignoring `limit`
+    pub fn count(&self, filter: &Filter) -> Result<usize, FormatError> {
+        let mut count = 0;
+        for slot in self.candidates(filter) {
+            if filter.matches_encoded(&self.record(slot).data, self.format)? {
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    /// Candidate slots for a filter, newest first
+    ///
+    /// Uses the smallest applicable index; every candidate still has to be
+    /// checked against the full filter.
+    fn candidates(&self, filter: &Filter) -> Vec<u32> {
+        if let Some(ids) = &filter.ids {
+            let mut entries: Vec<Entry> = ids
+                .iter()
+                .filter_map(|id| self.by_id.get(id))
+                .map(|&slot| self.record(slot).entry(slot))
+                .collect();
+            entries.sort_unstable();
+            entries.dedup();
+            return entries.into_iter().map(|(_, slot)| slot).collect();
+        }
+
+        // One set list per constrained field; each list is a union
+        let mut choices: Vec<Vec<&BTreeSet<Entry>>> = Vec::new();
+        if let Some(authors) = &filter.authors {
+            choices.push(
+                authors
+                    .iter()
+                    .filter_map(|a| self.by_author.get(a))
+                    .collect(),
+            );
+        }
+        if let Some(kinds) = &filter.kinds {
+            choices.push(kinds.iter().filter_map(|k| self.by_kind.get(k)).collect());
+        }
+        for (&letter, values) in &filter.tags {
+            choices.push(
+                values
+                    .iter()
+                    .filter_map(|v| self.by_tag.get(&(letter, v.clone())))
+                    .collect(),
+            );
+        }
+        let best = choices
+            .into_iter()
+            .min_by_key(|sets| sets.iter().map(|s| s.len()).sum::<usize>());
+
+        let since = filter.since.unwrap_or(i64::MIN);
+        let until = filter.until.unwrap_or(i64::MAX);
+        if since > until {
+            return Vec::new();
+        }
+        let range = (Reverse(until), 0)..=(Reverse(since), u32::MAX);
+
+        match best {
+            None => self.by_time.range(range).map(|&(_, slot)| slot).collect(),
+            Some(sets) => {
+                let mut entries: Vec<Entry> = sets
+                    .into_iter()
+                    .flat_map(|set| set.range(range.clone()).copied())
+                    .collect();
+                entries.sort_unstable();
+                entries.dedup();
+                entries.into_iter().map(|(_, slot)| slot).collect()
+            }
+        }
+    }
+
+    fn record(&self, slot: u32) -> &Record {
+        self.slots[slot as usize]
+            .as_ref()
+            .expect("index points at a live slot")
+    }
+
+    fn insert_record(&mut self, record: Record) {
+        let slot = match self.free.pop() {
+            Some(slot) => slot,
+            None => {
+                self.slots.push(None);
+                (self.slots.len() - 1) as u32
+            }
+        };
+        let entry = record.entry(slot);
+
+        self.by_id.insert(record.id, slot);
+        self.by_time.insert(entry);
+        self.by_author
+            .entry(record.pubkey)
+            .or_default()
+            .insert(entry);
+        self.by_kind.entry(record.kind).or_default().insert(entry);
+        for tag in &record.tags {
+            self.by_tag.entry(tag.clone()).or_default().insert(entry);
+        }
+        if let Some(key) = &record.replace_key {
+            self.replaceable.insert(key.clone(), slot);
+        }
+        self.encoded_bytes += record.data.len();
+
+        self.slots[slot as usize] = Some(record);
+    }
+
+    fn remove_slot(&mut self, slot: u32) {
+        let record = self.slots[slot as usize]
+            .take()
+            .expect("index points at a live slot");
+        let entry = record.entry(slot);
+
+        self.by_id.remove(&record.id);
+        self.by_time.remove(&entry);
+        remove_entry(&mut self.by_author, &record.pubkey, &entry);
+        remove_entry(&mut self.by_kind, &record.kind, &entry);
+        for tag in &record.tags {
+            remove_entry(&mut self.by_tag, tag, &entry);
+        }
+        if let Some(key) = &record.replace_key {
+            self.replaceable.remove(key);
+        }
+        self.encoded_bytes -= record.data.len();
+
+        self.free.push(slot);
+    }
+}
+
+fn remove_entry<K: std::hash::Hash + Eq>(
+    index: &mut HashMap<K, BTreeSet<Entry>>,
+    key: &K,
+    entry: &Entry,
+) {
+    if let Some(set) = index.get_mut(key) {
+        set.remove(entry);
+        if set.is_empty() {
+            index.remove(key);
+        }
+    }
+}
+
+fn replace_key(event: &NostrEvent) -> Option<ReplaceKey> {
+    match event.kind_class() {
+        KindClass::Replaceable => Some(ReplaceKey::Replaceable(event.kind, event.pubkey)),
+        KindClass::Addressable => Some(ReplaceKey::Addressable(
+            event.kind,
+            event.pubkey,
+            event.d_tag().unwrap_or_default().to_string(),
+        )),
+        KindClass::Regular | KindClass::Ephemeral => None,
+    }
+}
+
+/// Single-letter tags and their first value, as NIP-01 filters see them
+fn index_tags(event: &NostrEvent) -> Vec<(char, String)> {
+    let mut tags: Vec<(char, String)> = event
+        .tags
+        .iter()
+        .filter_map(|tag| {
+            let mut name = tag.first()?.chars();
+            match (name.next(), name.next()) {
+                (Some(letter), None) => Some((letter, tag.get(1)?.clone())),
+                _ => None,
+            }
+        })
+        .collect();
+    tags.sort_unstable();
+    tags.dedup();
+    tags
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_event() -> NostrEvent {
+        NostrEvent {
+            id: [0xab; 32],
+            pubkey: [0xcd; 32],
+            created_at: 1234567890,
+            kind: 1,
+            tags: vec![
+                vec!["p".to_string(), hex::encode([0x11; 32])],
+                vec!["t".to_string(), "nostr".to_string()],
+            ],
+            content: "Hello, Nostr!".to_string(),
+            sig: [0xef; 64],
+        }
+    }
+
+    fn event(id: u8, kind: u16, created_at: i64, tags: &[(&str, &str)]) -> NostrEvent {
+        NostrEvent {
+            id: [id; 32],
+            kind,
+            created_at,
+            tags: tags
+                .iter()
+                .map(|(k, v)| vec![k.to_string(), v.to_string()])
+                .collect(),
+            ..sample_event()
+        }
+    }
+
+    #[test]
+    fn test_replacement_rules() {
+        let mut store = EventStore::new(Format::DannyPack);
+
+        assert_eq!(store.insert(&sample_event()), InsertOutcome::Stored);
+        assert_eq!(store.insert(&sample_event()), InsertOutcome::Duplicate);
+        assert_eq!(
+            store.insert(&event(1, 20001, 100, &[])),
+            InsertOutcome::Ephemeral
+        );
+
+        // Replaceable: newest per (kind, pubkey) wins, ties go to the lower id
+        assert_eq!(store.insert(&event(2, 0, 100, &[])), InsertOutcome::Stored);
+        assert_eq!(store.insert(&event(3, 0, 50, &[])), InsertOutcome::Stale);
+        assert_eq!(
+            store.insert(&event(1, 0, 100, &[])),
+            InsertOutcome::Replaced { old_id: [2; 32] }
+        );
+        assert_eq!(store.insert(&event(4, 0, 100, &[])), InsertOutcome::Stale);
+        assert!(!store.contains(&[2; 32]));
+
+        // Addressable: keyed by d tag; a missing d tag is the empty string
+        let a = event(5, 30023, 100, &[("d", "post")]);
+        let b = event(6, 30023, 100, &[("d", "other")]);
+        let c = event(7, 30023, 200, &[("d", "post")]);
+        let d = event(8, 30023, 100, &[]);
+        let e = event(9, 30023, 200, &[("d", "")]);
+        assert_eq!(store.insert(&a), InsertOutcome::Stored);
+        assert_eq!(store.insert(&b), InsertOutcome::Stored);
+        assert_eq!(
+            store.insert(&c),
+            InsertOutcome::Replaced { old_id: [5; 32] }
+        );
+        assert_eq!(store.insert(&d), InsertOutcome::Stored);
+        assert_eq!(
+            store.insert(&e),
+            InsertOutcome::Replaced { old_id: [8; 32] }
+        );
+
+        // kind 1, kind 0, and three addressable events
+        assert_eq!(store.len(), 5);
+        let addressable = store.query(&Filter::new().kinds([30023])).unwrap();
+        let ids: Vec<u8> = addressable.iter().map(|e| e.id[0]).collect();
+        assert_eq!(ids, vec![7, 9, 6]);
+    }
+
+    #[test]
+    fn test_query_matches_filter_apply() {
+        let mut events = Vec::new();
+        for i in 0..60u8 {
+            let mut e = event(
+                i,
+                [1, 7, 6][i as usize % 3],
+                1000 + (i as i64 % 20),
+                &[("t", ["nostr", "rust"][i as usize % 2])],
+            );
+            e.pubkey = [i % 4; 32];
+            events.push(e);
+        }
+
+        let filters = [
+            Filter::new(),
+            Filter::new().kinds([1, 6]).limit(7),
+            Filter::new().authors([[1; 32], [2; 32]]).since(1005),
+            Filter::new().tag('t', ["rust"]).until(1010).limit(5),
+            Filter::new().ids([[3; 32], [4; 32], [99; 32]]),
+            Filter::new()
+                .kinds([7])
+                .authors([[1; 32]])
+                .tag('t', ["rust"]),
+            Filter::new().since(1010).until(1005),
+        ];
+
+        for format in [Format::Json, Format::CapnProto, Format::DannyPack] {
+            let mut store = EventStore::new(format);
+            assert_eq!(store.extend(&events), events.len());
+
+            for filter in &filters {
+                let mut expected: Vec<&NostrEvent> =
+                    events.iter().filter(|e| filter.matches(e)).collect();
+                expected.sort_by(|a, b| b.created_at.cmp(&a.created_at).then(a.id.cmp(&b.id)));
+                assert_eq!(store.count(filter).unwrap(), expected.len());
+                expected.truncate(filter.limit.unwrap_or(usize::MAX));
+
+                let got = store.query(filter).unwrap();
+                let got: Vec<&NostrEvent> = got.iter().collect();
+                assert_eq!(got, expected, "{} {:?}", format.name(), filter);
+            }
+        }
+    }
+
+    #[test]
+    fn test_remove_cleans_indexes() {
+        let mut store = EventStore::new(Format::ProtoBinary);
+        store.insert(&sample_event());
+        let bytes = store.encoded_bytes();
+        assert!(bytes > 0);
+
+        assert!(store.remove(&sample_event().id));
+        assert!(!store.remove(&sample_event().id));
+        assert!(store.is_empty());
+        assert_eq!(store.encoded_bytes(), 0);
+        assert!(store.by_author.is_empty() && store.by_kind.is_empty());
+        assert!(store.by_tag.is_empty() && store.by_time.is_empty());
+
+        // Freed slots are reused
+        store.insert(&sample_event());
+        assert_eq!(store.slots.len(), 1);
+        assert_eq!(store.encoded_bytes(), bytes);
+    }
+}
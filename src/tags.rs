@@ -0,0 +1,489 @@
+//! Typed accessors for common NIP tags
+//!
+//! `NostrEvent.tags` is a plain `Vec<Vec<String>>`. This module parses the
+//! tag shapes that most events use into typed values, validating hex ids and
+//! coordinates on the way. Hex must be lowercase, so parsed values serialize
+//! back to the same text:
+//!
+//! - `e`: event reference with relay hint, NIP-10 marker and author pubkey
+//! - `p`: pubkey reference with relay hint and petname
+//! - `a`: addressable event coordinate (`kind:pubkey:d-tag`) with relay hint
+//! - `d`, `t`, `r`: identifier, hashtags and references
+//! - `imeta`: NIP-92 media metadata
+//! - `expiration`: NIP-40 expiration timestamp
+//!
+//! Empty relay hints are treated as absent, as clients commonly emit `""`
+//! to reach later positions.
+
+use std::fmt;
+use std::str::FromStr;
+
+use thiserror::Error;
+
+use crate::event::{KindClass, NostrEvent};
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum TagError {
+    #[error("{0} tag has no value")]
+    MissingValue(&'static str),
+
+    #[error("Invalid hex in {tag} tag: {value:?}")]
+    InvalidHex { tag: &'static str, value: String },
+
+    #[error("Invalid coordinate: {0:?}")]
+    InvalidCoordinate(String),
+
+    #[error("Invalid NIP-10 marker: {0:?}")]
+    InvalidMarker(String),
+
+    #[error("Invalid timestamp: {0:?}")]
+    InvalidTimestamp(String),
+
+    #[error("Invalid imeta entry: {0:?}")]
+    InvalidImeta(String),
+}
+
+/// NIP-10 marker on an `e` tag
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Marker {
+    Root,
+    Reply,
+    Mention,
+}
+
+impl FromStr for Marker {
+    type Err = TagError;
+
+    fn from_str(s: &str) -> Result<Self, TagError> {
+        match s {
+            "root" => Ok(Marker::Root),
+            "reply" => Ok(Marker::Reply),
+            "mention" => Ok(Marker::Mention),
+            _ => Err(TagError::InvalidMarker(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for Marker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Marker::Root => write!(f, "root"),
+            Marker::Reply => write!(f, "reply"),
+            Marker::Mention => write!(f, "mention"),
+        }
+    }
+}
+
+/// `["e", <event id>, <relay>, <marker>, <pubkey>]`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EventRef {
+    pub id: [u8; 32],
+    pub relay: Option<String>,
+    pub marker: Option<Marker>,
+    pub pubkey: Option<[u8; 32]>,
+}
+
+impl EventRef {
+    pub fn parse(tag: &[String]) -> Result<Self, TagError> {
+        Ok(Self {
+            id: parse_hex32("e", value(tag, "e")?)?,
+            relay: optional(tag, 2).map(str::to_string),
+            marker: optional(tag, 3).map(str::parse).transpose()?,
+            pubkey: optional(tag, 4)
+                .map(|pubkey| parse_hex32("e", pubkey))
+                .transpose()?,
+        })
+    }
+}
+
+/// `["p", <pubkey>, <relay>, <petname>]`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PubkeyRef {
+    pub pubkey: [u8; 32],
+    pub relay: Option<String>,
+    pub petname: Option<String>,
+}
+
+impl PubkeyRef {
+    pub fn parse(tag: &[String]) -> Result<Self, TagError> {
+        Ok(Self {
+            pubkey: parse_hex32("p", value(tag, "p")?)?,
+            relay: optional(tag, 2).map(str::to_string),
+            petname: optional(tag, 3).map(str::to_string),
+        })
+    }
+}
+
+/// Addressable event coordinate, `<kind>:<pubkey>:<d-tag>`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Coordinate {
+    pub kind: u16,
+    pub pubkey: [u8; 32],
+    pub identifier: String,
+}
+
+impl Coordinate {
+    /// Coordinate of a replaceable or addressable event
+    pub fn of(event: &NostrEvent) -> Option<Self> {
+        let identifier = match event.kind_class() {
+            KindClass::Addressable => event.d_tag().unwrap_or(""),
+            KindClass::Replaceable => "",
+            KindClass::Regular | KindClass::Ephemeral => return None,
+        };
+        Some(Self {
+            kind: event.kind,
+            pubkey: event.pubkey,
+            identifier: identifier.to_string(),
+        })
+    }
+}
+
+impl FromStr for Coordinate {
+    type Err = TagError;
+
+    fn from_str(s: &str) -> Result<Self, TagError> {
+        let invalid = || TagError::InvalidCoordinate(s.to_string());
+        // The identifier may itself contain ':'
+        let mut parts = s.splitn(3, ':');
+        let kind = parts
+            .next()
+            .and_then(|k| k.parse().ok())
+            .ok_or_else(invalid)?;
+        let pubkey = parts.next().ok_or_else(invalid)?;
+        let identifier = parts.next().ok_or_else(invalid)?;
+
+        Ok(Self {
+            kind,
+            pubkey: decode_hex32(pubkey).ok_or_else(invalid)?,
+            identifier: identifier.to_string(),
+        })
+    }
+}
+
+impl fmt::Display for Coordinate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}",
+            self.kind,
+            hex::encode(self.pubkey),
+            self.identifier
+        )
+    }
+}
+
+/// `["a", <coordinate>, <relay>]`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AddressRef {
+    pub coordinate: Coordinate,
+    pub relay: Option<String>,
+}
+
+impl AddressRef {
+    pub fn parse(tag: &[String]) -> Result<Self, TagError> {
+        Ok(Self {
+            coordinate: value(tag, "a")?.parse()?,
+            relay: optional(tag, 2).map(str::to_string),
+        })
+    }
+}
+
+/// NIP-92 `["imeta", "url https://...", "m image/jpeg", ...]`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Imeta {
+    pub url: String,
+    /// Remaining `key value` entries, in tag order
+    pub fields: Vec<(String, String)>,
+}
+
+impl Imeta {
+    pub fn parse(tag: &[String]) -> Result<Self, TagError> {
+        let mut url = None;
+        let mut fields = Vec::new();
+        for entry in tag.iter().skip(1) {
+            let (key, value) = entry
+                .split_once(' ')
+                .ok_or_else(|| TagError::InvalidImeta(entry.clone()))?;
+            if key == "url" && url.is_none() {
+                url = Some(value.to_string());
+            } else {
+                fields.push((key.to_string(), value.to_string()));
+            }
+        }
+        Ok(Self {
+            url: url.ok_or(TagError::MissingValue("imeta url"))?,
+            fields,
+        })
+    }
+
+    /// First value for a key, e.g. `m`, `x`, `dim` or `blurhash`
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+impl NostrEvent {
+    /// Tags with the given name
+    pub fn tags_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a [String]> + 'a {
+        self.tags
+            .iter()
+            .filter(move |t| t.first().is_some_and(|n| n == name))
+            .map(Vec::as_slice)
+    }
+
+    /// `e` tags
+    pub fn event_refs(&self) -> impl Iterator<Item = Result<EventRef, TagError>> + '_ {
+        self.tags_named("e").map(EventRef::parse)
+    }
+
+    /// `p` tags
+    pub fn pubkey_refs(&self) -> impl Iterator<Item = Result<PubkeyRef, TagError>> + '_ {
+        self.tags_named("p").map(PubkeyRef::parse)
+    }
+
+    /// `a` tags
+    pub fn address_refs(&self) -> impl Iterator<Item = Result<AddressRef, TagError>> + '_ {
+        self.tags_named("a").map(AddressRef::parse)
+    }
+
+    /// `t` tag values
+    pub fn hashtags(&self) -> impl Iterator<Item = &str> {
+        self.tags_named("t")
+            .filter_map(|t| t.get(1))
+            .map(String::as_str)
+    }
+
+    /// `r` tag values (URLs, or relays in NIP-65 lists)
+    pub fn references(&self) -> impl Iterator<Item = &str> {
+        self.tags_named("r")
+            .filter_map(|t| t.get(1))
+            .map(String::as_str)
+    }
+
+    /// `imeta` tags
+    pub fn imetas(&self) -> impl Iterator<Item = Result<Imeta, TagError>> + '_ {
+        self.tags_named("imeta").map(Imeta::parse)
+    }
+
+    /// NIP-40 expiration timestamp from the first `expiration` tag
+    pub fn expiration(&self) -> Result<Option<i64>, TagError> {
+        self.tags_named("expiration")
+            .next()
+            .map(|tag| {
+                let value = value(tag, "expiration")?;
+                value
+                    .parse()
+                    .map_err(|_| TagError::InvalidTimestamp(value.to_string()))
+            })
+            .transpose()
+    }
+
+    /// NIP-10 root `e` tag: the `root` marker, or the first `e` tag for
+    /// unmarked (positional) threads
+    pub fn thread_root(&self) -> Result<Option<EventRef>, TagError> {
+        let refs = self.event_refs().collect::<Result<Vec<_>, _>>()?;
+        if refs.iter().any(|r| r.marker.is_some()) {
+            return Ok(refs.into_iter().find(|r| r.marker == Some(Marker::Root)));
+        }
+        Ok(refs.into_iter().next())
+    }
+}
+
+/// Whether a tag value survives hex compression losslessly
+///
+/// The binary formats store hex values as raw bytes; only non-empty,
+/// even-length lowercase hex decodes back to the same string.
+pub fn is_compressible_hex(value: &str) -> bool {
+    !value.is_empty()
+        && value.len().is_multiple_of(2)
+        && value
+            .bytes()
+            .all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+fn value<'a>(tag: &'a [String], name: &'static str) -> Result<&'a str, TagError> {
+    tag.get(1)
+        .map(String::as_str)
+        .ok_or(TagError::MissingValue(name))
+}
+
+fn optional(tag: &[String], index: usize) -> Option<&str> {
+    tag.get(index).map(String::as_str).filter(|s| !s.is_empty())
+}
+
+fn parse_hex32(tag: &'static str, value: &str) -> Result<[u8; 32], TagError> {
+    decode_hex32(value).ok_or_else(|| TagError::InvalidHex {
+        tag,
+        value: value.to_string(),
+    })
+}
+
+/// 32 bytes from lowercase hex; uppercase would not round-trip
+fn decode_hex32(value: &str) -> Option<[u8; 32]> {
+    let mut out = [0u8; 32];
+    if !is_compressible_hex(value) {
+        return None;
+    }
+    hex::decode_to_slice(value, &mut out).ok()?;
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tag(values: &[&str]) -> Vec<String> {
+        values.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn sample_event() -> NostrEvent {
+        NostrEvent {
+            id: [0xab; 32],
+            pubkey: [0xcd; 32],
+            created_at: 1234567890,
+            kind: 1,
+            tags: vec![
+                tag(&["e", &"11".repeat(32), "", "root"]),
+                tag(&[
+                    "e",
+                    &"22".repeat(32),
+                    "wss://relay.example.com",
+                    "reply",
+                    &"33".repeat(32),
+                ]),
+                tag(&["p", &"33".repeat(32), "", "alice"]),
+                tag(&["a", &format!("30023:{}:my:article", "44".repeat(32))]),
+                tag(&["t", "nostr"]),
+                tag(&["r", "https://example.com"]),
+                tag(&[
+                    "imeta",
+                    "url https://example.com/a.jpg",
+                    "m image/jpeg",
+                    "dim 640x480",
+                ]),
+                tag(&["expiration", "1700000000"]),
+            ],
+            content: "Hello, Nostr!".to_string(),
+            sig: [0xef; 64],
+        }
+    }
+
+    #[test]
+    fn test_typed_accessors() {
+        let event = sample_event();
+
+        let refs: Vec<EventRef> = event.event_refs().map(Result::unwrap).collect();
+        assert_eq!(refs.len(), 2);
+        assert_eq!(refs[0].relay, None);
+        assert_eq!(refs[1].relay.as_deref(), Some("wss://relay.example.com"));
+        assert_eq!(refs[1].marker, Some(Marker::Reply));
+        assert_eq!(refs[1].pubkey, Some([0x33; 32]));
+        assert_eq!(event.thread_root().unwrap().unwrap().id, [0x11; 32]);
+
+        let p = event.pubkey_refs().next().unwrap().unwrap();
+        assert_eq!(p.pubkey, [0x33; 32]);
+        assert_eq!((p.relay, p.petname.as_deref()), (None, Some("alice")));
+
+        let a = event.address_refs().next().unwrap().unwrap();
+        assert_eq!(a.coordinate.kind, 30023);
+        assert_eq!(a.coordinate.identifier, "my:article");
+        assert_eq!(
+            a.coordinate.to_string().parse::<Coordinate>().unwrap(),
+            a.coordinate
+        );
+
+        assert_eq!(event.hashtags().collect::<Vec<_>>(), vec!["nostr"]);
+        assert_eq!(
+            event.references().collect::<Vec<_>>(),
+            vec!["https://example.com"]
+        );
+
+        let imeta = event.imetas().next().unwrap().unwrap();
+        assert_eq!(imeta.url, "https://example.com/a.jpg");
+        assert_eq!(imeta.get("dim"), Some("640x480"));
+        assert_eq!(event.expiration(), Ok(Some(1700000000)));
+    }
+
+    #[test]
+    fn test_validation() {
+        assert!(matches!(
+            EventRef::parse(&tag(&["e", "abc"])),
+            Err(TagError::InvalidHex { tag: "e", .. })
+        ));
+        assert_eq!(
+            EventRef::parse(&tag(&["e"])),
+            Err(TagError::MissingValue("e"))
+        );
+        assert!(matches!(
+            EventRef::parse(&tag(&["e", &"11".repeat(32), "", "parent"])),
+            Err(TagError::InvalidMarker(_))
+        ));
+        assert!(matches!(
+            "30023:abc:x".parse::<Coordinate>(),
+            Err(TagError::InvalidCoordinate(_))
+        ));
+        assert!(matches!(
+            format!("kind:{}:x", "44".repeat(32)).parse::<Coordinate>(),
+            Err(TagError::InvalidCoordinate(_))
+        ));
+        assert!(matches!(
+            Imeta::parse(&tag(&["imeta", "m image/jpeg"])),
+            Err(TagError::MissingValue(_))
+        ));
+        assert!(matches!(
+            Imeta::parse(&tag(&["imeta", "url"])),
+            Err(TagError::InvalidImeta(_))
+        ));
+
+        let mut event = sample_event();
+        event.tags = vec![tag(&["expiration", "soon"])];
+        assert!(matches!(
+            event.expiration(),
+            Err(TagError::InvalidTimestamp(_))
+        ));
+
+        assert!(is_compressible_hex("deadbeef"));
+        assert!(!is_compressible_hex("DEADBEEF"));
+        assert!(!is_compressible_hex("abc"));
+        assert!(!is_compressible_hex(""));
+    }
+
+    #[test]
+    fn test_uppercase_hex() {
+        let upper = "AB".repeat(32);
+        assert!(matches!(
+            EventRef::parse(&tag(&["e", &upper])),
+            Err(TagError::InvalidHex { tag: "e", .. })
+        ));
+        assert!(matches!(
+            EventRef::parse(&tag(&["e", &"ab".repeat(32), "", "", &upper])),
+            Err(TagError::InvalidHex { tag: "e", .. })
+        ));
+        assert!(matches!(
+            PubkeyRef::parse(&tag(&["p", &upper])),
+            Err(TagError::InvalidHex { tag: "p", .. })
+        ));
+        assert!(matches!(
+            format!("30023:{}:x", upper).parse::<Coordinate>(),
+            Err(TagError::InvalidCoordinate(_))
+        ));
+
+        let lower = "ab".repeat(32);
+        let parsed = PubkeyRef::parse(&tag(&["p", &lower])).unwrap();
+        assert_eq!(hex::encode(parsed.pubkey), lower);
+    }
+
+    #[test]
+    fn test_coordinate_of_event() {
+        let mut event = sample_event();
+        assert_eq!(Coordinate::of(&event), None);
+
+        event.kind = 30023;
+        event.tags.push(tag(&["d", "post"]));
+        let coordinate = Coordinate::of(&event).unwrap();
+        assert_eq!(Some(coordinate.to_string()), event.addressable_coordinate());
+    }
+}
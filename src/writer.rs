@@ -0,0 +1,520 @@
+//! Event writer for .pb.gz files
+//!
+//! The inverse of [`EventLoader`](crate::loader::EventLoader): writes events as
+//! varint length-delimited protobuf records, compressed with gzip. Also provides
+//! sharding (by day, kind or count) and merging with dedup by event id.
+
+use std::collections::{BTreeMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use prost::Message;
+use thiserror::Error;
+
+use crate::event::NostrEvent;
+use crate::loader::{EventLoader, LoadError};
+use crate::proto;
+
+#[derive(Error, Debug)]
+pub enum WriteError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Load error: {0}")]
+    Load(#[from] LoadError),
+}
+
+/// Writer for .pb.gz event files
+pub struct EventWriter<W: Write = BufWriter<File>> {
+    encoder: GzEncoder<W>,
+    buffer: Vec<u8>,
+    count: usize,
+}
+
+impl EventWriter<BufWriter<File>> {
+    /// Create a .pb.gz file for writing
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<Self, WriteError> {
+        Self::create_with_compression(path, Compression::default())
+    }
+
+    /// Create a .pb.gz file with a specific gzip compression level
+    pub fn create_with_compression<P: AsRef<Path>>(
+        path: P,
+        compression: Compression,
+    ) -> Result<Self, WriteError> {
+        let file = File::create(path)?;
+        Ok(Self::with_compression(BufWriter::new(file), compression))
+    }
+}
+
+impl<W: Write> EventWriter<W> {
+    /// Write gzip-compressed events to any writer
+    pub fn new(writer: W) -> Self {
+        Self::with_compression(writer, Compression::default())
+    }
+
+    /// Write events to any writer with a specific gzip compression level
+    pub fn with_compression(writer: W, compression: Compression) -> Self {
+        Self {
+            encoder: GzEncoder::new(writer, compression),
+            buffer: Vec::with_capacity(64 * 1024),
+            count: 0,
+        }
+    }
+
+    /// Append one event
+    pub fn write_event(&mut self, event: &NostrEvent) -> Result<(), WriteError> {
+        let proto = proto::string::event_to_proto(event);
+        self.buffer.clear();
+        prost::encoding::encode_varint(proto.encoded_len() as u64, &mut self.buffer);
+        proto
+            .encode(&mut self.buffer)
+            .expect("Vec<u8> has unlimited capacity");
+        self.encoder.write_all(&self.buffer)?;
+        self.count += 1;
+        Ok(())
+    }
+
+    /// Append every event in `events`
+    pub fn write_all<'a, I>(&mut self, events: I) -> Result<(), WriteError>
+    where
+        I: IntoIterator<Item = &'a NostrEvent>,
+    {
+        for event in events {
+            self.write_event(event)?;
+        }
+        Ok(())
+    }
+
+    /// Number of events written so far
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Finish the gzip stream and return the inner writer
+    pub fn finish(self) -> Result<W, WriteError> {
+        let mut writer = self.encoder.finish()?;
+        writer.flush()?;
+        Ok(writer)
+    }
+}
+
+/// Write `events` to a single .pb.gz file
+pub fn write_events<P: AsRef<Path>>(path: P, events: &[NostrEvent]) -> Result<(), WriteError> {
+    let mut writer = EventWriter::create(path)?;
+    writer.write_all(events)?;
+    writer.finish()?;
+    Ok(())
+}
+
+/// How events are split across files
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShardBy {
+    /// One file per UTC day of `created_at`, named `YYYY_MM_DD.pb.gz`
+    Day,
+    /// One file per kind, named `kind_<kind>.pb.gz`
+    Kind,
+    /// At most N events per file, named `part_<index>.pb.gz`
+    Count(usize),
+}
+
+/// A file produced by a [`ShardedWriter`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Shard {
+    pub path: PathBuf,
+    pub events: usize,
+}
+
+/// Shards a [`ShardedWriter`] keeps open by default
+pub const DEFAULT_MAX_OPEN_SHARDS: usize = 64;
+
+/// Writes events into a directory of .pb.gz shards
+///
+/// At most [`with_max_open`](Self::with_max_open) shards are open at once,
+/// each holding a file descriptor and its buffers. When another is needed,
+/// the least recently written one is finished; if it receives events
+/// again, they are appended to its file as a further gzip member, which
+/// [`EventLoader`] reads as one stream. Input sorted by the shard key never
+/// reopens a shard.
+pub struct ShardedWriter {
+    dir: PathBuf,
+    shard_by: ShardBy,
+    compression: Compression,
+    max_open: usize,
+    writers: BTreeMap<String, OpenShard>,
+    /// Events written per shard, open or not
+    events: BTreeMap<String, usize>,
+    writes: u64,
+    part: usize,
+}
+
+struct OpenShard {
+    writer: EventWriter,
+    /// Value of `writes` at the last event written to this shard
+    last_write: u64,
+}
+
+impl ShardedWriter {
+    /// Write shards into `dir`, creating it if needed
+    pub fn create<P: Into<PathBuf>>(dir: P, shard_by: ShardBy) -> Result<Self, WriteError> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self {
+            dir,
+            shard_by,
+            compression: Compression::default(),
+            max_open: DEFAULT_MAX_OPEN_SHARDS,
+            writers: BTreeMap::new(),
+            events: BTreeMap::new(),
+            writes: 0,
+            part: 0,
+        })
+    }
+
+    /// Set how many shards may be open at once (at least one)
+    pub fn with_max_open(mut self, max_open: usize) -> Self {
+        self.max_open = max_open.max(1);
+        self
+    }
+
+    /// Set the gzip compression level for new shards
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Append one event to its shard
+    pub fn write_event(&mut self, event: &NostrEvent) -> Result<(), WriteError> {
+        let name = match self.shard_by {
+            ShardBy::Day => {
+                let (year, month, day) = utc_date(event.created_at);
+                format!("{:04}_{:02}_{:02}", year, month, day)
+            }
+            ShardBy::Kind => format!("kind_{}", event.kind),
+            ShardBy::Count(_) => format!("part_{:05}", self.part),
+        };
+
+        if !self.writers.contains_key(&name) {
+            self.open(&name)?;
+        }
+        self.writes += 1;
+        let shard = self.writers.get_mut(&name).expect("shard was just opened");
+        shard.writer.write_event(event)?;
+        shard.last_write = self.writes;
+
+        let events = self.events.entry(name.clone()).or_default();
+        *events += 1;
+        if let ShardBy::Count(n) = self.shard_by {
+            if *events >= n.max(1) {
+                self.close(&name)?;
+                self.part += 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// Append every event in `events`
+    pub fn write_all<'a, I>(&mut self, events: I) -> Result<(), WriteError>
+    where
+        I: IntoIterator<Item = &'a NostrEvent>,
+    {
+        for event in events {
+            self.write_event(event)?;
+        }
+        Ok(())
+    }
+
+    /// Number of shards currently open
+    pub fn open_shards(&self) -> usize {
+        self.writers.len()
+    }
+
+    /// Finish every open shard and list the files written, sorted by path
+    pub fn finish(mut self) -> Result<Vec<Shard>, WriteError> {
+        let names: Vec<String> = self.writers.keys().cloned().collect();
+        for name in names {
+            self.close(&name)?;
+        }
+        let mut shards: Vec<Shard> = self
+            .events
+            .iter()
+            .map(|(name, &events)| Shard {
+                path: self.path(name),
+                events,
+            })
+            .collect();
+        shards.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(shards)
+    }
+
+    /// Open a shard, first closing the least recently written one if the
+    /// limit is reached; a shard written before is appended to
+    fn open(&mut self, name: &str) -> Result<(), WriteError> {
+        if self.writers.len() >= self.max_open {
+            let oldest = self
+                .writers
+                .iter()
+                .min_by_key(|(_, shard)| shard.last_write)
+                .map(|(name, _)| name.clone());
+            if let Some(oldest) = oldest {
+                self.close(&oldest)?;
+            }
+        }
+
+        let path = self.path(name);
+        let writer = if self.events.contains_key(name) {
+            let file = OpenOptions::new().append(true).open(path)?;
+            EventWriter::with_compression(BufWriter::new(file), self.compression)
+        } else {
+            EventWriter::create_with_compression(path, self.compression)?
+        };
+        self.writers.insert(
+            name.to_string(),
+            OpenShard {
+                writer,
+                last_write: 0,
+            },
+        );
+        Ok(())
+    }
+
+    fn close(&mut self, name: &str) -> Result<(), WriteError> {
+        if let Some(shard) = self.writers.remove(name) {
+            shard.writer.finish()?;
+        }
+        Ok(())
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(format!("{}.pb.gz", name))
+    }
+}
+
+/// Write `events` into shards under `dir`
+pub fn write_sharded<P: Into<PathBuf>>(
+    dir: P,
+    events: &[NostrEvent],
+    shard_by: ShardBy,
+) -> Result<Vec<Shard>, WriteError> {
+    let mut writer = ShardedWriter::create(dir, shard_by)?;
+    writer.write_all(events)?;
+    writer.finish()
+}
+
+/// Convert a unix timestamp to a UTC (year, month, day)
+pub fn utc_date(timestamp: i64) -> (i64, u32, u32) {
+    // Days-to-civil conversion from Howard Hinnant's date algorithms
+    let days = timestamp.div_euclid(86_400) + 719_468;
+    let era = days.div_euclid(146_097);
+    let doe = days.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Remove events whose id was already seen, keeping the first occurrence
+///
+/// Returns the number of duplicates removed.
+pub fn dedup_by_id(events: &mut Vec<NostrEvent>) -> usize {
+    let before = events.len();
+    let mut seen = HashSet::with_capacity(events.len());
+    events.retain(|e| seen.insert(e.id));
+    before - events.len()
+}
+
+/// Summary of a [`merge_dedup`] run
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MergeReport {
+    /// Events read across all inputs
+    pub read: usize,
+    /// Events written to the output
+    pub written: usize,
+    /// Events dropped because their id was already written
+    pub duplicates: usize,
+}
+
+/// Merge .pb.gz files into one, dropping events whose id was already written
+///
+/// Inputs are streamed in order, so the first occurrence of each id wins and
+/// only the set of seen ids is kept in memory.
+pub fn merge_dedup<P, Q>(inputs: &[P], output: Q) -> Result<MergeReport, WriteError>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    let mut writer = EventWriter::create(output)?;
+    let mut seen = HashSet::new();
+    let mut report = MergeReport::default();
+
+    for input in inputs {
+        for event in EventLoader::open(input)? {
+            let event = event?;
+            report.read += 1;
+            if seen.insert(event.id) {
+                writer.write_event(&event)?;
+            } else {
+                report.duplicates += 1;
+            }
+        }
+    }
+
+    report.written = writer.count();
+    writer.finish()?;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loader::load_from_directory;
+
+    fn sample_event(i: usize, created_at: i64, kind: u16) -> NostrEvent {
+        NostrEvent {
+            id: [i as u8; 32],
+            pubkey: [0xcd; 32],
+            created_at,
+            kind,
+            tags: vec![vec!["p".to_string(), hex::encode([0xab; 32])]],
+            content: format!("Hello, Nostr! {}", i),
+            sig: [0xef; 64],
+        }
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("binostr-writer-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_write_then_load() {
+        let dir = temp_dir("roundtrip");
+        let path = dir.join("events.pb.gz");
+        let events: Vec<_> = (0..20).map(|i| sample_event(i, 1700000000, 1)).collect();
+
+        write_events(&path, &events).unwrap();
+        assert_eq!(
+            EventLoader::open(&path).unwrap().load_all().unwrap(),
+            events
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_utc_date() {
+        assert_eq!(utc_date(0), (1970, 1, 1));
+        assert_eq!(utc_date(1756684800), (2025, 9, 1));
+        assert_eq!(utc_date(1756771199), (2025, 9, 1));
+        assert_eq!(utc_date(951782400), (2000, 2, 29));
+        assert_eq!(utc_date(-1), (1969, 12, 31));
+    }
+
+    #[test]
+    fn test_shard_by_day_kind_count() {
+        let events = vec![
+            sample_event(1, 1756684800, 1),
+            sample_event(2, 1756771200, 7),
+            sample_event(3, 1756690000, 1),
+            sample_event(4, 1756857600, 7),
+            sample_event(5, 1756860000, 0),
+        ];
+
+        let dir = temp_dir("day");
+        let shards = write_sharded(&dir, &events, ShardBy::Day).unwrap();
+        let names: Vec<_> = shards
+            .iter()
+            .map(|s| s.path.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(
+            names,
+            vec!["2025_09_01.pb.gz", "2025_09_02.pb.gz", "2025_09_03.pb.gz"]
+        );
+        assert_eq!(shards[0].events, 2);
+        assert_eq!(load_from_directory(&dir).unwrap().len(), events.len());
+        fs::remove_dir_all(dir).unwrap();
+
+        let dir = temp_dir("kind");
+        let shards = write_sharded(&dir, &events, ShardBy::Kind).unwrap();
+        assert_eq!(shards.len(), 3);
+        let kind_7 = EventLoader::open(dir.join("kind_7.pb.gz"))
+            .unwrap()
+            .load_all()
+            .unwrap();
+        assert_eq!(kind_7, vec![events[1].clone(), events[3].clone()]);
+        fs::remove_dir_all(dir).unwrap();
+
+        let dir = temp_dir("count");
+        let shards = write_sharded(&dir, &events, ShardBy::Count(2)).unwrap();
+        let counts: Vec<_> = shards.iter().map(|s| s.events).collect();
+        assert_eq!(counts, vec![2, 2, 1]);
+        assert_eq!(load_from_directory(&dir).unwrap(), events);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_max_open_shards() {
+        // Kinds cycle, so every shard is closed and reopened several times
+        let events: Vec<_> = (0..40)
+            .map(|i| sample_event(i, 1700000000, (i % 10) as u16))
+            .collect();
+
+        let dir = temp_dir("max-open");
+        let mut writer = ShardedWriter::create(&dir, ShardBy::Kind)
+            .unwrap()
+            .with_max_open(3);
+        for event in &events {
+            writer.write_event(event).unwrap();
+            assert!(writer.open_shards() <= 3);
+        }
+        let shards = writer.finish().unwrap();
+
+        assert_eq!(shards.len(), 10);
+        assert!(shards.iter().all(|s| s.events == 4));
+        for kind in 0..10u16 {
+            let loaded = EventLoader::open(dir.join(format!("kind_{}.pb.gz", kind)))
+                .unwrap()
+                .load_all()
+                .unwrap();
+            let expected: Vec<_> = events.iter().filter(|e| e.kind == kind).cloned().collect();
+            assert_eq!(loaded, expected);
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_merge_dedup() {
+        let dir = temp_dir("merge");
+        let a: Vec<_> = (0..5).map(|i| sample_event(i, 1700000000, 1)).collect();
+        let b: Vec<_> = (3..8).map(|i| sample_event(i, 1700000000, 1)).collect();
+        write_events(dir.join("a.pb.gz"), &a).unwrap();
+        write_events(dir.join("b.pb.gz"), &b).unwrap();
+
+        let output = dir.join("merged.pb.gz");
+        let report = merge_dedup(&[dir.join("a.pb.gz"), dir.join("b.pb.gz")], &output).unwrap();
+        assert_eq!(
+            report,
+            MergeReport {
+                read: 10,
+                written: 8,
+                duplicates: 2
+            }
+        );
+
+        let merged = EventLoader::open(&output).unwrap().load_all().unwrap();
+        let mut expected = [a, b].concat();
+        assert_eq!(dedup_by_id(&mut expected), 2);
+        assert_eq!(merged, expected);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
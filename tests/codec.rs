@@ -0,0 +1,132 @@
+//! Async framing tests for the `codec` feature
+//!
+//! Run with: cargo test --features codec --test codec
+
+#![cfg(feature = "codec")]
+
+use binostr::codec::{
+    CodecError, DannyPack, DannyPackCodec, EventCodec, EventFormat, FormatCodec, JsonCodec,
+};
+use binostr::dannypack::DannyPackError;
+use binostr::stats::Format;
+use binostr::{codec, NostrEvent};
+use futures_util::{SinkExt, StreamExt};
+use tokio::io::AsyncWriteExt;
+use tokio_util::codec::{FramedRead, FramedWrite};
+
+fn sample_events() -> Vec<NostrEvent> {
+    (0..20)
+        .map(|i| NostrEvent {
+            id: [i as u8; 32],
+            pubkey: [0xcd; 32],
+            created_at: 1_700_000_000 + i,
+            kind: if i % 2 == 0 { 1 } else { 7 },
+            tags: vec![
+                vec!["e".to_string(), "ab".repeat(32)],
+                vec!["p".to_string(), "cd".repeat(32)],
+            ],
+            content: "x".repeat(i as usize * 50),
+            sig: [0xef; 64],
+        })
+        .collect()
+}
+
+/// Send all events through a duplex pipe and collect them on the other side
+async fn roundtrip<F>(events: &[NostrEvent]) -> Vec<NostrEvent>
+where
+    F: EventFormat + Send + 'static,
+    F::Error: Send + Sync + std::fmt::Debug,
+{
+    // Small pipe buffer so frames are split across reads
+    let (client, relay) = tokio::io::duplex(256);
+
+    let to_send = events.to_vec();
+    let writer = tokio::spawn(async move {
+        let mut sink = FramedWrite::new(client, EventCodec::<F>::new());
+        for event in &to_send {
+            sink.send(event).await.unwrap();
+        }
+    });
+
+    let mut stream = FramedRead::new(relay, EventCodec::<F>::new());
+    let mut received = Vec::new();
+    while let Some(event) = stream.next().await {
+        received.push(event.unwrap());
+    }
+    writer.await.unwrap();
+    received
+}
+
+#[tokio::test]
+async fn test_duplex_roundtrip_all_formats() {
+    let events = sample_events();
+
+    assert_eq!(roundtrip::<codec::Json>(&events).await, events);
+    assert_eq!(roundtrip::<codec::CborSchemaless>(&events).await, events);
+    assert_eq!(roundtrip::<codec::CborPacked>(&events).await, events);
+    assert_eq!(roundtrip::<codec::CborIntKey>(&events).await, events);
+    assert_eq!(roundtrip::<codec::ProtoString>(&events).await, events);
+    assert_eq!(roundtrip::<codec::ProtoBinary>(&events).await, events);
+    assert_eq!(roundtrip::<codec::CapnProto>(&events).await, events);
+    assert_eq!(roundtrip::<codec::CapnProtoPacked>(&events).await, events);
+    assert_eq!(roundtrip::<codec::DannyPack>(&events).await, events);
+    assert_eq!(roundtrip::<codec::Notepack>(&events).await, events);
+}
+
+#[tokio::test]
+async fn test_runtime_format_codec() {
+    let events = sample_events();
+
+    for &format in Format::all() {
+        let (client, relay) = tokio::io::duplex(4096);
+        let to_send = events.clone();
+        let writer = tokio::spawn(async move {
+            let mut sink = FramedWrite::new(client, FormatCodec::new(format));
+            for event in to_send {
+                sink.send(event).await.unwrap();
+            }
+        });
+
+        let received: Vec<_> = FramedRead::new(relay, FormatCodec::new(format))
+            .map(Result::unwrap)
+            .collect()
+            .await;
+        writer.await.unwrap();
+        assert_eq!(received, events, "{}", format.name());
+    }
+}
+
+#[tokio::test]
+async fn test_max_frame_size() {
+    let mut event = sample_events().remove(0);
+    event.content = "x".repeat(2000);
+
+    // Sender refuses to produce an oversized frame
+    let (client, _relay) = tokio::io::duplex(4096);
+    let mut sink = FramedWrite::new(client, JsonCodec::new().with_max_frame_size(1000));
+    assert!(matches!(
+        sink.send(&event).await,
+        Err(CodecError::FrameTooLarge { max: 1000, .. })
+    ));
+
+    // Receiver rejects a large length prefix before buffering the payload
+    let (mut client, relay) = tokio::io::duplex(4096);
+    client.write_all(&u32::MAX.to_be_bytes()).await.unwrap();
+    drop(client);
+    let mut stream = FramedRead::new(relay, DannyPackCodec::new());
+    assert!(matches!(
+        stream.next().await,
+        Some(Err(CodecError::FrameTooLarge { .. }))
+    ));
+}
+
+#[tokio::test]
+async fn test_typed_decode_error() {
+    let (mut client, relay) = tokio::io::duplex(4096);
+    client.write_all(&[0, 0, 0, 3, 1, 2, 3]).await.unwrap();
+    drop(client);
+
+    let mut stream = FramedRead::new(relay, EventCodec::<DannyPack>::new());
+    let err = stream.next().await.unwrap().unwrap_err();
+    assert!(matches!(err, CodecError::Format(DannyPackError::TooShort)));
+}